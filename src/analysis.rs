@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::{Diagnostic, line_column};
+use crate::{JumpTable, Token, source_offset};
+
+/// One cell operation in a run of instructions, relative to the data pointer
+/// at the start of the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellOp {
+    /// Adds `delta` (wrapping) to the cell at `offset`.
+    Add {
+        offset: isize,
+        delta: u8,
+    },
+    Output {
+        offset: isize,
+    },
+    Input {
+        offset: isize,
+    },
+}
+
+/// The net effect of a run of instructions containing no loops.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Effect {
+    /// Cell operations in execution order; consecutive additions to the same
+    /// cell are merged and additions that cancel out are dropped.
+    pub ops: Vec<CellOp>,
+    /// How far the data pointer moves over the whole run.
+    pub shift: isize,
+}
+
+impl Effect {
+    /// Computes the effect of `tokens`, which must not contain `[` or `]`.
+    pub fn of(tokens: &[Token]) -> Self {
+        let mut effect = Self::default();
+
+        for token in tokens {
+            let offset = effect.shift;
+            match token {
+                Token::IncrementPointer => effect.shift += 1,
+                Token::DecrementPointer => effect.shift -= 1,
+                Token::IncrementData => effect.add(offset, 1),
+                Token::DecrementData => effect.add(offset, u8::MAX),
+                Token::Output => effect.ops.push(CellOp::Output { offset }),
+                Token::Input => effect.ops.push(CellOp::Input { offset }),
+                Token::LoopStart | Token::LoopEnd => {
+                    panic!("Effect::of called with a loop instruction")
+                }
+                Token::Refresh => {}
+            }
+        }
+
+        effect
+    }
+
+    fn add(&mut self, offset: isize, delta: u8) {
+        if let Some(CellOp::Add {
+            offset: last_offset,
+            delta: last_delta,
+        }) = self.ops.last_mut()
+            && *last_offset == offset
+        {
+            *last_delta = last_delta.wrapping_add(delta);
+            if *last_delta == 0 {
+                self.ops.pop();
+            }
+        } else {
+            self.ops.push(CellOp::Add { offset, delta });
+        }
+    }
+
+    /// Returns the total amount added to the cell at `offset`.
+    pub fn delta_at(&self, offset: isize) -> u8 {
+        self.ops.iter().fold(0, |total, op| match *op {
+            CellOp::Add {
+                offset: op_offset,
+                delta,
+            } if op_offset == offset => total.wrapping_add(delta),
+            _ => total,
+        })
+    }
+
+    pub fn reads_input(&self) -> bool {
+        self.ops.iter().any(|op| matches!(op, CellOp::Input { .. }))
+    }
+}
+
+/// Returns the effect of a loop body that contains no loops, reads no input,
+/// and leaves the data pointer where it was, so every iteration adds the same
+/// amount to the tested cell.
+fn counting_effect(body: &[Token]) -> Option<Effect> {
+    if body.contains(&Token::LoopStart) {
+        return None;
+    }
+    let effect = Effect::of(body);
+    (effect.shift == 0 && !effect.reads_input()).then_some(effect)
+}
+
+/// Finds loops that never end once entered: counting loops (see
+/// [`counting_effect`]) that leave the tested cell unchanged. Returns the
+/// index of each such loop's `[`.
+pub fn find_infinite_loops(tokens: &[Token], jump_table: &JumpTable) -> Vec<usize> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|&(_, token)| *token == Token::LoopStart)
+        .filter_map(|(start, _)| {
+            let effect = counting_effect(&tokens[start + 1..jump_table[start]])?;
+            (effect.delta_at(0) == 0).then_some(start)
+        })
+        .collect()
+}
+
+/// Formats the position of the token at `token_index` as a line and column.
+pub fn describe_position(code: &str, token_index: usize) -> String {
+    let offset = source_offset(code, token_index).unwrap_or(code.len());
+    let (line, column) = line_column(code, offset);
+    format!("line {}, column {}", line, column)
+}
+
+/// Describes each loop found by [`find_infinite_loops`] with its position in
+/// `code`.
+pub fn loop_warnings(code: &str, tokens: &[Token], jump_table: &JumpTable) -> Vec<String> {
+    find_infinite_loops(tokens, jump_table)
+        .into_iter()
+        .map(|start| {
+            format!(
+                "Warning: the loop at {} never changes the cell it tests, so it never ends once entered",
+                describe_position(code, start)
+            )
+        })
+        .collect()
+}
+
+/// Reports each loop found by [`find_infinite_loops`] as a warning.
+pub fn loop_diagnostics(tokens: &[Token], jump_table: &JumpTable) -> Vec<Diagnostic> {
+    find_infinite_loops(tokens, jump_table)
+        .into_iter()
+        .map(|start| {
+            Diagnostic::warning(
+                "This loop never changes the cell it tests, so it never ends once entered",
+                start,
+            )
+        })
+        .collect()
+}
+
+/// A loop that adds the same nonzero amount to its tested cell on every
+/// iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountingLoop {
+    /// The index of the loop's `[`.
+    pub start: usize,
+    /// The amount added (wrapping) to the tested cell per iteration.
+    pub delta: u8,
+    /// The tested cell's value on entry, if it is the same on every run.
+    pub entry: Option<u8>,
+}
+
+impl CountingLoop {
+    /// Returns how many iterations run when the loop is entered with `value`,
+    /// or `None` if the cell never reaches zero.
+    pub fn iterations(&self, value: u8) -> Option<u32> {
+        (0..=u8::MAX)
+            .find_map(|k| (value.wrapping_add(k.wrapping_mul(self.delta)) == 0).then_some(k as u32))
+    }
+
+    /// Returns true if the entry value is known and never reaches zero.
+    pub fn never_ends(&self) -> bool {
+        self.entry
+            .is_some_and(|value| self.iterations(value).is_none())
+    }
+
+    /// Returns the largest power of two dividing `delta`. Only entry values
+    /// that are multiples of it reach zero.
+    pub fn divisor(&self) -> u32 {
+        1 << self.delta.trailing_zeros()
+    }
+}
+
+/// Finds every counting loop and, where possible, the value it is entered
+/// with.
+///
+/// Cell values are tracked from the start of the program (all zeros) through
+/// straight-line code and counting loops whose iteration count is known; the
+/// first input, non-counting loop, or endless loop stops the tracking.
+pub fn find_counting_loops(tokens: &[Token], jump_table: &JumpTable) -> Vec<CountingLoop> {
+    let mut loops = Vec::new();
+    let mut tape: Option<HashMap<isize, u8>> = Some(HashMap::new());
+    let mut pointer: isize = 0;
+    let mut index = 0;
+
+    while index < tokens.len() {
+        match tokens[index] {
+            Token::LoopStart => {
+                let end = jump_table[index];
+                let entry = tape
+                    .as_ref()
+                    .map(|tape| tape.get(&pointer).copied().unwrap_or(0));
+
+                if let Some(effect) = counting_effect(&tokens[index + 1..end]) {
+                    let delta = effect.delta_at(0);
+                    let counting_loop = CountingLoop {
+                        start: index,
+                        delta,
+                        entry,
+                    };
+                    if delta != 0 {
+                        loops.push(counting_loop);
+                    }
+
+                    match entry.and_then(|value| counting_loop.iterations(value)) {
+                        Some(iterations) => {
+                            for op in &effect.ops {
+                                if let CellOp::Add { offset, delta } = *op {
+                                    let total = delta.wrapping_mul(iterations as u8);
+                                    add_known(&mut tape, pointer + offset, total);
+                                }
+                            }
+                        }
+                        None => tape = None,
+                    }
+                    index = end + 1;
+                    continue;
+                }
+
+                if entry == Some(0) {
+                    index = end + 1;
+                    continue;
+                }
+                tape = None;
+            }
+            Token::LoopEnd | Token::Input => tape = None,
+            Token::IncrementPointer => pointer += 1,
+            Token::DecrementPointer => pointer -= 1,
+            Token::IncrementData => add_known(&mut tape, pointer, 1),
+            Token::DecrementData => add_known(&mut tape, pointer, u8::MAX),
+            Token::Output | Token::Refresh => {}
+        }
+        index += 1;
+    }
+
+    loops
+}
+
+fn add_known(tape: &mut Option<HashMap<isize, u8>>, offset: isize, delta: u8) {
+    if let Some(tape) = tape {
+        let cell = tape.entry(offset).or_insert(0);
+        *cell = cell.wrapping_add(delta);
+    }
+}
+
+/// Describes whether each counting loop in `code` terminates.
+pub fn termination_report(code: &str, tokens: &[Token], jump_table: &JumpTable) -> Vec<String> {
+    find_counting_loops(tokens, jump_table)
+        .into_iter()
+        .map(|counting_loop| {
+            let position = describe_position(code, counting_loop.start);
+            let change = counting_loop.delta as i8;
+            match counting_loop.entry {
+                Some(value) => match counting_loop.iterations(value) {
+                    Some(0) => format!("The loop at {} is never entered", position),
+                    Some(iterations) => format!(
+                        "The loop at {} ends after {} iterations (entered with {}, changing by {:+} each time)",
+                        position, iterations, value, change
+                    ),
+                    None => format!(
+                        "Warning: the loop at {} never ends (entered with {}, changing by {:+} each time)",
+                        position, value, change
+                    ),
+                },
+                None => match counting_loop.divisor() {
+                    1 => format!(
+                        "The loop at {} always ends, after at most 255 iterations (changing by {:+} each time)",
+                        position, change
+                    ),
+                    divisor => format!(
+                        "The loop at {} ends only if entered with a multiple of {}, after at most {} iterations (changing by {:+} each time)",
+                        position,
+                        divisor,
+                        256 / divisor - 1,
+                        change
+                    ),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Reports each counting loop that never ends as a warning.
+pub fn termination_diagnostics(tokens: &[Token], jump_table: &JumpTable) -> Vec<Diagnostic> {
+    find_counting_loops(tokens, jump_table)
+        .into_iter()
+        .filter_map(|counting_loop| {
+            let value = counting_loop.entry?;
+            counting_loop.never_ends().then(|| {
+                Diagnostic::warning(
+                    format!(
+                        "This loop never ends (entered with {}, changing by {:+} each time)",
+                        value, counting_loop.delta as i8
+                    ),
+                    counting_loop.start,
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_loops, tokenize};
+
+    #[test]
+    fn test_effect() {
+        let effect = Effect::of(&tokenize(">++<-+->+-+.<<,"));
+
+        insta::assert_debug_snapshot!(
+            effect,
+            @r"
+        Effect {
+            ops: [
+                Add {
+                    offset: 1,
+                    delta: 2,
+                },
+                Add {
+                    offset: 0,
+                    delta: 255,
+                },
+                Add {
+                    offset: 1,
+                    delta: 1,
+                },
+                Output {
+                    offset: 1,
+                },
+                Input {
+                    offset: -1,
+                },
+            ],
+            shift: -1,
+        }
+        "
+        );
+    }
+
+    #[test]
+    fn test_loop_warnings() {
+        let code = "+[-]\n[<>] [] [.] [>] [,] [+-+]\n[[]-]";
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+
+        insta::assert_debug_snapshot!(loop_warnings(code, &tokens, &jump_table), @r#"
+        [
+            "Warning: the loop at line 2, column 1 never changes the cell it tests, so it never ends once entered",
+            "Warning: the loop at line 2, column 6 never changes the cell it tests, so it never ends once entered",
+            "Warning: the loop at line 2, column 9 never changes the cell it tests, so it never ends once entered",
+            "Warning: the loop at line 3, column 2 never changes the cell it tests, so it never ends once entered",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_termination_report() {
+        let code = "++++++++[>++++++++<-]>+[++]>+++[--]\n,[-]>[>]<[++++]>[+]";
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+
+        insta::assert_debug_snapshot!(termination_report(code, &tokens, &jump_table), @r#"
+        [
+            "The loop at line 1, column 9 ends after 8 iterations (entered with 8, changing by -1 each time)",
+            "Warning: the loop at line 1, column 24 never ends (entered with 65, changing by +2 each time)",
+            "The loop at line 1, column 32 ends only if entered with a multiple of 2, after at most 127 iterations (changing by -2 each time)",
+            "The loop at line 2, column 2 always ends, after at most 255 iterations (changing by -1 each time)",
+            "The loop at line 2, column 10 ends only if entered with a multiple of 4, after at most 63 iterations (changing by +4 each time)",
+            "The loop at line 2, column 17 always ends, after at most 255 iterations (changing by +1 each time)",
+        ]
+        "#);
+    }
+}
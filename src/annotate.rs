@@ -0,0 +1,118 @@
+use crate::analysis::{CellOp, Effect};
+use crate::source::read_source;
+use crate::{Token, tokenize};
+
+/// Prints `filename` with each line of code followed by a description of
+/// what it does.
+pub fn run_annotate(filename: &str) -> Result<(), String> {
+    print!("{}", annotate(&read_source(filename)?));
+    Ok(())
+}
+
+/// Returns `code` with a comment after every line that contains code.
+///
+/// Comments describe cell changes relative to the data pointer at the start
+/// of each run of instructions between brackets, and never contain
+/// Brainfuck commands, so the listing is still the same program.
+pub fn annotate(code: &str) -> String {
+    let width = code
+        .lines()
+        .filter(|line| !tokenize(line).is_empty())
+        .map(|line| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+
+    code.lines()
+        .map(|line| {
+            let line = line.trim_end();
+            let tokens = tokenize(line);
+            if tokens.is_empty() {
+                format!("{}\n", line)
+            } else {
+                format!("{:width$}  ; {}\n", line, describe_line(&tokens))
+            }
+        })
+        .collect()
+}
+
+fn describe_line(tokens: &[Token]) -> String {
+    let mut parts = Vec::new();
+
+    for run in tokens.split_inclusive(|token| matches!(token, Token::LoopStart | Token::LoopEnd)) {
+        let (bracket, straight_line) = match run.split_last() {
+            Some((token @ (Token::LoopStart | Token::LoopEnd), rest)) => (Some(token), rest),
+            _ => (None, run),
+        };
+
+        describe_effect(&Effect::of(straight_line), &mut parts);
+
+        match bracket {
+            Some(Token::LoopStart) => parts.push("loop while here is nonzero".to_string()),
+            Some(_) => parts.push("end loop".to_string()),
+            None => {}
+        }
+    }
+
+    if parts.is_empty() {
+        "no effect".to_string()
+    } else {
+        parts.join("; ")
+    }
+}
+
+fn describe_effect(effect: &Effect, parts: &mut Vec<String>) {
+    for op in &effect.ops {
+        parts.push(match *op {
+            CellOp::Add { offset, delta } if delta < 128 => {
+                format!("add {} {}", delta, describe_offset(offset, "to"))
+            }
+            CellOp::Add { offset, delta } => {
+                format!(
+                    "subtract {} {}",
+                    delta.wrapping_neg(),
+                    describe_offset(offset, "from")
+                )
+            }
+            CellOp::Output { offset } => format!("print {}", describe_offset(offset, "")),
+            CellOp::Input { offset } => format!("read {}", describe_offset(offset, "into")),
+        });
+    }
+
+    match effect.shift {
+        0 => {}
+        shift if shift > 0 => parts.push(format!("move {} right", shift)),
+        shift => parts.push(format!("move {} left", -shift)),
+    }
+}
+
+fn describe_offset(offset: isize, preposition: &str) -> String {
+    let place = match offset {
+        0 => return "here".to_string(),
+        offset if offset > 0 => format!("{} right", offset),
+        offset => format!("{} left", -offset),
+    };
+
+    if preposition.is_empty() {
+        format!("the cell {}", place)
+    } else {
+        format!("{} the cell {}", preposition, place)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotate() {
+        let code = "Print A\n++++++++[>++++++++<-]>+.\n\n,[>+<-]  copy\n+-\n";
+
+        insta::assert_snapshot!(annotate(code), @r"
+        Print A
+        ++++++++[>++++++++<-]>+.  ; add 8 here; loop while here is nonzero; add 8 to the cell 1 right; subtract 1 here; end loop; add 1 to the cell 1 right; print the cell 1 right; move 1 right
+
+        ,[>+<-]  copy             ; read here; loop while here is nonzero; add 1 to the cell 1 right; subtract 1 here; end loop
+        +-                        ; no effect
+        ");
+    }
+}
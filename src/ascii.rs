@@ -0,0 +1,128 @@
+const CONTROL_NAMES: [&str; 32] = [
+    "NUL", "SOH", "STX", "ETX", "EOT", "ENQ", "ACK", "BEL", "BS", "HT", "LF", "VT", "FF", "CR",
+    "SO", "SI", "DLE", "DC1", "DC2", "DC3", "DC4", "NAK", "SYN", "ETB", "CAN", "EM", "SUB", "ESC",
+    "FS", "GS", "RS", "US",
+];
+
+/// Returns the ASCII name of `byte` as shown in the table: the character
+/// itself, `SP` for space, or the abbreviation of a control character.
+fn name(byte: u8) -> Option<String> {
+    match byte {
+        0..=31 => Some(CONTROL_NAMES[byte as usize].to_string()),
+        b' ' => Some("SP".to_string()),
+        127 => Some("DEL".to_string()),
+        33..=126 => Some((byte as char).to_string()),
+        128.. => None,
+    }
+}
+
+/// Describes `byte` as a character for display next to its value: a quoted
+/// character if printable, or the abbreviation of a control character.
+/// Bytes outside ASCII have no description.
+pub fn describe(byte: u8) -> Option<String> {
+    match byte {
+        b' '..=b'~' => Some(format!("'{}'", byte as char)),
+        _ => name(byte),
+    }
+}
+
+/// Renders the ASCII table in 8 columns of 16 values.
+pub fn table() -> String {
+    (0..16u8)
+        .map(|row| {
+            let entries: Vec<String> = (0..8u8)
+                .map(|column| {
+                    let byte = column * 16 + row;
+                    format!("{:>3} {:<3}", byte, name(byte).unwrap_or_default())
+                })
+                .collect();
+            format!("{}\n", entries.join("  ").trim_end())
+        })
+        .collect()
+}
+
+/// Looks up a value (`65`) or a character (`A` or `'A'`) and describes the
+/// other side.
+pub fn lookup(arg: &str) -> Result<String, String> {
+    if let Ok(byte) = arg.parse::<u8>() {
+        return describe(byte)
+            .map(|description| format!("{} = {}", byte, description))
+            .ok_or_else(|| format!("{} is not an ASCII character", byte));
+    }
+
+    let unquoted = arg
+        .strip_prefix('\'')
+        .and_then(|arg| arg.strip_suffix('\''))
+        .unwrap_or(arg);
+    let mut chars = unquoted.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(format!("'{}' = {}", c, c as u8)),
+        (Some(c), None) => Err(format!("'{}' is not an ASCII character", c)),
+        _ => Err(format!(
+            "Expected a value (0-255) or a single character, got '{}'",
+            arg
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        insta::assert_debug_snapshot!(
+            ["65", "10", "200", "A", "'7'", "' '", "é", "AB"].map(lookup),
+            @r#"
+        [
+            Ok(
+                "65 = 'A'",
+            ),
+            Ok(
+                "10 = LF",
+            ),
+            Err(
+                "200 is not an ASCII character",
+            ),
+            Ok(
+                "'A' = 65",
+            ),
+            Ok(
+                "'7' = 55",
+            ),
+            Ok(
+                "' ' = 32",
+            ),
+            Err(
+                "'é' is not an ASCII character",
+            ),
+            Err(
+                "Expected a value (0-255) or a single character, got 'AB'",
+            ),
+        ]
+        "#
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        insta::assert_snapshot!(table(), @r#"
+         0 NUL   16 DLE   32 SP    48 0     64 @     80 P     96 `    112 p
+         1 SOH   17 DC1   33 !     49 1     65 A     81 Q     97 a    113 q
+         2 STX   18 DC2   34 "     50 2     66 B     82 R     98 b    114 r
+         3 ETX   19 DC3   35 #     51 3     67 C     83 S     99 c    115 s
+         4 EOT   20 DC4   36 $     52 4     68 D     84 T    100 d    116 t
+         5 ENQ   21 NAK   37 %     53 5     69 E     85 U    101 e    117 u
+         6 ACK   22 SYN   38 &     54 6     70 F     86 V    102 f    118 v
+         7 BEL   23 ETB   39 '     55 7     71 G     87 W    103 g    119 w
+         8 BS    24 CAN   40 (     56 8     72 H     88 X    104 h    120 x
+         9 HT    25 EM    41 )     57 9     73 I     89 Y    105 i    121 y
+        10 LF    26 SUB   42 *     58 :     74 J     90 Z    106 j    122 z
+        11 VT    27 ESC   43 +     59 ;     75 K     91 [    107 k    123 {
+        12 FF    28 FS    44 ,     60 <     76 L     92 \    108 l    124 |
+        13 CR    29 GS    45 -     61 =     77 M     93 ]    109 m    125 }
+        14 SO    30 RS    46 .     62 >     78 N     94 ^    110 n    126 ~
+        15 SI    31 US    47 /     63 ?     79 O     95 _    111 o    127 DEL
+        "#);
+    }
+}
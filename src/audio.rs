@@ -0,0 +1,178 @@
+//! Plays program output as tones: each byte becomes a short note whose pitch
+//! is the byte taken as a MIDI note number, and 0 is a rest.
+
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::process::{Child, Command, Stdio};
+
+const SAMPLE_RATE: u32 = 22050;
+const NOTE_SECONDS: f64 = 0.12;
+const AMPLITUDE: f64 = 0.3 * i16::MAX as f64;
+
+fn frequency(byte: u8) -> Option<f64> {
+    (byte != 0).then(|| 440.0 * 2f64.powf(((byte % 128) as f64 - 69.0) / 12.0))
+}
+
+/// Renders one note as signed 16-bit little-endian samples.
+pub fn synthesize(byte: u8) -> Vec<u8> {
+    let sample_count = (SAMPLE_RATE as f64 * NOTE_SECONDS) as usize;
+    let fade = sample_count / 10;
+
+    (0..sample_count)
+        .flat_map(|i| {
+            let sample = frequency(byte).map_or(0.0, |frequency| {
+                let envelope = (i.min(sample_count - i) as f64 / fade as f64).min(1.0);
+                (TAU * frequency * i as f64 / SAMPLE_RATE as f64).sin() * AMPLITUDE * envelope
+            });
+            (sample as i16).to_le_bytes()
+        })
+        .collect()
+}
+
+/// Turns bytes written to it into tones written to an audio sink.
+pub struct ToneWriter {
+    sink: Box<dyn Write>,
+}
+
+impl Write for ToneWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.sink.write_all(&synthesize(byte))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Plays tones live through `aplay`, or records them to `wav_path`.
+pub fn tone_writer(wav_path: Option<&str>) -> Result<Box<dyn Write>, String> {
+    let sink: Box<dyn Write> = match wav_path {
+        Some(path) => Box::new(WavFile::create(path)?),
+        None => Box::new(Player::spawn()?),
+    };
+
+    Ok(Box::new(ToneWriter { sink }))
+}
+
+/// A mono 16-bit WAV file whose header is kept up to date on every flush.
+struct WavFile {
+    file: File,
+    data_len: u32,
+}
+
+impl WavFile {
+    fn create(path: &str) -> Result<Self, String> {
+        let mut wav = Self {
+            file: File::create(path).map_err(|e| format!("Cannot write {}: {}", path, e))?,
+            data_len: 0,
+        };
+        wav.write_header()
+            .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        Ok(wav)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(36 + self.data_len).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        header.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.data_len.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl Write for WavFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.data_len += written as u32;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.file.flush()
+    }
+}
+
+/// Streams raw samples to an `aplay` process.
+struct Player {
+    child: Child,
+}
+
+impl Player {
+    fn spawn() -> Result<Self, String> {
+        let child = Command::new("aplay")
+            .args(["-q", "-t", "raw", "-f", "S16_LE", "-c", "1", "-r"])
+            .arg(SAMPLE_RATE.to_string())
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                format!(
+                    "Cannot start aplay for audio output ({}); pass --output <file.wav> to record instead",
+                    e
+                )
+            })?;
+        Ok(Self { child })
+    }
+}
+
+impl Write for Player {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.child.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.child.stdin {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        drop(self.child.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthesize() {
+        let a4 = synthesize(69);
+        let rest = synthesize(0);
+
+        insta::assert_debug_snapshot!(
+            (a4.len(), a4.iter().any(|&b| b != 0), rest.iter().all(|&b| b == 0)),
+            @r"
+        (
+            5292,
+            true,
+            true,
+        )
+        "
+        );
+    }
+}
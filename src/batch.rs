@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::source::read_source;
+use crate::{InterpreterBuilder, JumpTable, Token, parse_loops, tokenize_with};
+
+/// A case's input file and whether its run succeeded.
+type CaseResult = (PathBuf, Result<(), String>);
+
+/// Runs `filename` once for every file in `inputs_dir`, in parallel, writing
+/// each run's output to a file of the same name in `out_dir`.
+pub fn run_map(
+    filename: &str,
+    inputs_dir: &str,
+    out_dir: &str,
+    builder: &InterpreterBuilder,
+) -> Result<(), String> {
+    let code = read_source(filename)?;
+
+    let mut cases: Vec<PathBuf> = fs::read_dir(inputs_dir)
+        .map_err(|e| format!("Cannot read {}: {}", inputs_dir, e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    cases.sort();
+
+    fs::create_dir_all(out_dir).map_err(|e| format!("Cannot create {}: {}", out_dir, e))?;
+
+    let results = map_inputs(&code, &cases, Path::new(out_dir), builder)?;
+    let failures = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    for (case, result) in &results {
+        if let Err(e) = result {
+            eprintln!("{}: {}", case.display(), e);
+        }
+    }
+    eprintln!(
+        "Ran {} cases, {} failed; outputs are in {}",
+        results.len(),
+        failures,
+        out_dir
+    );
+
+    match failures {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} cases failed", failures, results.len())),
+    }
+}
+
+/// Parses `code` once and runs it against each of `cases` on a pool of
+/// threads, returning the outcome of each case in order.
+fn map_inputs(
+    code: &str,
+    cases: &[PathBuf],
+    out_dir: &Path,
+    builder: &InterpreterBuilder,
+) -> Result<Vec<CaseResult>, String> {
+    let extensions = builder.build().extensions();
+    let tokens = tokenize_with(code, extensions);
+    let jump_table = parse_loops(&tokens)?;
+
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(cases.len());
+    let next_case = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; cases.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next_case.fetch_add(1, Ordering::Relaxed);
+                    let Some(case) = cases.get(index) else {
+                        break;
+                    };
+                    let result = run_case(&tokens, &jump_table, builder, case, out_dir);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    Ok(cases
+        .iter()
+        .cloned()
+        .zip(results.into_inner().unwrap().into_iter().flatten())
+        .collect())
+}
+
+/// Runs one case. The output produced before an error is still written.
+fn run_case(
+    tokens: &[Token],
+    jump_table: &JumpTable,
+    builder: &InterpreterBuilder,
+    case: &Path,
+    out_dir: &Path,
+) -> Result<(), String> {
+    let input = fs::read(case).map_err(|e| format!("Cannot read input: {}", e))?;
+    let out_path = out_dir.join(case.file_name().unwrap_or_default());
+    let mut output = Vec::new();
+
+    let result = builder
+        .build()
+        .run_with_io(tokens, jump_table, &input[..], &mut output);
+
+    fs::write(&out_path, &output)
+        .map_err(|e| format!("Cannot write {}: {}", out_path.display(), e))?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_inputs() {
+        let dir = std::env::temp_dir().join(format!("bf-repl-map-{}", std::process::id()));
+        let (inputs, outputs) = (dir.join("cases"), dir.join("results"));
+        fs::create_dir_all(&inputs).unwrap();
+        fs::create_dir_all(&outputs).unwrap();
+        fs::write(inputs.join("a.txt"), "abc").unwrap();
+        fs::write(inputs.join("b.txt"), "").unwrap();
+        fs::write(inputs.join("c.txt"), "z").unwrap();
+
+        let cases: Vec<PathBuf> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| inputs.join(name))
+            .collect();
+        // Echoes the input with each byte incremented, moving right after
+        // each one; an empty input then runs off the left end of the tape.
+        let code = ",[+.>,]<<<<<<<<<";
+        let builder = InterpreterBuilder::default().tape_size(16);
+        let results: Vec<_> = map_inputs(code, &cases, &outputs, &builder)
+            .unwrap()
+            .into_iter()
+            .map(|(case, result)| {
+                let name = case.file_name().unwrap().to_string_lossy().into_owned();
+                let output = fs::read_to_string(outputs.join(&name)).unwrap();
+                (name, output, result)
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        insta::assert_debug_snapshot!(results, @r#"
+        [
+            (
+                "a.txt",
+                "bcd",
+                Ok(
+                    (),
+                ),
+            ),
+            (
+                "b.txt",
+                "",
+                Err(
+                    "Data pointer out of bounds (left)",
+                ),
+            ),
+            (
+                "c.txt",
+                "{",
+                Ok(
+                    (),
+                ),
+            ),
+        ]
+        "#);
+    }
+}
@@ -0,0 +1,350 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::cell::Cell;
+
+/// An integer of any size, for cells that never wrap around.
+///
+/// Stored as a sign and 32-bit limbs, least significant first, with no
+/// leading zero limbs; zero is never negative, so equal values compare and
+/// hash equal.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    /// Adds one to the magnitude.
+    fn grow(&mut self) {
+        for limb in &mut self.magnitude {
+            let (sum, carry) = limb.overflowing_add(1);
+            *limb = sum;
+            if !carry {
+                return;
+            }
+        }
+        self.magnitude.push(1);
+    }
+
+    /// Adds the value with the given sign and magnitude, whose limbs are
+    /// least significant first with no leading zeros.
+    fn add_signed(&mut self, negative: bool, mut magnitude: Vec<u32>) {
+        if self.negative == negative || self.is_zero() {
+            add_magnitudes(&mut self.magnitude, &magnitude);
+            self.negative = negative || self.negative;
+        } else if compare_magnitudes(&self.magnitude, &magnitude) == Ordering::Less {
+            subtract_magnitudes(&mut magnitude, &self.magnitude);
+            self.magnitude = magnitude;
+            self.negative = negative;
+        } else {
+            subtract_magnitudes(&mut self.magnitude, &magnitude);
+        }
+
+        while self.magnitude.last() == Some(&0) {
+            self.magnitude.pop();
+        }
+        if self.magnitude.is_empty() {
+            self.negative = false;
+        }
+    }
+
+    /// Subtracts one from the magnitude, which must not be zero.
+    fn shrink(&mut self) {
+        for limb in &mut self.magnitude {
+            let (difference, borrow) = limb.overflowing_sub(1);
+            *limb = difference;
+            if !borrow {
+                break;
+            }
+        }
+        if self.magnitude.last() == Some(&0) {
+            self.magnitude.pop();
+        }
+        if self.magnitude.is_empty() {
+            self.negative = false;
+        }
+    }
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(sum: &mut Vec<u32>, addend: &[u32]) {
+    if sum.len() < addend.len() {
+        sum.resize(addend.len(), 0);
+    }
+    let mut carry = 0u64;
+    for (i, limb) in sum.iter_mut().enumerate() {
+        let total = *limb as u64 + addend.get(i).copied().unwrap_or_default() as u64 + carry;
+        *limb = total as u32;
+        carry = total >> 32;
+        if carry == 0 && i >= addend.len() {
+            return;
+        }
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+}
+
+/// Subtracts `subtrahend` from `difference`, which must be at least as large.
+fn subtract_magnitudes(difference: &mut [u32], subtrahend: &[u32]) {
+    let mut borrow = false;
+    for (i, limb) in difference.iter_mut().enumerate() {
+        let other = subtrahend.get(i).copied().unwrap_or_default();
+        let (value, borrowed) = limb.overflowing_sub(other);
+        let (value, borrowed_again) = value.overflowing_sub(borrow as u32);
+        *limb = value;
+        borrow = borrowed || borrowed_again;
+        if !borrow && i >= subtrahend.len() {
+            return;
+        }
+    }
+}
+
+/// Returns `magnitude` times `factor`, with no leading zero limbs.
+fn scale(magnitude: &[u32], factor: u64) -> Vec<u32> {
+    if factor == 0 {
+        return Vec::new();
+    }
+    let mut product = Vec::with_capacity(magnitude.len() + 2);
+    let mut carry = 0u128;
+    for &limb in magnitude {
+        carry += limb as u128 * factor as u128;
+        product.push(carry as u32);
+        carry >>= 32;
+    }
+    while carry > 0 {
+        product.push(carry as u32);
+        carry >>= 32;
+    }
+    product
+}
+
+impl Cell for BigInt {
+    fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    fn increment(&mut self) {
+        if self.negative {
+            self.shrink();
+        } else {
+            self.grow();
+        }
+    }
+
+    fn decrement(&mut self) {
+        if self.is_zero() {
+            self.negative = true;
+            self.magnitude.push(1);
+        } else if self.negative {
+            self.grow();
+        } else {
+            self.shrink();
+        }
+    }
+
+    fn add(&mut self, delta: i64) {
+        let delta = Self::from(delta);
+        self.add_signed(delta.negative, delta.magnitude);
+    }
+
+    fn add_product(&mut self, value: &Self, factor: i64) {
+        self.add_signed(
+            value.negative != (factor < 0),
+            scale(&value.magnitude, factor.unsigned_abs()),
+        );
+    }
+
+    /// Counts past `u64::MAX` are clamped to it.
+    fn count_to_zero(&self, up: bool) -> Option<u64> {
+        if up != self.negative && !self.is_zero() {
+            return None;
+        }
+        Some(match self.magnitude[..] {
+            [] => 0,
+            [low] => low as u64,
+            [low, high] => (high as u64) << 32 | low as u64,
+            _ => u64::MAX,
+        })
+    }
+
+    fn is_max(&self) -> bool {
+        false
+    }
+
+    fn is_min(&self) -> bool {
+        false
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        Self::from(byte as i64)
+    }
+
+    /// Returns the low eight bits of the two's complement, so -1 is 255 as
+    /// with wrapping cells.
+    fn to_byte(&self) -> u8 {
+        let low = self.magnitude.first().copied().unwrap_or_default() as u8;
+        if self.negative {
+            low.wrapping_neg()
+        } else {
+            low
+        }
+    }
+
+    fn try_to_byte(&self) -> Option<u8> {
+        match self.magnitude[..] {
+            [] => Some(0),
+            [limb] if !self.negative => u8::try_from(limb).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let mut magnitude = value.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push(magnitude as u32);
+            magnitude >>= 32;
+        }
+        Self {
+            negative: value < 0,
+            magnitude: limbs,
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CHUNK: u64 = 1_000_000_000;
+
+        // Divide by 10^9 repeatedly, collecting nine digits at a time.
+        let mut limbs = self.magnitude.clone();
+        let mut chunks = Vec::new();
+        while !limbs.is_empty() {
+            let mut remainder = 0u64;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 32) | *limb as u64;
+                *limb = (value / CHUNK) as u32;
+                remainder = value % CHUNK;
+            }
+            chunks.push(remainder);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        let mut digits = String::from(if self.negative { "-" } else { "" });
+        match chunks.split_last() {
+            Some((most, rest)) => {
+                digits.push_str(&most.to_string());
+                for chunk in rest.iter().rev() {
+                    digits.push_str(&format!("{:09}", chunk));
+                }
+            }
+            None => digits.push('0'),
+        }
+        f.pad(&digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_big_int() {
+        let mut carried = BigInt::from(u32::MAX as i64);
+        carried.increment();
+        let mut borrowed = carried.clone();
+        borrowed.decrement();
+        let mut negative = BigInt::default();
+        negative.decrement();
+        let mut back_to_zero = negative.clone();
+        back_to_zero.increment();
+
+        let values = [
+            carried,
+            borrowed,
+            negative,
+            back_to_zero.clone(),
+            BigInt::from(i64::MIN),
+            BigInt::from(1_000_000_000_000_000_007),
+        ];
+
+        insta::assert_debug_snapshot!(
+            values.map(|value| (value.to_string(), value.to_byte(), value.try_to_byte())),
+            @r#"
+        [
+            (
+                "4294967296",
+                0,
+                None,
+            ),
+            (
+                "4294967295",
+                255,
+                None,
+            ),
+            (
+                "-1",
+                255,
+                None,
+            ),
+            (
+                "0",
+                0,
+                Some(
+                    0,
+                ),
+            ),
+            (
+                "-9223372036854775808",
+                0,
+                None,
+            ),
+            (
+                "1000000000000000007",
+                7,
+                None,
+            ),
+        ]
+        "#
+        );
+        assert_eq!(back_to_zero, BigInt::default());
+    }
+
+    #[test]
+    fn test_add_product() {
+        let mut values = [
+            BigInt::from(5),
+            BigInt::from(5),
+            BigInt::from(-5),
+            BigInt::from(u32::MAX as i64),
+            BigInt::from(i64::MAX),
+        ];
+        values[0].add(-7);
+        values[1].add_product(&BigInt::from(-3), -2);
+        values[2].add_product(&BigInt::from(5), 1);
+        values[3].add(1);
+        let square = values[4].clone();
+        values[4].add_product(&square, i64::MAX);
+
+        insta::assert_debug_snapshot!(values.map(|value| value.to_string()), @r#"
+        [
+            "-2",
+            "11",
+            "0",
+            "4294967296",
+            "85070591730234615856620279821087277056",
+        ]
+        "#);
+    }
+}
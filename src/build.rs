@@ -0,0 +1,138 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::bytecode::Bytecode;
+use crate::codegen::{Backend, BackendKind, Settings, lower};
+use crate::source::read_source;
+use crate::{parse_loops, tokenize};
+
+/// Compiles `filename` into a native executable at `output` (by default the
+/// file name without its extension) by way of the source `backend` generates.
+///
+/// The source is written to a temporary file and removed afterwards unless
+/// `keep_source` is set, in which case it is kept next to the executable.
+pub fn run_build(
+    filename: &str,
+    output: Option<&str>,
+    backend: BackendKind,
+    keep_source: bool,
+) -> Result<(), String> {
+    let backend = backend
+        .backend()
+        .filter(|backend| !backend.compilers().is_empty())
+        .ok_or("This target cannot be built into an executable; use compile")?;
+    let code = read_source(filename)?;
+    let tokens = tokenize(&code);
+    parse_loops(&tokens)?;
+
+    let executable = match output {
+        Some(output) => PathBuf::from(output),
+        None => default_executable(filename)?,
+    };
+    let source_path = if keep_source {
+        executable.with_extension(backend.extension())
+    } else {
+        env::temp_dir().join(format!(
+            "bf-repl-{}-{}.{}",
+            std::process::id(),
+            executable.file_name().unwrap_or_default().to_string_lossy(),
+            backend.extension()
+        ))
+    };
+
+    let source = backend.generate(&lower(&tokens), &Settings::default());
+    fs::write(&source_path, source)
+        .map_err(|e| format!("Cannot write {}: {}", source_path.display(), e))?;
+    let result = compile(backend.as_ref(), &source_path, &executable);
+    if !keep_source {
+        let _ = fs::remove_file(&source_path);
+    }
+    result?;
+
+    eprintln!("Built {}", executable.display());
+    if keep_source {
+        eprintln!("Generated source kept at {}", source_path.display());
+    }
+    Ok(())
+}
+
+/// Translates `filename` into source code with `backend`, or into bytecode,
+/// writing it to `output` or, without one, to stdout.
+pub fn run_compile(
+    filename: &str,
+    output: Option<&str>,
+    backend: BackendKind,
+    settings: &Settings,
+) -> Result<(), String> {
+    let backend = backend.backend();
+    if let Some(backend) = &backend {
+        backend.check(settings)?;
+    }
+    let code = read_source(filename)?;
+    let tokens = tokenize(&code);
+    parse_loops(&tokens)?;
+
+    let source = match backend {
+        Some(backend) => backend.generate(&lower(&tokens), settings),
+        None => Bytecode::compile(tokens)?.to_bytes(),
+    };
+    match output {
+        Some(path) => fs::write(path, source).map_err(|e| format!("Cannot write {}: {}", path, e)),
+        None => io::stdout()
+            .write_all(&source)
+            .map_err(|e| format!("Cannot write output: {}", e)),
+    }
+}
+
+fn default_executable(filename: &str) -> Result<PathBuf, String> {
+    let path = Path::new(filename);
+    let executable = path.with_extension(env::consts::EXE_EXTENSION);
+    if executable == path {
+        return Err(format!(
+            "Cannot derive an executable name from {}; pass -o <file>",
+            filename
+        ));
+    }
+    Ok(executable)
+}
+
+/// Runs the compiler named by `$CC` (which may include flags), or the first of the backend's compilers
+/// that can be started.
+fn compile(backend: &dyn Backend, source_path: &Path, executable: &Path) -> Result<(), String> {
+    let compilers = match env::var("CC") {
+        Ok(cc) if !cc.is_empty() => vec![cc],
+        _ => backend
+            .compilers()
+            .iter()
+            .map(|&cc| cc.to_string())
+            .collect(),
+    };
+
+    for compiler in &compilers {
+        let mut words = compiler.split_whitespace();
+        let Some(program) = words.next() else {
+            continue;
+        };
+        let status = Command::new(program)
+            .args(words)
+            .arg(backend.optimize_flag())
+            .arg("-o")
+            .arg(executable)
+            .arg(source_path)
+            .status();
+
+        match status {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => return Err(format!("{} failed with {}", compiler, status)),
+            Err(_) => continue,
+        }
+    }
+
+    Err(format!(
+        "No compiler found (tried {}); set CC to choose one",
+        compilers.join(", ")
+    ))
+}
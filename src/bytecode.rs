@@ -0,0 +1,245 @@
+//! The `.bfc` format: a program's tokens together with its [`ir`] form, so
+//! running it skips reading the source and compiling.
+//!
+//! After the magic bytes come the number of tokens and the tokens as their
+//! symbols, one byte each, then the number of instructions and each
+//! instruction as a tag byte, its operands, and the number of tokens it
+//! covers. Numbers are LEB128, zigzag-encoded where they can be negative.
+//! Each instruction starts where the one before it ends, so token indices
+//! are not stored.
+
+use std::fs;
+
+use crate::ir::{self, Instruction, Op};
+use crate::{Extensions, JumpTable, Token, parse_loops, tokenize_with};
+
+const MAGIC: &[u8] = b"\x7fBFC1";
+
+/// A compiled program, as stored in a `.bfc` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytecode {
+    pub tokens: Vec<Token>,
+    pub jump_table: JumpTable,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Bytecode {
+    pub fn compile(tokens: Vec<Token>) -> Result<Self, String> {
+        let jump_table = parse_loops(&tokens)?;
+        let instructions = ir::compile(&tokens, &jump_table);
+        Ok(Self {
+            tokens,
+            jump_table,
+            instructions,
+        })
+    }
+
+    pub fn is_bytecode(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        write_number(&mut bytes, self.tokens.len() as u64);
+        bytes.extend(self.tokens.iter().map(|token| token.symbol() as u8));
+
+        write_number(&mut bytes, self.instructions.len() as u64);
+        for instruction in &self.instructions {
+            match instruction.op {
+                Op::Add(delta) => {
+                    bytes.push(0);
+                    write_signed(&mut bytes, delta);
+                }
+                Op::Move(distance) => {
+                    bytes.push(1);
+                    write_signed(&mut bytes, distance as i64);
+                }
+                Op::Output => bytes.push(2),
+                Op::Input => bytes.push(3),
+                Op::JumpIfZero(target) | Op::JumpIfNonZero(target) => {
+                    bytes.push(if matches!(instruction.op, Op::JumpIfZero(_)) {
+                        4
+                    } else {
+                        5
+                    });
+                    write_number(&mut bytes, target.map_or(0, |target| target as u64 + 1));
+                }
+                Op::Refresh => bytes.push(6),
+                Op::Clear { up } => bytes.extend([7, up as u8]),
+                Op::Multiply { up, left, right } => {
+                    bytes.extend([8, up as u8]);
+                    write_number(&mut bytes, left as u64);
+                    write_number(&mut bytes, right as u64);
+                }
+                Op::Scan { right } => bytes.extend([9, right as u8]),
+            }
+            write_number(&mut bytes, instruction.len as u64);
+        }
+        bytes
+    }
+
+    /// Reads a program written by [`to_bytes`](Self::to_bytes), checking
+    /// that its instructions match its tokens.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let Some(rest) = bytes.strip_prefix(MAGIC) else {
+            return Err("Not a bytecode file".to_string());
+        };
+        let mut reader = Reader { bytes: rest };
+
+        let token_count = reader.number()?;
+        let symbols = reader.take(token_count)?;
+        let tokens = tokenize_with(
+            &String::from_utf8_lossy(symbols),
+            Extensions { graphics: true },
+        );
+        if tokens.len() != symbols.len() {
+            return Err("Bytecode has an unknown token".to_string());
+        }
+        let jump_table = parse_loops(&tokens)?;
+
+        let instruction_count = reader.number()?;
+        let mut instructions = Vec::new();
+        let mut token = 0usize;
+        for _ in 0..instruction_count {
+            let op = match reader.byte()? {
+                0 => Op::Add(reader.signed()?),
+                1 => Op::Move(reader.signed()? as isize),
+                2 => Op::Output,
+                3 => Op::Input,
+                tag @ (4 | 5) => {
+                    let target = reader.number()?.checked_sub(1);
+                    if tag == 4 {
+                        Op::JumpIfZero(target)
+                    } else {
+                        Op::JumpIfNonZero(target)
+                    }
+                }
+                6 => Op::Refresh,
+                7 => Op::Clear {
+                    up: reader.byte()? != 0,
+                },
+                8 => Op::Multiply {
+                    up: reader.byte()? != 0,
+                    left: reader.number()?.try_into().map_err(|_| invalid())?,
+                    right: reader.number()?.try_into().map_err(|_| invalid())?,
+                },
+                9 => Op::Scan {
+                    right: reader.byte()? != 0,
+                },
+                _ => return Err(invalid()),
+            };
+            let len = reader.number()?;
+            instructions.push(Instruction { op, token, len });
+            token = token.saturating_add(len);
+        }
+        if !reader.bytes.is_empty() {
+            return Err(invalid());
+        }
+
+        ir::validate(&tokens, &jump_table, &instructions)
+            .map_err(|e| format!("Corrupt bytecode: {}", e))?;
+        Ok(Self {
+            tokens,
+            jump_table,
+            instructions,
+        })
+    }
+
+    /// Reads `filename` if it is a bytecode file, and returns `None` if it
+    /// is anything else.
+    pub fn load(filename: &str) -> Result<Option<Self>, String> {
+        let bytes = fs::read(filename).map_err(|e| format!("Cannot read {}: {}", filename, e))?;
+        if !Self::is_bytecode(&bytes) {
+            return Ok(None);
+        }
+        Self::from_bytes(&bytes)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", filename, e))
+    }
+}
+
+fn invalid() -> String {
+    "Corrupt bytecode".to_string()
+}
+
+fn write_number(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn write_signed(bytes: &mut Vec<u8>, value: i64) {
+    write_number(bytes, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let (&byte, rest) = self.bytes.split_first().ok_or_else(invalid)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() {
+            return Err(invalid());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| invalid());
+            }
+        }
+        Err(invalid())
+    }
+
+    fn signed(&mut self) -> Result<i64, String> {
+        let value = self.number()? as u64;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize;
+
+    #[test]
+    fn test_round_trip() {
+        let bytecode = Bytecode::compile(tokenize("+++[->++<]>[<]--.,[+]")).unwrap();
+        let bytes = bytecode.to_bytes();
+        assert_eq!(Bytecode::from_bytes(&bytes).unwrap(), bytecode);
+
+        // Bytes that decode but do not match the tokens are rejected, here a
+        // `[->++<]` claiming never to leave its starting cell.
+        let mut tampered = bytecode.clone();
+        tampered.instructions[1].op = Op::Multiply {
+            up: false,
+            left: 0,
+            right: 0,
+        };
+        let mut corrupt = Vec::new();
+        for bytes in [tampered.to_bytes(), bytes[..bytes.len() - 1].to_vec()] {
+            corrupt.push(Bytecode::from_bytes(&bytes).unwrap_err());
+        }
+        insta::assert_debug_snapshot!(corrupt, @r#"
+        [
+            "Corrupt bytecode: Invalid instruction 1",
+            "Corrupt bytecode",
+        ]
+        "#);
+    }
+}
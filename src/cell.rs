@@ -0,0 +1,213 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// A value on the tape.
+///
+/// `+` and `-` wrap around at the ends of the cell's range. `,` stores the
+/// byte read and `.` writes the cell's low eight bits, so programs see the
+/// same bytes whatever the cell width.
+pub trait Cell: Clone + Default + PartialEq + Eq + Hash + Debug + Display + 'static {
+    fn is_zero(&self) -> bool;
+
+    fn increment(&mut self);
+
+    fn decrement(&mut self);
+
+    /// Adds `delta`, wrapping around like `increment` and `decrement`.
+    fn add(&mut self, delta: i64);
+
+    /// Adds `value` times `factor`, wrapping around like `add`.
+    fn add_product(&mut self, value: &Self, factor: i64);
+
+    /// Returns how many increments (if `up`) or decrements bring the cell to
+    /// zero, or `None` if they never do.
+    fn count_to_zero(&self, up: bool) -> Option<u64>;
+
+    /// Returns true if `increment` would wrap around.
+    fn is_max(&self) -> bool;
+
+    /// Returns true if `decrement` would wrap around.
+    fn is_min(&self) -> bool;
+
+    fn from_byte(byte: u8) -> Self;
+
+    /// Returns the low eight bits, which `.` writes.
+    fn to_byte(&self) -> u8;
+
+    /// Returns the value if it is a byte, for describing it as a character.
+    fn try_to_byte(&self) -> Option<u8>;
+
+    /// Returns the index of the first zero cell, which `[>]` stops on.
+    fn find_zero(cells: &[Self]) -> Option<usize> {
+        cells.iter().position(Self::is_zero)
+    }
+
+    /// Returns the index of the last zero cell, which `[<]` stops on.
+    fn rfind_zero(cells: &[Self]) -> Option<usize> {
+        cells.iter().rposition(Self::is_zero)
+    }
+}
+
+macro_rules! impl_cell {
+    ($($ty:ty $({ $($extra:item)* })?),*) => {
+        $(
+            impl Cell for $ty {
+                $($($extra)*)?
+
+                #[inline]
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+
+                #[inline]
+                fn increment(&mut self) {
+                    *self = self.wrapping_add(1);
+                }
+
+                #[inline]
+                fn decrement(&mut self) {
+                    *self = self.wrapping_sub(1);
+                }
+
+                #[inline]
+                fn add(&mut self, delta: i64) {
+                    // Truncating keeps the two's complement, so negative
+                    // deltas subtract.
+                    *self = self.wrapping_add(delta as $ty);
+                }
+
+                #[inline]
+                fn add_product(&mut self, value: &Self, factor: i64) {
+                    self.add(factor.wrapping_mul(*value as i64));
+                }
+
+                #[inline]
+                fn count_to_zero(&self, up: bool) -> Option<u64> {
+                    Some(if up { self.wrapping_neg() } else { *self } as u64)
+                }
+
+                #[inline]
+                fn is_max(&self) -> bool {
+                    *self == <$ty>::MAX
+                }
+
+                #[inline]
+                fn is_min(&self) -> bool {
+                    *self == 0
+                }
+
+                #[inline]
+                fn from_byte(byte: u8) -> Self {
+                    byte.into()
+                }
+
+                #[inline]
+                fn to_byte(&self) -> u8 {
+                    *self as u8
+                }
+
+                fn try_to_byte(&self) -> Option<u8> {
+                    u8::try_from(*self).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_cell!(
+    u8 {
+        #[cfg(unix)]
+        fn find_zero(cells: &[Self]) -> Option<usize> {
+            // SAFETY: memchr reads at most `cells.len()` bytes from the
+            // slice, and returns null or a pointer into it.
+            let found = unsafe { libc::memchr(cells.as_ptr().cast(), 0, cells.len()) };
+            (!found.is_null()).then(|| found as usize - cells.as_ptr() as usize)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn rfind_zero(cells: &[Self]) -> Option<usize> {
+            // SAFETY: as for memchr above.
+            let found = unsafe { libc::memrchr(cells.as_ptr().cast(), 0, cells.len()) };
+            (!found.is_null()).then(|| found as usize - cells.as_ptr() as usize)
+        }
+    },
+    u16,
+    u32
+);
+
+/// The cell width chosen on the command line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CellSize {
+    #[default]
+    Bits8,
+    Bits16,
+    Bits32,
+    /// Integers of any size; see [`BigInt`](crate::bignum::BigInt).
+    Big,
+}
+
+impl CellSize {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "8" => Ok(Self::Bits8),
+            "16" => Ok(Self::Bits16),
+            "32" => Ok(Self::Bits32),
+            "bignum" => Ok(Self::Big),
+            _ => Err(format!(
+                "Unknown cell size '{}' (expected 8, 16, 32, or bignum)",
+                name
+            )),
+        }
+    }
+}
+
+/// What `+` and `-` do at the ends of a cell's range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    /// Stay at the end of the range.
+    Saturate,
+    /// Fail the run.
+    Error,
+}
+
+impl Overflow {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "wrap" => Ok(Self::Wrap),
+            "saturate" => Ok(Self::Saturate),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Unknown overflow policy '{}' (expected wrap, saturate, or error)",
+                name
+            )),
+        }
+    }
+
+    /// Runs `+` on `cell`, which is the instruction at `index`.
+    #[inline]
+    pub fn increment<C: Cell>(self, cell: &mut C, index: usize) -> Result<(), String> {
+        if self == Self::Wrap || !cell.is_max() {
+            cell.increment();
+            Ok(())
+        } else if self == Self::Saturate {
+            Ok(())
+        } else {
+            Err(format!("Cell overflow at index {}", index))
+        }
+    }
+
+    /// Runs `-` on `cell`, which is the instruction at `index`.
+    #[inline]
+    pub fn decrement<C: Cell>(self, cell: &mut C, index: usize) -> Result<(), String> {
+        if self == Self::Wrap || !cell.is_min() {
+            cell.decrement();
+            Ok(())
+        } else if self == Self::Saturate {
+            Ok(())
+        } else {
+            Err(format!("Cell underflow at index {}", index))
+        }
+    }
+}
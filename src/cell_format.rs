@@ -0,0 +1,57 @@
+use crate::ascii;
+use crate::cell::Cell;
+
+/// How memory views show the value of each cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CellFormat {
+    #[default]
+    Decimal,
+    Hex,
+    /// Printable characters quoted, control characters by name, and other
+    /// values in decimal.
+    Ascii,
+    Binary,
+}
+
+impl CellFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "dec" | "decimal" => Ok(Self::Decimal),
+            "hex" => Ok(Self::Hex),
+            "ascii" => Ok(Self::Ascii),
+            "bin" | "binary" => Ok(Self::Binary),
+            _ => Err(format!(
+                "Unknown display format '{}' (expected dec, hex, ascii, or bin)",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Decimal => "dec",
+            Self::Hex => "hex",
+            Self::Ascii => "ascii",
+            Self::Binary => "bin",
+        }
+    }
+
+    /// The width of a column in a memory view.
+    pub fn width(self) -> usize {
+        match self {
+            Self::Binary => 9,
+            _ => 7,
+        }
+    }
+
+    /// Formats `value`. Cells wider than a byte that hold a value above 255
+    /// are always shown in decimal.
+    pub fn format<C: Cell>(self, value: &C) -> String {
+        match (self, value.try_to_byte()) {
+            (Self::Hex, Some(byte)) => format!("{:02X}", byte),
+            (Self::Ascii, Some(byte)) => ascii::describe(byte).unwrap_or_else(|| byte.to_string()),
+            (Self::Binary, Some(byte)) => format!("{:08b}", byte),
+            _ => value.to_string(),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::analysis::{
+    find_counting_loops, loop_diagnostics, loop_warnings, termination_diagnostics,
+    termination_report,
+};
+use crate::diagnostic::ErrorFormat;
+use crate::source::read_source;
+use crate::{match_loops, tokenize};
+
+/// Reports problems in `filename` without running it; fails if any are found.
+///
+/// With `analyze`, also reports whether each counting loop terminates. In
+/// JSON format only the problems themselves are printed.
+pub fn run_check(filename: &str, analyze: bool, error_format: ErrorFormat) -> Result<(), String> {
+    let code = read_source(filename)?;
+    let sources = [(filename, code.as_str())];
+    let tokens = tokenize(&code);
+    let jump_table = match_loops(&tokens)
+        .map_err(|diagnostic| error_format.report_error(diagnostic, &sources))?;
+
+    if error_format == ErrorFormat::Json {
+        let mut diagnostics = loop_diagnostics(&tokens, &jump_table);
+        if analyze {
+            diagnostics.extend(termination_diagnostics(&tokens, &jump_table));
+        }
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic.to_json(&sources));
+        }
+        return match diagnostics.len() {
+            0 => Ok(()),
+            _ => Err(String::new()),
+        };
+    }
+
+    let warnings = loop_warnings(&code, &tokens, &jump_table);
+    let mut problems = warnings.len();
+
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+
+    if analyze {
+        for line in termination_report(&code, &tokens, &jump_table) {
+            eprintln!("{}", line);
+        }
+        problems += find_counting_loops(&tokens, &jump_table)
+            .iter()
+            .filter(|counting_loop| counting_loop.never_ends())
+            .count();
+    }
+
+    match problems {
+        0 => {
+            eprintln!("No problems found in {}", filename);
+            Ok(())
+        }
+        1 => Err(format!("1 problem found in {}", filename)),
+        count => Err(format!("{} problems found in {}", count, filename)),
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::Write;
+
+/// Copies `data` to the system clipboard and returns a description of how.
+///
+/// The first clipboard tool that can be started is used (`pbcopy`,
+/// `wl-copy`, `xclip`, `xsel`, or `clip.exe`); without one, the data is sent
+/// to the terminal through `terminal` as an OSC 52 escape sequence, which
+/// most terminal emulators honor, even over SSH.
+#[cfg(feature = "clipboard")]
+pub fn copy(data: &[u8], terminal: &mut dyn Write) -> Result<String, String> {
+    use std::process::{Command, Stdio};
+
+    const TOOLS: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+        ("clip.exe", &[]),
+    ];
+
+    for (tool, args) in TOOLS {
+        let Ok(mut child) = Command::new(tool)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(data).is_ok());
+        if written && child.wait().is_ok_and(|status| status.success()) {
+            return Ok(tool.to_string());
+        }
+    }
+
+    write!(terminal, "\x1b]52;c;{}\x07", base64(data))
+        .and_then(|_| terminal.flush())
+        .map_err(|e| e.to_string())?;
+    Ok("the terminal".to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_data: &[u8], _terminal: &mut dyn Write) -> Result<String, String> {
+    Err("Clipboard support requires building with --features clipboard".to_string())
+}
+
+#[cfg(feature = "clipboard")]
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    data.chunks(3)
+        .flat_map(|chunk| {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+                bits | (byte as u32) << (16 - 8 * i)
+            });
+            (0..4).map(move |i| {
+                if i <= chunk.len() {
+                    ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char
+                } else {
+                    '='
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "clipboard"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        insta::assert_debug_snapshot!(["", "f", "fo", "foo", "foob"].map(|s| base64(s.as_bytes())), @r#"
+        [
+            "",
+            "Zg==",
+            "Zm8=",
+            "Zm9v",
+            "Zm9vYg==",
+        ]
+        "#);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::cell::CellSize;
+use crate::{EofBehavior, Interpreter, Token};
+
+mod c;
+#[cfg(feature = "llvm")]
+mod llvm;
+mod rust;
+mod wasm;
+
+pub use c::CBackend;
+#[cfg(feature = "llvm")]
+pub use llvm::LlvmBackend;
+pub use rust::RustBackend;
+pub use wasm::WasmBackend;
+
+/// An operation in the form handed to backends: runs of `+`/`-` and `<`/`>`
+/// are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Adds the number of `+` minus the number of `-` to the current cell,
+    /// wrapping around at the ends of its range.
+    Add(i64),
+    /// Moves the data pointer by `shift`, having moved as far as `min` and
+    /// `max` (relative to the start) along the way.
+    Move {
+        shift: isize,
+        min: isize,
+        max: isize,
+    },
+    Output,
+    Input,
+    LoopStart,
+    LoopEnd,
+}
+
+/// Merges runs of tokens into [`Op`]s, dropping ones with no effect.
+pub fn lower(tokens: &[Token]) -> Vec<Op> {
+    let mut ops = Vec::new();
+
+    for token in tokens {
+        let last = ops.last_mut();
+        match (token, last) {
+            (Token::IncrementData, Some(Op::Add(delta))) => *delta += 1,
+            (Token::DecrementData, Some(Op::Add(delta))) => *delta -= 1,
+            (Token::IncrementData, _) => ops.push(Op::Add(1)),
+            (Token::DecrementData, _) => ops.push(Op::Add(-1)),
+            (Token::IncrementPointer | Token::DecrementPointer, last) => {
+                let step = if *token == Token::IncrementPointer {
+                    1
+                } else {
+                    -1
+                };
+                match last {
+                    Some(Op::Move { shift, min, max }) => {
+                        *shift += step;
+                        *min = (*min).min(*shift);
+                        *max = (*max).max(*shift);
+                    }
+                    _ => ops.push(Op::Move {
+                        shift: step,
+                        min: step.min(0),
+                        max: step.max(0),
+                    }),
+                }
+            }
+            (Token::Output, _) => ops.push(Op::Output),
+            (Token::Input, _) => ops.push(Op::Input),
+            (Token::LoopStart, _) => ops.push(Op::LoopStart),
+            (Token::LoopEnd, _) => ops.push(Op::LoopEnd),
+            (Token::Refresh, _) => {}
+        }
+
+        if ops.last() == Some(&Op::Add(0)) {
+            ops.pop();
+        }
+    }
+
+    ops
+}
+
+/// How generated programs behave where the interpreter can be configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub tape_size: usize,
+    pub cell_size: CellSize,
+    pub eof: EofBehavior,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tape_size: Interpreter::DEFAULT_MEMORY_SIZE,
+            cell_size: CellSize::Bits8,
+            eof: EofBehavior::Zero,
+        }
+    }
+}
+
+/// Translates a program into source code for a native compiler, or into a
+/// module a host runs.
+///
+/// Generated programs behave like the interpreter: cells wrap, input at end
+/// of file is handled as `settings` says, and moving off either end of the
+/// tape is a runtime error.
+pub trait Backend {
+    /// The extension of the generated source file.
+    fn extension(&self) -> &'static str;
+
+    /// Compilers that accept the generated source, tried in order. Empty if
+    /// `build` cannot turn it into an executable.
+    fn compilers(&self) -> &'static [&'static str];
+
+    /// The flag that turns on the compiler's optimizations.
+    fn optimize_flag(&self) -> &'static str {
+        "-O2"
+    }
+
+    /// Returns an error if generated programs cannot behave as `settings`
+    /// says. By default only 8-bit cells reading 0 at end of file are
+    /// supported.
+    fn check(&self, settings: &Settings) -> Result<(), String> {
+        if settings.cell_size != CellSize::Bits8 || settings.eof != EofBehavior::Zero {
+            return Err(
+                "This target supports only 8-bit cells and --eof 0 (try --target rust)".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn generate(&self, ops: &[Op], settings: &Settings) -> Vec<u8>;
+}
+
+/// The backends `build` and `compile` can use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    #[default]
+    C,
+    #[cfg(feature = "llvm")]
+    Llvm,
+    Rust,
+    Wasm,
+    /// The `.bfc` format `run` loads; see [`bytecode`](crate::bytecode).
+    Bytecode,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "c" => Ok(Self::C),
+            #[cfg(feature = "llvm")]
+            "llvm" => Ok(Self::Llvm),
+            #[cfg(not(feature = "llvm"))]
+            "llvm" => Err("The LLVM backend requires building with --features llvm".to_string()),
+            "rust" => Ok(Self::Rust),
+            "wasm" => Ok(Self::Wasm),
+            "bfc" => Ok(Self::Bytecode),
+            _ => Err(format!(
+                "Unknown backend '{}' (expected 'c', 'llvm', 'rust', 'wasm', or 'bfc')",
+                name
+            )),
+        }
+    }
+
+    /// Returns `None` for [`Self::Bytecode`], which is made from the
+    /// interpreter's instructions rather than [`Op`]s.
+    pub fn backend(self) -> Option<Box<dyn Backend>> {
+        match self {
+            Self::C => Some(Box::new(CBackend)),
+            #[cfg(feature = "llvm")]
+            Self::Llvm => Some(Box::new(LlvmBackend)),
+            Self::Rust => Some(Box::new(RustBackend)),
+            Self::Wasm => Some(Box::new(WasmBackend)),
+            Self::Bytecode => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize;
+
+    #[test]
+    fn test_lower() {
+        insta::assert_debug_snapshot!(lower(&tokenize("++-+-[>+<<>-]>>.,%")), @r"
+        [
+            Add(
+                1,
+            ),
+            LoopStart,
+            Move {
+                shift: 1,
+                min: 0,
+                max: 1,
+            },
+            Add(
+                1,
+            ),
+            Move {
+                shift: -1,
+                min: -2,
+                max: 0,
+            },
+            Add(
+                -1,
+            ),
+            LoopEnd,
+            Move {
+                shift: 2,
+                min: 0,
+                max: 2,
+            },
+            Output,
+            Input,
+        ]
+        ");
+    }
+}
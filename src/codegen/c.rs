@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+use super::{Backend, Op, Settings};
+
+/// Generates portable C.
+pub struct CBackend;
+
+impl Backend for CBackend {
+    fn extension(&self) -> &'static str {
+        "c"
+    }
+
+    fn compilers(&self) -> &'static [&'static str] {
+        &["cc", "gcc", "clang"]
+    }
+
+    fn generate(&self, ops: &[Op], settings: &Settings) -> Vec<u8> {
+        let mut c = format!(
+            "#include <stdio.h>
+#include <stdlib.h>
+
+#define TAPE_SIZE {}
+
+static unsigned char tape[TAPE_SIZE];
+
+static void out_of_bounds(const char *side) {{
+    fflush(stdout);
+    fprintf(stderr, \"Data pointer out of bounds (%s)\\n\", side);
+    exit(1);
+}}
+
+int main(void) {{
+    size_t dp = TAPE_SIZE / 2;
+",
+            settings.tape_size
+        );
+        let mut depth = 1;
+
+        for op in ops {
+            if *op == Op::LoopEnd {
+                depth -= 1;
+            }
+            let indent = "    ".repeat(depth);
+
+            match *op {
+                Op::Add(delta) => match delta as u8 {
+                    0 => {}
+                    delta @ 0..128 => writeln!(c, "{}tape[dp] += {};", indent, delta).unwrap(),
+                    delta => {
+                        writeln!(c, "{}tape[dp] -= {};", indent, delta.wrapping_neg()).unwrap()
+                    }
+                },
+                Op::Move { shift, min, max } => {
+                    if min < 0 {
+                        writeln!(c, "{}if (dp < {}) out_of_bounds(\"left\");", indent, -min)
+                            .unwrap();
+                    }
+                    if max > 0 {
+                        writeln!(
+                            c,
+                            "{}if (dp + {} >= TAPE_SIZE) out_of_bounds(\"right\");",
+                            indent, max
+                        )
+                        .unwrap();
+                    }
+                    match shift {
+                        0 => {}
+                        shift if shift > 0 => writeln!(c, "{}dp += {};", indent, shift).unwrap(),
+                        shift => writeln!(c, "{}dp -= {};", indent, -shift).unwrap(),
+                    }
+                }
+                Op::Output => writeln!(c, "{}putchar(tape[dp]);", indent).unwrap(),
+                Op::Input => writeln!(
+                    c,
+                    "{}{{ fflush(stdout); int byte = getchar(); tape[dp] = byte == EOF ? 0 : byte; }}",
+                    indent
+                )
+                .unwrap(),
+                Op::LoopStart => {
+                    writeln!(c, "{}while (tape[dp]) {{", indent).unwrap();
+                    depth += 1;
+                }
+                Op::LoopEnd => writeln!(c, "{}}}", indent).unwrap(),
+            }
+        }
+
+        c.push_str("    fflush(stdout);\n    return 0;\n}\n");
+        c.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::lower;
+    use crate::tokenize;
+
+    #[test]
+    fn test_c_backend() {
+        insta::assert_snapshot!(String::from_utf8(CBackend.generate(
+            &lower(&tokenize("++-[>+<<>-]>>.,")),
+            &Settings {
+                tape_size: 100,
+                ..Settings::default()
+            },
+        ))
+        .unwrap(), @r#"
+        #include <stdio.h>
+        #include <stdlib.h>
+
+        #define TAPE_SIZE 100
+
+        static unsigned char tape[TAPE_SIZE];
+
+        static void out_of_bounds(const char *side) {
+            fflush(stdout);
+            fprintf(stderr, "Data pointer out of bounds (%s)\n", side);
+            exit(1);
+        }
+
+        int main(void) {
+            size_t dp = TAPE_SIZE / 2;
+            tape[dp] += 1;
+            while (tape[dp]) {
+                if (dp + 1 >= TAPE_SIZE) out_of_bounds("right");
+                dp += 1;
+                tape[dp] += 1;
+                if (dp < 2) out_of_bounds("left");
+                dp -= 1;
+                tape[dp] -= 1;
+            }
+            if (dp + 2 >= TAPE_SIZE) out_of_bounds("right");
+            dp += 2;
+            putchar(tape[dp]);
+            { fflush(stdout); int byte = getchar(); tape[dp] = byte == EOF ? 0 : byte; }
+            fflush(stdout);
+            return 0;
+        }
+        "#);
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt::Write;
+
+use super::{Backend, Op, Settings};
+
+const LEFT: &str = "Data pointer out of bounds (left)\n";
+const RIGHT: &str = "Data pointer out of bounds (right)\n";
+
+/// Generates textual LLVM IR, which `clang` compiles (and cross-compiles
+/// with `--target`) like any other source file.
+pub struct LlvmBackend;
+
+impl Backend for LlvmBackend {
+    fn extension(&self) -> &'static str {
+        "ll"
+    }
+
+    fn compilers(&self) -> &'static [&'static str] {
+        &["clang"]
+    }
+
+    fn generate(&self, ops: &[Op], settings: &Settings) -> Vec<u8> {
+        let tape_size = settings.tape_size;
+        let mut ir = format!(
+            "@tape = internal global [{size} x i8] zeroinitializer
+@left = private constant [{left_len} x i8] c\"{left}\"
+@right = private constant [{right_len} x i8] c\"{right}\"
+
+declare i32 @putchar(i32)
+declare i32 @getchar()
+declare i32 @fflush(ptr)
+declare i64 @write(i32, ptr, i64)
+declare void @exit(i32) noreturn
+
+define internal void @out_of_bounds(ptr %message, i64 %len) noreturn {{
+  call i32 @fflush(ptr null)
+  call i64 @write(i32 2, ptr %message, i64 %len)
+  call void @exit(i32 1)
+  unreachable
+}}
+
+define i32 @main() {{
+entry:
+  %dp = alloca i64
+  store i64 {start}, ptr %dp
+",
+            size = tape_size,
+            start = tape_size / 2,
+            left_len = LEFT.len(),
+            left = escape(LEFT),
+            right_len = RIGHT.len(),
+            right = escape(RIGHT),
+        );
+        let mut function = Function {
+            ir: &mut ir,
+            tape_size,
+            next_value: 0,
+            next_label: 0,
+            loops: Vec::new(),
+        };
+
+        for op in ops {
+            function.emit(*op);
+        }
+
+        ir.push_str(&format!(
+            "  call i32 @fflush(ptr null)
+  ret i32 0
+
+oob_left:
+  call void @out_of_bounds(ptr @left, i64 {})
+  unreachable
+
+oob_right:
+  call void @out_of_bounds(ptr @right, i64 {})
+  unreachable
+}}
+",
+            LEFT.len(),
+            RIGHT.len()
+        ));
+        ir.into_bytes()
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+            _ => format!("\\{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The body of `main` being generated.
+struct Function<'a> {
+    ir: &'a mut String,
+    tape_size: usize,
+    next_value: usize,
+    next_label: usize,
+    /// Label numbers of the enclosing loops.
+    loops: Vec<usize>,
+}
+
+impl Function<'_> {
+    fn value(&mut self) -> String {
+        self.next_value += 1;
+        format!("%v{}", self.next_value)
+    }
+
+    fn label(&mut self) -> usize {
+        self.next_label += 1;
+        self.next_label
+    }
+
+    /// Loads the data pointer and returns it and the address of its cell.
+    fn cell(&mut self) -> (String, String) {
+        let (dp, cell) = (self.value(), self.value());
+        writeln!(self.ir, "  {} = load i64, ptr %dp", dp).unwrap();
+        writeln!(
+            self.ir,
+            "  {} = getelementptr inbounds [{} x i8], ptr @tape, i64 0, i64 {}",
+            cell, self.tape_size, dp
+        )
+        .unwrap();
+        (dp, cell)
+    }
+
+    /// Branches to `failure` if `condition` holds and continues otherwise.
+    fn check(&mut self, condition: &str, failure: &str) {
+        let label = self.label();
+        writeln!(
+            self.ir,
+            "  br i1 {}, label %{}, label %ok{}\nok{}:",
+            condition, failure, label, label
+        )
+        .unwrap();
+    }
+
+    fn emit(&mut self, op: Op) {
+        match op {
+            Op::Add(delta) => {
+                let (_, cell) = self.cell();
+                let (old, new) = (self.value(), self.value());
+                writeln!(self.ir, "  {} = load i8, ptr {}", old, cell).unwrap();
+                writeln!(self.ir, "  {} = add i8 {}, {}", new, old, delta as u8).unwrap();
+                writeln!(self.ir, "  store i8 {}, ptr {}", new, cell).unwrap();
+            }
+            Op::Move { shift, min, max } => {
+                let dp = self.value();
+                writeln!(self.ir, "  {} = load i64, ptr %dp", dp).unwrap();
+                if min < 0 {
+                    let below = self.value();
+                    writeln!(self.ir, "  {} = icmp ult i64 {}, {}", below, dp, -min).unwrap();
+                    self.check(&below, "oob_left");
+                }
+                if max > 0 {
+                    let limit = self.tape_size.saturating_sub(max as usize);
+                    let beyond = self.value();
+                    writeln!(self.ir, "  {} = icmp uge i64 {}, {}", beyond, dp, limit).unwrap();
+                    self.check(&beyond, "oob_right");
+                }
+                if shift != 0 {
+                    let moved = self.value();
+                    writeln!(self.ir, "  {} = add i64 {}, {}", moved, dp, shift).unwrap();
+                    writeln!(self.ir, "  store i64 {}, ptr %dp", moved).unwrap();
+                }
+            }
+            Op::Output => {
+                let (_, cell) = self.cell();
+                let (byte, char) = (self.value(), self.value());
+                writeln!(self.ir, "  {} = load i8, ptr {}", byte, cell).unwrap();
+                writeln!(self.ir, "  {} = zext i8 {} to i32", char, byte).unwrap();
+                writeln!(self.ir, "  call i32 @putchar(i32 {})", char).unwrap();
+            }
+            Op::Input => {
+                writeln!(self.ir, "  call i32 @fflush(ptr null)").unwrap();
+                let (_, cell) = self.cell();
+                let (char, eof, byte, value) =
+                    (self.value(), self.value(), self.value(), self.value());
+                writeln!(self.ir, "  {} = call i32 @getchar()", char).unwrap();
+                writeln!(self.ir, "  {} = icmp slt i32 {}, 0", eof, char).unwrap();
+                writeln!(self.ir, "  {} = trunc i32 {} to i8", byte, char).unwrap();
+                writeln!(
+                    self.ir,
+                    "  {} = select i1 {}, i8 0, i8 {}",
+                    value, eof, byte
+                )
+                .unwrap();
+                writeln!(self.ir, "  store i8 {}, ptr {}", value, cell).unwrap();
+            }
+            Op::LoopStart => {
+                let label = self.label();
+                self.loops.push(label);
+                writeln!(self.ir, "  br label %loop{}\nloop{}:", label, label).unwrap();
+                let (_, cell) = self.cell();
+                let (byte, nonzero) = (self.value(), self.value());
+                writeln!(self.ir, "  {} = load i8, ptr {}", byte, cell).unwrap();
+                writeln!(self.ir, "  {} = icmp ne i8 {}, 0", nonzero, byte).unwrap();
+                writeln!(
+                    self.ir,
+                    "  br i1 {}, label %body{}, label %end{}\nbody{}:",
+                    nonzero, label, label, label
+                )
+                .unwrap();
+            }
+            Op::LoopEnd => {
+                let label = self.loops.pop().expect("loops are balanced");
+                writeln!(self.ir, "  br label %loop{}\nend{}:", label, label).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::lower;
+    use crate::tokenize;
+
+    #[test]
+    fn test_llvm_backend() {
+        insta::assert_snapshot!(String::from_utf8(LlvmBackend.generate(
+            &lower(&tokenize("+[>-<-]<.,")),
+            &Settings {
+                tape_size: 8,
+                ..Settings::default()
+            },
+        ))
+        .unwrap(), @r#"
+        @tape = internal global [8 x i8] zeroinitializer
+        @left = private constant [34 x i8] c"Data pointer out of bounds (left)\0A"
+        @right = private constant [35 x i8] c"Data pointer out of bounds (right)\0A"
+
+        declare i32 @putchar(i32)
+        declare i32 @getchar()
+        declare i32 @fflush(ptr)
+        declare i64 @write(i32, ptr, i64)
+        declare void @exit(i32) noreturn
+
+        define internal void @out_of_bounds(ptr %message, i64 %len) noreturn {
+          call i32 @fflush(ptr null)
+          call i64 @write(i32 2, ptr %message, i64 %len)
+          call void @exit(i32 1)
+          unreachable
+        }
+
+        define i32 @main() {
+        entry:
+          %dp = alloca i64
+          store i64 4, ptr %dp
+          %v1 = load i64, ptr %dp
+          %v2 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v1
+          %v3 = load i8, ptr %v2
+          %v4 = add i8 %v3, 1
+          store i8 %v4, ptr %v2
+          br label %loop1
+        loop1:
+          %v5 = load i64, ptr %dp
+          %v6 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v5
+          %v7 = load i8, ptr %v6
+          %v8 = icmp ne i8 %v7, 0
+          br i1 %v8, label %body1, label %end1
+        body1:
+          %v9 = load i64, ptr %dp
+          %v10 = icmp uge i64 %v9, 7
+          br i1 %v10, label %oob_right, label %ok2
+        ok2:
+          %v11 = add i64 %v9, 1
+          store i64 %v11, ptr %dp
+          %v12 = load i64, ptr %dp
+          %v13 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v12
+          %v14 = load i8, ptr %v13
+          %v15 = add i8 %v14, 255
+          store i8 %v15, ptr %v13
+          %v16 = load i64, ptr %dp
+          %v17 = icmp ult i64 %v16, 1
+          br i1 %v17, label %oob_left, label %ok3
+        ok3:
+          %v18 = add i64 %v16, -1
+          store i64 %v18, ptr %dp
+          %v19 = load i64, ptr %dp
+          %v20 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v19
+          %v21 = load i8, ptr %v20
+          %v22 = add i8 %v21, 255
+          store i8 %v22, ptr %v20
+          br label %loop1
+        end1:
+          %v23 = load i64, ptr %dp
+          %v24 = icmp ult i64 %v23, 1
+          br i1 %v24, label %oob_left, label %ok4
+        ok4:
+          %v25 = add i64 %v23, -1
+          store i64 %v25, ptr %dp
+          %v26 = load i64, ptr %dp
+          %v27 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v26
+          %v28 = load i8, ptr %v27
+          %v29 = zext i8 %v28 to i32
+          call i32 @putchar(i32 %v29)
+          call i32 @fflush(ptr null)
+          %v30 = load i64, ptr %dp
+          %v31 = getelementptr inbounds [8 x i8], ptr @tape, i64 0, i64 %v30
+          %v32 = call i32 @getchar()
+          %v33 = icmp slt i32 %v32, 0
+          %v34 = trunc i32 %v32 to i8
+          %v35 = select i1 %v33, i8 0, i8 %v34
+          store i8 %v35, ptr %v31
+          call i32 @fflush(ptr null)
+          ret i32 0
+
+        oob_left:
+          call void @out_of_bounds(ptr @left, i64 34)
+          unreachable
+
+        oob_right:
+          call void @out_of_bounds(ptr @right, i64 35)
+          unreachable
+        }
+        "#);
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt::Write;
+
+use super::{Backend, Op, Settings};
+use crate::EofBehavior;
+use crate::cell::CellSize;
+
+/// Generates a standalone Rust program, which supports every cell size but
+/// bignum and every EOF behavior.
+pub struct RustBackend;
+
+impl Backend for RustBackend {
+    fn extension(&self) -> &'static str {
+        "rs"
+    }
+
+    fn compilers(&self) -> &'static [&'static str] {
+        &["rustc"]
+    }
+
+    fn optimize_flag(&self) -> &'static str {
+        "-O"
+    }
+
+    fn check(&self, settings: &Settings) -> Result<(), String> {
+        if settings.cell_size == CellSize::Big {
+            return Err("The rust target does not support bignum cells".to_string());
+        }
+        Ok(())
+    }
+
+    fn generate(&self, ops: &[Op], settings: &Settings) -> Vec<u8> {
+        let bits = match settings.cell_size {
+            CellSize::Bits8 => 8,
+            CellSize::Bits16 => 16,
+            CellSize::Bits32 | CellSize::Big => 32,
+        };
+        let eof = match settings.eof {
+            EofBehavior::Zero => "*cell = 0",
+            EofBehavior::MinusOne => "*cell = Cell::MAX",
+            EofBehavior::Unchanged => "{}",
+        };
+        let mut rust = format!(
+            "#![allow(unused)]
+
+use std::io::{{self, BufWriter, Read, Write}};
+use std::process;
+
+const TAPE_SIZE: usize = {};
+
+type Cell = u{};
+
+fn out_of_bounds(output: &mut impl Write, side: &str) -> ! {{
+    let _ = output.flush();
+    eprintln!(\"Data pointer out of bounds ({{}})\", side);
+    process::exit(1);
+}}
+
+fn read(input: &mut impl Read, output: &mut impl Write, cell: &mut Cell) {{
+    let _ = output.flush();
+    let mut byte = [0];
+    match input.read(&mut byte) {{
+        Ok(1) => *cell = byte[0] as Cell,
+        _ => {},
+    }}
+}}
+
+fn main() {{
+    let mut tape: Vec<Cell> = vec![0; TAPE_SIZE];
+    let mut dp = TAPE_SIZE / 2;
+    let mut input = io::stdin().lock();
+    let mut output = BufWriter::new(io::stdout().lock());
+",
+            settings.tape_size, bits, eof
+        );
+        let modulus = 1i64 << bits;
+        let mut depth = 1;
+
+        for op in ops {
+            if *op == Op::LoopEnd {
+                depth -= 1;
+            }
+            let indent = "    ".repeat(depth);
+
+            match *op {
+                Op::Add(delta) => match delta.rem_euclid(modulus) {
+                    0 => {}
+                    delta if delta < modulus / 2 => writeln!(
+                        rust,
+                        "{}tape[dp] = tape[dp].wrapping_add({});",
+                        indent, delta
+                    )
+                    .unwrap(),
+                    delta => writeln!(
+                        rust,
+                        "{}tape[dp] = tape[dp].wrapping_sub({});",
+                        indent,
+                        modulus - delta
+                    )
+                    .unwrap(),
+                },
+                Op::Move { shift, min, max } => {
+                    if min < 0 {
+                        writeln!(
+                            rust,
+                            "{}if dp < {} {{ out_of_bounds(&mut output, \"left\"); }}",
+                            indent, -min
+                        )
+                        .unwrap();
+                    }
+                    if max > 0 {
+                        writeln!(
+                            rust,
+                            "{}if dp + {} >= TAPE_SIZE {{ out_of_bounds(&mut output, \"right\"); }}",
+                            indent, max
+                        )
+                        .unwrap();
+                    }
+                    match shift {
+                        0 => {}
+                        shift if shift > 0 => writeln!(rust, "{}dp += {};", indent, shift).unwrap(),
+                        shift => writeln!(rust, "{}dp -= {};", indent, -shift).unwrap(),
+                    }
+                }
+                Op::Output => writeln!(
+                    rust,
+                    "{}let _ = output.write_all(&[tape[dp] as u8]);",
+                    indent
+                )
+                .unwrap(),
+                Op::Input => writeln!(
+                    rust,
+                    "{}read(&mut input, &mut output, &mut tape[dp]);",
+                    indent
+                )
+                .unwrap(),
+                Op::LoopStart => {
+                    writeln!(rust, "{}while tape[dp] != 0 {{", indent).unwrap();
+                    depth += 1;
+                }
+                Op::LoopEnd => writeln!(rust, "{}}}", indent).unwrap(),
+            }
+        }
+
+        rust.push_str("    let _ = output.flush();\n}\n");
+        rust.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::lower;
+    use crate::tokenize;
+
+    #[test]
+    fn test_rust_backend() {
+        let settings = Settings {
+            tape_size: 100,
+            cell_size: CellSize::Bits16,
+            eof: EofBehavior::MinusOne,
+        };
+        insta::assert_snapshot!(String::from_utf8(RustBackend.generate(&lower(&tokenize("++-[>+<<>-]>>.,")), &settings)).unwrap(), @r#"
+        #![allow(unused)]
+
+        use std::io::{self, BufWriter, Read, Write};
+        use std::process;
+
+        const TAPE_SIZE: usize = 100;
+
+        type Cell = u16;
+
+        fn out_of_bounds(output: &mut impl Write, side: &str) -> ! {
+            let _ = output.flush();
+            eprintln!("Data pointer out of bounds ({})", side);
+            process::exit(1);
+        }
+
+        fn read(input: &mut impl Read, output: &mut impl Write, cell: &mut Cell) {
+            let _ = output.flush();
+            let mut byte = [0];
+            match input.read(&mut byte) {
+                Ok(1) => *cell = byte[0] as Cell,
+                _ => *cell = Cell::MAX,
+            }
+        }
+
+        fn main() {
+            let mut tape: Vec<Cell> = vec![0; TAPE_SIZE];
+            let mut dp = TAPE_SIZE / 2;
+            let mut input = io::stdin().lock();
+            let mut output = BufWriter::new(io::stdout().lock());
+            tape[dp] = tape[dp].wrapping_add(1);
+            while tape[dp] != 0 {
+                if dp + 1 >= TAPE_SIZE { out_of_bounds(&mut output, "right"); }
+                dp += 1;
+                tape[dp] = tape[dp].wrapping_add(1);
+                if dp < 2 { out_of_bounds(&mut output, "left"); }
+                dp -= 1;
+                tape[dp] = tape[dp].wrapping_sub(1);
+            }
+            if dp + 2 >= TAPE_SIZE { out_of_bounds(&mut output, "right"); }
+            dp += 2;
+            let _ = output.write_all(&[tape[dp] as u8]);
+            read(&mut input, &mut output, &mut tape[dp]);
+            let _ = output.flush();
+        }
+        "#);
+    }
+}
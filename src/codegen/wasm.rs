@@ -0,0 +1,289 @@
+use super::{Backend, Op, Settings};
+use crate::EofBehavior;
+use crate::cell::CellSize;
+
+/// The size of a WebAssembly memory page.
+const PAGE_SIZE: usize = 65536;
+
+// Opcodes used by the generated code.
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+const BR: u8 = 0x0c;
+const BR_IF: u8 = 0x0d;
+const RETURN: u8 = 0x0f;
+const CALL: u8 = 0x10;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const I32_CONST: u8 = 0x41;
+const I32_EQZ: u8 = 0x45;
+const I32_LT_S: u8 = 0x48;
+const I32_LT_U: u8 = 0x49;
+const I32_GE_U: u8 = 0x4f;
+const I32_ADD: u8 = 0x6a;
+const I32_AND: u8 = 0x71;
+const I32_SHL: u8 = 0x74;
+/// The block type of blocks that take and leave nothing on the stack.
+const EMPTY: u8 = 0x40;
+const I32: u8 = 0x7f;
+
+// Indices of the imported functions and of `main`'s locals.
+const PUTCHAR: u8 = 0;
+const GETCHAR: u8 = 1;
+const DP: u8 = 0;
+const BYTE: u8 = 1;
+
+/// Generates a binary WebAssembly module.
+///
+/// The module imports `putchar` (taking a byte) and `getchar` (returning a
+/// byte, or a negative number at end of file) from `env`, and exports its
+/// tape as `memory` and the program as `main`, which returns 0, or 1 if the
+/// data pointer moved off the tape. It supports every cell size but bignum
+/// and every EOF behavior.
+pub struct WasmBackend;
+
+impl Backend for WasmBackend {
+    fn extension(&self) -> &'static str {
+        "wasm"
+    }
+
+    /// Modules are run by a host rather than compiled, so there are none.
+    fn compilers(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn check(&self, settings: &Settings) -> Result<(), String> {
+        if settings.cell_size == CellSize::Big {
+            return Err("The wasm target does not support bignum cells".to_string());
+        }
+        if settings.tape_size.saturating_mul(cell_width(settings)) > u32::MAX as usize {
+            return Err("The wasm target supports tapes of at most 4 GiB".to_string());
+        }
+        Ok(())
+    }
+
+    fn generate(&self, ops: &[Op], settings: &Settings) -> Vec<u8> {
+        let width = cell_width(settings);
+        let mut code = Code {
+            bytes: Vec::new(),
+            shift: width.trailing_zeros() as u8,
+        };
+        code.constant(settings.tape_size as u32 / 2);
+        code.bytes.extend([LOCAL_SET, DP]);
+
+        for op in ops {
+            match *op {
+                Op::Add(delta) => {
+                    code.address();
+                    code.address();
+                    code.load();
+                    code.constant(delta as u32);
+                    code.bytes.push(I32_ADD);
+                    code.store();
+                }
+                Op::Move { shift, min, max } => {
+                    if min < 0 {
+                        code.bytes.extend([LOCAL_GET, DP]);
+                        code.constant(min.unsigned_abs() as u32);
+                        code.bytes.push(I32_LT_U);
+                        code.fail_if();
+                    }
+                    if max > 0 {
+                        code.bytes.extend([LOCAL_GET, DP]);
+                        code.constant(max as u32);
+                        code.bytes.push(I32_ADD);
+                        code.constant(settings.tape_size as u32);
+                        code.bytes.push(I32_GE_U);
+                        code.fail_if();
+                    }
+                    if shift != 0 {
+                        code.bytes.extend([LOCAL_GET, DP]);
+                        code.constant(shift as u32);
+                        code.bytes.extend([I32_ADD, LOCAL_SET, DP]);
+                    }
+                }
+                Op::Output => {
+                    code.address();
+                    code.load();
+                    code.constant(0xff);
+                    code.bytes.extend([I32_AND, CALL, PUTCHAR]);
+                }
+                Op::Input => {
+                    code.bytes.extend([CALL, GETCHAR, LOCAL_SET, BYTE]);
+                    code.bytes.extend([LOCAL_GET, BYTE]);
+                    code.constant(0);
+                    code.bytes.extend([I32_LT_S, IF, EMPTY]);
+                    match settings.eof {
+                        EofBehavior::Zero => {
+                            code.address();
+                            code.constant(0);
+                            code.store();
+                        }
+                        EofBehavior::MinusOne => {
+                            code.address();
+                            code.constant(u32::MAX);
+                            code.store();
+                        }
+                        EofBehavior::Unchanged => {}
+                    }
+                    code.bytes.push(ELSE);
+                    code.address();
+                    code.bytes.extend([LOCAL_GET, BYTE]);
+                    code.store();
+                    code.bytes.push(END);
+                }
+                Op::LoopStart => {
+                    code.bytes.extend([BLOCK, EMPTY, LOOP, EMPTY]);
+                    code.address();
+                    code.load();
+                    code.bytes.extend([I32_EQZ, BR_IF, 1]);
+                }
+                Op::LoopEnd => code.bytes.extend([BR, 0, END, END]),
+            }
+        }
+        code.constant(0);
+        code.bytes.push(END);
+
+        let mut module = b"\0asm\x01\0\0\0".to_vec();
+        // Types: 0 is `(i32) -> ()` for putchar, 1 is `() -> i32` for
+        // getchar and main.
+        section(&mut module, 1, &[2, 0x60, 1, I32, 0, 0x60, 0, 1, I32]);
+        let mut imports = vec![2];
+        for (name, ty) in [("putchar", 0), ("getchar", 1)] {
+            name_bytes(&mut imports, "env");
+            name_bytes(&mut imports, name);
+            imports.extend([0, ty]);
+        }
+        section(&mut module, 2, &imports);
+        section(&mut module, 3, &[1, 1]);
+        let pages = (settings.tape_size * width).div_ceil(PAGE_SIZE).max(1);
+        let mut memory = vec![1, 0];
+        leb128(&mut memory, pages as u64);
+        section(&mut module, 5, &memory);
+        let mut exports = vec![2];
+        name_bytes(&mut exports, "main");
+        exports.extend([0, 2]);
+        name_bytes(&mut exports, "memory");
+        exports.extend([2, 0]);
+        section(&mut module, 7, &exports);
+
+        // One function with two i32 locals: the data pointer and the last
+        // byte read.
+        let mut body = vec![1, 2, I32];
+        body.extend(code.bytes);
+        let mut function = Vec::new();
+        leb128(&mut function, body.len() as u64);
+        function.extend(body);
+        let mut functions = vec![1];
+        functions.extend(function);
+        section(&mut module, 10, &functions);
+        module
+    }
+}
+
+fn cell_width(settings: &Settings) -> usize {
+    match settings.cell_size {
+        CellSize::Bits8 => 1,
+        CellSize::Bits16 => 2,
+        CellSize::Bits32 | CellSize::Big => 4,
+    }
+}
+
+/// The body of `main` being generated.
+struct Code {
+    bytes: Vec<u8>,
+    /// The base-2 logarithm of the cell width, which turns indices into
+    /// addresses.
+    shift: u8,
+}
+
+impl Code {
+    fn constant(&mut self, value: u32) {
+        self.bytes.push(I32_CONST);
+        sleb128(&mut self.bytes, value as i32 as i64);
+    }
+
+    /// Pushes the address of the current cell.
+    fn address(&mut self) {
+        self.bytes.extend([LOCAL_GET, DP]);
+        if self.shift > 0 {
+            self.constant(self.shift as u32);
+            self.bytes.push(I32_SHL);
+        }
+    }
+
+    /// Replaces an address with the cell there.
+    fn load(&mut self) {
+        // i32.load8_u, i32.load16_u, or i32.load, aligned to the cell.
+        let opcode = [0x2d, 0x2f, 0x28][self.shift as usize];
+        self.bytes.extend([opcode, self.shift, 0]);
+    }
+
+    /// Stores a value at an address, keeping the bits that fit in a cell.
+    fn store(&mut self) {
+        // i32.store8, i32.store16, or i32.store.
+        let opcode = [0x3a, 0x3b, 0x36][self.shift as usize];
+        self.bytes.extend([opcode, self.shift, 0]);
+    }
+
+    /// Returns 1 from `main` if the value on the stack is not zero.
+    fn fail_if(&mut self) {
+        self.bytes.extend([IF, EMPTY]);
+        self.constant(1);
+        self.bytes.extend([RETURN, END]);
+    }
+}
+
+fn section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    module.push(id);
+    leb128(module, contents.len() as u64);
+    module.extend(contents);
+}
+
+fn name_bytes(bytes: &mut Vec<u8>, name: &str) {
+    leb128(bytes, name.len() as u64);
+    bytes.extend(name.as_bytes());
+}
+
+fn leb128(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn sleb128(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::lower;
+    use crate::tokenize;
+
+    #[test]
+    fn test_wasm_backend() {
+        let settings = Settings {
+            tape_size: 100,
+            ..Settings::default()
+        };
+        let module = WasmBackend.generate(&lower(&tokenize("+[>-<-]<.,")), &settings);
+        insta::assert_snapshot!(format!("{:02x?}", module), @"[00, 61, 73, 6d, 01, 00, 00, 00, 01, 09, 02, 60, 01, 7f, 00, 60, 00, 01, 7f, 02, 1d, 02, 03, 65, 6e, 76, 07, 70, 75, 74, 63, 68, 61, 72, 00, 00, 03, 65, 6e, 76, 07, 67, 65, 74, 63, 68, 61, 72, 00, 01, 03, 02, 01, 01, 05, 03, 01, 00, 01, 07, 11, 02, 04, 6d, 61, 69, 6e, 00, 02, 06, 6d, 65, 6d, 6f, 72, 79, 02, 00, 0a, a4, 01, 01, a1, 01, 01, 02, 7f, 41, 32, 21, 00, 20, 00, 20, 00, 2d, 00, 00, 41, 01, 6a, 3a, 00, 00, 02, 40, 03, 40, 20, 00, 2d, 00, 00, 45, 0d, 01, 20, 00, 41, 01, 6a, 41, e4, 00, 4f, 04, 40, 41, 01, 0f, 0b, 20, 00, 41, 01, 6a, 21, 00, 20, 00, 20, 00, 2d, 00, 00, 41, 7f, 6a, 3a, 00, 00, 20, 00, 41, 01, 49, 04, 40, 41, 01, 0f, 0b, 20, 00, 41, 7f, 6a, 21, 00, 20, 00, 20, 00, 2d, 00, 00, 41, 7f, 6a, 3a, 00, 00, 0c, 00, 0b, 0b, 20, 00, 41, 01, 49, 04, 40, 41, 01, 0f, 0b, 20, 00, 41, 7f, 6a, 21, 00, 20, 00, 2d, 00, 00, 41, ff, 01, 71, 10, 00, 10, 01, 21, 01, 20, 01, 41, 00, 48, 04, 40, 20, 00, 41, 00, 3a, 00, 00, 05, 20, 00, 20, 01, 3a, 00, 00, 0b, 41, 00, 0b]");
+    }
+}
@@ -0,0 +1,396 @@
+//! The REPL's commands: their names, arguments, and help, and how a line
+//! is told apart from code.
+
+/// A REPL command as listed by `help`.
+#[derive(Debug)]
+pub(crate) struct CommandInfo {
+    pub name: &'static str,
+    /// Other names that run the same command.
+    pub aliases: &'static [&'static str],
+    /// The arguments, as shown after the name in usage messages.
+    pub args: &'static str,
+    pub help: &'static str,
+}
+
+impl CommandInfo {
+    /// Returns `Usage: <name> <args>`, as shown when a command is given the
+    /// wrong arguments.
+    pub fn usage(&self) -> String {
+        if self.args.is_empty() {
+            format!("Usage: {}", self.name)
+        } else {
+            format!("Usage: {} {}", self.name, self.args)
+        }
+    }
+}
+
+const fn command(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    args: &'static str,
+    help: &'static str,
+) -> CommandInfo {
+    CommandInfo {
+        name,
+        aliases,
+        args,
+        help,
+    }
+}
+
+pub(crate) const COMMANDS: &[CommandInfo] = &[
+    command(
+        "help",
+        &[],
+        "[<command>]",
+        "List the commands, or explain one",
+    ),
+    command(
+        "mem",
+        &["memory"],
+        "[<address> | <start>..<end>] [--hex|--ascii|--bin|--dec]",
+        "Show the cells around the data pointer or <address>, or a range",
+    ),
+    command(
+        "show",
+        &[],
+        "[<address>]",
+        "Show the current cell or the cell at <address>",
+    ),
+    command(
+        "jump",
+        &["ptr"],
+        "<address>",
+        "Move the data pointer to <address>",
+    ),
+    command(
+        "set",
+        &[],
+        "[<address>] <value>",
+        "Set the current cell; 'set max-steps' and 'set display' change settings",
+    ),
+    command(
+        "dump",
+        &[],
+        "<start> [<length>]",
+        "Show the cells from <start> as a hexdump with their characters",
+    ),
+    command(
+        "fill",
+        &[],
+        "<start> <end> <value>",
+        "Set the cells from <start> up to <end> to <value>",
+    ),
+    command(
+        "clear",
+        &[],
+        "<start> <end>",
+        "Set the cells from <start> up to <end> to 0",
+    ),
+    command(
+        "ascii",
+        &[],
+        "[<value>|<char>]",
+        "Show the ASCII table, or one character and its value",
+    ),
+    command(
+        "output",
+        &[],
+        "<file> | output off",
+        "Write program output to <file>, or to the terminal again",
+    ),
+    command(
+        "dry",
+        &[],
+        "<code>",
+        "Run <code> on a copy of the tape and show what it would change",
+    ),
+    command(
+        "debug",
+        &[],
+        "<code>",
+        "Load <code> paused before its first instruction",
+    ),
+    command(
+        "step",
+        &[],
+        "",
+        "Run one instruction of the debugged program",
+    ),
+    command(
+        "next",
+        &[],
+        "",
+        "Run one instruction, or the whole loop at a '['",
+    ),
+    command(
+        "continue",
+        &[],
+        "",
+        "Run the debugged program until it stops",
+    ),
+    command(
+        "where",
+        &[],
+        "",
+        "Show where the debugged program is paused",
+    ),
+    command(
+        "break",
+        &[],
+        "[<n> | clear]",
+        "Stop before instruction <n>, or list or clear breakpoints",
+    ),
+    command(
+        "watch",
+        &[],
+        "[<address> | clear]",
+        "Stop when the cell at <address> changes, or list or clear watches",
+    ),
+    command(
+        "back",
+        &[],
+        "[steps]",
+        "Undo the last steps of the debugged program",
+    ),
+    command(
+        "undo",
+        &[],
+        "",
+        "Take back the last change to the tape or the data pointer",
+    ),
+    command(
+        "store",
+        &[],
+        "<slot>",
+        "Keep the last line of code in slot <slot>",
+    ),
+    command("run", &[], "<slot>", "Run the code kept in slot <slot>"),
+    command("list", &[], "", "List the code kept in slots"),
+    command(
+        "checkpoint",
+        &[],
+        "[<name>]",
+        "Save the tape and data pointer as <name>, or list checkpoints",
+    ),
+    command(
+        "rollback",
+        &[],
+        "<name>",
+        "Restore the tape and data pointer saved as <name>",
+    ),
+    command(
+        "load",
+        &[],
+        "<file>",
+        "Run the program in <file> on the current tape",
+    ),
+    command(
+        "reset",
+        &[],
+        "[all]",
+        "Start again with a fresh tape; 'all' also forgets history and displays",
+    ),
+    command(
+        "session",
+        &[],
+        "list | session new <name> | session switch <name>",
+        "Keep several tapes and switch between them",
+    ),
+    command(
+        "display",
+        &[],
+        "[<expr>]",
+        "Show <expr> after every run, or show all displays",
+    ),
+    command(
+        "undisplay",
+        &[],
+        "[<n>]",
+        "Remove display <n>, or all of them",
+    ),
+    command(
+        "histogram",
+        &[],
+        "[<code>]",
+        "Chart how often each command occurs",
+    ),
+    command(
+        "heatmap",
+        &[],
+        "[on|off]",
+        "Count and chart how often each cell is accessed",
+    ),
+    command(
+        "find",
+        &[],
+        "<byte|\"text\"> [<start>..<end>]",
+        "List the addresses where a byte or text is stored",
+    ),
+    command(
+        "copy-output",
+        &[],
+        "",
+        "Copy the last run's output to the clipboard",
+    ),
+    command(
+        "copy-source",
+        &[],
+        "",
+        "Copy the last line of code to the clipboard",
+    ),
+    command("history", &[], "", "List the lines of code run so far"),
+    command("rerun", &[], "<n>", "Run line <n> of the history again"),
+    command(
+        "edit",
+        &[],
+        "<n>",
+        "Put line <n> of the history on the next prompt",
+    ),
+    command(
+        "save",
+        &["write-session"],
+        "<file>",
+        "Write the code that ran without errors to <file> as a program",
+    ),
+    command("exit", &["quit"], "", "Leave the REPL"),
+];
+
+/// Finds the command called `name`, by its name or an alias.
+pub(crate) fn lookup(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name || command.aliases.contains(&name))
+}
+
+/// A line naming a command, split into the command and its arguments.
+#[derive(Debug)]
+pub(crate) struct Invocation<'a> {
+    pub command: &'static CommandInfo,
+    /// The words after the command.
+    pub args: Vec<&'a str>,
+    /// Everything after the command, for commands that take code or text.
+    pub rest: Option<&'a str>,
+}
+
+/// Splits `line` into a command and its arguments. Returns `Ok(None)` if the
+/// line is code, and an error naming the closest command if the line starts
+/// with `:` and an unknown name.
+pub(crate) fn parse(line: &str) -> Result<Option<Invocation<'_>>, String> {
+    let line = line.trim();
+    let (word, rest) = match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, Some(rest.trim_start())),
+        None => (line, None),
+    };
+    let (name, explicit) = match word.strip_prefix(':') {
+        Some(name) => (name, true),
+        None => (word, false),
+    };
+
+    let Some(command) = lookup(name) else {
+        return if explicit {
+            Err(unknown_command(name))
+        } else {
+            Ok(None)
+        };
+    };
+
+    Ok(Some(Invocation {
+        command,
+        args: rest.map_or_else(Vec::new, |rest| rest.split_whitespace().collect()),
+        rest,
+    }))
+}
+
+/// Returns the message for a command that does not exist, suggesting the
+/// closest one.
+pub(crate) fn unknown_command(name: &str) -> String {
+    match suggest(name) {
+        Some(suggestion) => format!(
+            "Unknown command ':{}'; did you mean ':{}'?",
+            name, suggestion
+        ),
+        None => format!("Unknown command ':{}'; type ':help' for a list", name),
+    }
+}
+
+/// Returns the command whose name `name` is most likely a misspelling of.
+fn suggest(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .flat_map(|command| std::iter::once(&command.name).chain(command.aliases))
+        .map(|&candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, candidate)| distance <= 2 && distance < candidate.len())
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Counts the characters that must be inserted, removed, or replaced to
+/// turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != cb);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Writes the list of commands shown by `help`.
+pub(crate) fn write_help(out: &mut dyn std::io::Write) -> std::io::Result<()> {
+    let width = COMMANDS
+        .iter()
+        .map(|command| command.name.len())
+        .max()
+        .unwrap_or(0);
+    for command in COMMANDS {
+        writeln!(out, "  {:width$}  {}", command.name, command.help)?;
+    }
+    writeln!(
+        out,
+        "Commands may start with ':'. Anything else runs as code; 'help <command>' explains one."
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parsed = [
+            ":show 5",
+            "memory",
+            "debug +[-] ",
+            "+[->+<]",
+            "hello world",
+            ":memroy",
+            ":xyzzy",
+        ]
+        .map(|line| match parse(line) {
+            Ok(Some(invocation)) => format!(
+                "{} {:?} {:?}",
+                invocation.command.name, invocation.args, invocation.rest
+            ),
+            Ok(None) => "code".to_string(),
+            Err(e) => e,
+        });
+
+        insta::assert_debug_snapshot!(parsed, @r#"
+        [
+            "show [\"5\"] Some(\"5\")",
+            "mem [] None",
+            "debug [\"+[-]\"] Some(\"+[-]\")",
+            "code",
+            "code",
+            "Unknown command ':memroy'; did you mean ':memory'?",
+            "Unknown command ':xyzzy'; type ':help' for a list",
+        ]
+        "#);
+    }
+}
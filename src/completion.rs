@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::Path;
+
+use crate::commands::COMMANDS;
+
+/// Commands whose argument is a file name.
+const FILE_COMMANDS: [&str; 4] = ["load", "output", "save", "write-session"];
+
+/// Completes the word that `line` ends with: a command name at the start
+/// of the line, or a path after a command that takes a file. Returns the
+/// byte offset where the word starts and the words it could become, in
+/// order; commands carry a trailing space and directories a trailing `/`.
+pub(crate) fn complete(line: &str) -> (usize, Vec<String>) {
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &line[start..];
+    let before = line[..start].trim();
+
+    if before.is_empty() {
+        let (colon, name) = match word.strip_prefix(':') {
+            Some(name) => (":", name),
+            None => ("", word),
+        };
+        // Code starts with a symbol, so only letters are taken for a command.
+        if name.is_empty() && colon.is_empty() {
+            return (start, Vec::new());
+        }
+        let mut candidates: Vec<String> = COMMANDS
+            .iter()
+            .filter(|command| command.name.starts_with(name))
+            .map(|command| format!("{}{} ", colon, command.name))
+            .collect();
+        candidates.sort();
+        return (start, candidates);
+    }
+
+    let command = before.trim_start_matches(':');
+    if FILE_COMMANDS.contains(&command) {
+        (start, complete_path(word))
+    } else {
+        (start, Vec::new())
+    }
+}
+
+/// Lists the files and directories that `word` could name. Hidden ones are
+/// only offered once a `.` is typed.
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let dir = std::env::temp_dir().join(format!("bf-repl-complete-{}", std::process::id()));
+        fs::create_dir_all(dir.join("programs")).unwrap();
+        fs::write(dir.join("out.txt"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let dir = dir.display().to_string();
+
+        let lines = [
+            ":h".to_string(),
+            "sa".to_string(),
+            "+[".to_string(),
+            ":show 1".to_string(),
+            format!(":output {}/", dir),
+            format!("write-session {}/p", dir),
+        ];
+        let completions: Vec<_> = lines
+            .iter()
+            .map(|line| {
+                let (start, candidates) = complete(line);
+                let candidates: Vec<_> = candidates
+                    .iter()
+                    .map(|candidate| candidate.replace(&dir, "DIR"))
+                    .collect();
+                (start, candidates)
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        insta::assert_debug_snapshot!(completions, @r#"
+        [
+            (
+                0,
+                [
+                    ":heatmap ",
+                    ":help ",
+                    ":histogram ",
+                    ":history ",
+                ],
+            ),
+            (
+                0,
+                [
+                    "save ",
+                ],
+            ),
+            (
+                0,
+                [],
+            ),
+            (
+                6,
+                [],
+            ),
+            (
+                8,
+                [
+                    "DIR/out.txt",
+                    "DIR/programs/",
+                ],
+            ),
+            (
+                14,
+                [
+                    "DIR/programs/",
+                ],
+            ),
+        ]
+        "#);
+    }
+}
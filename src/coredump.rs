@@ -0,0 +1,397 @@
+use std::fs;
+use std::io::{self, Write};
+
+use crate::cell::Cell;
+use crate::{Extensions, Interpreter, gzip};
+
+const MAGIC: &str = "BFCORE 1";
+/// The largest tape a sparse dump may claim, so a corrupt header cannot
+/// make loading allocate without bound.
+const MAX_TAPE_SIZE: usize = 1 << 30;
+
+/// How a core dump is written. Loading detects the format by itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// The tape is stored cell by cell.
+    #[default]
+    Plain,
+    /// Runs of zero cells are skipped.
+    Sparse,
+    /// Sparse, then compressed with gzip.
+    Gzip,
+}
+
+impl DumpFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "plain" => Ok(Self::Plain),
+            "sparse" => Ok(Self::Sparse),
+            "gzip" => Ok(Self::Gzip),
+            _ => Err(format!(
+                "Unknown core dump format '{}' (expected plain, sparse, or gzip)",
+                name
+            )),
+        }
+    }
+}
+
+/// The state of a failed run, written to disk so it can be examined later.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CoreDump {
+    pub error: String,
+    pub program: String,
+    pub instruction_pointer: usize,
+    pub data_pointer: usize,
+    pub input_position: usize,
+    /// The extensions the program ran with, which decide what counts as an
+    /// instruction when `instruction_pointer` is found in `program`.
+    pub extensions: Extensions,
+    pub memory: Vec<u8>,
+}
+
+impl CoreDump {
+    /// Records the state of `interpreter`. Cells wider than a byte are
+    /// stored as their low eight bits.
+    pub fn capture<C: Cell>(interpreter: &Interpreter<C>, program: &str, error: &str) -> Self {
+        Self {
+            error: error.to_string(),
+            program: program.to_string(),
+            instruction_pointer: interpreter.instruction_pointer,
+            data_pointer: interpreter.data_pointer,
+            input_position: interpreter.input_position,
+            extensions: interpreter.extensions(),
+            memory: interpreter.memory.iter().map(Cell::to_byte).collect(),
+        }
+    }
+
+    /// Writes the dump; with `sparse`, the tape is written as its length
+    /// followed by a `runs` section (see [`encode_runs`]).
+    pub fn write_to(&self, writer: &mut impl Write, sparse: bool) -> io::Result<()> {
+        writeln!(writer, "{}", MAGIC)?;
+        writeln!(writer, "ip {}", self.instruction_pointer)?;
+        writeln!(writer, "dp {}", self.data_pointer)?;
+        writeln!(writer, "input {}", self.input_position)?;
+        if self.extensions.graphics {
+            writeln!(writer, "extensions graphics")?;
+        }
+
+        let runs;
+        let memory = if sparse {
+            writeln!(writer, "tape {}", self.memory.len())?;
+            runs = encode_runs(&self.memory);
+            ("runs", &runs[..])
+        } else {
+            ("memory", &self.memory[..])
+        };
+
+        for (name, bytes) in [
+            ("error", self.error.as_bytes()),
+            ("program", self.program.as_bytes()),
+            memory,
+        ] {
+            writeln!(writer, "{} {}", name, bytes.len())?;
+            writer.write_all(bytes)?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a dump in any [`DumpFormat`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if gzip::is_gzip(bytes) {
+            return Self::from_bytes(&gzip::decompress(bytes)?);
+        }
+
+        let mut reader = DumpReader { bytes, position: 0 };
+
+        if reader.line()? != MAGIC {
+            return Err("Not a core dump".to_string());
+        }
+
+        let instruction_pointer = reader.number("ip")?;
+        let data_pointer = reader.number("dp")?;
+        let input_position = reader.number("input")?;
+        let mut extensions = Extensions::default();
+        if let Some(names) = reader.peek_line()?.strip_prefix("extensions ") {
+            reader.line()?;
+            for name in names.split_whitespace() {
+                match name {
+                    "graphics" => extensions.graphics = true,
+                    _ => return Err(format!("Unknown extension '{}' in core dump", name)),
+                }
+            }
+        }
+        let tape_size = match reader.peek_line()?.starts_with("tape ") {
+            true => Some(reader.number("tape")?),
+            false => None,
+        };
+        let error = String::from_utf8_lossy(reader.blob("error")?).into_owned();
+        let program = String::from_utf8_lossy(reader.blob("program")?).into_owned();
+        let memory = match tape_size {
+            Some(tape_size) => decode_runs(reader.blob("runs")?, tape_size)?,
+            None => reader.blob("memory")?.to_vec(),
+        };
+
+        if data_pointer >= memory.len() {
+            return Err("Core dump data pointer is outside the tape".to_string());
+        }
+
+        Ok(Self {
+            error,
+            program,
+            instruction_pointer,
+            data_pointer,
+            input_position,
+            extensions,
+            memory,
+        })
+    }
+
+    pub fn save(&self, filename: &str, format: DumpFormat) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes, format != DumpFormat::Plain)
+            .map_err(|e| e.to_string())?;
+        if format == DumpFormat::Gzip {
+            bytes = gzip::compress(&bytes);
+        }
+        fs::write(filename, bytes).map_err(|e| format!("Cannot write {}: {}", filename, e))
+    }
+
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let bytes = fs::read(filename).map_err(|e| format!("Cannot read {}: {}", filename, e))?;
+        Self::from_bytes(&bytes).map_err(|e| format!("{}: {}", filename, e))
+    }
+}
+
+/// Encodes a tape as a sequence of runs, each the number of zero cells to
+/// skip followed by the number of cells that follow and those cells, both
+/// counts as LEB128 numbers. Zeros after the last run are left out.
+fn encode_runs(memory: &[u8]) -> Vec<u8> {
+    let mut runs = Vec::new();
+    let mut position = 0;
+
+    while let Some(start) = memory[position..].iter().position(|&cell| cell != 0) {
+        let start = position + start;
+        // A lone zero costs less to store than to skip.
+        let end = (start..memory.len())
+            .find(|&i| memory[i..].iter().take(2).all(|&cell| cell == 0))
+            .unwrap_or(memory.len());
+        write_number(&mut runs, start - position);
+        write_number(&mut runs, end - start);
+        runs.extend_from_slice(&memory[start..end]);
+        position = end;
+    }
+
+    runs
+}
+
+fn decode_runs(mut runs: &[u8], tape_size: usize) -> Result<Vec<u8>, String> {
+    if tape_size > MAX_TAPE_SIZE {
+        return Err(format!(
+            "Core dump tape of {} cells is too large (at most {})",
+            tape_size, MAX_TAPE_SIZE
+        ));
+    }
+    let mut memory = Vec::with_capacity(tape_size);
+
+    while !runs.is_empty() {
+        let zeros = read_number(&mut runs)?;
+        let len = read_number(&mut runs)?;
+        let cells = runs.get(..len).ok_or("Truncated run in core dump")?;
+        let end = memory
+            .len()
+            .checked_add(zeros)
+            .and_then(|end| end.checked_add(len));
+        if end.is_none_or(|end| end > tape_size) {
+            return Err("Core dump runs extend past the tape".to_string());
+        }
+        memory.resize(memory.len() + zeros, 0);
+        memory.extend_from_slice(cells);
+        runs = &runs[len..];
+    }
+
+    memory.resize(tape_size, 0);
+    Ok(memory)
+}
+
+fn write_number(bytes: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_number(bytes: &mut &[u8]) -> Result<usize, String> {
+    let mut n = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("Truncated run in core dump")?;
+        *bytes = rest;
+        n |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err("Run length too large in core dump".to_string())
+}
+
+struct DumpReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> DumpReader<'a> {
+    fn line(&mut self) -> Result<&'a str, String> {
+        let rest = &self.bytes[self.position..];
+        let end = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("Truncated core dump")?;
+        self.position += end + 1;
+        std::str::from_utf8(&rest[..end]).map_err(|_| "Malformed core dump header".to_string())
+    }
+
+    fn peek_line(&mut self) -> Result<&'a str, String> {
+        let position = self.position;
+        let line = self.line();
+        self.position = position;
+        line
+    }
+
+    fn number(&mut self, key: &str) -> Result<usize, String> {
+        let line = self.line()?;
+        line.strip_prefix(key)
+            .and_then(|value| value.strip_prefix(' '))
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Expected '{}' in core dump, found '{}'", key, line))
+    }
+
+    fn blob(&mut self, key: &str) -> Result<&'a [u8], String> {
+        let len = self.number(key)?;
+        let end = self.position + len;
+        if end >= self.bytes.len() || self.bytes[end] != b'\n' {
+            return Err(format!("Truncated '{}' section in core dump", key));
+        }
+        let blob = &self.bytes[self.position..end];
+        self.position = end + 1;
+        Ok(blob)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_dump_round_trip() {
+        let dump = CoreDump {
+            error: "Data pointer out of bounds (left)".to_string(),
+            program: "+[<]\n".to_string(),
+            instruction_pointer: 2,
+            data_pointer: 0,
+            input_position: 3,
+            extensions: Extensions::default(),
+            memory: b"Hi!".to_vec(),
+        };
+
+        let mut bytes = Vec::new();
+        dump.write_to(&mut bytes, false).unwrap();
+
+        insta::assert_snapshot!(String::from_utf8_lossy(&bytes), @r"
+        BFCORE 1
+        ip 2
+        dp 0
+        input 3
+        error 33
+        Data pointer out of bounds (left)
+        program 5
+        +[<]
+
+        memory 3
+        Hi!
+        ");
+        assert_eq!(CoreDump::from_bytes(&bytes), Ok(dump));
+    }
+
+    #[test]
+    fn test_core_dump_truncated() {
+        let result = CoreDump::from_bytes(b"BFCORE 1\nip 2\ndp 0\ninput 0\nerror 40\nshort\n");
+
+        insta::assert_debug_snapshot!(result, @r#"
+        Err(
+            "Truncated 'error' section in core dump",
+        )
+        "#);
+    }
+
+    #[test]
+    fn test_sparse_core_dump() {
+        let mut memory = vec![0; 300];
+        memory[1..4].copy_from_slice(b"a\0b");
+        memory[200] = b'z';
+        let dump = CoreDump {
+            error: "Oops".to_string(),
+            program: "+".to_string(),
+            instruction_pointer: 0,
+            data_pointer: 200,
+            input_position: 0,
+            extensions: Extensions { graphics: true },
+            memory,
+        };
+
+        let mut bytes = Vec::new();
+        dump.write_to(&mut bytes, true).unwrap();
+
+        insta::assert_snapshot!(bytes.escape_ascii().to_string().replace("\\n", "\n"), @r"
+        BFCORE 1
+        ip 0
+        dp 200
+        input 0
+        extensions graphics
+        tape 300
+        error 4
+        Oops
+        program 1
+        +
+        runs 9
+        \x01\x03a\x00b\xc4\x01\x01z
+        ");
+        assert_eq!(CoreDump::from_bytes(&bytes).as_ref(), Ok(&dump));
+        assert_eq!(CoreDump::from_bytes(&gzip::compress(&bytes)), Ok(dump));
+    }
+
+    #[test]
+    fn test_sparse_core_dump_bad_tape() {
+        let header = "BFCORE 1\nip 0\ndp 0\ninput 0\n";
+        let sections = "error 0\n\nprogram 0\n\n";
+        let mut huge_run = b"runs 12\n".to_vec();
+        huge_run.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        huge_run.extend_from_slice(b"\x01z\n");
+
+        let results = [
+            format!(
+                "{}tape 18446744073709551615\n{}runs 0\n\n",
+                header, sections
+            )
+            .into_bytes(),
+            [
+                format!("{}tape 10\n{}", header, sections).as_bytes(),
+                &huge_run,
+            ]
+            .concat(),
+        ]
+        .map(|bytes| CoreDump::from_bytes(&bytes));
+
+        insta::assert_debug_snapshot!(results, @r#"
+        [
+            Err(
+                "Core dump tape of 18446744073709551615 cells is too large (at most 1073741824)",
+            ),
+            Err(
+                "Core dump runs extend past the tape",
+            ),
+        ]
+        "#);
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::Interpreter;
+use crate::cell::Cell;
+
+/// How many steps pass between state samples.
+pub const CHECK_INTERVAL: u64 = 1 << 16;
+
+/// Detects a run that has entered a cycle by sampling hashes of its state.
+///
+/// Uses Brent's algorithm: one saved sample is compared against each new
+/// one and replaced at power-of-two distances, so memory stays constant and
+/// any cycle is found once the distance exceeds its length. Input consumed
+/// is part of the state, so waiting on input is never mistaken for a cycle.
+pub struct CycleDetector {
+    saved: Option<u64>,
+    power: u64,
+    since_saved: u64,
+}
+
+impl CycleDetector {
+    pub fn new() -> Self {
+        Self {
+            saved: None,
+            power: 1,
+            since_saved: 0,
+        }
+    }
+
+    /// Records the interpreter's current state, returning `true` if it
+    /// matches an earlier sample.
+    pub fn observe<C: Cell>(&mut self, interpreter: &Interpreter<C>) -> bool {
+        let mut hasher = DefaultHasher::new();
+        interpreter.instruction_pointer.hash(&mut hasher);
+        interpreter.data_pointer.hash(&mut hasher);
+        interpreter.input_position.hash(&mut hasher);
+        interpreter.memory.hash(&mut hasher);
+        let state = hasher.finish();
+
+        if self.saved == Some(state) {
+            return true;
+        }
+
+        self.since_saved += 1;
+        if self.since_saved == self.power {
+            self.saved = Some(state);
+            self.power *= 2;
+            self.since_saved = 0;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, parse_loops, tokenize};
+
+    #[test]
+    fn test_detect_cycles() {
+        let results = ["+[>+<]", "+[>+[-]<-]+[-]"].map(|code| {
+            let tokens = tokenize(code);
+            let jump_table = parse_loops(&tokens).unwrap();
+            let mut interpreter = Interpreter::builder().detect_cycles(true).build();
+            interpreter.run_with_io(&tokens, &jump_table, &b""[..], &mut Vec::new())
+        });
+
+        insta::assert_debug_snapshot!(results, @r#"
+        [
+            Err(
+                "Infinite loop detected: state repeated",
+            ),
+            Ok(
+                (),
+            ),
+        ]
+        "#);
+    }
+}
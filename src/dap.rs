@@ -0,0 +1,496 @@
+//! `bf-repl dap`: a Debug Adapter Protocol server on stdin and stdout, so
+//! editors can debug programs with this interpreter behind them.
+
+use std::io::{self, BufRead, Cursor, Write};
+use std::path::Path;
+
+use crate::debugger::{Debugger, Stop};
+use crate::json::{self, Value};
+use crate::source::read_source;
+use crate::{Interpreter, InterpreterBuilder};
+
+/// The one thread a program has.
+const THREAD_ID: u64 = 1;
+/// The `variablesReference` of the pointers scope.
+const POINTERS: u64 = 1;
+/// The `variablesReference` of the tape scope.
+const TAPE: u64 = 2;
+/// How many cells on each side of the data pointer the tape scope shows
+/// when the editor does not ask for a range.
+const TAPE_WINDOW: usize = 8;
+
+/// Serves the Debug Adapter Protocol on stdin and stdout until the editor
+/// disconnects. Each launch runs a program on a fresh interpreter from
+/// `builder`.
+pub fn run_dap(builder: &InterpreterBuilder) -> Result<(), String> {
+    let stdin = io::stdin();
+    Adapter::new(builder, io::stdout().lock())
+        .serve(&mut stdin.lock())
+        .map_err(|e| e.to_string())
+}
+
+struct Adapter<'a, W: Write> {
+    builder: &'a InterpreterBuilder,
+    out: W,
+    seq: u64,
+    interpreter: Interpreter,
+    debugger: Option<Debugger>,
+    path: String,
+    /// What `,` reads, from the `input` launch argument.
+    input: Cursor<Vec<u8>>,
+    stop_on_entry: bool,
+    /// Breakpoint lines set before the program was loaded.
+    pending_lines: Vec<usize>,
+    launched: bool,
+    configured: bool,
+}
+
+impl<'a, W: Write> Adapter<'a, W> {
+    fn new(builder: &'a InterpreterBuilder, out: W) -> Self {
+        Self {
+            builder,
+            out,
+            seq: 0,
+            interpreter: builder.build(),
+            debugger: None,
+            path: String::new(),
+            input: Cursor::new(Vec::new()),
+            stop_on_entry: false,
+            pending_lines: Vec::new(),
+            launched: false,
+            configured: false,
+        }
+    }
+
+    fn serve(mut self, input: &mut dyn BufRead) -> io::Result<()> {
+        while let Some(message) = json::read_message(input)? {
+            let request = match Value::parse(&message) {
+                Ok(request) => request,
+                Err(e) => {
+                    self.send_event("output", stderr_output(e))?;
+                    continue;
+                }
+            };
+            let command = request.get("command").as_str().unwrap_or_default();
+            let result = self.handle(command, request.get("arguments"));
+            let response = match &result {
+                Ok(body) => json::object([
+                    ("type", "response".into()),
+                    ("request_seq", request.get("seq").clone()),
+                    ("success", true.into()),
+                    ("command", command.into()),
+                    ("body", body.clone()),
+                ]),
+                Err(message) => json::object([
+                    ("type", "response".into()),
+                    ("request_seq", request.get("seq").clone()),
+                    ("success", false.into()),
+                    ("command", command.into()),
+                    ("message", message.as_str().into()),
+                ]),
+            };
+            self.send(response)?;
+            if command == "disconnect" {
+                break;
+            }
+            self.after(command)?;
+        }
+        Ok(())
+    }
+
+    /// Answers a request, returning the body of its response.
+    fn handle(&mut self, command: &str, arguments: &Value) -> Result<Value, String> {
+        match command {
+            "initialize" => Ok(json::object([
+                ("supportsConfigurationDoneRequest", true.into()),
+                ("supportsStepBack", true.into()),
+            ])),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => {
+                let lines: Vec<usize> = arguments
+                    .get("breakpoints")
+                    .as_array()
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint.get("line").as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let verified = self.set_breakpoints(&lines);
+                let breakpoints = lines
+                    .iter()
+                    .zip(verified)
+                    .map(|(&line, verified)| {
+                        json::object([("verified", verified.into()), ("line", line.into())])
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json::object([("breakpoints", breakpoints.into())]))
+            }
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json::object([(
+                "threads",
+                vec![json::object([
+                    ("id", THREAD_ID.into()),
+                    ("name", "main".into()),
+                ])]
+                .into(),
+            )])),
+            "stackTrace" => {
+                let frames = match &self.debugger {
+                    Some(debugger) => {
+                        let (line, column) = debugger.location(&self.interpreter);
+                        vec![json::object([
+                            ("id", 1u64.into()),
+                            ("name", "main".into()),
+                            ("line", line.into()),
+                            ("column", column.into()),
+                            ("source", self.source()),
+                        ])]
+                    }
+                    None => Vec::new(),
+                };
+                Ok(json::object([
+                    ("totalFrames", frames.len().into()),
+                    ("stackFrames", frames.into()),
+                ]))
+            }
+            "scopes" => Ok(json::object([(
+                "scopes",
+                vec![
+                    json::object([
+                        ("name", "Pointers".into()),
+                        ("variablesReference", POINTERS.into()),
+                        ("expensive", false.into()),
+                    ]),
+                    json::object([
+                        ("name", "Tape".into()),
+                        ("variablesReference", TAPE.into()),
+                        ("indexedVariables", self.interpreter.memory_size().into()),
+                        ("expensive", false.into()),
+                    ]),
+                ]
+                .into(),
+            )])),
+            "variables" => Ok(json::object([(
+                "variables",
+                self.variables(arguments).into(),
+            )])),
+            "next" | "stepIn" | "continue" => Ok(Value::Null),
+            "stepBack" | "reverseContinue" => match &self.debugger {
+                Some(_) => Ok(Value::Null),
+                None => Err("The program has finished".to_string()),
+            },
+            "terminate" | "disconnect" => Ok(Value::Null),
+            _ => Err(format!("Unsupported request '{}'", command)),
+        }
+    }
+
+    /// Does what a request asks for after it has been answered, since the
+    /// events it causes must come after the response.
+    fn after(&mut self, command: &str) -> io::Result<()> {
+        match command {
+            "initialize" => self.send_event("initialized", Value::Null),
+            "launch" | "configurationDone" => self.start(),
+            "next" | "stepIn" | "continue" => self.run(command),
+            "stepBack" | "reverseContinue" => {
+                let Some(debugger) = &mut self.debugger else {
+                    return Ok(());
+                };
+                if command == "stepBack" {
+                    debugger.back(&mut self.interpreter, 1);
+                } else {
+                    while debugger.back(&mut self.interpreter, 1) > 0 && !debugger.at_breakpoint() {
+                    }
+                }
+                self.send_stopped("step", None)
+            }
+            "terminate" => {
+                self.debugger = None;
+                self.send_event("terminated", Value::Null)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments
+            .get("program")
+            .as_str()
+            .ok_or("The launch configuration has no 'program'")?;
+        let code = read_source(path)?;
+
+        self.interpreter = self.builder.build();
+        let mut debugger = Debugger::load(&code, &self.interpreter)?;
+        for &line in &self.pending_lines {
+            if let Some(instruction) = debugger.first_on_line(line, &self.interpreter) {
+                debugger.set_breakpoint(instruction)?;
+            }
+        }
+
+        self.debugger = Some(debugger);
+        self.path = path.to_string();
+        self.input = Cursor::new(
+            arguments
+                .get("input")
+                .as_str()
+                .unwrap_or_default()
+                .as_bytes()
+                .to_vec(),
+        );
+        self.stop_on_entry = arguments.get("stopOnEntry").as_bool().unwrap_or(false);
+        self.launched = true;
+        Ok(Value::Null)
+    }
+
+    /// Replaces the breakpoints with ones on `lines`, returning whether each
+    /// line has an instruction to stop at.
+    fn set_breakpoints(&mut self, lines: &[usize]) -> Vec<bool> {
+        let Some(debugger) = &mut self.debugger else {
+            self.pending_lines = lines.to_vec();
+            return vec![true; lines.len()];
+        };
+
+        debugger.clear_breakpoints();
+        lines
+            .iter()
+            .map(
+                |&line| match debugger.first_on_line(line, &self.interpreter) {
+                    Some(instruction) => debugger.set_breakpoint(instruction).is_ok(),
+                    None => false,
+                },
+            )
+            .collect()
+    }
+
+    /// Starts the program once it is both launched and configured.
+    fn start(&mut self) -> io::Result<()> {
+        if !(self.launched && self.configured) {
+            return Ok(());
+        }
+        self.launched = false;
+
+        match &self.debugger {
+            Some(debugger) if debugger.at_breakpoint() => self.send_stopped("breakpoint", None),
+            Some(_) if self.stop_on_entry => self.send_stopped("entry", None),
+            Some(_) => self.run("continue"),
+            None => Ok(()),
+        }
+    }
+
+    fn run(&mut self, command: &str) -> io::Result<()> {
+        let Some(debugger) = &mut self.debugger else {
+            return Ok(());
+        };
+
+        let mut output = Vec::new();
+        let stop = match command {
+            "stepIn" => debugger.step(&mut self.interpreter, &mut self.input, &mut output),
+            "next" => debugger.next(&mut self.interpreter, &mut self.input, &mut output),
+            _ => debugger.resume(&mut self.interpreter, &mut self.input, &mut output),
+        };
+
+        if !output.is_empty() {
+            self.send_event(
+                "output",
+                json::object([
+                    ("category", "stdout".into()),
+                    (
+                        "output",
+                        String::from_utf8_lossy(&output).into_owned().into(),
+                    ),
+                ]),
+            )?;
+        }
+
+        match stop {
+            Stop::Paused => self.send_stopped("step", None),
+            Stop::Breakpoint => self.send_stopped("breakpoint", None),
+            Stop::Watch {
+                address, old, new, ..
+            } => self.send_stopped(
+                "data breakpoint",
+                Some(format!("Cell {} changed from {} to {}", address, old, new)),
+            ),
+            Stop::Failed(e) => self.send_stopped("exception", Some(e)),
+            Stop::Interrupted => self.send_stopped("pause", None),
+            Stop::Finished => {
+                self.debugger = None;
+                self.send_event("exited", json::object([("exitCode", 0u64.into())]))?;
+                self.send_event("terminated", Value::Null)
+            }
+        }
+    }
+
+    /// Lists the variables of a scope, or the cells in the range asked for.
+    fn variables(&self, arguments: &Value) -> Vec<Value> {
+        let variable = |name: String, value: String| {
+            json::object([
+                ("name", name.into()),
+                ("value", value.into()),
+                ("variablesReference", 0u64.into()),
+            ])
+        };
+        let memory = &self.interpreter.memory;
+        let data_pointer = self.interpreter.data_pointer;
+
+        match arguments.get("variablesReference").as_u64() {
+            Some(POINTERS) => {
+                let Some(debugger) = &self.debugger else {
+                    return Vec::new();
+                };
+                vec![
+                    variable(
+                        "instruction pointer".to_string(),
+                        debugger.instruction_pointer().to_string(),
+                    ),
+                    variable("data pointer".to_string(), data_pointer.to_string()),
+                    variable("cell".to_string(), memory[data_pointer].to_string()),
+                    variable("steps".to_string(), debugger.steps().to_string()),
+                ]
+            }
+            Some(TAPE) => {
+                let start = match arguments.get("start").as_u64() {
+                    Some(start) => start as usize,
+                    None => data_pointer.saturating_sub(TAPE_WINDOW),
+                };
+                let count = match arguments.get("count").as_u64() {
+                    Some(count) => count as usize,
+                    None => 2 * TAPE_WINDOW + 1,
+                };
+                let end = start.saturating_add(count).min(memory.len());
+                (start.min(end)..end)
+                    .map(|address| variable(address.to_string(), memory[address].to_string()))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn source(&self) -> Value {
+        let name = Path::new(&self.path).file_name().map_or_else(
+            || self.path.clone(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        json::object([("name", name.into()), ("path", self.path.as_str().into())])
+    }
+
+    fn send_stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        let mut body = json::object([
+            ("reason", reason.into()),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]);
+        if let (Some(text), Value::Object(members)) = (text, &mut body) {
+            members.push(("text".to_string(), text.into()));
+        }
+        self.send_event("stopped", body)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json::object([
+            ("type", "event".into()),
+            ("event", event.into()),
+            ("body", body),
+        ]))
+    }
+
+    /// Numbers `message` and writes it with its header, leaving out an empty
+    /// body.
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        self.seq += 1;
+        let Value::Object(mut members) = message else {
+            unreachable!("messages are objects");
+        };
+        members.retain(|(name, value)| !(name == "body" && *value == Value::Null));
+        members.insert(0, ("seq".to_string(), self.seq.into()));
+        json::write_message(&mut self.out, &Value::Object(members))
+    }
+}
+
+fn stderr_output(message: String) -> Value {
+    json::object([
+        ("category", "stderr".into()),
+        ("output", format!("{}\n", message).into()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_session() {
+        let path = std::env::temp_dir().join(format!("bf-repl-dap-{}.bf", std::process::id()));
+        std::fs::write(&path, "++[>+<-]\n>.,.\n").unwrap();
+        let path = path.to_str().unwrap().replace('\\', "/");
+
+        let requests = [
+            r#"{"seq":1,"type":"request","command":"initialize","arguments":{}}"#.to_string(),
+            format!(
+                r#"{{"seq":2,"type":"request","command":"launch","arguments":{{"program":"{}","input":"A"}}}}"#,
+                path
+            ),
+            format!(
+                r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":2}},{{"line":5}}]}}}}"#,
+                path
+            ),
+            r#"{"seq":4,"type":"request","command":"configurationDone"}"#.to_string(),
+            r#"{"seq":5,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#
+                .to_string(),
+            r#"{"seq":6,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#
+                .to_string(),
+            r#"{"seq":7,"type":"request","command":"variables","arguments":{"variablesReference":2,"start":15000,"count":2}}"#
+                .to_string(),
+            r#"{"seq":8,"type":"request","command":"stepBack","arguments":{"threadId":1}}"#
+                .to_string(),
+            r#"{"seq":9,"type":"request","command":"continue","arguments":{"threadId":1}}"#
+                .to_string(),
+            r#"{"seq":10,"type":"request","command":"continue","arguments":{"threadId":1}}"#
+                .to_string(),
+            r#"{"seq":11,"type":"request","command":"disconnect"}"#.to_string(),
+        ];
+        let input: String = requests
+            .iter()
+            .map(|request| format!("Content-Length: {}\r\n\r\n{}", request.len(), request))
+            .collect();
+
+        let builder = Interpreter::builder();
+        let mut output = Vec::new();
+        Adapter::new(&builder, &mut output)
+            .serve(&mut input.as_bytes())
+            .unwrap();
+
+        let transcript = String::from_utf8(output)
+            .unwrap()
+            .replace(&path, "/tmp/program.bf")
+            .replace(
+                &format!("bf-repl-dap-{}.bf", std::process::id()),
+                "program.bf",
+            )
+            .split("Content-Length: ")
+            .filter_map(|message| message.split_once("\r\n\r\n"))
+            .map(|(_, body)| format!("{}\n", body))
+            .collect::<String>();
+        insta::assert_snapshot!(transcript, @r#"
+        {"seq":1,"type":"response","request_seq":1,"success":true,"command":"initialize","body":{"supportsConfigurationDoneRequest":true,"supportsStepBack":true}}
+        {"seq":2,"type":"event","event":"initialized"}
+        {"seq":3,"type":"response","request_seq":2,"success":true,"command":"launch"}
+        {"seq":4,"type":"response","request_seq":3,"success":true,"command":"setBreakpoints","body":{"breakpoints":[{"verified":true,"line":2},{"verified":false,"line":5}]}}
+        {"seq":5,"type":"response","request_seq":4,"success":true,"command":"configurationDone"}
+        {"seq":6,"type":"event","event":"stopped","body":{"reason":"breakpoint","threadId":1,"allThreadsStopped":true}}
+        {"seq":7,"type":"response","request_seq":5,"success":true,"command":"stackTrace","body":{"totalFrames":1,"stackFrames":[{"id":1,"name":"main","line":2,"column":1,"source":{"name":"program.bf","path":"/tmp/program.bf"}}]}}
+        {"seq":8,"type":"response","request_seq":6,"success":true,"command":"variables","body":{"variables":[{"name":"instruction pointer","value":"8","variablesReference":0},{"name":"data pointer","value":"15000","variablesReference":0},{"name":"cell","value":"0","variablesReference":0},{"name":"steps","value":"13","variablesReference":0}]}}
+        {"seq":9,"type":"response","request_seq":7,"success":true,"command":"variables","body":{"variables":[{"name":"15000","value":"0","variablesReference":0},{"name":"15001","value":"2","variablesReference":0}]}}
+        {"seq":10,"type":"response","request_seq":8,"success":true,"command":"stepBack"}
+        {"seq":11,"type":"event","event":"stopped","body":{"reason":"step","threadId":1,"allThreadsStopped":true}}
+        {"seq":12,"type":"response","request_seq":9,"success":true,"command":"continue"}
+        {"seq":13,"type":"event","event":"stopped","body":{"reason":"breakpoint","threadId":1,"allThreadsStopped":true}}
+        {"seq":14,"type":"response","request_seq":10,"success":true,"command":"continue"}
+        {"seq":15,"type":"event","event":"output","body":{"category":"stdout","output":"\u0002A"}}
+        {"seq":16,"type":"event","event":"exited","body":{"exitCode":0}}
+        {"seq":17,"type":"event","event":"terminated"}
+        {"seq":18,"type":"response","request_seq":11,"success":true,"command":"disconnect"}
+        "#);
+    }
+}
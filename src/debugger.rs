@@ -0,0 +1,347 @@
+//! The REPL's step debugger: a program loaded with `debug` and run on the
+//! session's tape a little at a time.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, Read, Write};
+
+use crate::cell::Cell;
+use crate::diagnostic::line_column;
+use crate::{Interpreter, JumpTable, Token, interrupt, parse_loops, token_for, tokenize_with};
+
+/// How many cells on each side of the data pointer are shown at each stop.
+const MEMORY_WINDOW: usize = 3;
+/// How many instructions apart `continue` checks for Ctrl-C.
+const INTERRUPT_CHECK_INTERVAL: u64 = 1 << 16;
+/// How many steps `back` can undo.
+const HISTORY_LIMIT: usize = 100_000;
+
+/// A program paused between instructions.
+///
+/// The instruction pointer and step count are kept here rather than in the
+/// interpreter, so code run at the prompt while paused does not move them.
+///
+/// A `#` in the source is a breakpoint before the instruction after it.
+/// The tokenizer skips `#` like any other comment character, so the
+/// program runs the same outside the debugger.
+///
+/// The last steps are remembered so they can be undone. Undoing restores
+/// the pointers and cells, but output stays printed and input stays read.
+pub(crate) struct Debugger<C: Cell = u8> {
+    code: String,
+    tokens: Vec<Token>,
+    jump_table: JumpTable,
+    /// The instructions marked with `#`.
+    markers: BTreeSet<usize>,
+    /// The instructions given to `break`.
+    breakpoints: BTreeSet<usize>,
+    /// The cells given to `watch`.
+    watches: BTreeSet<usize>,
+    instruction_pointer: usize,
+    steps: u64,
+    /// What each remembered step changed, oldest first.
+    history: VecDeque<Delta<C>>,
+}
+
+/// What one step changed, so it can be undone.
+struct Delta<C> {
+    instruction_pointer: usize,
+    data_pointer: usize,
+    /// The current cell before a `+`, `-`, or `,`.
+    cell: Option<C>,
+    /// Whether the step added a cell to the end of the tape.
+    grew: bool,
+}
+
+/// Why a debugged program stopped running.
+pub(crate) enum Stop {
+    /// It paused before the next instruction.
+    Paused,
+    /// It reached a breakpoint.
+    Breakpoint,
+    /// The instruction at `instruction` changed a watched cell.
+    Watch {
+        address: usize,
+        old: String,
+        new: String,
+        instruction: usize,
+    },
+    /// It ran past its last instruction.
+    Finished,
+    /// An instruction failed; the program is paused on it.
+    Failed(String),
+    /// Ctrl-C was pressed.
+    Interrupted,
+}
+
+impl<C: Cell> Debugger<C> {
+    /// Loads `code` paused before its first instruction.
+    pub fn load(code: &str, interpreter: &Interpreter<C>) -> Result<Self, String> {
+        let tokens = tokenize_with(code, interpreter.extensions());
+        if tokens.is_empty() {
+            return Err("No instructions to debug".to_string());
+        }
+        let jump_table = parse_loops(&tokens)?;
+
+        let extensions = interpreter.extensions();
+        let mut markers = BTreeSet::new();
+        let mut instructions = 0;
+        for c in code.chars() {
+            if c == '#' {
+                markers.insert(instructions);
+            } else if token_for(c, extensions).is_some() {
+                instructions += 1;
+            }
+        }
+
+        Ok(Self {
+            code: code.to_string(),
+            tokens,
+            jump_table,
+            markers,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeSet::new(),
+            instruction_pointer: 0,
+            steps: 0,
+            history: VecDeque::new(),
+        })
+    }
+
+    /// Returns true if paused on a breakpoint.
+    pub fn at_breakpoint(&self) -> bool {
+        self.is_breakpoint(self.instruction_pointer)
+    }
+
+    fn is_breakpoint(&self, instruction: usize) -> bool {
+        self.markers.contains(&instruction) || self.breakpoints.contains(&instruction)
+    }
+
+    /// Pauses runs before the instruction at index `instruction`.
+    pub fn set_breakpoint(&mut self, instruction: usize) -> Result<(), String> {
+        if instruction >= self.tokens.len() {
+            return Err(format!(
+                "Instruction {} is out of range (0-{})",
+                instruction,
+                self.tokens.len() - 1
+            ));
+        }
+        self.breakpoints.insert(instruction);
+        Ok(())
+    }
+
+    /// Removes the breakpoints set with
+    /// [`set_breakpoint`](Self::set_breakpoint), leaving `#` markers.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Returns the breakpoints set with
+    /// [`set_breakpoint`](Self::set_breakpoint), in order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Pauses runs after any instruction that changes the cell at `address`.
+    pub fn watch(&mut self, address: usize) {
+        self.watches.insert(address);
+    }
+
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    /// Returns the watched cells, in order.
+    pub fn watches(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watches.iter().copied()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    /// Returns the instruction the program is paused before.
+    pub fn next_token(&self) -> Token {
+        self.tokens[self.instruction_pointer]
+    }
+
+    /// Returns the byte offset in the source of each instruction.
+    fn offsets(&self, interpreter: &Interpreter<C>) -> impl Iterator<Item = usize> + '_ {
+        let extensions = interpreter.extensions();
+        self.code
+            .char_indices()
+            .filter(move |&(_, c)| token_for(c, extensions).is_some())
+            .map(|(offset, _)| offset)
+    }
+
+    /// Returns the line and column, both counted from 1, of the next
+    /// instruction.
+    pub fn location(&self, interpreter: &Interpreter<C>) -> (usize, usize) {
+        let offset = self
+            .offsets(interpreter)
+            .nth(self.instruction_pointer)
+            .unwrap_or(0);
+        line_column(&self.code, offset)
+    }
+
+    /// Returns the first instruction on `line`, counted from 1.
+    pub fn first_on_line(&self, line: usize, interpreter: &Interpreter<C>) -> Option<usize> {
+        self.offsets(interpreter)
+            .position(|offset| line_column(&self.code, offset).0 == line)
+    }
+
+    /// Runs one instruction.
+    pub fn step(
+        &mut self,
+        interpreter: &mut Interpreter<C>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Stop {
+        self.run_until(interpreter, input, output, |_| true)
+    }
+
+    /// Runs one instruction, or a whole loop if paused on its `[`.
+    pub fn next(
+        &mut self,
+        interpreter: &mut Interpreter<C>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Stop {
+        match self.tokens[self.instruction_pointer] {
+            Token::LoopStart => {
+                let end = self.jump_table[self.instruction_pointer];
+                self.run_until(interpreter, input, output, |ip| ip > end)
+            }
+            _ => self.step(interpreter, input, output),
+        }
+    }
+
+    /// Runs until the program finishes, fails, or reaches a breakpoint.
+    pub fn resume(
+        &mut self,
+        interpreter: &mut Interpreter<C>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Stop {
+        self.run_until(interpreter, input, output, |_| false)
+    }
+
+    /// Runs instructions until the instruction pointer satisfies `pause` or
+    /// reaches a breakpoint, or a watched cell changes.
+    fn run_until(
+        &mut self,
+        interpreter: &mut Interpreter<C>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        pause: impl Fn(usize) -> bool,
+    ) -> Stop {
+        interpreter.instruction_pointer = self.instruction_pointer;
+        interpreter.steps = self.steps;
+
+        let stop = loop {
+            // Only `+`, `-`, and `,` change cells, and only the current one.
+            let instruction = interpreter.instruction_pointer;
+            let address = interpreter.data_pointer;
+            let tape_size = interpreter.memory.len();
+            let old = matches!(
+                self.tokens[instruction],
+                Token::IncrementData | Token::DecrementData | Token::Input
+            )
+            .then(|| interpreter.memory[address].clone());
+
+            if let Err(e) = interpreter.step(&self.tokens, &self.jump_table, input, output) {
+                break Stop::Failed(e);
+            }
+
+            let changed = old
+                .as_ref()
+                .filter(|old| **old != interpreter.memory[address])
+                .map(C::to_string);
+            if self.history.len() == HISTORY_LIMIT {
+                self.history.pop_front();
+            }
+            self.history.push_back(Delta {
+                instruction_pointer: instruction,
+                data_pointer: address,
+                cell: old,
+                grew: interpreter.memory.len() > tape_size,
+            });
+
+            if let Some(old) = changed
+                && self.watches.contains(&address)
+            {
+                break Stop::Watch {
+                    address,
+                    old,
+                    new: interpreter.memory[address].to_string(),
+                    instruction,
+                };
+            }
+            if interpreter.instruction_pointer >= self.tokens.len() {
+                break Stop::Finished;
+            }
+            if pause(interpreter.instruction_pointer) {
+                break Stop::Paused;
+            }
+            if self.is_breakpoint(interpreter.instruction_pointer) {
+                break Stop::Breakpoint;
+            }
+            if interpreter.steps.is_multiple_of(INTERRUPT_CHECK_INTERVAL)
+                && interrupt::take_request()
+            {
+                break Stop::Interrupted;
+            }
+        };
+
+        self.instruction_pointer = interpreter.instruction_pointer;
+        self.steps = interpreter.steps;
+        stop
+    }
+
+    /// Undoes up to `count` steps, returning how many were undone.
+    pub fn back(&mut self, interpreter: &mut Interpreter<C>, count: usize) -> usize {
+        let mut undone = 0;
+        while undone < count
+            && let Some(delta) = self.history.pop_back()
+        {
+            if delta.grew {
+                interpreter.memory.pop();
+            }
+            if let Some(cell) = delta.cell
+                && let Some(slot) = interpreter.memory.get_mut(delta.data_pointer)
+            {
+                *slot = cell;
+            }
+            interpreter.data_pointer = delta.data_pointer;
+            self.instruction_pointer = delta.instruction_pointer;
+            self.steps -= 1;
+            undone += 1;
+        }
+        undone
+    }
+
+    /// Writes the next instruction, marked in its line of the source, and
+    /// the cells around the data pointer.
+    pub fn write_location(
+        &self,
+        out: &mut dyn Write,
+        interpreter: &Interpreter<C>,
+    ) -> io::Result<()> {
+        let (line, column) = self.location(interpreter);
+        let text = self.code.lines().nth(line - 1).unwrap_or("");
+
+        writeln!(
+            out,
+            "Step {}: paused at instruction {} of {} ('{}')",
+            self.steps,
+            self.instruction_pointer,
+            self.tokens.len(),
+            self.tokens[self.instruction_pointer].symbol()
+        )?;
+        writeln!(out, "  {}", text)?;
+        writeln!(out, "  {}^", " ".repeat(column - 1))?;
+        interpreter.write_memory_snapshot(out, MEMORY_WINDOW)
+    }
+}
@@ -0,0 +1,167 @@
+use crate::source_offset;
+
+/// How errors and warnings about a program are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    /// One JSON record per line, for editors and graders.
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown error format '{}' (expected 'human' or 'json')",
+                name
+            )),
+        }
+    }
+
+    /// Turns a located error into the error to return from a command. In
+    /// JSON format the record is printed here, and the returned error is
+    /// empty so that `main` does not print it again.
+    pub fn report_error(self, diagnostic: Diagnostic, sources: &Sources) -> String {
+        match self {
+            Self::Human => diagnostic.message,
+            Self::Json => {
+                eprintln!("{}", diagnostic.to_json(sources));
+                String::new()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with a program, optionally located at one of its instructions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub token_index: Option<usize>,
+}
+
+/// A program's source files, in the order they were joined.
+pub type Sources<'a> = [(&'a str, &'a str)];
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, token_index: Option<usize>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            token_index,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, token_index: usize) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            token_index: Some(token_index),
+        }
+    }
+
+    /// Renders the diagnostic as a single line of JSON with the file, line,
+    /// column (both from 1), and byte span of its instruction in `sources`.
+    /// Location fields are `null` when the diagnostic has no location.
+    pub fn to_json(&self, sources: &Sources) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let code: String = sources.iter().map(|(_, code)| *code).collect();
+        let location = self
+            .token_index
+            .and_then(|index| source_offset(&code, index))
+            .and_then(|offset| locate(sources, offset));
+
+        let location = match location {
+            Some((file, line, column, start)) => format!(
+                "\"file\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}",
+                json_string(file),
+                line,
+                column,
+                start,
+                start + 1
+            ),
+            None => "\"file\":null,\"line\":null,\"column\":null,\"span\":null".to_string(),
+        };
+
+        format!(
+            "{{\"severity\":\"{}\",\"message\":{},{}}}",
+            severity,
+            json_string(&self.message),
+            location
+        )
+    }
+}
+
+/// Finds the file containing byte `offset` of the joined `sources`, and
+/// the line, column, and byte offset within that file.
+fn locate<'a>(sources: &Sources<'a>, mut offset: usize) -> Option<(&'a str, usize, usize, usize)> {
+    for &(file, code) in sources {
+        if offset < code.len() {
+            let (line, column) = line_column(code, offset);
+            return Some((file, line, column, offset));
+        }
+        offset -= code.len();
+    }
+    None
+}
+
+/// Returns the line and column, both counted from 1, of byte `offset` in
+/// `code`.
+pub fn line_column(code: &str, offset: usize) -> (usize, usize) {
+    let line = code[..offset].matches('\n').count() + 1;
+    let line_start = code[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let column = code[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let sources = [("a.bf", "+[\n"), ("b.bf", "x\n >\"]")];
+        let diagnostics = [
+            Diagnostic::error("Unmatched '['", Some(1)),
+            Diagnostic::warning("a \"quoted\"\tmessage", 2),
+            Diagnostic::error("Cannot read c.bf", None),
+        ];
+
+        insta::assert_snapshot!(
+            diagnostics.map(|d| d.to_json(&sources)).join("\n"),
+            @r#"
+        {"severity":"error","message":"Unmatched '['","file":"a.bf","line":1,"column":2,"span":{"start":1,"end":2}}
+        {"severity":"warning","message":"a \"quoted\"\tmessage","file":"b.bf","line":2,"column":2,"span":{"start":3,"end":4}}
+        {"severity":"error","message":"Cannot read c.bf","file":null,"line":null,"column":null,"span":null}
+        "#
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::{Interpreter, parse_address};
+
+/// An expression re-evaluated and shown after code runs, like gdb's `display`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayExpr {
+    /// `dp`: the data pointer.
+    DataPointer,
+    /// `cell`: the cell under the data pointer.
+    CurrentCell,
+    /// `cell(N)`: the cell at address `N`.
+    Cell(usize),
+    /// `sum(A..B)`: the sum of the cells from `A` up to but excluding `B`.
+    Sum(usize, usize),
+}
+
+impl DisplayExpr {
+    pub fn parse(expr: &str, memory_size: usize) -> Result<Self, String> {
+        let expr: String = expr.split_whitespace().collect();
+
+        if expr == "dp" {
+            return Ok(Self::DataPointer);
+        }
+        if expr == "cell" {
+            return Ok(Self::CurrentCell);
+        }
+        if let Some(addr_str) = call_argument(&expr, "cell") {
+            return Ok(Self::Cell(parse_address(addr_str, memory_size)?));
+        }
+        if let Some(range) = call_argument(&expr, "sum") {
+            let (start_str, end_str) =
+                range.split_once("..").ok_or("Usage: sum(<start>..<end>)")?;
+            let start = parse_address(start_str, memory_size)?;
+            let end = parse_address(end_str, memory_size + 1)?;
+            if end < start {
+                return Err(format!("Invalid range {}..{}", start, end));
+            }
+            return Ok(Self::Sum(start, end));
+        }
+
+        Err(format!(
+            "Unknown display expression '{}' (expected dp, cell, cell(N), or sum(A..B))",
+            expr
+        ))
+    }
+
+    pub fn eval(&self, interpreter: &Interpreter) -> u64 {
+        match *self {
+            Self::DataPointer => interpreter.data_pointer as u64,
+            Self::CurrentCell => interpreter.memory[interpreter.data_pointer] as u64,
+            Self::Cell(addr) => interpreter.memory[addr] as u64,
+            Self::Sum(start, end) => interpreter.memory[start..end]
+                .iter()
+                .map(|&cell| cell as u64)
+                .sum(),
+        }
+    }
+}
+
+fn call_argument<'a>(expr: &'a str, name: &str) -> Option<&'a str> {
+    expr.strip_prefix(name)?
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+impl fmt::Display for DisplayExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DataPointer => write!(f, "dp"),
+            Self::CurrentCell => write!(f, "cell"),
+            Self::Cell(addr) => write!(f, "cell({})", addr),
+            Self::Sum(start, end) => write!(f, "sum({}..{})", start, end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_display_expr() {
+        insta::assert_debug_snapshot!(
+            ["dp", "cell", "cell( 5 )", "sum(0..10)", "sum(3..1)", "cell(99)", "dp + 1"]
+                .map(|expr| DisplayExpr::parse(expr, 10).map(|expr| expr.to_string())),
+            @r#"
+        [
+            Ok(
+                "dp",
+            ),
+            Ok(
+                "cell",
+            ),
+            Ok(
+                "cell(5)",
+            ),
+            Ok(
+                "sum(0..10)",
+            ),
+            Err(
+                "Invalid range 3..1",
+            ),
+            Err(
+                "Address 99 is out of bounds (0-9)",
+            ),
+            Err(
+                "Unknown display expression 'dp+1' (expected dp, cell, cell(N), or sum(A..B))",
+            ),
+        ]
+        "#
+        );
+    }
+}
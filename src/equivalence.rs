@@ -0,0 +1,127 @@
+use crate::{Interpreter, Token, parse_loops};
+
+/// How a run ended: its output, final data pointer and tape, and error.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    output: Vec<u8>,
+    data_pointer: usize,
+    memory: Vec<u8>,
+    error: Option<String>,
+}
+
+/// Runs `tokens` on `input` for at most `max_steps` instructions, returning
+/// `None` if the program has not halted by then.
+fn run_bounded(tokens: &[Token], input: &[u8], max_steps: u64) -> Result<Option<Outcome>, String> {
+    let jump_table = parse_loops(tokens)?;
+    let mut interpreter = Interpreter::new();
+    let mut input = input;
+    let mut output = Vec::new();
+    let mut error = None;
+
+    while interpreter.instruction_pointer < tokens.len() {
+        if interpreter.steps >= max_steps {
+            return Ok(None);
+        }
+        if let Err(e) = interpreter.step(tokens, &jump_table, &mut input, &mut output) {
+            error = Some(e);
+            break;
+        }
+    }
+
+    Ok(Some(Outcome {
+        output,
+        data_pointer: interpreter.data_pointer,
+        memory: interpreter.memory,
+        error,
+    }))
+}
+
+/// Checks that `transformed` behaves like `original` on each of `inputs`:
+/// same output, same final tape and data pointer, and same error, if any.
+///
+/// Inputs on which `original` does not halt within `max_steps` are skipped;
+/// `transformed` may take up to `slowdown` times as many steps. Returns the
+/// number of inputs checked.
+pub fn check(
+    original: &[Token],
+    transformed: &[Token],
+    inputs: &[&[u8]],
+    max_steps: u64,
+    slowdown: u64,
+) -> Result<usize, String> {
+    let mut checked = 0;
+
+    for input in inputs {
+        let Some(expected) = run_bounded(original, input, max_steps)? else {
+            continue;
+        };
+        let actual = run_bounded(transformed, input, max_steps.saturating_mul(slowdown))?;
+
+        match actual {
+            Some(actual) if actual == expected => checked += 1,
+            Some(actual) if actual.output != expected.output => {
+                return Err(format!(
+                    "Programs differ on input \"{}\": output \"{}\" instead of \"{}\"",
+                    input.escape_ascii(),
+                    actual.output.escape_ascii(),
+                    expected.output.escape_ascii()
+                ));
+            }
+            Some(_) => {
+                return Err(format!(
+                    "Programs differ on input \"{}\": same output but different final state",
+                    input.escape_ascii()
+                ));
+            }
+            None => {
+                return Err(format!(
+                    "Programs differ on input \"{}\": only the original halts",
+                    input.escape_ascii()
+                ));
+            }
+        }
+    }
+
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize;
+
+    #[test]
+    fn test_check() {
+        let inputs: [&[u8]; 2] = [b"", b"ab"];
+        let check = |a: &str, b: &str| check(&tokenize(a), &tokenize(b), &inputs, 1000, 2);
+
+        insta::assert_debug_snapshot!(
+            [
+                check(",[.,]", "+-,[+-.,][]"),
+                check(",[.,]", ",[+.,]"),
+                check(",.", ",.>+"),
+                check("+[]", "+[]-"),
+                check("+[-]", "+[-]+[]"),
+            ],
+            @r#"
+        [
+            Ok(
+                2,
+            ),
+            Err(
+                "Programs differ on input \"ab\": output \"bc\" instead of \"ab\"",
+            ),
+            Err(
+                "Programs differ on input \"\": same output but different final state",
+            ),
+            Ok(
+                0,
+            ),
+            Err(
+                "Programs differ on input \"\": only the original halts",
+            ),
+        ]
+        "#
+        );
+    }
+}
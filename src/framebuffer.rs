@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+
+use crate::cell::Cell;
+
+/// A tape region drawn to the terminal as pixels when `%` runs.
+///
+/// Each cell is one pixel whose value picks a color from the 256-color
+/// terminal palette (0 is black). Two pixel rows share one line of text by
+/// drawing the upper half-block with separate foreground and background
+/// colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    start: usize,
+    width: usize,
+    height: usize,
+    drawn_lines: usize,
+}
+
+impl Framebuffer {
+    /// Parses `WIDTHxHEIGHT` or `WIDTHxHEIGHT@ADDRESS`; the region starts at
+    /// cell 0 unless an address is given.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid framebuffer '{}' (expected WIDTHxHEIGHT or WIDTHxHEIGHT@ADDRESS)",
+                spec
+            )
+        };
+
+        let (size, start) = match spec.split_once('@') {
+            Some((size, start)) => (size, start.parse().map_err(|_| invalid())?),
+            None => (spec, 0),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let width: usize = width.parse().map_err(|_| invalid())?;
+        let height: usize = height.parse().map_err(|_| invalid())?;
+
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            start,
+            width,
+            height,
+            drawn_lines: 0,
+        })
+    }
+
+    /// Draws the region of `memory`, replacing the previous frame if any.
+    /// Cells wider than a byte are drawn with their low eight bits.
+    pub fn render<C: Cell>(&mut self, memory: &[C], out: &mut dyn Write) -> Result<(), String> {
+        let end = self.start + self.width * self.height;
+        let Some(pixels) = memory.get(self.start..end) else {
+            return Err(format!(
+                "Framebuffer {}..{} does not fit on a tape of {} cells",
+                self.start,
+                end,
+                memory.len()
+            ));
+        };
+
+        let pixels: Vec<u8> = pixels.iter().map(Cell::to_byte).collect();
+        self.write_frame(&pixels, out).map_err(|e| e.to_string())
+    }
+
+    fn write_frame(&mut self, pixels: &[u8], out: &mut dyn Write) -> io::Result<()> {
+        if self.drawn_lines > 0 {
+            write!(out, "\x1b[{}A\r", self.drawn_lines)?;
+        }
+
+        let rows: Vec<&[u8]> = pixels.chunks(self.width).collect();
+        for pair in rows.chunks(2) {
+            for x in 0..self.width {
+                let top = pair[0][x];
+                let bottom = pair.get(1).map_or(0, |row| row[x]);
+                write!(out, "\x1b[38;5;{}m\x1b[48;5;{}m\u{2580}", top, bottom)?;
+            }
+            writeln!(out, "\x1b[0m")?;
+        }
+
+        self.drawn_lines = self.height.div_ceil(2);
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_framebuffer() {
+        insta::assert_debug_snapshot!(
+            ["32x16", "8x8@100", "8x0", "8", "8x8@-1"].map(Framebuffer::parse),
+            @r#"
+        [
+            Ok(
+                Framebuffer {
+                    start: 0,
+                    width: 32,
+                    height: 16,
+                    drawn_lines: 0,
+                },
+            ),
+            Ok(
+                Framebuffer {
+                    start: 100,
+                    width: 8,
+                    height: 8,
+                    drawn_lines: 0,
+                },
+            ),
+            Err(
+                "Invalid framebuffer '8x0' (expected WIDTHxHEIGHT or WIDTHxHEIGHT@ADDRESS)",
+            ),
+            Err(
+                "Invalid framebuffer '8' (expected WIDTHxHEIGHT or WIDTHxHEIGHT@ADDRESS)",
+            ),
+            Err(
+                "Invalid framebuffer '8x8@-1' (expected WIDTHxHEIGHT or WIDTHxHEIGHT@ADDRESS)",
+            ),
+        ]
+        "#
+        );
+    }
+
+    #[test]
+    fn test_render_framebuffer() {
+        let mut framebuffer = Framebuffer::parse("2x3@1").unwrap();
+        let memory: [u8; 7] = [9, 1, 2, 3, 4, 5, 6];
+        let mut out = Vec::new();
+
+        framebuffer.render(&memory, &mut out).unwrap();
+        framebuffer.render(&memory, &mut out).unwrap();
+        let error = framebuffer.render(&memory[..4], &mut out);
+
+        insta::assert_debug_snapshot!((String::from_utf8(out).unwrap(), error), @r#"
+        (
+            "\u{1b}[38;5;1m\u{1b}[48;5;3m▀\u{1b}[38;5;2m\u{1b}[48;5;4m▀\u{1b}[0m\n\u{1b}[38;5;5m\u{1b}[48;5;0m▀\u{1b}[38;5;6m\u{1b}[48;5;0m▀\u{1b}[0m\n\u{1b}[2A\r\u{1b}[38;5;1m\u{1b}[48;5;3m▀\u{1b}[38;5;2m\u{1b}[48;5;4m▀\u{1b}[0m\n\u{1b}[38;5;5m\u{1b}[48;5;0m▀\u{1b}[38;5;6m\u{1b}[48;5;0m▀\u{1b}[0m\n",
+            Err(
+                "Framebuffer 1..7 does not fit on a tape of 4 cells",
+            ),
+        )
+        "#);
+    }
+}
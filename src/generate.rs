@@ -0,0 +1,195 @@
+use std::fs;
+
+use crate::Token;
+use crate::rng::Rng;
+
+const LINE_WIDTH: usize = 64;
+
+/// Settings for [`generate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenOptions {
+    /// The number of instructions, including brackets.
+    pub len: usize,
+    /// How deeply loops may nest.
+    pub max_depth: usize,
+    /// Relative weights of `> < + - . , [`; `]` follows from `[`.
+    pub mix: [usize; 7],
+}
+
+const MIX_SYMBOLS: [Token; 7] = [
+    Token::IncrementPointer,
+    Token::DecrementPointer,
+    Token::IncrementData,
+    Token::DecrementData,
+    Token::Output,
+    Token::Input,
+    Token::LoopStart,
+];
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        Self {
+            len: 500,
+            max_depth: 4,
+            mix: [2, 2, 3, 3, 1, 1, 1],
+        }
+    }
+}
+
+impl GenOptions {
+    /// Parses an instruction mix such as `"+3 -3 >2 <2 .1 ,1 [1"`. Commands
+    /// that are left out get a weight of 0.
+    pub fn parse_mix(spec: &str) -> Result<[usize; 7], String> {
+        let mut mix = [0; 7];
+
+        for item in spec.split_whitespace() {
+            let mut chars = item.chars();
+            let symbol = chars.next().unwrap_or_default();
+            let index = MIX_SYMBOLS
+                .iter()
+                .position(|token| token.symbol() == symbol)
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown command '{}' in mix (expected one of ><+-.,[)",
+                        symbol
+                    )
+                })?;
+            mix[index] = chars
+                .as_str()
+                .parse()
+                .map_err(|_| format!("Invalid weight in mix item '{}'", item))?;
+        }
+
+        if mix[..6].iter().all(|&weight| weight == 0) {
+            return Err("The mix needs a nonzero weight for a command other than '['".to_string());
+        }
+
+        Ok(mix)
+    }
+}
+
+/// Writes a random program to `output` (or stdout).
+pub fn run_gen_random(
+    options: &GenOptions,
+    output: Option<&str>,
+    seed: Option<u64>,
+) -> Result<(), String> {
+    let seed = seed.unwrap_or_else(Rng::time_seed);
+    let code = generate(options, &mut Rng::new(seed));
+    eprintln!("Generated {} instructions (seed {})", options.len, seed);
+
+    match output {
+        Some(path) => fs::write(path, code).map_err(|e| format!("Cannot write {}: {}", path, e)),
+        None => {
+            print!("{}", code);
+            Ok(())
+        }
+    }
+}
+
+/// Generates a program of exactly `options.len` instructions whose brackets
+/// are balanced. Loops are never empty, so a loop entered on a nonzero cell
+/// still has a chance to end.
+pub fn generate(options: &GenOptions, rng: &mut Rng) -> String {
+    let mut code = String::with_capacity(options.len);
+    let mut depth = 0;
+    let mut last = None;
+    let total_weight: usize = options.mix.iter().sum();
+
+    for position in 0..options.len {
+        let remaining = options.len - position;
+        let can_close = depth > 0 && last != Some(Token::LoopStart);
+        let can_open = depth < options.max_depth && remaining >= depth + 3;
+
+        let token = if can_close && (remaining == depth || rng.chance(options.mix[6], total_weight))
+        {
+            Token::LoopEnd
+        } else {
+            loop {
+                let mut pick = rng.below(total_weight);
+                let index = options
+                    .mix
+                    .iter()
+                    .position(|&weight| {
+                        let hit = pick < weight;
+                        pick = pick.saturating_sub(weight);
+                        hit
+                    })
+                    .expect("weights add up to the total");
+                if MIX_SYMBOLS[index] != Token::LoopStart || can_open {
+                    break MIX_SYMBOLS[index];
+                }
+            }
+        };
+
+        match token {
+            Token::LoopStart => depth += 1,
+            Token::LoopEnd => depth -= 1,
+            _ => {}
+        }
+        code.push(token.symbol());
+        last = Some(token);
+    }
+
+    let lines: Vec<&str> = code
+        .as_bytes()
+        .chunks(LINE_WIDTH)
+        .map(|line| std::str::from_utf8(line).expect("instructions are ASCII"))
+        .collect();
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_loops, tokenize};
+
+    #[test]
+    fn test_generate() {
+        let options = GenOptions {
+            len: 120,
+            max_depth: 2,
+            ..GenOptions::default()
+        };
+        let code = generate(&options, &mut Rng::new(7));
+        let tokens = tokenize(&code);
+        assert_eq!(tokens.len(), 120);
+        assert!(parse_loops(&tokens).is_ok());
+
+        insta::assert_snapshot!(code, @r"
+        ,---.++---[-+-[--,+-><->+<>>-]<+<,,>--+><]--+<->---+-+--.<>><[+>
+        .,+-+-><][[-+-+<<-<<+<-+<].<>,<+.<>-]<->.-----[.],++>++>
+        ");
+    }
+
+    #[test]
+    fn test_parse_mix() {
+        insta::assert_debug_snapshot!(
+            ["+3 -3 [1", "+1 x2", "+a", "[5"].map(GenOptions::parse_mix),
+            @r#"
+        [
+            Ok(
+                [
+                    0,
+                    0,
+                    3,
+                    3,
+                    0,
+                    0,
+                    1,
+                ],
+            ),
+            Err(
+                "Unknown command 'x' in mix (expected one of ><+-.,[)",
+            ),
+            Err(
+                "Invalid weight in mix item '+a'",
+            ),
+            Err(
+                "The mix needs a nonzero weight for a command other than '['",
+            ),
+        ]
+        "#
+        );
+    }
+}
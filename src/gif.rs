@@ -0,0 +1,179 @@
+//! A small animated GIF encoder (GIF89a), enough to write tape animations
+//! without pulling in an image library.
+
+use std::collections::HashMap;
+
+const MIN_CODE_SIZE: u8 = 8;
+const CLEAR_CODE: u16 = 1 << MIN_CODE_SIZE;
+const END_CODE: u16 = CLEAR_CODE + 1;
+const MAX_CODE: u16 = 4095;
+
+/// Encodes `frames`, each `width * height` palette indexes in row order, as
+/// an animation that loops forever, showing each frame for `delay`
+/// hundredths of a second.
+pub fn encode(
+    width: u16,
+    height: u16,
+    palette: &[[u8; 3]; 256],
+    frames: &[Vec<u8>],
+    delay: u16,
+) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(width.to_le_bytes());
+    gif.extend(height.to_le_bytes());
+    // A global 256-color palette, then the background color and aspect ratio.
+    gif.extend([0xf7, 0, 0]);
+    gif.extend(palette.iter().flatten());
+    // Loop forever.
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+    for frame in frames {
+        gif.extend([0x21, 0xf9, 4, 0]);
+        gif.extend(delay.to_le_bytes());
+        gif.extend([0, 0]);
+
+        gif.push(0x2c);
+        gif.extend([0, 0, 0, 0]);
+        gif.extend(width.to_le_bytes());
+        gif.extend(height.to_le_bytes());
+        gif.push(0);
+
+        gif.push(MIN_CODE_SIZE);
+        for block in compress(frame).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3b);
+    gif
+}
+
+/// Compresses palette indexes with GIF's variant of LZW.
+fn compress(pixels: &[u8]) -> Vec<u8> {
+    let mut writer = CodeWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = MIN_CODE_SIZE as u32 + 1;
+    let mut next_code = END_CODE + 1;
+
+    writer.write(CLEAR_CODE, code_size);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        writer.write(END_CODE, code_size);
+        return writer.finish();
+    };
+    let mut prefix = first as u16;
+
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+        if next_code <= MAX_CODE {
+            table.insert((prefix, pixel), next_code);
+            if next_code == 1 << code_size {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            writer.write(CLEAR_CODE, code_size);
+            table.clear();
+            code_size = MIN_CODE_SIZE as u32 + 1;
+            next_code = END_CODE + 1;
+        }
+        prefix = pixel as u16;
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(END_CODE, code_size);
+    writer.finish()
+}
+
+#[derive(Default)]
+struct CodeWriter {
+    data: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.data.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.data.push(self.buffer as u8);
+        }
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes GIF LZW data, to check [`compress`] against.
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut output = Vec::new();
+        let (mut buffer, mut count, mut position) = (0u32, 0u32, 0);
+        let mut code_size = MIN_CODE_SIZE as u32 + 1;
+        let mut previous: Option<Vec<u8>> = None;
+
+        loop {
+            while count < code_size {
+                buffer |= (data[position] as u32) << count;
+                position += 1;
+                count += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as u16;
+            buffer >>= code_size;
+            count -= code_size;
+
+            if code == CLEAR_CODE {
+                table = (0..=255).map(|i| vec![i]).collect();
+                table.extend([vec![], vec![]]);
+                code_size = MIN_CODE_SIZE as u32 + 1;
+                previous = None;
+                continue;
+            }
+            if code == END_CODE {
+                return output;
+            }
+
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [&previous[..], &previous[..1]].concat(),
+                (None, None) => panic!("invalid first code"),
+            };
+            if let Some(previous) = previous
+                && table.len() <= MAX_CODE as usize
+            {
+                table.push([&previous[..], &entry[..1]].concat());
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+            output.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let noisy: Vec<u8> = (0..20000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        for pixels in [vec![], vec![7; 5000], b"abababababcabc".to_vec(), noisy] {
+            assert_eq!(decompress(&compress(&pixels)), pixels);
+        }
+    }
+}
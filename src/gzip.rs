@@ -0,0 +1,501 @@
+//! A small gzip decoder and encoder (RFC 1951 and RFC 1952), enough to read
+//! compressed program sources and write compressed core dumps without
+//! pulling in a compression library.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The largest distance a deflate match may reach back.
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+/// Decompresses every member of a gzip stream.
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let member_start = output.len();
+        let data = skip_header(rest)?;
+        let mut reader = BitReader::new(data);
+        inflate(&mut reader, &mut output)?;
+
+        let trailer = &data[reader.position..];
+        if trailer.len() < 8 {
+            return Err("Truncated gzip trailer".to_string());
+        }
+        let expected_crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        if crc32(&output[member_start..]) != expected_crc {
+            return Err("gzip checksum mismatch".to_string());
+        }
+        rest = &trailer[8..];
+    }
+
+    Ok(output)
+}
+
+/// Compresses `bytes` into a single gzip member. Matches are found through
+/// a hash of the next three bytes and encoded with the fixed Huffman codes,
+/// which is quick and does well on the long runs of zeros in a tape.
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.bits(1, 1); // Final block
+    writer.bits(1, 2); // Fixed Huffman codes
+
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let mut length = 0;
+        let mut distance = 0;
+
+        if position + MIN_MATCH <= bytes.len() {
+            let hash = hash(&bytes[position..]);
+            let candidate = last_seen[hash];
+            last_seen[hash] = position;
+
+            if candidate != usize::MAX && position - candidate <= WINDOW_SIZE {
+                length = bytes[candidate..]
+                    .iter()
+                    .zip(&bytes[position..])
+                    .take(MAX_MATCH)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                distance = position - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            writer.length(length);
+            writer.distance(distance);
+            for skipped in position + 1..(position + length).min(bytes.len() - MIN_MATCH + 1) {
+                last_seen[hash(&bytes[skipped..])] = skipped;
+            }
+            position += length;
+        } else {
+            writer.literal(bytes[position] as u16);
+            position += 1;
+        }
+    }
+    writer.literal(256);
+
+    let mut output = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    output.extend(writer.finish());
+    output.extend(crc32(bytes).to_le_bytes());
+    output.extend((bytes.len() as u32).to_le_bytes());
+    output
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let key = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+    (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+fn skip_header(bytes: &[u8]) -> Result<&[u8], String> {
+    if bytes.len() < 10 || !is_gzip(bytes) {
+        return Err("Not a gzip stream".to_string());
+    }
+    if bytes[2] != 8 {
+        return Err("Unsupported gzip compression method".to_string());
+    }
+
+    let flags = bytes[3];
+    let mut position = 10;
+
+    let truncated = || "Truncated gzip header".to_string();
+
+    if flags & 0x04 != 0 {
+        let extra = bytes.get(position..position + 2).ok_or_else(truncated)?;
+        position += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = bytes
+                .get(position..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or_else(truncated)?;
+            position += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        position += 2;
+    }
+
+    bytes.get(position..).ok_or_else(truncated)
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("Unexpected end of deflate data")?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `n` bits of `value`, least significant first.
+    fn bits(&mut self, value: u32, n: u32) {
+        self.buffer |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.data.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first.
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    /// Writes a literal/length symbol with the fixed Huffman code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let index = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+        self.literal(257 + index as u16);
+        self.bits(
+            (length - LENGTH_BASE[index] as usize) as u32,
+            LENGTH_EXTRA[index] as u32,
+        );
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+        self.code(index as u32, 5);
+        self.bits(
+            (distance - DISTANCE_BASE[index] as usize) as u32,
+            DISTANCE_EXTRA[index] as u32,
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.data.push(self.buffer as u8);
+        }
+        self.data
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("Invalid Huffman code in deflate data".to_string())
+    }
+}
+
+fn inflate(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), String> {
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => inflate_stored(reader, output)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                inflate_block(reader, output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(reader)?;
+                inflate_block(reader, output, &literals, &distances)?;
+            }
+            _ => return Err("Invalid deflate block type".to_string()),
+        }
+
+        if last {
+            reader.align_to_byte();
+            return Ok(());
+        }
+    }
+}
+
+fn inflate_stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), String> {
+    reader.align_to_byte();
+    let len = reader.bits(16)? as usize;
+    let complement = reader.bits(16)? as usize;
+    if len != !complement & 0xffff {
+        return Err("Corrupt stored deflate block".to_string());
+    }
+
+    let block = reader
+        .data
+        .get(reader.position..reader.position + len)
+        .ok_or("Unexpected end of deflate data")?;
+    output.extend_from_slice(block);
+    reader.position += len;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("Repeat with no previous length")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+
+    if lengths.len() != literal_count + distance_count {
+        return Err("Too many code lengths in deflate data".to_string());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("Invalid length code in deflate data".to_string());
+                }
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("Invalid distance code in deflate data".to_string());
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err("Distance too far back in deflate data".to_string());
+                }
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress_fixed_and_dynamic_blocks() {
+        // gzip.compress(b"++[>+<-]"), which uses a fixed Huffman block.
+        let fixed = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xd3, 0xd6, 0x8e, 0xb6,
+            0xd3, 0xb6, 0xd1, 0x8d, 0x05, 0x00, 0xb1, 0x71, 0x6e, 0x1b, 0x08, 0x00, 0x00, 0x00,
+        ];
+        // A 238-byte program with comments, which uses a dynamic Huffman block.
+        let dynamic = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x95, 0x8d, 0x3b, 0x0a,
+            0xc3, 0x30, 0x0c, 0x86, 0x77, 0x9f, 0x42, 0x7b, 0x2c, 0x1d, 0x20, 0x08, 0x41, 0xe8,
+            0xd2, 0xb9, 0xab, 0xf1, 0x10, 0xb0, 0x09, 0x01, 0x07, 0x17, 0xdb, 0x34, 0xd7, 0xaf,
+            0x9c, 0xb8, 0x07, 0xe8, 0x0f, 0x92, 0x3e, 0xbd, 0x9f, 0x31, 0xa5, 0x0c, 0x67, 0x2e,
+            0x29, 0xc0, 0xbb, 0xe4, 0xad, 0xac, 0x87, 0x99, 0x86, 0x9c, 0xfc, 0x7c, 0x87, 0xdb,
+            0x58, 0x85, 0x5e, 0x94, 0x04, 0x45, 0x26, 0xc7, 0xbe, 0xa7, 0x42, 0x82, 0x88, 0x34,
+            0xf6, 0xa8, 0x03, 0x69, 0x91, 0x91, 0xf8, 0x62, 0xbc, 0x34, 0x82, 0x82, 0x6e, 0x92,
+            0xde, 0x23, 0xf3, 0x58, 0xdb, 0x0c, 0xd6, 0x91, 0xf5, 0x00, 0xaf, 0xf8, 0x89, 0xa5,
+            0xc6, 0x19, 0xc4, 0x3a, 0xb1, 0x9e, 0x1d, 0xb1, 0x56, 0x97, 0x10, 0x2a, 0xb4, 0x33,
+            0x43, 0xd8, 0xb7, 0xbd, 0x55, 0x9d, 0xd6, 0x36, 0xeb, 0x77, 0x7d, 0xfc, 0xa7, 0xc8,
+            0x7c, 0x01, 0x4a, 0x3e, 0x7e, 0x3d, 0xee, 0x00, 0x00, 0x00,
+        ];
+
+        insta::assert_debug_snapshot!(
+            (decompress(&fixed).map(String::from_utf8), decompress(&dynamic).map(|b| b.len())),
+            @r#"
+        (
+            Ok(
+                Ok(
+                    "++[>+<-]",
+                ),
+            ),
+            Ok(
+                238,
+            ),
+        )
+        "#
+        );
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let mut tape = vec![0u8; 30000];
+        tape[15000..15013].copy_from_slice(b"Hello, World!");
+        let inputs = [
+            Vec::new(),
+            b"++[>+<-]".to_vec(),
+            b"abcabcabcabcx".to_vec(),
+            tape,
+        ];
+
+        for input in &inputs {
+            assert_eq!(decompress(&compress(input)).as_ref(), Ok(input));
+        }
+        insta::assert_debug_snapshot!(inputs.map(|input| compress(&input).len()), @r"
+        [
+            20,
+            28,
+            25,
+            228,
+        ]
+        ");
+    }
+}
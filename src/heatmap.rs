@@ -0,0 +1,147 @@
+use crate::Token;
+
+/// The most rows [`Heatmap::render`] prints; wider regions share rows.
+const MAX_ROWS: usize = 32;
+const BAR_WIDTH: u64 = 40;
+
+/// How many times each cell was read and written in a run.
+///
+/// `+` and `-` read and write the current cell, `.`, `[`, and `]` read it,
+/// and `,` writes it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl Heatmap {
+    /// Counts the accesses `token` made to the cell at `address`.
+    pub fn record(&mut self, token: Token, address: usize) {
+        let (read, write) = match token {
+            Token::IncrementData | Token::DecrementData => (true, true),
+            Token::Output | Token::LoopStart | Token::LoopEnd => (true, false),
+            Token::Input => (false, true),
+            Token::IncrementPointer | Token::DecrementPointer | Token::Refresh => return,
+        };
+        if address >= self.reads.len() {
+            self.reads.resize(address + 1, 0);
+            self.writes.resize(address + 1, 0);
+        }
+        self.reads[address] += read as u64;
+        self.writes[address] += write as u64;
+    }
+
+    pub fn reads(&self, address: usize) -> u64 {
+        self.reads.get(address).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, address: usize) -> u64 {
+        self.writes.get(address).copied().unwrap_or(0)
+    }
+
+    /// Renders a bar chart of accesses from the first cell accessed to the
+    /// last, one row per cell or, for regions wider than 32 cells, per
+    /// equal share of the region. Rows without accesses are left out, and
+    /// the busiest row gets a full-width bar.
+    pub fn render(&self) -> String {
+        let touched = |address: &usize| self.reads(*address) + self.writes(*address) > 0;
+        let Some(first) = (0..self.reads.len()).find(touched) else {
+            return "No cells accessed\n".to_string();
+        };
+        let last = (0..self.reads.len()).rfind(touched).unwrap_or(first);
+        let cells_per_row = (last - first + 1).div_ceil(MAX_ROWS);
+
+        let rows: Vec<(String, u64, u64)> = (first..=last)
+            .step_by(cells_per_row)
+            .map(|start| {
+                let end = (start + cells_per_row).min(last + 1);
+                let label = if end - start == 1 {
+                    start.to_string()
+                } else {
+                    format!("{}-{}", start, end - 1)
+                };
+                let reads = (start..end).map(|address| self.reads(address)).sum();
+                let writes = (start..end).map(|address| self.writes(address)).sum();
+                (label, reads, writes)
+            })
+            .filter(|&(_, reads, writes)| reads + writes > 0)
+            .collect();
+
+        let max = rows
+            .iter()
+            .map(|&(_, reads, writes)| reads + writes)
+            .max()
+            .unwrap_or(1);
+        let label_width = rows
+            .iter()
+            .map(|(label, ..)| label.len())
+            .chain(["cells".len()])
+            .max()
+            .unwrap_or(0);
+        let reads_width = rows
+            .iter()
+            .map(|&(_, reads, _)| reads.to_string().len())
+            .chain(["reads".len()])
+            .max()
+            .unwrap_or(0);
+        let writes_width = rows
+            .iter()
+            .map(|&(_, _, writes)| writes.to_string().len())
+            .chain(["writes".len()])
+            .max()
+            .unwrap_or(0);
+
+        let mut chart = format!(
+            "{:<label_width$}  {:>reads_width$}  {:>writes_width$}\n",
+            "cells", "reads", "writes"
+        );
+        for (label, reads, writes) in rows {
+            let bar = ((reads + writes) * BAR_WIDTH).div_ceil(max) as usize;
+            chart.push_str(&format!(
+                "{:<label_width$}  {:>reads_width$}  {:>writes_width$}  {}\n",
+                label,
+                reads,
+                writes,
+                "#".repeat(bar)
+            ));
+        }
+        chart
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, parse_loops, tokenize};
+
+    #[test]
+    fn test_render_heatmap() {
+        let tokens = tokenize("++++++++[>++++[>++<-]<-]>>.,>>+");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::builder().tape_size(10).heatmap(true).build();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b""[..], Vec::new())
+            .unwrap();
+
+        insta::assert_snapshot!(interpreter.heatmap().unwrap().render(), @r"
+        cells  reads  writes
+        5         25      16  ##########
+        6        104      64  ########################################
+        7         65      65  ###############################
+        9          1       1  #
+        ");
+
+        // The cells accessed span 41 cells, so each row covers two.
+        let tokens = tokenize(&format!("++{}+", ">".repeat(40)));
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::builder().tape_size(100).heatmap(true).build();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b""[..], Vec::new())
+            .unwrap();
+
+        insta::assert_snapshot!(interpreter.heatmap().unwrap().render(), @r"
+        cells  reads  writes
+        50-51      2       2  ########################################
+        90         1       1  ####################
+        ");
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+
+/// How many bytes each line shows.
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes `bytes` as a classic hexdump: the address of each line's first
+/// byte, sixteen bytes in hex split into two groups of eight, and the same
+/// bytes as characters, with `.` for anything unprintable. Addresses count
+/// from `start` in decimal, as everywhere else in the REPL.
+pub(crate) fn write_hexdump(out: &mut dyn Write, bytes: &[u8], start: usize) -> io::Result<()> {
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write!(out, "{:>6} ", start + line * BYTES_PER_LINE)?;
+        for i in 0..BYTES_PER_LINE {
+            if i % 8 == 0 {
+                write!(out, " ")?;
+            }
+            match chunk.get(i) {
+                Some(byte) => write!(out, "{:02x} ", byte)?,
+                None => write!(out, "   ")?,
+            }
+        }
+
+        let text: String = chunk
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' => byte as char,
+                _ => '.',
+            })
+            .collect();
+        writeln!(out, " |{}|", text)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let mut out = Vec::new();
+        write_hexdump(&mut out, b"Hello, world!\n\0\x7f\xffBrainfuck", 100).unwrap();
+
+        insta::assert_snapshot!(String::from_utf8(out).unwrap(), @r"
+        100  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 7f  |Hello, world!...|
+        116  ff 42 72 61 69 6e 66 75  63 6b                    |.Brainfuck|
+        ");
+    }
+}
@@ -0,0 +1,78 @@
+use crate::Token;
+
+const COMMANDS: [(char, Token); 8] = [
+    ('>', Token::IncrementPointer),
+    ('<', Token::DecrementPointer),
+    ('+', Token::IncrementData),
+    ('-', Token::DecrementData),
+    ('.', Token::Output),
+    (',', Token::Input),
+    ('[', Token::LoopStart),
+    (']', Token::LoopEnd),
+];
+
+const BAR_WIDTH: u64 = 40;
+
+/// How often each of the eight commands occurs, in a program's source or
+/// in a run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    counts: [u64; 8],
+}
+
+impl Histogram {
+    pub fn from_tokens(tokens: &[Token]) -> Self {
+        let mut histogram = Self::default();
+        for &token in tokens {
+            histogram.record(token);
+        }
+        histogram
+    }
+
+    /// Counts one occurrence of `token`. Extension instructions are not
+    /// counted.
+    pub fn record(&mut self, token: Token) {
+        if let Some(index) = COMMANDS.iter().position(|&(_, command)| command == token) {
+            self.counts[index] += 1;
+        }
+    }
+
+    /// Renders a bar chart with one row per command, scaled so the most
+    /// frequent command gets a full-width bar.
+    pub fn render(&self) -> String {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let width = max.to_string().len();
+
+        COMMANDS
+            .iter()
+            .zip(self.counts)
+            .map(|(&(symbol, _), count)| {
+                let bar = (count * BAR_WIDTH).div_ceil(max) as usize;
+                format!("{} {:>width$} {}", symbol, count, "#".repeat(bar))
+                    .trim_end()
+                    .to_string()
+                    + "\n"
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize;
+
+    #[test]
+    fn test_render_histogram() {
+        insta::assert_snapshot!(Histogram::from_tokens(&tokenize("++++++++[>++++<-]>.")).render(), @r"
+        >  2 #######
+        <  1 ####
+        + 12 ########################################
+        -  1 ####
+        .  1 ####
+        ,  0
+        [  1 ####
+        ]  1 ####
+        ");
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::io::{self, Write};
+
+use crate::coredump::CoreDump;
+use crate::{Interpreter, parse_address, source_offset_with};
+
+/// The inspector's commands, each with how to use it and what it does.
+const COMMANDS: [(&str, &str); 6] = [
+    ("help", "List these commands"),
+    ("mem", "Show the cells around the data pointer"),
+    (
+        "show [<addr>]",
+        "Show the current cell or the cell at <addr>",
+    ),
+    ("where", "Show the faulting instruction in its source line"),
+    (
+        "export <start> <end> <file>",
+        "Write the cells from <start> up to <end> to <file>",
+    ),
+    ("exit", "Leave the inspector"),
+];
+
+/// Browses a core dump without re-running the program it came from.
+pub fn run_inspector(core_path: &str) -> Result<(), String> {
+    let dump = CoreDump::load(core_path)?;
+    let mut interpreter = Interpreter::new();
+    interpreter.memory = dump.memory.clone();
+    interpreter.data_pointer = dump.data_pointer;
+    interpreter.instruction_pointer = dump.instruction_pointer;
+    interpreter.input_position = dump.input_position;
+
+    eprintln!("Core dump {}", core_path);
+    eprintln!("Error: {}", dump.error);
+    eprintln!(
+        "Stopped at instruction {} with the data pointer at {} after reading {} input bytes",
+        dump.instruction_pointer, dump.data_pointer, dump.input_position
+    );
+    eprintln!("Type 'help' for the commands, or 'where' to show the faulting instruction.");
+
+    loop {
+        eprint!("inspect> ");
+
+        let mut input = String::new();
+
+        let bytes_read = io::stdin()
+            .read_line(&mut input)
+            .map_err(|e| e.to_string())?;
+
+        if bytes_read == 0 {
+            eprintln!();
+            break;
+        }
+
+        let parts: Vec<&str> = input.split_whitespace().collect();
+
+        match parts.as_slice() {
+            [] => {}
+            ["quit" | "exit"] => break,
+            ["help"] => write_help(&mut io::stderr()).map_err(|e| e.to_string())?,
+            ["mem" | "memory"] => interpreter.print_memory_snapshot(5),
+            ["show"] => interpreter.print_current_cell(),
+            ["show", addr_str] => match parse_address(addr_str, dump.memory.len()) {
+                Ok(addr) => interpreter.print_cell(addr),
+                Err(e) => eprintln!("{}", e),
+            },
+            ["where"] => eprint!("{}", faulting_instruction(&dump)),
+            ["export", start_str, end_str, filename] => {
+                match export_region(&dump, start_str, end_str, filename) {
+                    Ok(len) => eprintln!("Exported {} cells to {}", len, filename),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            ["export", ..] => eprintln!("Usage: export <start> <end> <file>"),
+            _ => eprintln!("Unknown command; the inspector cannot run code (type 'help')"),
+        }
+    }
+
+    Ok(())
+}
+
+fn write_help(out: &mut impl Write) -> io::Result<()> {
+    let width = COMMANDS
+        .iter()
+        .map(|(usage, _)| usage.len())
+        .max()
+        .unwrap_or(0);
+    for (usage, help) in COMMANDS {
+        writeln!(out, "  {:width$}  {}", usage, help)?;
+    }
+    Ok(())
+}
+
+/// Shows the source line of the instruction the dump stopped at, with a
+/// caret under the instruction.
+fn faulting_instruction(dump: &CoreDump) -> String {
+    let Some(offset) = source_offset_with(&dump.program, dump.instruction_pointer, dump.extensions)
+    else {
+        return format!(
+            "Instruction {} is past the end of the program\n",
+            dump.instruction_pointer
+        );
+    };
+
+    let line_start = dump.program[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = dump.program[offset..]
+        .find('\n')
+        .map_or(dump.program.len(), |i| offset + i);
+    let line_number = dump.program[..offset].matches('\n').count() + 1;
+    let column = dump.program[line_start..offset].chars().count();
+
+    format!(
+        "Line {}, column {}:\n{}\n{}^\n",
+        line_number,
+        column + 1,
+        &dump.program[line_start..line_end],
+        " ".repeat(column)
+    )
+}
+
+fn export_region(
+    dump: &CoreDump,
+    start_str: &str,
+    end_str: &str,
+    filename: &str,
+) -> Result<usize, String> {
+    let start = parse_address(start_str, dump.memory.len())?;
+    let end = parse_address(end_str, dump.memory.len() + 1)?;
+
+    if end < start {
+        return Err(format!("Invalid range {}..{}", start, end));
+    }
+
+    fs::write(filename, &dump.memory[start..end])
+        .map_err(|e| format!("Cannot write {}: {}", filename, e))?;
+
+    Ok(end - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Extensions;
+
+    #[test]
+    fn test_help() {
+        let mut out = Vec::new();
+        write_help(&mut out).unwrap();
+
+        insta::assert_snapshot!(String::from_utf8_lossy(&out), @r"
+          help                         List these commands
+          mem                          Show the cells around the data pointer
+          show [<addr>]                Show the current cell or the cell at <addr>
+          where                        Show the faulting instruction in its source line
+          export <start> <end> <file>  Write the cells from <start> up to <end> to <file>
+          exit                         Leave the inspector
+        ");
+    }
+
+    #[test]
+    fn test_faulting_instruction_with_graphics() {
+        let dump = CoreDump {
+            error: "Data pointer out of bounds (left)".to_string(),
+            program: "+%\n%<".to_string(),
+            instruction_pointer: 3,
+            data_pointer: 0,
+            input_position: 0,
+            extensions: Extensions { graphics: true },
+            memory: vec![1],
+        };
+
+        insta::assert_snapshot!(faulting_instruction(&dump), @r"
+        Line 2, column 2:
+        %<
+         ^
+        ");
+    }
+}
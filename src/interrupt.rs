@@ -0,0 +1,85 @@
+//! Ctrl-C for programs run from the REPL: while a [`Catch`] is alive, SIGINT
+//! asks the running program to stop instead of ending the process. Pressing
+//! it again before the program notices ends the process as usual.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when an interrupt arrives. Compiled code reads it directly.
+pub(crate) static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks the running program to stop, as Ctrl-C does under a [`Catch`].
+pub(crate) fn request() {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Returns true if an interrupt arrived since the last call.
+pub(crate) fn take_request() -> bool {
+    REQUESTED.load(Ordering::Relaxed) && REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Routes SIGINT to running programs until dropped. Does nothing on
+/// platforms without Unix signals.
+pub struct Catch {
+    _private: (),
+}
+
+impl Catch {
+    pub fn new() -> Self {
+        REQUESTED.store(false, Ordering::Relaxed);
+        #[cfg(unix)]
+        signals::install();
+        Self { _private: () }
+    }
+}
+
+impl Default for Catch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Catch {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        signals::uninstall();
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::REQUESTED;
+
+    /// The handler from before [`install`], such as the one restoring the
+    /// terminal.
+    static PREVIOUS: AtomicUsize = AtomicUsize::new(libc::SIG_DFL);
+
+    pub fn install() {
+        // SAFETY: the handler only touches atomics and calls
+        // async-signal-safe functions.
+        let previous = unsafe {
+            libc::signal(
+                libc::SIGINT,
+                handle_signal as *const () as libc::sighandler_t,
+            )
+        };
+        PREVIOUS.store(previous, Ordering::SeqCst);
+    }
+
+    pub fn uninstall() {
+        // SAFETY: puts back the handler `install` replaced.
+        unsafe { libc::signal(libc::SIGINT, PREVIOUS.load(Ordering::SeqCst)) };
+    }
+
+    extern "C" fn handle_signal(_: libc::c_int) {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            // SAFETY: re-raising with the previous handler does what the
+            // signal would have done without this one.
+            unsafe {
+                libc::signal(libc::SIGINT, PREVIOUS.load(Ordering::SeqCst));
+                libc::raise(libc::SIGINT);
+            }
+        }
+    }
+}
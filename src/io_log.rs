@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// An input or output event performed by `,` or `.`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoEvent {
+    Input(u8),
+    EndOfInput,
+    Output(u8),
+}
+
+/// A newline-delimited JSON log of a program's input and output.
+///
+/// Each line records one event with the seconds elapsed since the log was
+/// created and the index of the instruction that caused it:
+///
+/// ```text
+/// {"time":0.000113,"ip":12,"event":"output","byte":72}
+/// ```
+///
+/// Clones share the same log, and every event is flushed as it is written
+/// so the log is complete even if the program never finishes.
+#[derive(Debug, Clone)]
+pub struct IoLog(Arc<Mutex<LogFile>>);
+
+#[derive(Debug)]
+struct LogFile {
+    out: BufWriter<File>,
+    start: Instant,
+}
+
+impl IoLog {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        Ok(Self(Arc::new(Mutex::new(LogFile {
+            out: BufWriter::new(file),
+            start: Instant::now(),
+        }))))
+    }
+
+    pub fn record(&self, instruction_pointer: usize, event: IoEvent) -> Result<(), String> {
+        let mut log = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let time = log.start.elapsed().as_secs_f64();
+        let details = match event {
+            IoEvent::Input(byte) => format!("\"event\":\"input\",\"byte\":{}", byte),
+            IoEvent::EndOfInput => "\"event\":\"eof\"".to_string(),
+            IoEvent::Output(byte) => format!("\"event\":\"output\",\"byte\":{}", byte),
+        };
+
+        writeln!(
+            log.out,
+            "{{\"time\":{:.6},\"ip\":{},{}}}",
+            time, instruction_pointer, details
+        )
+        .and_then(|_| log.out.flush())
+        .map_err(|e| format!("Cannot write I/O log: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, parse_loops, tokenize};
+
+    use super::*;
+
+    #[test]
+    fn test_io_log() {
+        let path =
+            std::env::temp_dir().join(format!("bf-repl-io-log-{}.ndjson", std::process::id()));
+        let log = IoLog::create(path.to_str().unwrap()).unwrap();
+        let tokens = tokenize(",+.>,.,");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::builder().io_log(Some(log)).build();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b"a"[..], &mut Vec::new())
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // Timestamps vary from run to run, so only the rest of each event is
+        // compared.
+        let events: Vec<&str> = contents
+            .lines()
+            .map(|line| line.split_once(",").unwrap().1)
+            .collect();
+
+        insta::assert_snapshot!(events.join("\n"), @r#"
+        "ip":0,"event":"input","byte":97}
+        "ip":2,"event":"output","byte":98}
+        "ip":4,"event":"eof"}
+        "ip":5,"event":"output","byte":0}
+        "ip":6,"event":"eof"}
+        "#);
+    }
+}
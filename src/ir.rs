@@ -0,0 +1,492 @@
+//! The form programs are executed in when no step hooks are enabled.
+//!
+//! Runs of `+` and `-` fold into one [`Op::Add`] and runs of `>` or `<` into
+//! one [`Op::Move`], so the interpreter does in one dispatch what the tokens
+//! do in many. Loops with a known outcome get an instruction in front that
+//! does the whole loop at once; it falls through to the loop itself when it
+//! cannot, such as when the loop would never end. Each instruction
+//! remembers the tokens it came from, so errors and step counts are the
+//! same as running the tokens one at a time.
+
+use crate::{JumpTable, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Adds the number of `+` minus the number of `-` to the current cell.
+    Add(i64),
+    /// Moves the data pointer right by a run of `>`, or left by a run of `<`.
+    Move(isize),
+    Output,
+    Input,
+    /// `[`: continues after the instruction at the index, its matching `]`,
+    /// if the current cell is zero. `None` if the jump table has no entry.
+    JumpIfZero(Option<usize>),
+    /// `]`: continues after the instruction at the index, its matching `[`,
+    /// if the current cell is not zero.
+    JumpIfNonZero(Option<usize>),
+    Refresh,
+    /// `[-]`, or `[+]` if `up`: sets the current cell to zero and continues
+    /// after the loop that follows, counting the steps the loop would take.
+    /// Takes no steps itself, since the tokens are the loop's.
+    Clear {
+        up: bool,
+    },
+    /// A loop of only `+`, `-`, `<`, and `>` that returns to its starting
+    /// cell and counts it down by one, or up if `up`: adds the starting
+    /// cell times each of the loop's other additions to their cells, then
+    /// clears the starting cell, as running the loop would. The loop must
+    /// stay within `left` cells to the left and `right` to the right. Takes
+    /// no steps itself, like `Clear`.
+    Multiply {
+        up: bool,
+        left: u32,
+        right: u32,
+    },
+    /// `[>]`, or `[<]` if not `right`: moves to the nearest zero cell in
+    /// that direction and continues after the loop that follows, if there
+    /// is one before the end of the tape. Takes no steps itself, like
+    /// `Clear`.
+    Scan {
+        right: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: Op,
+    /// The index of the first token folded into this instruction.
+    pub token: usize,
+    /// The number of tokens folded into this instruction.
+    pub len: usize,
+}
+
+/// Compiles `tokens`, resolving jumps through `jump_table`.
+pub fn compile(tokens: &[Token], jump_table: &JumpTable) -> Vec<Instruction> {
+    let mut instructions: Vec<Instruction> = Vec::new();
+    // The instruction each bracket token became, for resolving jumps.
+    let mut bracket_instructions = vec![None; tokens.len()];
+
+    for (index, &token) in tokens.iter().enumerate() {
+        let op = match token {
+            Token::IncrementData | Token::DecrementData => {
+                let delta = if token == Token::IncrementData { 1 } else { -1 };
+                if let Some(Instruction {
+                    op: Op::Add(sum),
+                    len,
+                    ..
+                }) = instructions.last_mut()
+                {
+                    *sum += delta;
+                    *len += 1;
+                    continue;
+                }
+                Op::Add(delta)
+            }
+            Token::IncrementPointer | Token::DecrementPointer => {
+                let delta = if token == Token::IncrementPointer {
+                    1
+                } else {
+                    -1
+                };
+                // Only runs in one direction fold, so a run stops exactly
+                // where the tokens would leave the tape.
+                if let Some(Instruction {
+                    op: Op::Move(distance),
+                    len,
+                    ..
+                }) = instructions.last_mut()
+                    && distance.signum() == delta
+                {
+                    *distance += delta;
+                    *len += 1;
+                    continue;
+                }
+                Op::Move(delta)
+            }
+            Token::Output => Op::Output,
+            Token::Input => Op::Input,
+            Token::LoopStart => {
+                bracket_instructions[index] = Some(instructions.len());
+                Op::JumpIfZero(None)
+            }
+            Token::LoopEnd => {
+                if let Some(&start) = jump_table.get(index)
+                    && let Some(position) = bracket_instructions[start]
+                    && let Some(op) = shortcut(&instructions[position + 1..])
+                {
+                    instructions.insert(
+                        position,
+                        Instruction {
+                            op,
+                            token: start,
+                            len: 0,
+                        },
+                    );
+                    bracket_instructions[start] = Some(position + 1);
+                }
+                bracket_instructions[index] = Some(instructions.len());
+                Op::JumpIfNonZero(None)
+            }
+            Token::Refresh => Op::Refresh,
+        };
+        instructions.push(Instruction {
+            op,
+            token: index,
+            len: 1,
+        });
+    }
+
+    for instruction in &mut instructions {
+        let target = jump_table
+            .get(instruction.token)
+            .and_then(|&target| bracket_instructions.get(target).copied().flatten());
+        match &mut instruction.op {
+            Op::JumpIfZero(jump) | Op::JumpIfNonZero(jump) => *jump = target,
+            _ => {}
+        }
+    }
+
+    instructions
+}
+
+/// Checks that `instructions` are what [`compile`] makes of `tokens`, as the
+/// interpreter relies on, for instructions read from a file.
+pub fn validate(
+    tokens: &[Token],
+    jump_table: &JumpTable,
+    instructions: &[Instruction],
+) -> Result<(), String> {
+    let mut next_token = 0;
+    for (pc, &Instruction { op, token, len }) in instructions.iter().enumerate() {
+        let Some(symbols) = tokens
+            .get(token..token.saturating_add(len))
+            .filter(|_| token == next_token)
+        else {
+            return Err(format!("Invalid instruction {}", pc));
+        };
+        next_token += len;
+        // A jump must land on the instruction for the matching bracket.
+        let jumps_to_match = |target: Option<usize>| {
+            target
+                .and_then(|target| instructions.get(target))
+                .is_some_and(|other| other.len == 1 && jump_table.get(token) == Some(&other.token))
+        };
+        let valid = match op {
+            Op::Add(delta) => {
+                len > 0
+                    && symbols
+                        .iter()
+                        .map(|&symbol| match symbol {
+                            Token::IncrementData => Some(1),
+                            Token::DecrementData => Some(-1),
+                            _ => None,
+                        })
+                        .sum::<Option<i64>>()
+                        == Some(delta)
+            }
+            Op::Move(distance) => {
+                let symbol = if distance > 0 {
+                    Token::IncrementPointer
+                } else {
+                    Token::DecrementPointer
+                };
+                len > 0
+                    && distance.unsigned_abs() == len
+                    && symbols.iter().all(|&other| other == symbol)
+            }
+            Op::Output => symbols == [Token::Output],
+            Op::Input => symbols == [Token::Input],
+            Op::Refresh => symbols == [Token::Refresh],
+            Op::JumpIfZero(target) => symbols == [Token::LoopStart] && jumps_to_match(target),
+            Op::JumpIfNonZero(target) => symbols == [Token::LoopEnd] && jumps_to_match(target),
+            Op::Clear { .. } | Op::Multiply { .. } | Op::Scan { .. } => {
+                match instructions.get(pc + 1) {
+                    Some(Instruction {
+                        op: Op::JumpIfZero(Some(end)),
+                        ..
+                    }) if len == 0 && *end > pc + 1 && *end <= instructions.len() => {
+                        shortcut(&instructions[pc + 2..*end]) == Some(op)
+                    }
+                    _ => false,
+                }
+            }
+        };
+        if !valid {
+            return Err(format!("Invalid instruction {}", pc));
+        }
+    }
+
+    if next_token != tokens.len() {
+        return Err("Instructions do not cover the program".to_string());
+    }
+    Ok(())
+}
+
+/// Returns the instruction that runs a loop with `body` all at once, if
+/// there is one.
+fn shortcut(body: &[Instruction]) -> Option<Op> {
+    if let [
+        Instruction {
+            op: Op::Add(delta @ (1 | -1)),
+            len: 1,
+            ..
+        },
+    ] = body
+    {
+        return Some(Op::Clear { up: *delta > 0 });
+    }
+    if let [
+        Instruction {
+            op: Op::Move(distance @ (1 | -1)),
+            ..
+        },
+    ] = body
+    {
+        return Some(Op::Scan {
+            right: *distance > 0,
+        });
+    }
+
+    let (mut offset, mut left, mut right) = (0isize, 0isize, 0isize);
+    let (mut counter, mut multiplies) = (0, false);
+    for instruction in body {
+        match instruction.op {
+            Op::Add(delta) if offset == 0 => counter += delta,
+            Op::Add(_) => multiplies = true,
+            Op::Move(distance) => {
+                offset += distance;
+                left = left.min(offset);
+                right = right.max(offset);
+            }
+            _ => return None,
+        }
+    }
+
+    if offset != 0 || !multiplies || !matches!(counter, 1 | -1) {
+        return None;
+    }
+    Some(Op::Multiply {
+        up: counter > 0,
+        left: left.unsigned_abs().try_into().ok()?,
+        right: right.unsigned_abs().try_into().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_loops, tokenize};
+
+    #[test]
+    fn test_compile() {
+        let tokens = tokenize("+++--[>>><<-]>.[+]<[->++>>-<<<][<]");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let ops: Vec<_> = compile(&tokens, &jump_table)
+            .iter()
+            .map(|instruction| (instruction.op, instruction.token, instruction.len))
+            .collect();
+
+        insta::assert_debug_snapshot!(ops, @r"
+        [
+            (
+                Add(
+                    1,
+                ),
+                0,
+                5,
+            ),
+            (
+                JumpIfZero(
+                    Some(
+                        5,
+                    ),
+                ),
+                5,
+                1,
+            ),
+            (
+                Move(
+                    3,
+                ),
+                6,
+                3,
+            ),
+            (
+                Move(
+                    -2,
+                ),
+                9,
+                2,
+            ),
+            (
+                Add(
+                    -1,
+                ),
+                11,
+                1,
+            ),
+            (
+                JumpIfNonZero(
+                    Some(
+                        1,
+                    ),
+                ),
+                12,
+                1,
+            ),
+            (
+                Move(
+                    1,
+                ),
+                13,
+                1,
+            ),
+            (
+                Output,
+                14,
+                1,
+            ),
+            (
+                Clear {
+                    up: true,
+                },
+                15,
+                0,
+            ),
+            (
+                JumpIfZero(
+                    Some(
+                        11,
+                    ),
+                ),
+                15,
+                1,
+            ),
+            (
+                Add(
+                    1,
+                ),
+                16,
+                1,
+            ),
+            (
+                JumpIfNonZero(
+                    Some(
+                        9,
+                    ),
+                ),
+                17,
+                1,
+            ),
+            (
+                Move(
+                    -1,
+                ),
+                18,
+                1,
+            ),
+            (
+                Multiply {
+                    up: false,
+                    left: 0,
+                    right: 3,
+                },
+                19,
+                0,
+            ),
+            (
+                JumpIfZero(
+                    Some(
+                        21,
+                    ),
+                ),
+                19,
+                1,
+            ),
+            (
+                Add(
+                    -1,
+                ),
+                20,
+                1,
+            ),
+            (
+                Move(
+                    1,
+                ),
+                21,
+                1,
+            ),
+            (
+                Add(
+                    2,
+                ),
+                22,
+                2,
+            ),
+            (
+                Move(
+                    2,
+                ),
+                24,
+                2,
+            ),
+            (
+                Add(
+                    -1,
+                ),
+                26,
+                1,
+            ),
+            (
+                Move(
+                    -3,
+                ),
+                27,
+                3,
+            ),
+            (
+                JumpIfNonZero(
+                    Some(
+                        14,
+                    ),
+                ),
+                30,
+                1,
+            ),
+            (
+                Scan {
+                    right: false,
+                },
+                31,
+                0,
+            ),
+            (
+                JumpIfZero(
+                    Some(
+                        25,
+                    ),
+                ),
+                31,
+                1,
+            ),
+            (
+                Move(
+                    -1,
+                ),
+                32,
+                1,
+            ),
+            (
+                JumpIfNonZero(
+                    Some(
+                        23,
+                    ),
+                ),
+                33,
+                1,
+            ),
+        ]
+        ");
+    }
+}
@@ -0,0 +1,520 @@
+//! Compiles the [`ir`](crate::ir) to x86-64 machine code and runs it, for
+//! `--engine jit`.
+//!
+//! The code is emitted directly rather than through a code generator
+//! library, so the feature needs no dependencies. It handles byte cells that
+//! wrap around; whenever an instruction needs more than that, such as a move
+//! off the end of the tape, the compiled code returns and the interpreter
+//! carries on from that instruction, so runs end exactly as interpreted
+//! ones do. It returns the same way at the end of a loop after Ctrl-C.
+
+use std::any::TypeId;
+use std::ffi::c_void;
+use std::io::{Read, Write};
+use std::ptr;
+
+use crate::cell::{Cell, Overflow};
+use crate::interrupt;
+use crate::ir::{Instruction, Op};
+use crate::{Engine, EofBehavior, Interpreter};
+
+/// What the compiled code reads on entry and writes back on exit, at
+/// offsets it hardcodes.
+#[repr(C)]
+struct State {
+    tape: *mut u8,
+    len: usize,
+    data_pointer: usize,
+    steps: u64,
+    io: *mut c_void,
+}
+
+/// The input and output the compiled code calls back into Rust for.
+struct Io<'a> {
+    input: &'a mut dyn Read,
+    output: &'a mut dyn Write,
+    eof: EofBehavior,
+    input_position: usize,
+    error: Option<String>,
+}
+
+/// Writes `byte`, returning true on failure.
+extern "C" fn write_byte(io: *mut c_void, byte: u8) -> bool {
+    // SAFETY: the compiled code passes `State::io`, which points to the
+    // `Io` that `run_jit` keeps alive for the call.
+    let io = unsafe { &mut *io.cast::<Io>() };
+    match io.output.write_all(&[byte]).and_then(|_| io.output.flush()) {
+        Ok(()) => false,
+        Err(e) => {
+            io.error = Some(e.to_string());
+            true
+        }
+    }
+}
+
+/// Reads a byte into `cell`, returning true on failure.
+extern "C" fn read_byte(io: *mut c_void, cell: *mut u8) -> bool {
+    // SAFETY: as in `write_byte`; `cell` is the current cell on the tape.
+    let (io, cell) = unsafe { (&mut *io.cast::<Io>(), &mut *cell) };
+    let mut byte = [0u8; 1];
+    match io.input.read(&mut byte) {
+        Ok(0) => io.eof.apply(cell),
+        Ok(_) => {
+            *cell = byte[0];
+            io.input_position += 1;
+        }
+        Err(e) => {
+            io.error = Some(e.to_string());
+            return true;
+        }
+    }
+    false
+}
+
+/// Where a jump goes.
+#[derive(Clone, Copy)]
+enum Target {
+    /// The start of the instruction at the index.
+    Instruction(usize),
+    /// The code that saves the state and returns.
+    Exit,
+}
+
+/// Emits machine code, patching jumps once their targets are known.
+///
+/// Throughout, `rbx` holds the tape, `r12` the data pointer, `r13` the
+/// tape's length, `r14` the [`State`], and `r15` the step count.
+#[derive(Default)]
+struct Assembler {
+    code: Vec<u8>,
+    /// The offset of each instruction's code.
+    labels: Vec<usize>,
+    exit: usize,
+    /// Offsets of 32-bit jump displacements and where they go.
+    fixups: Vec<(usize, Target)>,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn emit_u32(&mut self, value: u32) {
+        self.emit(&value.to_le_bytes());
+    }
+
+    /// Emits a jump instruction ending in a 32-bit displacement.
+    fn jump(&mut self, opcode: &[u8], target: Target) {
+        self.emit(opcode);
+        self.fixups.push((self.code.len(), target));
+        self.emit_u32(0);
+    }
+
+    /// Returns to the interpreter at instruction `pc`.
+    fn bail(&mut self, pc: usize) {
+        // mov eax, pc
+        self.emit(&[0xb8]);
+        self.emit_u32(pc as u32);
+        // jmp exit
+        self.jump(&[0xe9], Target::Exit);
+    }
+
+    /// Returns to the interpreter at instruction `pc` if the call just made
+    /// failed.
+    fn bail_if_failed(&mut self, pc: usize) {
+        // test al, al; jz past the 10-byte bail
+        self.emit(&[0x84, 0xc0, 0x74, 0x0a]);
+        self.bail(pc);
+    }
+
+    fn add_steps(&mut self, steps: u32) {
+        // add r15, steps
+        self.emit(&[0x49, 0x81, 0xc7]);
+        self.emit_u32(steps);
+    }
+
+    /// Calls `function`, whose arguments are already in `rdi` and `rsi`.
+    fn call(&mut self, function: usize) {
+        // mov rax, function; call rax
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(function as u64).to_le_bytes());
+        self.emit(&[0xff, 0xd0]);
+    }
+
+    /// Returns to the interpreter at instruction `pc` if Ctrl-C was
+    /// pressed, so the interpreter can stop the run.
+    fn bail_if_interrupted(&mut self, pc: usize) {
+        // mov rax, &REQUESTED; cmp byte [rax], 0; je past the 10-byte bail
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(interrupt::REQUESTED.as_ptr() as u64).to_le_bytes());
+        self.emit(&[0x80, 0x38, 0x00, 0x74, 0x0a]);
+        self.bail(pc);
+    }
+
+    /// Adds `count` iterations of a loop of `len` tokens to the steps, as
+    /// [`loop_steps`](crate::loop_steps) counts them. `count` is in `rax`.
+    fn add_loop_steps(&mut self, len: usize) {
+        // imul rax, rax, len - 1; add r15, rax; add r15, 1
+        self.emit(&[0x48, 0x69, 0xc0]);
+        self.emit_u32(len as u32 - 1);
+        self.emit(&[0x49, 0x01, 0xc7]);
+        self.add_steps(1);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for (offset, target) in std::mem::take(&mut self.fixups) {
+            let destination = match target {
+                Target::Instruction(index) => self.labels[index],
+                Target::Exit => self.exit,
+            };
+            let displacement = destination as i64 - (offset + 4) as i64;
+            self.code[offset..offset + 4].copy_from_slice(&(displacement as i32).to_le_bytes());
+        }
+        self.code
+    }
+}
+
+/// Returns the index of the `]` closing the loop that starts right after
+/// the instruction at `pc`.
+fn loop_end(instructions: &[Instruction], pc: usize) -> Option<usize> {
+    match instructions.get(pc + 1)?.op {
+        Op::JumpIfZero(end) => end,
+        _ => None,
+    }
+}
+
+/// Compiles `instructions` to a function taking a [`State`] and returning
+/// the index of the instruction the interpreter continues from.
+fn assemble(instructions: &[Instruction]) -> Vec<u8> {
+    let mut asm = Assembler::default();
+
+    // push rbx, r12, r13, r14, r15, leaving the stack 16-byte aligned
+    asm.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+    // mov r14, rdi
+    asm.emit(&[0x49, 0x89, 0xfe]);
+    // mov rbx, [r14]; mov r13, [r14 + 8]; mov r12, [r14 + 16];
+    // mov r15, [r14 + 24]
+    asm.emit(&[0x49, 0x8b, 0x5e, 0x00, 0x4d, 0x8b, 0x6e, 0x08]);
+    asm.emit(&[0x4d, 0x8b, 0x66, 0x10, 0x4d, 0x8b, 0x7e, 0x18]);
+
+    for (pc, &Instruction { op, token, len }) in instructions.iter().enumerate() {
+        asm.labels.push(asm.code.len());
+        let len = len as u32;
+
+        match op {
+            Op::Add(delta) => {
+                if delta as u8 != 0 {
+                    // add byte [rbx + r12], delta
+                    asm.emit(&[0x42, 0x80, 0x04, 0x23, delta as u8]);
+                }
+                asm.add_steps(len);
+            }
+            Op::Move(distance) => {
+                let Ok(distance) = i32::try_from(distance) else {
+                    asm.bail(pc);
+                    continue;
+                };
+                // mov rax, r12; add rax, distance; cmp rax, r13
+                asm.emit(&[0x4c, 0x89, 0xe0, 0x48, 0x05]);
+                asm.emit_u32(distance as u32);
+                asm.emit(&[0x4c, 0x39, 0xe8]);
+                // jb past the 10-byte bail; a move left of zero wraps
+                // around to a large index, so one check covers both ends.
+                asm.emit(&[0x72, 0x0a]);
+                asm.bail(pc);
+                // mov r12, rax
+                asm.emit(&[0x49, 0x89, 0xc4]);
+                asm.add_steps(len);
+            }
+            Op::Output => {
+                // mov rdi, [r14 + 32]; movzx esi, byte [rbx + r12]
+                asm.emit(&[0x49, 0x8b, 0x7e, 0x20, 0x42, 0x0f, 0xb6, 0x34, 0x23]);
+                asm.call(write_byte as *const () as usize);
+                asm.bail_if_failed(pc);
+                asm.add_steps(len);
+            }
+            Op::Input => {
+                // mov rdi, [r14 + 32]; lea rsi, [rbx + r12]
+                asm.emit(&[0x49, 0x8b, 0x7e, 0x20, 0x4a, 0x8d, 0x34, 0x23]);
+                asm.call(read_byte as *const () as usize);
+                asm.bail_if_failed(pc);
+                asm.add_steps(len);
+            }
+            Op::JumpIfZero(Some(target)) | Op::JumpIfNonZero(Some(target)) => {
+                if matches!(op, Op::JumpIfNonZero(_)) {
+                    // Every loop jumps back from here, so even one that
+                    // never ends can be interrupted.
+                    asm.bail_if_interrupted(pc);
+                }
+                asm.add_steps(len);
+                // cmp byte [rbx + r12], 0
+                asm.emit(&[0x42, 0x80, 0x3c, 0x23, 0x00]);
+                // je or jne past the matching bracket
+                let opcode = if matches!(op, Op::JumpIfZero(_)) {
+                    0x84
+                } else {
+                    0x85
+                };
+                asm.jump(&[0x0f, opcode], Target::Instruction(target + 1));
+            }
+            Op::JumpIfZero(None) | Op::JumpIfNonZero(None) => asm.bail(pc),
+            Op::Refresh => asm.add_steps(len),
+            Op::Clear { up } => {
+                let Some(end) = loop_end(instructions, pc) else {
+                    continue;
+                };
+                // movzx eax, byte [rbx + r12], negated if counting up
+                asm.emit(&[0x42, 0x0f, 0xb6, 0x04, 0x23]);
+                if up {
+                    // neg al; movzx eax, al
+                    asm.emit(&[0xf6, 0xd8, 0x0f, 0xb6, 0xc0]);
+                }
+                asm.add_loop_steps(instructions[end].token + 1 - token);
+                // mov byte [rbx + r12], 0
+                asm.emit(&[0x42, 0xc6, 0x04, 0x23, 0x00]);
+                asm.jump(&[0xe9], Target::Instruction(end + 1));
+            }
+            Op::Multiply { up, left, right } => {
+                let Some(end) = loop_end(instructions, pc) else {
+                    continue;
+                };
+                let (Ok(left), Ok(right)) = (i32::try_from(left), i32::try_from(right)) else {
+                    continue;
+                };
+                // Near the ends of the tape, run the loop itself.
+                // cmp r12, left; jb skip
+                asm.emit(&[0x49, 0x81, 0xfc]);
+                asm.emit_u32(left as u32);
+                asm.emit(&[0x0f, 0x82]);
+                let skip_low = asm.code.len();
+                asm.emit_u32(0);
+                // lea rax, [r12 + right]; cmp rax, r13; jae skip
+                asm.emit(&[0x49, 0x8d, 0x84, 0x24]);
+                asm.emit_u32(right as u32);
+                asm.emit(&[0x4c, 0x39, 0xe8, 0x0f, 0x83]);
+                let skip_high = asm.code.len();
+                asm.emit_u32(0);
+
+                // movzx ecx, byte [rbx + r12], negated if counting up
+                asm.emit(&[0x42, 0x0f, 0xb6, 0x0c, 0x23]);
+                if up {
+                    // neg cl
+                    asm.emit(&[0xf6, 0xd9]);
+                }
+                let mut offset = 0isize;
+                for instruction in &instructions[pc + 2..end] {
+                    match instruction.op {
+                        Op::Move(distance) => offset += distance,
+                        Op::Add(delta) if offset != 0 => {
+                            // imul eax, ecx, delta; add [rbx + r12 + offset], al
+                            asm.emit(&[0x69, 0xc1]);
+                            asm.emit_u32(delta as u32);
+                            asm.emit(&[0x42, 0x00, 0x84, 0x23]);
+                            asm.emit_u32(offset as u32);
+                        }
+                        _ => {}
+                    }
+                }
+                // mov eax, ecx
+                asm.emit(&[0x89, 0xc8]);
+                asm.add_loop_steps(instructions[end].token + 1 - token);
+                // mov byte [rbx + r12], 0
+                asm.emit(&[0x42, 0xc6, 0x04, 0x23, 0x00]);
+                asm.jump(&[0xe9], Target::Instruction(end + 1));
+
+                let skip = asm.code.len();
+                for at in [skip_low, skip_high] {
+                    let displacement = (skip - (at + 4)) as u32;
+                    asm.code[at..at + 4].copy_from_slice(&displacement.to_le_bytes());
+                }
+            }
+            // The loop that follows is compiled too, and is quick enough.
+            Op::Scan { .. } => {}
+        }
+    }
+
+    asm.labels.push(asm.code.len());
+    // mov eax, instructions.len(), finishing the run
+    asm.emit(&[0xb8]);
+    asm.emit_u32(instructions.len() as u32);
+
+    asm.exit = asm.code.len();
+    // mov [r14 + 16], r12; mov [r14 + 24], r15
+    asm.emit(&[0x4d, 0x89, 0x66, 0x10, 0x4d, 0x89, 0x7e, 0x18]);
+    // pop r15, r14, r13, r12, rbx; ret
+    asm.emit(&[0x41, 0x5f, 0x41, 0x5e, 0x41, 0x5d, 0x41, 0x5c, 0x5b, 0xc3]);
+
+    asm.finish()
+}
+
+/// Machine code in memory mapped executable.
+struct Code {
+    pointer: *mut c_void,
+    len: usize,
+}
+
+impl Code {
+    fn new(bytes: &[u8]) -> Result<Self, String> {
+        // SAFETY: a fresh anonymous mapping is written only within its
+        // length, then made executable and read-only.
+        unsafe {
+            let pointer = libc::mmap(
+                ptr::null_mut(),
+                bytes.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if pointer == libc::MAP_FAILED {
+                return Err(format!(
+                    "Cannot map JIT code: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            let code = Self {
+                pointer,
+                len: bytes.len(),
+            };
+            ptr::copy_nonoverlapping(bytes.as_ptr(), pointer.cast(), bytes.len());
+            if libc::mprotect(pointer, bytes.len(), libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return Err(format!(
+                    "Cannot make JIT code executable: {}",
+                    std::io::Error::last_os_error()
+                ));
+            }
+            Ok(code)
+        }
+    }
+
+    /// Runs the code on `state`.
+    ///
+    /// # Safety
+    ///
+    /// The code must come from [`assemble`], and `state` must describe a
+    /// live tape and `Io`.
+    unsafe fn run(&self, state: &mut State) -> usize {
+        // SAFETY: `assemble` emits a function with this signature.
+        let function: extern "C" fn(*mut State) -> usize =
+            unsafe { std::mem::transmute(self.pointer) };
+        function(state)
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was made in `new` and is not used after this.
+        unsafe {
+            libc::munmap(self.pointer, self.len);
+        }
+    }
+}
+
+impl<C: Cell> Interpreter<C> {
+    /// Runs as much of `instructions` as compiled code can, returning the
+    /// instruction the interpreter continues from: 0 when the JIT is off or
+    /// cannot run these cells, or the length when the run is over.
+    ///
+    /// On error the instruction pointer is left on the failing instruction.
+    pub(crate) fn run_jit(
+        &mut self,
+        instructions: &[Instruction],
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<usize, String> {
+        // Compiled code does not check a step limit or the time.
+        if self.engine != Engine::Jit
+            || self.max_steps.is_some()
+            || self.timeout.is_some()
+            || TypeId::of::<C>() != TypeId::of::<u8>()
+            || self.overflow != Overflow::Wrap
+            || instructions.len() >= u32::MAX as usize
+        {
+            return Ok(0);
+        }
+
+        let code = Code::new(&assemble(instructions))?;
+        let mut io = Io {
+            input,
+            output,
+            eof: self.eof,
+            input_position: self.input_position,
+            error: None,
+        };
+        let mut state = State {
+            tape: self.memory.as_mut_ptr().cast(),
+            len: self.memory.len(),
+            data_pointer: self.data_pointer,
+            steps: self.steps,
+            io: (&raw mut io).cast(),
+        };
+        // SAFETY: `C` is `u8`, so the tape is bytes, and `io` outlives the
+        // call.
+        let resume = unsafe { code.run(&mut state) };
+
+        self.data_pointer = state.data_pointer;
+        self.steps = state.steps;
+        self.input_position = io.input_position;
+        match io.error {
+            Some(e) => {
+                self.instruction_pointer = instructions[resume].token;
+                Err(e)
+            }
+            None => Ok(resume),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InterpreterBuilder, parse_loops, tokenize};
+
+    use super::*;
+
+    /// Runs `code` with `builder` interpreted and compiled, asserting that
+    /// every observable part of the runs match.
+    fn assert_same(builder: InterpreterBuilder, code: &str, input: &[u8]) {
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+        let [interpreted, compiled] = [Engine::Interp, Engine::Jit].map(|engine| {
+            let mut interpreter = builder.clone().engine(engine).build();
+            let mut output = Vec::new();
+            let result = interpreter.run_with_io(&tokens, &jump_table, input, &mut output);
+            (
+                result,
+                interpreter.instruction_pointer,
+                interpreter.data_pointer,
+                interpreter.steps,
+                interpreter.input_position,
+                interpreter.memory,
+                output,
+            )
+        });
+        assert_eq!(interpreted, compiled, "{}", code);
+    }
+
+    #[test]
+    fn test_jit_matches_interpreter() {
+        let small = InterpreterBuilder::default().tape_size(8);
+        for code in [
+            "++++++++[>++++++++<-]>+.+.",
+            ",[.,]",
+            "+[>+]",
+            "<",
+            "+++[-]>--[+]<+[>+>+++<<-]>[<+>>>+<<-]",
+            "+>+>+<<[>]<[<]>.",
+            "+[->>>>>>>+<<<<<<<]",
+        ] {
+            assert_same(small.clone(), code, b"hi");
+            assert_same(small.clone().wrap_pointer(true), code, b"hi");
+            if code != "+[>+]" {
+                // Growing, that one never ends.
+                assert_same(
+                    small.clone().grow(true).eof(EofBehavior::Unchanged),
+                    code,
+                    b"",
+                );
+            }
+        }
+    }
+}
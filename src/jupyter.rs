@@ -0,0 +1,737 @@
+//! `bf-repl jupyter-kernel`: a Jupyter kernel, so the cells of a notebook
+//! run one after another on the same tape, each showing the cells around
+//! the data pointer when it finishes.
+//!
+//! Messages travel over ZeroMQ sockets (see [`zmtp`](crate::zmtp)) as JSON
+//! signed with HMAC-SHA256. Cells run on the main thread; each socket's
+//! connections are read on threads of their own, so heartbeats and
+//! interrupts are answered while a cell runs.
+
+use std::fs;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interrupt;
+use crate::json::{self, Value};
+use crate::line_editor::bracket_depth;
+use crate::rng::Rng;
+use crate::sha256::hmac_sha256;
+use crate::zmtp;
+use crate::{Interpreter, InterpreterBuilder, parse_loops, tokenize_with};
+
+const PROTOCOL_VERSION: &str = "5.3";
+/// The frame between a message's routing identities and its signature.
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+/// How many cells on each side of the data pointer a cell's result shows.
+const MEMORY_WINDOW: usize = 5;
+
+/// Serves the kernel described by the connection file Jupyter passes in,
+/// until it is asked to shut down. Cells run on an interpreter from
+/// `builder`, and `,` reads end of input.
+pub fn run_kernel(connection_file: &str, builder: &InterpreterBuilder) -> Result<(), String> {
+    let text = fs::read_to_string(connection_file)
+        .map_err(|e| format!("Cannot read {}: {}", connection_file, e))?;
+    let connection = Value::parse(&text).map_err(|e| format!("{}: {}", connection_file, e))?;
+
+    if connection.get("transport").as_str() != Some("tcp") {
+        return Err("Only the tcp transport is supported".to_string());
+    }
+    let key = connection.get("key").as_str().unwrap_or_default();
+    if !key.is_empty() && connection.get("signature_scheme").as_str() != Some("hmac-sha256") {
+        return Err("Only hmac-sha256 signatures are supported".to_string());
+    }
+    let ip = connection.get("ip").as_str().unwrap_or("127.0.0.1");
+    let listen = |name: &str| {
+        let port = connection
+            .get(name)
+            .as_u64()
+            .ok_or_else(|| format!("{} has no {}", connection_file, name))?;
+        let addr = format!("{}:{}", ip, port);
+        TcpListener::bind(&addr).map_err(|e| format!("Cannot listen on {}: {}", addr, e))
+    };
+
+    let session = Arc::new(Session::new(key.as_bytes()));
+    let subscribers: Subscribers = Arc::default();
+    let (requests, received) = mpsc::channel();
+
+    accept(listen("hb_port")?, "REP", |mut stream| {
+        while let Some(frames) = zmtp::read_message(&mut stream)? {
+            let frames: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+            zmtp::write_message(&mut stream, &frames)?;
+        }
+        Ok(())
+    });
+    accept(listen("iopub_port")?, "PUB", {
+        let subscribers = Arc::clone(&subscribers);
+        move |mut stream| {
+            lock(&subscribers).push(Box::new(stream.try_clone()?));
+            // Subscriptions arrive as messages; every subscriber gets
+            // everything anyway.
+            while zmtp::read_message(&mut stream)?.is_some() {}
+            Ok(())
+        }
+    });
+    // Programs read end of input, so nothing is ever asked for on stdin.
+    accept(listen("stdin_port")?, "ROUTER", |mut stream| {
+        while zmtp::read_message(&mut stream)?.is_some() {}
+        Ok(())
+    });
+    for name in ["shell_port", "control_port"] {
+        let session = Arc::clone(&session);
+        let requests = requests.clone();
+        accept(listen(name)?, "ROUTER", move |stream| {
+            receive_requests(stream, &session, &requests)
+        });
+    }
+
+    let mut kernel = Kernel {
+        publisher: Publisher {
+            session: Arc::clone(&session),
+            subscribers,
+        },
+        interpreter: builder.build(),
+        execution_count: 0,
+    };
+    for (message, reply_to) in received {
+        let mut reply_to = lock(&reply_to);
+        match kernel.handle(&message, &mut **reply_to) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => eprintln!("Cannot reply to {}: {}", message.msg_type(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the `kernel.json` that tells Jupyter how to start this kernel,
+/// in the user's Jupyter data directory.
+pub fn install_kernel() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Cannot find bf-repl: {}", e))?;
+    let dir = jupyter_data_dir()
+        .ok_or("Cannot find the Jupyter data directory; set JUPYTER_DATA_DIR")?
+        .join("kernels")
+        .join("brainfuck");
+    let spec = json::object([
+        (
+            "argv",
+            vec![
+                exe.display().to_string().into(),
+                "jupyter-kernel".into(),
+                "{connection_file}".into(),
+            ]
+            .into(),
+        ),
+        ("display_name", "Brainfuck".into()),
+        ("language", "brainfuck".into()),
+        ("interrupt_mode", "message".into()),
+    ]);
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let path = dir.join("kernel.json");
+    fs::write(&path, format!("{}\n", spec))
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    println!("Installed the Brainfuck kernel in {}", dir.display());
+    Ok(())
+}
+
+/// Where Jupyter looks for kernels installed by the user.
+fn jupyter_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("jupyter"));
+    }
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    if cfg!(target_os = "macos") {
+        return Some(home.join("Library").join("Jupyter"));
+    }
+    Some(match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir).join("jupyter"),
+        None => home.join(".local").join("share").join("jupyter"),
+    })
+}
+
+type Subscribers = Arc<Mutex<Vec<Box<dyn Write + Send>>>>;
+/// A request, and the connection its reply goes to.
+type Request = (Message, Arc<Mutex<Box<dyn Write + Send>>>);
+
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Completes the handshake with each connection to `listener` as a
+/// `socket_type` socket, then hands the connection to `serve` on a thread
+/// of its own.
+fn accept<F>(listener: TcpListener, socket_type: &'static str, serve: F)
+where
+    F: Fn(TcpStream) -> io::Result<()> + Clone + Send + 'static,
+{
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let serve = serve.clone();
+            thread::spawn(move || {
+                let mut stream = stream;
+                if let Err(e) =
+                    zmtp::handshake(&mut stream, socket_type).and_then(|()| serve(stream))
+                {
+                    eprintln!("Jupyter connection failed: {}", e);
+                }
+            });
+        }
+    });
+}
+
+/// Passes the requests arriving on `stream` to the main thread, except
+/// interrupts, which are answered here since the main thread may be busy
+/// running the cell they are meant to stop.
+fn receive_requests(
+    stream: TcpStream,
+    session: &Session,
+    requests: &Sender<Request>,
+) -> io::Result<()> {
+    let reply_to: Arc<Mutex<Box<dyn Write + Send>>> =
+        Arc::new(Mutex::new(Box::new(stream.try_clone()?)));
+    let mut stream = stream;
+    while let Some(frames) = zmtp::read_message(&mut stream)? {
+        let message = match session.parse(frames) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Ignoring a Jupyter message: {}", e);
+                continue;
+            }
+        };
+        if message.msg_type() == "interrupt_request" {
+            interrupt::request();
+            session.reply(&mut **lock(&reply_to), &message, status_ok())?;
+        } else if requests.send((message, Arc::clone(&reply_to))).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn status_ok() -> Value {
+    json::object([("status", "ok".into())])
+}
+
+/// A message from a Jupyter client.
+struct Message {
+    /// The frames before the delimiter, which replies carry back.
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value,
+}
+
+impl Message {
+    fn msg_type(&self) -> &str {
+        self.header.get("msg_type").as_str().unwrap_or_default()
+    }
+}
+
+/// Signs, checks, and numbers the messages of one kernel run.
+struct Session {
+    key: Vec<u8>,
+    id: String,
+    sent: AtomicU64,
+}
+
+impl Session {
+    fn new(key: &[u8]) -> Self {
+        let mut rng = Rng::new(Rng::time_seed());
+        let id = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+        Self {
+            key: key.to_vec(),
+            id,
+            sent: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the hex HMAC of `parts`, or nothing when messages are not
+    /// signed.
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            return String::new();
+        }
+        hmac_sha256(&self.key, parts)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn parse(&self, frames: Vec<Vec<u8>>) -> Result<Message, String> {
+        let delimiter = frames
+            .iter()
+            .position(|frame| frame == DELIMITER)
+            .ok_or("No <IDS|MSG> delimiter")?;
+        let [signature, header, parent_header, metadata, content, ..] = &frames[delimiter + 1..]
+        else {
+            return Err("Too few frames".to_string());
+        };
+
+        let expected = self.sign(&[header, parent_header, metadata, content]);
+        // Compared without stopping at the first difference, so the time
+        // taken does not hint at the right signature.
+        let matches = signature.len() == expected.len()
+            && signature
+                .iter()
+                .zip(expected.as_bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        if !matches {
+            return Err("Bad signature".to_string());
+        }
+
+        let parse = |frame: &[u8]| {
+            Value::parse(&String::from_utf8_lossy(frame)).map_err(|e| format!("Bad JSON: {}", e))
+        };
+        Ok(Message {
+            identities: frames[..delimiter].to_vec(),
+            header: parse(header)?,
+            content: parse(content)?,
+        })
+    }
+
+    /// Writes a `msg_type` message in response to `parent`, with
+    /// `identities` in front.
+    fn send(
+        &self,
+        out: &mut dyn Write,
+        identities: &[Vec<u8>],
+        msg_type: &str,
+        parent: &Message,
+        content: Value,
+    ) -> io::Result<()> {
+        let sent = self.sent.fetch_add(1, Ordering::Relaxed);
+        let header = json::object([
+            ("msg_id", format!("{}-{}", self.id, sent).into()),
+            ("session", self.id.as_str().into()),
+            ("username", "bf-repl".into()),
+            ("date", now().into()),
+            ("msg_type", msg_type.into()),
+            ("version", PROTOCOL_VERSION.into()),
+        ])
+        .to_string();
+        let parent_header = parent.header.to_string();
+        let metadata = "{}";
+        let content = content.to_string();
+        let parts = [
+            header.as_bytes(),
+            parent_header.as_bytes(),
+            metadata.as_bytes(),
+            content.as_bytes(),
+        ];
+        let signature = self.sign(&parts);
+
+        let mut frames: Vec<&[u8]> = identities.iter().map(Vec::as_slice).collect();
+        frames.extend([DELIMITER, signature.as_bytes()]);
+        frames.extend(parts);
+        zmtp::write_message(out, &frames)
+    }
+
+    /// Answers `request` with its `_reply` message.
+    fn reply(&self, out: &mut dyn Write, request: &Message, content: Value) -> io::Result<()> {
+        let msg_type = request.msg_type();
+        let reply_type = format!(
+            "{}_reply",
+            msg_type.strip_suffix("_request").unwrap_or(msg_type)
+        );
+        self.send(out, &request.identities, &reply_type, request, content)
+    }
+}
+
+/// The current time as ISO 8601, as message headers carry it.
+fn now() -> String {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's
+    // days_from_civil algorithm run backwards.
+    let days = seconds / 86400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        elapsed.subsec_micros()
+    )
+}
+
+/// Sends messages on the iopub socket to every subscriber.
+struct Publisher {
+    session: Arc<Session>,
+    subscribers: Subscribers,
+}
+
+impl Publisher {
+    fn publish(&self, msg_type: &str, parent: &Message, content: Value) {
+        let topic = [msg_type.as_bytes().to_vec()];
+        // Subscribers that hung up are dropped.
+        lock(&self.subscribers).retain_mut(|subscriber| {
+            self.session
+                .send(&mut **subscriber, &topic, msg_type, parent, content.clone())
+                .is_ok()
+        });
+    }
+
+    fn status(&self, parent: &Message, state: &str) {
+        self.publish(
+            "status",
+            parent,
+            json::object([("execution_state", state.into())]),
+        );
+    }
+}
+
+/// Publishes a program's output as `stream` messages, a line at a time.
+struct StreamOutput<'a> {
+    publisher: &'a Publisher,
+    parent: &'a Message,
+    pending: Vec<u8>,
+}
+
+impl StreamOutput<'_> {
+    fn send_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.publisher.publish(
+            "stream",
+            self.parent,
+            json::object([("name", "stdout".into()), ("text", text.into())]),
+        );
+    }
+}
+
+impl Write for StreamOutput<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if buf.contains(&b'\n') {
+            self.send_pending();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Kernel {
+    publisher: Publisher,
+    interpreter: Interpreter,
+    execution_count: u64,
+}
+
+impl Kernel {
+    /// Handles `request`, writing the reply to `reply_to`. Returns `false`
+    /// once the kernel should shut down.
+    fn handle(&mut self, request: &Message, reply_to: &mut dyn Write) -> io::Result<bool> {
+        self.publisher.status(request, "busy");
+        let content = match request.msg_type() {
+            "kernel_info_request" => Some(json::object([
+                ("status", "ok".into()),
+                ("protocol_version", PROTOCOL_VERSION.into()),
+                ("implementation", "bf-repl".into()),
+                ("implementation_version", env!("CARGO_PKG_VERSION").into()),
+                (
+                    "language_info",
+                    json::object([
+                        ("name", "brainfuck".into()),
+                        ("version", "".into()),
+                        ("mimetype", "text/x-brainfuck".into()),
+                        ("file_extension", ".bf".into()),
+                    ]),
+                ),
+                ("banner", "Brainfuck REPL".into()),
+                ("help_links", Vec::new().into()),
+            ])),
+            "execute_request" => Some(self.execute(request)),
+            "is_complete_request" => {
+                let code = request.content.get("code").as_str().unwrap_or_default();
+                Some(if bracket_depth(code) > 0 {
+                    json::object([("status", "incomplete".into()), ("indent", "".into())])
+                } else {
+                    json::object([("status", "complete".into())])
+                })
+            }
+            "comm_info_request" => Some(json::object([
+                ("status", "ok".into()),
+                ("comms", json::object([])),
+            ])),
+            "shutdown_request" => Some(json::object([
+                ("status", "ok".into()),
+                ("restart", request.content.get("restart").clone()),
+            ])),
+            _ => None,
+        };
+
+        if let Some(content) = content {
+            self.publisher.session.reply(reply_to, request, content)?;
+        }
+        self.publisher.status(request, "idle");
+        Ok(request.msg_type() != "shutdown_request")
+    }
+
+    /// Runs a cell, publishing its output and the cells around the data
+    /// pointer, and returns the content of the reply.
+    fn execute(&mut self, request: &Message) -> Value {
+        let code = request.content.get("code").as_str().unwrap_or_default();
+        let silent = request.content.get("silent").as_bool().unwrap_or(false);
+        if request
+            .content
+            .get("store_history")
+            .as_bool()
+            .unwrap_or(!silent)
+        {
+            self.execution_count += 1;
+        }
+        let execution_count = self.execution_count;
+
+        if !silent {
+            self.publisher.publish(
+                "execute_input",
+                request,
+                json::object([
+                    ("code", code.into()),
+                    ("execution_count", execution_count.into()),
+                ]),
+            );
+        }
+
+        match self.run(code, request) {
+            Ok(()) => {
+                if !silent {
+                    self.publisher.publish(
+                        "execute_result",
+                        request,
+                        json::object([
+                            ("execution_count", execution_count.into()),
+                            (
+                                "data",
+                                json::object([
+                                    ("text/plain", self.memory_text().into()),
+                                    ("text/html", self.memory_html().into()),
+                                ]),
+                            ),
+                            ("metadata", json::object([])),
+                        ]),
+                    );
+                }
+                json::object([
+                    ("status", "ok".into()),
+                    ("execution_count", execution_count.into()),
+                    ("user_expressions", json::object([])),
+                    ("payload", Vec::new().into()),
+                ])
+            }
+            Err(e) => {
+                let error = [
+                    ("ename", "Error".into()),
+                    ("evalue", e.as_str().into()),
+                    ("traceback", vec![e.into()].into()),
+                ];
+                self.publisher
+                    .publish("error", request, json::object(error.clone()));
+                let [ename, evalue, traceback] = error;
+                json::object([
+                    ("status", "error".into()),
+                    ("execution_count", execution_count.into()),
+                    ename,
+                    evalue,
+                    traceback,
+                ])
+            }
+        }
+    }
+
+    fn run(&mut self, code: &str, request: &Message) -> Result<(), String> {
+        let tokens = tokenize_with(code, self.interpreter.extensions());
+        let jump_table = parse_loops(&tokens)?;
+
+        // An interrupt that came while no cell was running is not for this
+        // one.
+        interrupt::take_request();
+        let mut output = StreamOutput {
+            publisher: &self.publisher,
+            parent: request,
+            pending: Vec::new(),
+        };
+        let result =
+            self.interpreter
+                .run_with_io(&tokens, &jump_table, &mut io::empty(), &mut output);
+        output.send_pending();
+        result
+    }
+
+    fn memory_text(&self) -> String {
+        let mut text = Vec::new();
+        self.interpreter
+            .write_memory_snapshot(&mut text, MEMORY_WINDOW)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8_lossy(&text).trim_end().to_string()
+    }
+
+    /// The cells around the data pointer as an HTML table, with the
+    /// current cell in bold.
+    fn memory_html(&self) -> String {
+        let data_pointer = self.interpreter.data_pointer();
+        let memory = self.interpreter.memory();
+        let cells = data_pointer.saturating_sub(MEMORY_WINDOW)
+            ..(data_pointer + MEMORY_WINDOW + 1).min(memory.len());
+
+        let mut addresses = String::from("<tr><th>Addr</th>");
+        let mut values = String::from("<tr><th>Data</th>");
+        for address in cells {
+            let (open, close) = match address == data_pointer {
+                true => ("<b>", "</b>"),
+                false => ("", ""),
+            };
+            addresses.push_str(&format!("<td>{}{}{}</td>", open, address, close));
+            values.push_str(&format!("<td>{}{}{}</td>", open, memory[address], close));
+        }
+        format!("<table>{}</tr>{}</tr></table>", addresses, values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            lock(&self.0).write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Lists the type and content of each message written to `buffer`,
+    /// checking its signature and that it answers `parent_id`.
+    fn messages(session: &Session, buffer: &SharedBuffer, parent_id: &str) -> String {
+        let bytes = std::mem::take(&mut *lock(&buffer.0));
+        let mut stream = Cursor::new(bytes);
+        let mut transcript = String::new();
+        while let Some(frames) = zmtp::read_message(&mut stream).unwrap() {
+            let parent = Value::parse(&String::from_utf8_lossy(&frames[frames.len() - 3])).unwrap();
+            assert_eq!(parent.get("msg_id").as_str(), Some(parent_id));
+            let message = session.parse(frames).unwrap();
+            transcript.push_str(&format!("{} {}\n", message.msg_type(), message.content));
+        }
+        transcript
+    }
+
+    #[test]
+    fn test_kernel() {
+        let session = Arc::new(Session::new(b"secret"));
+        let iopub = SharedBuffer::default();
+        let mut kernel = Kernel {
+            publisher: Publisher {
+                session: Arc::clone(&session),
+                subscribers: Arc::new(Mutex::new(vec![Box::new(iopub.clone())])),
+            },
+            interpreter: InterpreterBuilder::default().tape_size(8).build(),
+            execution_count: 0,
+        };
+
+        let mut transcript = String::new();
+        for (id, msg_type, content) in [
+            ("1", "kernel_info_request", "{}"),
+            (
+                "2",
+                "execute_request",
+                r#"{"code":"++++++++[>++++++++<-]>+.+.>++"}"#,
+            ),
+            ("3", "execute_request", r#"{"code":"<<<<<"}"#),
+            ("4", "is_complete_request", r#"{"code":"+[>"}"#),
+            ("5", "shutdown_request", r#"{"restart":false}"#),
+        ] {
+            let header = format!(r#"{{"msg_id":"{}","msg_type":"{}"}}"#, id, msg_type);
+            let signature = session.sign(&[header.as_bytes(), b"{}", b"{}", content.as_bytes()]);
+            let frames = [b"client".as_slice(), DELIMITER, signature.as_bytes()]
+                .into_iter()
+                .chain([header.as_bytes(), b"{}", b"{}", content.as_bytes()])
+                .map(<[u8]>::to_vec)
+                .collect();
+            let request = session.parse(frames).unwrap();
+
+            let reply = SharedBuffer::default();
+            let running = kernel.handle(&request, &mut reply.clone()).unwrap();
+            transcript.push_str(&messages(&session, &iopub, id));
+            transcript.push_str(&format!("> {}", messages(&session, &reply, id)));
+            if !running {
+                transcript.push_str("(shut down)\n");
+            }
+        }
+
+        insta::assert_snapshot!(transcript, @r#"
+        status {"execution_state":"busy"}
+        status {"execution_state":"idle"}
+        > kernel_info_reply {"status":"ok","protocol_version":"5.3","implementation":"bf-repl","implementation_version":"0.1.0","language_info":{"name":"brainfuck","version":"","mimetype":"text/x-brainfuck","file_extension":".bf"},"banner":"Brainfuck REPL","help_links":[]}
+        status {"execution_state":"busy"}
+        execute_input {"code":"++++++++[>++++++++<-]>+.+.>++","execution_count":1}
+        stream {"name":"stdout","text":"AB"}
+        execute_result {"execution_count":1,"data":{"text/plain":"Addr:      1      2      3      4      5      6      7\nData:      0      0      0      0     66      2      0\nPtrs:                                     ^^^^^","text/html":"<table><tr><th>Addr</th><td>1</td><td>2</td><td>3</td><td>4</td><td>5</td><td><b>6</b></td><td>7</td></tr><tr><th>Data</th><td>0</td><td>0</td><td>0</td><td>0</td><td>66</td><td><b>2</b></td><td>0</td></tr></table>"},"metadata":{}}
+        status {"execution_state":"idle"}
+        > execute_reply {"status":"ok","execution_count":1,"user_expressions":{},"payload":[]}
+        status {"execution_state":"busy"}
+        execute_input {"code":"<<<<<","execution_count":2}
+        execute_result {"execution_count":2,"data":{"text/plain":"Addr:      0      1      2      3      4      5      6\nData:      0      0      0      0      0     66      2\nPtrs:         ^^^^^","text/html":"<table><tr><th>Addr</th><td>0</td><td><b>1</b></td><td>2</td><td>3</td><td>4</td><td>5</td><td>6</td></tr><tr><th>Data</th><td>0</td><td><b>0</b></td><td>0</td><td>0</td><td>0</td><td>66</td><td>2</td></tr></table>"},"metadata":{}}
+        status {"execution_state":"idle"}
+        > execute_reply {"status":"ok","execution_count":2,"user_expressions":{},"payload":[]}
+        status {"execution_state":"busy"}
+        status {"execution_state":"idle"}
+        > is_complete_reply {"status":"incomplete","indent":""}
+        status {"execution_state":"busy"}
+        status {"execution_state":"idle"}
+        > shutdown_reply {"status":"ok","restart":false}
+        (shut down)
+        "#);
+    }
+
+    #[test]
+    fn test_bad_signature() {
+        let session = Session::new(b"secret");
+        let frames = [DELIMITER, b"0000", b"{}", b"{}", b"{}", b"{}"]
+            .map(<[u8]>::to_vec)
+            .to_vec();
+
+        insta::assert_debug_snapshot!(session.parse(frames).map(|_| ()), @r#"
+        Err(
+            "Bad signature",
+        )
+        "#);
+    }
+}
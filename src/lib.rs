@@ -0,0 +1,1881 @@
+//! A Brainfuck interpreter, with the tools built around it: a REPL, static
+//! analysis, code generation, and more.
+//!
+//! ```
+//! use bf_repl::{Interpreter, parse_loops, tokenize};
+//!
+//! let tokens = tokenize("++++++++[>++++++++<-]>+.");
+//! let jump_table = parse_loops(&tokens).unwrap();
+//! let mut output = Vec::new();
+//! Interpreter::new()
+//!     .run_with_io(&tokens, &jump_table, &b""[..], &mut output)
+//!     .unwrap();
+//! assert_eq!(output, b"A");
+//! ```
+
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::slice;
+use std::time::{Duration, Instant};
+
+pub mod analysis;
+pub mod annotate;
+mod ascii;
+#[cfg(feature = "audio")]
+mod audio;
+pub mod batch;
+pub mod bignum;
+pub mod build;
+pub mod bytecode;
+pub mod cell;
+pub mod cell_format;
+pub mod check;
+mod clipboard;
+pub mod codegen;
+mod commands;
+mod completion;
+pub mod coredump;
+mod cycle;
+pub mod dap;
+mod debugger;
+pub mod diagnostic;
+mod display;
+mod equivalence;
+pub mod framebuffer;
+pub mod generate;
+mod gif;
+mod gzip;
+pub mod heatmap;
+mod hexdump;
+pub mod histogram;
+pub mod inspect;
+pub mod interrupt;
+pub mod io_log;
+pub mod ir;
+#[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+mod jit;
+mod json;
+pub mod jupyter;
+pub mod line_editor;
+pub mod lsp;
+pub mod messages;
+pub mod metrics;
+pub mod obfuscate;
+pub mod profile;
+pub mod program;
+pub mod program_io;
+pub mod repl;
+mod rng;
+pub mod scheduler;
+mod search;
+pub mod server;
+mod sha256;
+pub mod source;
+pub mod summary;
+pub mod tape_recording;
+pub mod trace;
+pub mod tui;
+pub mod visualize;
+mod zmtp;
+
+use bytecode::Bytecode;
+use cell::{Cell, Overflow};
+use cell_format::CellFormat;
+use cycle::CycleDetector;
+use diagnostic::Diagnostic;
+use framebuffer::Framebuffer;
+use heatmap::Heatmap;
+use histogram::Histogram;
+use io_log::{IoEvent, IoLog};
+use ir::{Instruction, Op};
+use profile::Profile;
+use repl::parse_address;
+use tape_recording::TapeRecording;
+use trace::Trace;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Token {
+    IncrementPointer, // >
+    DecrementPointer, // <
+    IncrementData,    // +
+    DecrementData,    // -
+    Output,           // .
+    Input,            // ,
+    LoopStart,        // [
+    LoopEnd,          // ]
+    Refresh,          // % (graphics extension)
+}
+
+impl Token {
+    /// Returns the source character for this instruction.
+    pub fn symbol(self) -> char {
+        match self {
+            Token::IncrementPointer => '>',
+            Token::DecrementPointer => '<',
+            Token::IncrementData => '+',
+            Token::DecrementData => '-',
+            Token::Output => '.',
+            Token::Input => ',',
+            Token::LoopStart => '[',
+            Token::LoopEnd => ']',
+            Token::Refresh => '%',
+        }
+    }
+}
+
+/// How programs run when nothing needs to happen between instructions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    #[default]
+    Interp,
+    /// Compile to machine code first. Requires the `jit` feature on x86-64.
+    Jit,
+}
+
+impl Engine {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "interp" => Ok(Self::Interp),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+            "jit" => Ok(Self::Jit),
+            #[cfg(not(all(feature = "jit", target_arch = "x86_64", unix)))]
+            "jit" => Err(
+                "The JIT engine requires building with --features jit on x86-64 Unix".to_string(),
+            ),
+            _ => Err(format!(
+                "Unknown engine '{}' (expected interp or jit)",
+                name
+            )),
+        }
+    }
+}
+
+/// What `,` stores when there is no more input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EofBehavior {
+    #[default]
+    Zero,
+    MinusOne,
+    /// Leave the cell as it was.
+    Unchanged,
+}
+
+impl EofBehavior {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "0" => Ok(Self::Zero),
+            "minus-one" => Ok(Self::MinusOne),
+            "unchanged" => Ok(Self::Unchanged),
+            _ => Err(format!(
+                "Unknown EOF behavior '{}' (expected 0, minus-one, or unchanged)",
+                name
+            )),
+        }
+    }
+
+    fn apply<C: Cell>(self, cell: &mut C) {
+        match self {
+            Self::Zero => *cell = C::default(),
+            Self::MinusOne => {
+                *cell = C::default();
+                cell.decrement();
+            }
+            Self::Unchanged => {}
+        }
+    }
+}
+
+/// Opt-in instructions beyond the standard eight.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Extensions {
+    /// `%` redraws the framebuffer.
+    pub graphics: bool,
+}
+
+fn token_for(c: char, extensions: Extensions) -> Option<Token> {
+    match c {
+        '>' => Some(Token::IncrementPointer),
+        '<' => Some(Token::DecrementPointer),
+        '+' => Some(Token::IncrementData),
+        '-' => Some(Token::DecrementData),
+        '.' => Some(Token::Output),
+        ',' => Some(Token::Input),
+        '[' => Some(Token::LoopStart),
+        ']' => Some(Token::LoopEnd),
+        '%' if extensions.graphics => Some(Token::Refresh),
+        _ => None,
+    }
+}
+
+pub fn tokenize(code: &str) -> Vec<Token> {
+    tokenize_with(code, Extensions::default())
+}
+
+pub fn tokenize_with(code: &str, extensions: Extensions) -> Vec<Token> {
+    code.chars()
+        .filter_map(|c| token_for(c, extensions))
+        .collect()
+}
+
+/// Returns the byte offset in `code` of the token at `token_index`.
+pub fn source_offset(code: &str, token_index: usize) -> Option<usize> {
+    source_offset_with(code, token_index, Extensions::default())
+}
+
+pub fn source_offset_with(code: &str, token_index: usize, extensions: Extensions) -> Option<usize> {
+    code.char_indices()
+        .filter(|&(_, c)| token_for(c, extensions).is_some())
+        .nth(token_index)
+        .map(|(offset, _)| offset)
+}
+
+/// How many steps apart runs check for a timeout or an interrupt.
+const POLL_INTERVAL: u64 = 1 << 16;
+
+/// Returns an error if the run has been interrupted or has gone on for
+/// longer than `timeout`.
+fn poll(start_time: Instant, timeout: Option<Duration>, steps: u64) -> Result<(), String> {
+    if interrupt::take_request() {
+        return Err(format!("Interrupted ({} steps executed)", steps));
+    }
+    match timeout {
+        Some(timeout) if start_time.elapsed() >= timeout => Err(format!(
+            "Timed out after {:?} ({} steps executed)",
+            timeout, steps
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn report_progress(steps: u64, start_time: Instant) {
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let rate = if elapsed > 0.0 {
+        steps as f64 / elapsed
+    } else {
+        0.0
+    };
+
+    eprintln!(
+        "[progress] {} steps, {:.1}M instructions/s, {:.2}s elapsed",
+        steps,
+        rate / 1e6,
+        elapsed
+    );
+}
+
+/// The index of the matching bracket for each `[` and `]`, indexed by
+/// instruction position. Entries for other instructions are zero.
+pub type JumpTable = Vec<usize>;
+
+/// Returns the steps a loop of `len` tokens, brackets included, takes to
+/// run its body `count` times: the `[`, then the body and the `]` each time.
+fn loop_steps(count: u64, len: usize) -> u64 {
+    count.saturating_mul(len as u64 - 1).saturating_add(1)
+}
+
+pub fn parse_loops(tokens: &[Token]) -> Result<JumpTable, String> {
+    match_loops(tokens).map_err(|diagnostic| diagnostic.message)
+}
+
+/// Like [`parse_loops`], but an unmatched bracket is reported with its
+/// location.
+pub fn match_loops(tokens: &[Token]) -> Result<JumpTable, Diagnostic> {
+    let mut jump_table: JumpTable = vec![0; tokens.len()];
+    let mut loop_stack: Vec<usize> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LoopStart => {
+                loop_stack.push(i);
+            }
+            Token::LoopEnd => {
+                if let Some(start_index) = loop_stack.pop() {
+                    jump_table[start_index] = i;
+                    jump_table[i] = start_index;
+                } else {
+                    return Err(Diagnostic::error(
+                        format!("Unmatched ']' at index {}", i),
+                        Some(i),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if loop_stack.is_empty() {
+        Ok(jump_table)
+    } else {
+        Err(Diagnostic::error(
+            format!("Unmatched '[' at index {}", loop_stack[0]),
+            Some(loop_stack[0]),
+        ))
+    }
+}
+
+/// Runs programs on a tape of `C` cells, bytes unless chosen otherwise
+/// with [`InterpreterBuilder::build_with_cells`].
+#[derive(Clone)]
+pub struct Interpreter<C: Cell = u8> {
+    memory: Vec<C>,
+    data_pointer: usize,
+    instruction_pointer: usize,
+    input_position: usize,
+    steps: u64,
+    progress_interval: Option<u64>,
+    framebuffer: Option<Framebuffer>,
+    detect_cycles: bool,
+    io_log: Option<IoLog>,
+    trace: Option<Trace>,
+    histogram: Option<Histogram>,
+    profile: Option<Profile>,
+    heatmap: Option<Heatmap>,
+    tape_recording: Option<TapeRecording>,
+    grow: bool,
+    wrap_pointer: bool,
+    eof: EofBehavior,
+    overflow: Overflow,
+    engine: Engine,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures and creates an [`Interpreter`].
+///
+/// ```
+/// # use bf_repl::Interpreter;
+/// let interpreter = Interpreter::builder().tape_size(65536).build();
+/// assert_eq!(interpreter.memory_size(), 65536);
+/// ```
+#[derive(Debug, Clone)]
+pub struct InterpreterBuilder {
+    tape_size: usize,
+    progress_interval: Option<u64>,
+    framebuffer: Option<Framebuffer>,
+    detect_cycles: bool,
+    io_log: Option<IoLog>,
+    trace: Option<Trace>,
+    histogram: bool,
+    profile: bool,
+    heatmap: bool,
+    sample_interval: Option<u64>,
+    grow: bool,
+    wrap_pointer: bool,
+    eof: EofBehavior,
+    overflow: Overflow,
+    engine: Engine,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl Default for InterpreterBuilder {
+    fn default() -> Self {
+        Self {
+            tape_size: Interpreter::DEFAULT_MEMORY_SIZE,
+            progress_interval: None,
+            framebuffer: None,
+            detect_cycles: false,
+            io_log: None,
+            trace: None,
+            histogram: false,
+            profile: false,
+            heatmap: false,
+            sample_interval: None,
+            grow: false,
+            wrap_pointer: false,
+            eof: EofBehavior::Zero,
+            overflow: Overflow::Wrap,
+            engine: Engine::Interp,
+            max_steps: None,
+            timeout: None,
+        }
+    }
+}
+
+impl InterpreterBuilder {
+    /// Sets the number of cells on the tape. The data pointer starts in the
+    /// middle of the tape.
+    pub fn tape_size(mut self, tape_size: usize) -> Self {
+        assert!(tape_size > 0, "tape size must be at least one cell");
+        self.tape_size = tape_size;
+        self
+    }
+
+    /// Adds cells to the right end of the tape as the data pointer reaches
+    /// them, instead of failing with "Data pointer out of bounds (right)".
+    pub fn grow(mut self, grow: bool) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    /// Moves the data pointer from the first cell to the last and back
+    /// instead of failing at the ends of the tape. With [`grow`](Self::grow),
+    /// the tape still grows at the right end.
+    pub fn wrap_pointer(mut self, wrap_pointer: bool) -> Self {
+        self.wrap_pointer = wrap_pointer;
+        self
+    }
+
+    /// Sets what `,` stores at the end of input.
+    pub fn eof(mut self, eof: EofBehavior) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    /// Sets what `+` and `-` do at the ends of a cell's range.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets how runs without step hooks execute.
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Fails runs that would execute more than `max_steps` instructions.
+    pub fn max_steps(mut self, max_steps: Option<u64>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Fails runs still going after `timeout`. Time spent waiting for input
+    /// counts, but a run blocked reading input is not interrupted.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Lowers the step limit and timeout to `max_steps` and `timeout`,
+    /// keeping any stricter limits already set.
+    pub fn limit_runs(mut self, max_steps: u64, timeout: Duration) -> Self {
+        self.max_steps = Some(
+            self.max_steps
+                .map_or(max_steps, |steps| steps.min(max_steps)),
+        );
+        self.timeout = Some(self.timeout.map_or(timeout, |t| t.min(timeout)));
+        self
+    }
+
+    /// Reports progress to stderr every `interval` executed instructions.
+    pub fn progress_interval(mut self, interval: Option<u64>) -> Self {
+        self.progress_interval = interval.filter(|&interval| interval > 0);
+        self
+    }
+
+    /// Enables the `%` instruction, which draws `framebuffer` to the output.
+    pub fn framebuffer(mut self, framebuffer: Option<Framebuffer>) -> Self {
+        self.framebuffer = framebuffer;
+        self
+    }
+
+    /// Aborts runs that return to an earlier state, which can never finish.
+    pub fn detect_cycles(mut self, detect_cycles: bool) -> Self {
+        self.detect_cycles = detect_cycles;
+        self
+    }
+
+    /// Records every input and output event in `io_log`.
+    pub fn io_log(mut self, io_log: Option<IoLog>) -> Self {
+        self.io_log = io_log;
+        self
+    }
+
+    /// Records every executed instruction in `trace`.
+    pub fn trace(mut self, trace: Option<Trace>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Counts how often each command is executed in a run.
+    pub fn histogram(mut self, histogram: bool) -> Self {
+        self.histogram = histogram;
+        self
+    }
+
+    /// Counts how often each instruction of the program is executed in a
+    /// run, for [`Interpreter::profile`].
+    pub fn profile(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Counts how often each cell is read and written in a run, for
+    /// [`Interpreter::heatmap`].
+    pub fn heatmap(mut self, heatmap: bool) -> Self {
+        self.heatmap = heatmap;
+        self
+    }
+
+    /// Records the tape every `interval` executed instructions, for
+    /// [`Interpreter::tape_recording`].
+    pub fn record_tape(mut self, interval: Option<u64>) -> Self {
+        self.sample_interval = interval;
+        self
+    }
+
+    pub fn build(&self) -> Interpreter {
+        self.build_with_cells()
+    }
+
+    /// Like [`build`](Self::build), but with `C` cells, such as `u16` or
+    /// `u32` for programs that count past 255.
+    pub fn build_with_cells<C: Cell>(&self) -> Interpreter<C> {
+        Interpreter {
+            memory: vec![C::default(); self.tape_size],
+            data_pointer: self.tape_size / 2,
+            instruction_pointer: 0,
+            input_position: 0,
+            steps: 0,
+            progress_interval: self.progress_interval,
+            framebuffer: self.framebuffer.clone(),
+            detect_cycles: self.detect_cycles,
+            io_log: self.io_log.clone(),
+            trace: self.trace.clone(),
+            histogram: self.histogram.then(Histogram::default),
+            profile: self.profile.then(Profile::default),
+            heatmap: self.heatmap.then(Heatmap::default),
+            tape_recording: self.sample_interval.map(TapeRecording::new),
+            grow: self.grow,
+            wrap_pointer: self.wrap_pointer,
+            eof: self.eof,
+            overflow: self.overflow,
+            engine: self.engine,
+            max_steps: self.max_steps,
+            timeout: self.timeout,
+        }
+    }
+}
+
+impl Interpreter {
+    pub const DEFAULT_MEMORY_SIZE: usize = 30000;
+
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Creates an interpreter with `memory_size` cells instead of
+    /// [`DEFAULT_MEMORY_SIZE`](Self::DEFAULT_MEMORY_SIZE).
+    pub fn with_memory_size(memory_size: usize) -> Self {
+        Self::builder().tape_size(memory_size).build()
+    }
+
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+}
+
+impl<C: Cell> Interpreter<C> {
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Returns the tape.
+    pub fn memory(&self) -> &[C] {
+        &self.memory
+    }
+
+    pub fn data_pointer(&self) -> usize {
+        self.data_pointer
+    }
+
+    /// Returns the index of the next instruction to run, or of the failing
+    /// instruction after an error.
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    /// Returns the instruction set extensions this interpreter executes.
+    pub fn extensions(&self) -> Extensions {
+        Extensions {
+            graphics: self.framebuffer.is_some(),
+        }
+    }
+
+    /// Returns how runs without step hooks execute.
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// Returns the number of instructions a run may execute, if limited.
+    pub fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    /// Sets the number of instructions later runs may execute, as
+    /// [`InterpreterBuilder::max_steps`] does.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    /// Returns the number of instructions executed by the last run.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Returns how often each command was executed by the last run, if
+    /// counting was enabled.
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref()
+    }
+
+    /// Returns how often each instruction was executed by the last run, if
+    /// profiling was enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Returns how often each cell was read and written by the last run, if
+    /// counting was enabled.
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_ref()
+    }
+
+    /// Starts or stops counting cell accesses in later runs, as
+    /// [`InterpreterBuilder::heatmap`] does.
+    pub fn set_heatmap(&mut self, heatmap: bool) {
+        if heatmap != self.heatmap.is_some() {
+            self.heatmap = heatmap.then(Heatmap::default);
+        }
+    }
+
+    /// Returns the tape snapshots taken during the last run, if recording
+    /// was enabled.
+    pub fn tape_recording(&self) -> Option<&TapeRecording> {
+        self.tape_recording.as_ref()
+    }
+
+    /// Runs a program reading from stdin and writing to stdout.
+    pub fn run(&mut self, tokens: &[Token], jump_table: &JumpTable) -> Result<(), String> {
+        self.run_with_io(tokens, jump_table, io::stdin().lock(), io::stdout().lock())
+    }
+
+    /// Runs a program with `,` reading from `input` and `.` writing to
+    /// `output`, which can be buffers, files, sockets, or anything else
+    /// that reads or writes bytes. Pass `&mut` to keep using them after the
+    /// run.
+    pub fn run_with_io(
+        &mut self,
+        tokens: &[Token],
+        jump_table: &JumpTable,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), String> {
+        self.run_program(tokens, jump_table, None, &mut input, &mut output)
+    }
+
+    /// Runs a program loaded from a `.bfc` file like
+    /// [`run_with_io`](Self::run_with_io), using its compiled instructions
+    /// instead of compiling the tokens again.
+    pub fn run_bytecode_with_io(
+        &mut self,
+        bytecode: &Bytecode,
+        mut input: impl Read,
+        mut output: impl Write,
+    ) -> Result<(), String> {
+        self.run_program(
+            &bytecode.tokens,
+            &bytecode.jump_table,
+            Some(&bytecode.instructions),
+            &mut input,
+            &mut output,
+        )
+    }
+
+    /// Runs `tokens`. When no step hooks are enabled, `instructions` are
+    /// executed instead, compiling them from the tokens if not given.
+    fn run_program(
+        &mut self,
+        tokens: &[Token],
+        jump_table: &JumpTable,
+        instructions: Option<&[Instruction]>,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<(), String> {
+        let start_time = Instant::now();
+        self.instruction_pointer = 0;
+        self.steps = 0;
+        if let Some(histogram) = &mut self.histogram {
+            *histogram = Histogram::default();
+        }
+        if let Some(profile) = &mut self.profile {
+            *profile = Profile::default();
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            *heatmap = Heatmap::default();
+        }
+        if let Some(recording) = &mut self.tape_recording {
+            recording.clear();
+            recording.capture(&self.memory, self.data_pointer);
+        }
+
+        let result = if self.has_step_hooks() {
+            self.run_loop(tokens, jump_table, input, output, start_time)
+        } else {
+            match instructions {
+                Some(instructions) => {
+                    self.run_fast(tokens, instructions, input, output, start_time)
+                }
+                None => self.run_fast(
+                    tokens,
+                    &ir::compile(tokens, jump_table),
+                    input,
+                    output,
+                    start_time,
+                ),
+            }
+            // The fast path stops short of the step limit, if there is one,
+            // so the last steps run one at a time.
+            .and_then(|()| self.run_loop(tokens, jump_table, input, output, start_time))
+        };
+
+        if let Some(recording) = &mut self.tape_recording {
+            recording.capture(&self.memory, self.data_pointer);
+        }
+        if let Some(trace) = &self.trace {
+            trace.flush()?;
+        }
+        result
+    }
+
+    fn run_loop(
+        &mut self,
+        tokens: &[Token],
+        jump_table: &JumpTable,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        start_time: Instant,
+    ) -> Result<(), String> {
+        let tokens_len = tokens.len();
+        let mut cycle_detector = self.detect_cycles.then(CycleDetector::new);
+
+        while self.instruction_pointer < tokens_len {
+            if let Some(max_steps) = self.max_steps
+                && self.steps >= max_steps
+            {
+                return Err(format!("Step limit of {} reached", max_steps));
+            }
+            self.step(tokens, jump_table, input, output)?;
+
+            if self.steps.is_multiple_of(POLL_INTERVAL) {
+                poll(start_time, self.timeout, self.steps)?;
+            }
+
+            if let Some(recording) = &mut self.tape_recording {
+                recording.observe(self.steps, &self.memory, self.data_pointer);
+            }
+
+            if let Some(interval) = self.progress_interval
+                && self.steps.is_multiple_of(interval)
+            {
+                report_progress(self.steps, start_time);
+            }
+
+            if let Some(detector) = &mut cycle_detector
+                && self.steps.is_multiple_of(cycle::CHECK_INTERVAL)
+                && detector.observe(self)
+            {
+                return Err("Infinite loop detected: state repeated".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if anything needs to run after each step.
+    fn has_step_hooks(&self) -> bool {
+        self.progress_interval.is_some()
+            || self.detect_cycles
+            || self.framebuffer.is_some()
+            || self.io_log.is_some()
+            || self.trace.is_some()
+            || self.histogram.is_some()
+            || self.profile.is_some()
+            || self.heatmap.is_some()
+            || self.tape_recording.is_some()
+    }
+
+    /// Runs like [`run_loop`](Self::run_loop) when no step hooks are enabled,
+    /// executing `instructions`, the program's [`ir`] form, with the pointers
+    /// in locals and without the tape's bounds checks.
+    ///
+    /// The data pointer is checked once on entry, and every move keeps it on
+    /// the tape, so each cell access is in bounds. With a step limit, this
+    /// stops before the instruction that would pass it, leaving the rest to
+    /// the step loop.
+    fn run_fast(
+        &mut self,
+        tokens: &[Token],
+        instructions: &[Instruction],
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        start_time: Instant,
+    ) -> Result<(), String> {
+        if self.data_pointer >= self.memory.len() {
+            return Err("Data pointer out of bounds (right)".to_string());
+        }
+
+        #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+        let start = self.run_jit(instructions, input, output)?;
+        #[cfg(not(all(feature = "jit", target_arch = "x86_64", unix)))]
+        let start = 0;
+        let mut last_cell = self.memory.len() - 1;
+        let mut memory = self.memory.as_mut_ptr();
+        let mut pc = start;
+        let mut ip;
+        let mut dp = self.data_pointer;
+        let mut steps = self.steps;
+        let mut input_position = self.input_position;
+        let overflow = self.overflow;
+        let max_steps = self.max_steps.unwrap_or(u64::MAX);
+        // Polling happens on jumps, since only loops run for long.
+        let mut next_poll = POLL_INTERVAL;
+
+        let result = 'run: loop {
+            let Some(&Instruction { op, token, len }) = instructions.get(pc) else {
+                ip = tokens.len();
+                break Ok(());
+            };
+            ip = token;
+            if steps.saturating_add(len as u64) > max_steps {
+                break Ok(());
+            }
+            // SAFETY: `dp <= last_cell` holds on entry and after every move
+            // below, and `memory` and `last_cell` are refreshed whenever the
+            // tape grows; nothing else touches the tape during the loop.
+            let cell = unsafe { &mut *memory.add(dp) };
+
+            match op {
+                Op::Add(delta) if overflow == Overflow::Wrap => cell.add(delta),
+                Op::Add(_) => {
+                    // Saturating or failing depends on the order of the `+`
+                    // and `-`, so replay them.
+                    for (index, &symbol) in tokens.iter().enumerate().skip(token).take(len) {
+                        let result = if symbol == Token::IncrementData {
+                            overflow.increment(cell, index)
+                        } else {
+                            overflow.decrement(cell, index)
+                        };
+                        if let Err(e) = result {
+                            ip = index;
+                            steps += (index - token) as u64;
+                            break 'run Err(e);
+                        }
+                    }
+                }
+                Op::Move(distance) if distance > 0 => {
+                    let distance = distance.unsigned_abs();
+                    if last_cell - dp >= distance {
+                        dp += distance;
+                    } else if self.grow {
+                        dp += distance;
+                        last_cell = dp;
+                        self.memory.resize(dp + 1, C::default());
+                        memory = self.memory.as_mut_ptr();
+                    } else if self.wrap_pointer {
+                        dp = (dp + distance) % (last_cell + 1);
+                    } else {
+                        // Stop on the `>` that would leave the tape.
+                        let moved = last_cell - dp;
+                        dp = last_cell;
+                        ip += moved;
+                        steps += moved as u64;
+                        break Err("Data pointer out of bounds (right)".to_string());
+                    }
+                }
+                Op::Move(distance) => {
+                    let distance = distance.unsigned_abs();
+                    if dp >= distance {
+                        dp -= distance;
+                    } else if self.wrap_pointer {
+                        let cells = last_cell + 1;
+                        dp = (dp + cells - distance % cells) % cells;
+                    } else {
+                        ip += dp;
+                        steps += dp as u64;
+                        dp = 0;
+                        break Err("Data pointer out of bounds (left)".to_string());
+                    }
+                }
+                Op::Output => {
+                    if let Err(e) = output
+                        .write_all(&[cell.to_byte()])
+                        .and_then(|_| output.flush())
+                    {
+                        break Err(e.to_string());
+                    }
+                }
+                Op::Input => {
+                    let mut byte = [0u8; 1];
+                    match input.read(&mut byte) {
+                        Ok(0) => self.eof.apply(cell),
+                        Ok(_) => {
+                            *cell = C::from_byte(byte[0]);
+                            input_position += 1;
+                        }
+                        Err(e) => break Err(e.to_string()),
+                    }
+                }
+                Op::JumpIfZero(target) | Op::JumpIfNonZero(target)
+                    if cell.is_zero() == matches!(op, Op::JumpIfZero(_)) =>
+                {
+                    if steps >= next_poll {
+                        if let Err(e) = poll(start_time, self.timeout, steps) {
+                            break Err(e);
+                        }
+                        next_poll = steps.saturating_add(POLL_INTERVAL);
+                    }
+                    match target {
+                        Some(target) => pc = target,
+                        None => {
+                            break Err(format!(
+                                "Jump table missing entry for '{}' at {}",
+                                tokens[token].symbol(),
+                                token
+                            ));
+                        }
+                    }
+                }
+                Op::JumpIfZero(_) | Op::JumpIfNonZero(_) | Op::Refresh => {}
+                Op::Clear { up } => {
+                    // Counting up stops at the top of the range unless cells
+                    // wrap.
+                    let count = (overflow == Overflow::Wrap || !up)
+                        .then(|| cell.count_to_zero(up))
+                        .flatten();
+                    if let (Some(count), Op::JumpIfZero(Some(end))) =
+                        (count, instructions[pc + 1].op)
+                        && let skipped = loop_steps(count, instructions[end].token + 1 - token)
+                        && steps.saturating_add(skipped) <= max_steps
+                    {
+                        *cell = C::default();
+                        pc = end;
+                        steps = steps.saturating_add(skipped);
+                    }
+                }
+                Op::Scan { right } => {
+                    // SAFETY: `dp <= last_cell`, and the tape is
+                    // `last_cell + 1` cells long.
+                    let found = unsafe {
+                        if right {
+                            let rest = slice::from_raw_parts(memory.add(dp), last_cell + 1 - dp);
+                            C::find_zero(rest).map(|i| dp + i)
+                        } else {
+                            C::rfind_zero(slice::from_raw_parts(memory, dp + 1))
+                        }
+                    };
+                    if let (Some(zero), Op::JumpIfZero(Some(end))) =
+                        (found, instructions[pc + 1].op)
+                        && let count = zero.abs_diff(dp) as u64
+                        && let skipped = loop_steps(count, instructions[end].token + 1 - token)
+                        && steps.saturating_add(skipped) <= max_steps
+                    {
+                        dp = zero;
+                        pc = end;
+                        steps = steps.saturating_add(skipped);
+                    }
+                }
+                Op::Multiply { up, left, right } => {
+                    // Saturating or failing depends on the order of the
+                    // additions, so only wrapping cells multiply.
+                    let count = (overflow == Overflow::Wrap)
+                        .then(|| cell.count_to_zero(up))
+                        .flatten();
+                    if let (Some(count), Op::JumpIfZero(Some(end))) =
+                        (count, instructions[pc + 1].op)
+                        && dp >= left as usize
+                        && last_cell - dp >= right as usize
+                        && let skipped = loop_steps(count, instructions[end].token + 1 - token)
+                        && steps.saturating_add(skipped) <= max_steps
+                    {
+                        let value = cell.clone();
+                        let mut offset = 0;
+                        for instruction in &instructions[pc + 2..end] {
+                            match instruction.op {
+                                Op::Move(distance) => offset += distance,
+                                Op::Add(delta) if offset != 0 => {
+                                    // SAFETY: the loop stays within `left`
+                                    // and `right` of `dp`, checked above.
+                                    let target =
+                                        unsafe { &mut *memory.add(dp.wrapping_add_signed(offset)) };
+                                    target.add_product(&value, if up { -delta } else { delta });
+                                }
+                                _ => {}
+                            }
+                        }
+                        *cell = C::default();
+                        pc = end;
+                        steps = steps.saturating_add(skipped);
+                    }
+                }
+            }
+
+            pc += 1;
+            steps += len as u64;
+        };
+
+        self.instruction_pointer = ip;
+        self.data_pointer = dp;
+        self.steps = steps;
+        self.input_position = input_position;
+        result
+    }
+
+    fn log_io(&self, event: IoEvent) -> Result<(), String> {
+        match &self.io_log {
+            Some(io_log) => io_log.record(self.instruction_pointer, event),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes the instruction at the instruction pointer and moves past it.
+    ///
+    /// On error the instruction pointer is left on the failing instruction.
+    pub fn step(
+        &mut self,
+        tokens: &[Token],
+        jump_table: &JumpTable,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<(), String> {
+        let token_index = self.instruction_pointer;
+        let token = tokens[token_index];
+
+        match token {
+            Token::IncrementPointer => {
+                if self.data_pointer + 1 < self.memory.len() {
+                    self.data_pointer += 1;
+                } else if self.grow {
+                    self.memory.push(C::default());
+                    self.data_pointer += 1;
+                } else if self.wrap_pointer {
+                    self.data_pointer = 0;
+                } else {
+                    return Err("Data pointer out of bounds (right)".to_string());
+                }
+            }
+            Token::DecrementPointer => {
+                if self.data_pointer > 0 {
+                    self.data_pointer -= 1;
+                } else if self.wrap_pointer {
+                    self.data_pointer = self.memory.len() - 1;
+                } else {
+                    return Err("Data pointer out of bounds (left)".to_string());
+                }
+            }
+            Token::IncrementData => self.overflow.increment(
+                &mut self.memory[self.data_pointer],
+                self.instruction_pointer,
+            )?,
+            Token::DecrementData => self.overflow.decrement(
+                &mut self.memory[self.data_pointer],
+                self.instruction_pointer,
+            )?,
+            Token::Output => {
+                let byte = self.memory[self.data_pointer].to_byte();
+                output
+                    .write_all(&[byte])
+                    .and_then(|_| output.flush())
+                    .map_err(|e| e.to_string())?;
+                self.log_io(IoEvent::Output(byte))?;
+            }
+            Token::Input => {
+                let mut byte = [0u8; 1];
+                match input.read(&mut byte) {
+                    Ok(0) => {
+                        self.eof.apply(&mut self.memory[self.data_pointer]);
+                        self.log_io(IoEvent::EndOfInput)?;
+                    }
+                    Ok(_) => {
+                        self.memory[self.data_pointer] = C::from_byte(byte[0]);
+                        self.input_position += 1;
+                        self.log_io(IoEvent::Input(byte[0]))?;
+                    }
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            Token::LoopStart => {
+                if self.memory[self.data_pointer].is_zero() {
+                    self.instruction_pointer =
+                        *jump_table.get(self.instruction_pointer).ok_or_else(|| {
+                            format!(
+                                "Jump table missing entry for '[' at {}",
+                                self.instruction_pointer
+                            )
+                        })?;
+                }
+            }
+            Token::LoopEnd => {
+                if !self.memory[self.data_pointer].is_zero() {
+                    self.instruction_pointer =
+                        *jump_table.get(self.instruction_pointer).ok_or_else(|| {
+                            format!(
+                                "Jump table missing entry for ']' at {}",
+                                self.instruction_pointer
+                            )
+                        })?;
+                }
+            }
+            Token::Refresh => {
+                if let Some(framebuffer) = &mut self.framebuffer {
+                    framebuffer.render(&self.memory, output)?;
+                }
+            }
+        }
+
+        self.instruction_pointer += 1;
+        self.steps += 1;
+        if let Some(histogram) = &mut self.histogram {
+            histogram.record(token);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.record(token_index);
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record(token, self.data_pointer);
+        }
+        if let Some(trace) = &self.trace {
+            trace.record(
+                self.steps,
+                token_index,
+                token,
+                self.data_pointer,
+                &self.memory[self.data_pointer],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn print_memory_snapshot(&self, range: usize) {
+        let _ = self.write_memory_snapshot(&mut io::stderr(), range);
+    }
+
+    pub fn write_memory_snapshot(&self, out: &mut dyn Write, range: usize) -> io::Result<()> {
+        self.write_memory_around(out, self.data_pointer, range, CellFormat::Decimal)
+    }
+
+    /// Writes the cells up to `range` away from `center` on either side.
+    pub fn write_memory_around(
+        &self,
+        out: &mut dyn Write,
+        center: usize,
+        range: usize,
+        format: CellFormat,
+    ) -> io::Result<()> {
+        let start = center.saturating_sub(range);
+        let end = (center + range + 1).min(self.memory.len());
+        self.write_memory_cells(out, start..end, format)
+    }
+
+    /// Writes the addresses and values of `cells`, marking the data pointer
+    /// if it is among them.
+    pub fn write_memory_cells(
+        &self,
+        out: &mut dyn Write,
+        cells: Range<usize>,
+        format: CellFormat,
+    ) -> io::Result<()> {
+        let width = format.width();
+
+        write!(out, "Addr:")?;
+        for i in cells.clone() {
+            write!(out, "{:>width$}", i)?;
+        }
+        writeln!(out)?;
+
+        write!(out, "Data:")?;
+        for i in cells.clone() {
+            write!(out, "{:>width$}", format.format(&self.memory[i]))?;
+        }
+        writeln!(out)?;
+
+        write!(out, "Ptrs:")?;
+        for i in cells {
+            if i == self.data_pointer {
+                write!(out, "  {}", "^".repeat(width - 2))?;
+            } else {
+                write!(out, "{:width$}", "")?;
+            }
+        }
+        writeln!(out)
+    }
+
+    fn print_cell(&self, data_pointer: usize) {
+        let _ = self.write_cell(&mut io::stderr(), data_pointer);
+    }
+
+    fn print_current_cell(&self) {
+        self.print_cell(self.data_pointer);
+    }
+
+    fn write_cell(&self, out: &mut dyn Write, data_pointer: usize) -> io::Result<()> {
+        let value = &self.memory[data_pointer];
+        match value.try_to_byte().and_then(ascii::describe) {
+            Some(description) => {
+                writeln!(out, "Cell[{}] = {} {}", data_pointer, value, description)
+            }
+            None => writeln!(out, "Cell[{}] = {}", data_pointer, value),
+        }
+    }
+
+    fn write_current_cell(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.write_cell(out, self.data_pointer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_basic() {
+        let code = "+-><.,[]";
+        let tokens = tokenize(code);
+
+        insta::assert_debug_snapshot!(
+            tokens,
+            @r"
+        [
+            IncrementData,
+            DecrementData,
+            IncrementPointer,
+            DecrementPointer,
+            Output,
+            Input,
+            LoopStart,
+            LoopEnd,
+        ]
+        "
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_comments() {
+        let code = "++ Hello World! [<]";
+        let tokens = tokenize(code);
+
+        insta::assert_debug_snapshot!(
+            tokens,
+            @r"
+        [
+            IncrementData,
+            IncrementData,
+            LoopStart,
+            DecrementPointer,
+            LoopEnd,
+        ]
+        "
+        );
+    }
+
+    #[test]
+    fn test_tokenize_empty() {
+        let code = "";
+        let tokens = tokenize(code);
+
+        insta::assert_debug_snapshot!(tokens, @"[]");
+    }
+
+    #[test]
+    fn test_parse_loops_unmatched_loop_start() {
+        let tokens = tokenize("[<>]++[");
+        let result = parse_loops(&tokens);
+
+        insta::assert_debug_snapshot!(result, @r#"
+        Err(
+            "Unmatched '[' at index 6",
+        )
+        "#);
+    }
+
+    #[test]
+    fn test_parse_loops_unmatched_loop_end() {
+        let tokens = tokenize("[<>]++[]]");
+        let result = parse_loops(&tokens);
+
+        insta::assert_debug_snapshot!(result, @r#"
+        Err(
+            "Unmatched ']' at index 8",
+        )
+        "#);
+    }
+
+    #[test]
+    fn test_source_offset() {
+        let code = "ab+\n[-]";
+
+        insta::assert_debug_snapshot!(
+            (source_offset(code, 0), source_offset(code, 2), source_offset(code, 4)),
+            @r"
+        (
+            Some(
+                2,
+            ),
+            Some(
+                5,
+            ),
+            None,
+        )
+        "
+        );
+    }
+
+    #[test]
+    fn test_run_with_io() {
+        let tokens = tokenize(",[.,]++++++++++.");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut output = Vec::new();
+        let result =
+            interpreter.run_with_io(&tokens, &jump_table, io::Cursor::new("echo"), &mut output);
+
+        insta::assert_debug_snapshot!((result, String::from_utf8(output)), @r#"
+        (
+            Ok(
+                (),
+            ),
+            Ok(
+                "echo\n",
+            ),
+        )
+        "#);
+    }
+
+    #[test]
+    fn test_builder_tape_size() {
+        let tokens = tokenize(">>>");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::builder().tape_size(4).build();
+        assert_eq!(Interpreter::with_memory_size(65536).memory_size(), 65536);
+        let result = interpreter.run_with_io(&tokens, &jump_table, &b""[..], &mut Vec::new());
+
+        insta::assert_debug_snapshot!((interpreter.memory_size(), interpreter.data_pointer, result), @r#"
+        (
+            4,
+            3,
+            Err(
+                "Data pointer out of bounds (right)",
+            ),
+        )
+        "#);
+    }
+
+    /// Everything a run can change.
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        result: Result<(), String>,
+        instruction_pointer: usize,
+        data_pointer: usize,
+        steps: u64,
+        input_position: usize,
+        memory: Vec<u8>,
+        output: Vec<u8>,
+    }
+
+    fn run_outcome(builder: &InterpreterBuilder, code: &str, input: &[u8]) -> Outcome {
+        let mut interpreter = builder.build();
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut output = Vec::new();
+        let result = interpreter.run_with_io(&tokens, &jump_table, input, &mut output);
+        Outcome {
+            result,
+            instruction_pointer: interpreter.instruction_pointer,
+            data_pointer: interpreter.data_pointer,
+            steps: interpreter.steps,
+            input_position: interpreter.input_position,
+            memory: interpreter.memory,
+            output,
+        }
+    }
+
+    #[test]
+    fn test_fast_path_matches_step_loop() {
+        let fast = InterpreterBuilder::default().tape_size(16);
+        // Counting commands is a step hook, so this takes the step loop.
+        let hooked = fast.clone().histogram(true);
+
+        for code in [
+            "++++++++[>++++++++<-]>+.+.",
+            ",[.,]",
+            "+[>+]",
+            "<",
+            "-[[-]>+<]>.",
+            ">>>>>>>>+<<-<<<<<<<<<<+",
+            "+++[>>>>>>>>>-<<<<<<<<+-+]",
+        ] {
+            assert_eq!(
+                run_outcome(&fast, code, b"hi"),
+                run_outcome(&hooked, code, b"hi"),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_grow() {
+        let fast = InterpreterBuilder::default().tape_size(2).grow(true);
+        let hooked = fast.clone().histogram(true);
+        let outcome = run_outcome(&fast, ">+>++>+++<<<-", b"");
+
+        assert_eq!(outcome, run_outcome(&hooked, ">+>++>+++<<<-", b""));
+        insta::assert_debug_snapshot!((outcome.result, outcome.data_pointer, outcome.memory), @r"
+        (
+            Ok(
+                (),
+            ),
+            1,
+            [
+                0,
+                255,
+                1,
+                2,
+                3,
+            ],
+        )
+        ");
+    }
+
+    #[test]
+    fn test_wrap_pointer() {
+        let fast = InterpreterBuilder::default()
+            .tape_size(3)
+            .wrap_pointer(true);
+        let hooked = fast.clone().histogram(true);
+        let outcome = run_outcome(&fast, ">+>++<<<+++", b"");
+
+        assert_eq!(outcome, run_outcome(&hooked, ">+>++<<<+++", b""));
+        insta::assert_debug_snapshot!((outcome.result, outcome.data_pointer, outcome.memory), @r"
+        (
+            Ok(
+                (),
+            ),
+            0,
+            [
+                5,
+                0,
+                1,
+            ],
+        )
+        ");
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut outcomes = Vec::new();
+        for (code, max_steps) in [("+[]", 100), ("++[->+++<]>[-]", 9), ("+++>>", 5)] {
+            let fast = InterpreterBuilder::default()
+                .tape_size(16)
+                .max_steps(Some(max_steps));
+            let hooked = fast.clone().histogram(true);
+            let outcome = run_outcome(&fast, code, b"");
+            assert_eq!(outcome, run_outcome(&hooked, code, b""), "{}", code);
+            outcomes.push((outcome.result, outcome.instruction_pointer, outcome.steps));
+        }
+
+        insta::assert_debug_snapshot!(outcomes, @r#"
+        [
+            (
+                Err(
+                    "Step limit of 100 reached",
+                ),
+                2,
+                100,
+            ),
+            (
+                Err(
+                    "Step limit of 9 reached",
+                ),
+                9,
+                9,
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                5,
+                5,
+            ),
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let fast = InterpreterBuilder::default().timeout(Some(Duration::from_millis(10)));
+        let hooked = fast.clone().histogram(true);
+
+        for builder in [fast, hooked] {
+            let outcome = run_outcome(&builder, "+[]", b"");
+            let error = outcome.result.unwrap_err();
+            assert!(error.starts_with("Timed out after 10ms ("), "{}", error);
+            assert!(error.contains(&format!("({} steps executed)", outcome.steps)));
+        }
+    }
+
+    #[test]
+    fn test_limit_runs() {
+        let limits = [
+            InterpreterBuilder::default(),
+            InterpreterBuilder::default().max_steps(Some(1_000)),
+            InterpreterBuilder::default()
+                .max_steps(Some(5))
+                .timeout(Some(Duration::from_secs(60))),
+        ]
+        .map(|builder| {
+            let builder = builder.limit_runs(100, Duration::from_secs(10));
+            (builder.max_steps, builder.timeout)
+        });
+
+        assert_eq!(
+            limits,
+            [
+                (Some(100), Some(Duration::from_secs(10))),
+                (Some(100), Some(Duration::from_secs(10))),
+                (Some(5), Some(Duration::from_secs(10))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eof() {
+        let outputs = [
+            EofBehavior::Zero,
+            EofBehavior::MinusOne,
+            EofBehavior::Unchanged,
+        ]
+        .map(|eof| {
+            let fast = InterpreterBuilder::default().eof(eof);
+            let outcome = run_outcome(&fast, "+++,.,.", b"a");
+            assert_eq!(outcome, run_outcome(&fast.histogram(true), "+++,.,.", b"a"));
+            outcome.output
+        });
+
+        insta::assert_debug_snapshot!(outputs, @r"
+        [
+            [
+                97,
+                0,
+            ],
+            [
+                97,
+                255,
+            ],
+            [
+                97,
+                97,
+            ],
+        ]
+        ");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let results = [Overflow::Wrap, Overflow::Saturate, Overflow::Error].map(|overflow| {
+            let fast = InterpreterBuilder::default().overflow(overflow);
+            let outcome = run_outcome(&fast, "-.+>+.-", b"");
+            assert_eq!(
+                outcome,
+                run_outcome(&fast.clone().histogram(true), "-.+>+.-", b"")
+            );
+            let runs = run_outcome(&fast, "+-+---+", b"");
+            assert_eq!(runs, run_outcome(&fast.histogram(true), "+-+---+", b""));
+            (outcome.result, outcome.output)
+        });
+
+        insta::assert_debug_snapshot!(results, @r#"
+        [
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    255,
+                    1,
+                ],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    0,
+                    1,
+                ],
+            ),
+            (
+                Err(
+                    "Cell underflow at index 0",
+                ),
+                [],
+            ),
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_clear_loops() {
+        for overflow in [Overflow::Wrap, Overflow::Saturate, Overflow::Error] {
+            let fast = InterpreterBuilder::default().overflow(overflow);
+            let hooked = fast.clone().histogram(true);
+            for code in ["+++[-]>[+]", "+++[+]", "--[+]", "+[[-]+>]"] {
+                if overflow == Overflow::Saturate && code == "+++[+]" {
+                    // Stuck at 255, so it never ends.
+                    continue;
+                }
+                assert_eq!(
+                    run_outcome(&fast, code, b""),
+                    run_outcome(&hooked, code, b""),
+                    "{:?} {}",
+                    overflow,
+                    code
+                );
+            }
+        }
+
+        insta::assert_debug_snapshot!(
+            (run_cells::<u16>("+++[+]"), run_cells::<bignum::BigInt>("---[+]+++[-]")),
+            @r#"
+        (
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "0",
+                    "0",
+                ],
+                [],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "0",
+                    "0",
+                ],
+                [],
+            ),
+        )
+        "#
+        );
+    }
+
+    #[test]
+    fn test_multiply_loops() {
+        let code = "++++++++[->++++++++<]>+[->+>--<<]>.>.[+<+>]<<[>>>+<<<-]";
+        for builder in [
+            InterpreterBuilder::default().tape_size(8),
+            InterpreterBuilder::default().tape_size(4),
+            InterpreterBuilder::default().overflow(Overflow::Error),
+            InterpreterBuilder::default()
+                .tape_size(4)
+                .wrap_pointer(true),
+        ] {
+            assert_eq!(
+                run_outcome(&builder, code, b""),
+                run_outcome(&builder.clone().histogram(true), code, b""),
+                "{:?}",
+                builder
+            );
+        }
+
+        insta::assert_debug_snapshot!(
+            (
+                run_cells::<u16>(code),
+                run_cells::<bignum::BigInt>("---[+>+++<]>[-<++>]<.")
+            ),
+            @r#"
+        (
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "0",
+                    "0",
+                ],
+                [
+                    65,
+                    126,
+                ],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "18",
+                    "18",
+                ],
+                [
+                    18,
+                ],
+            ),
+        )
+        "#
+        );
+    }
+
+    #[test]
+    fn test_scan_loops() {
+        let fast = InterpreterBuilder::default().tape_size(8);
+        for builder in [
+            fast.clone(),
+            fast.clone().grow(true),
+            fast.clone().wrap_pointer(true),
+        ] {
+            // The last two run off the right and left ends of the tape.
+            for code in ["+>+>+<<[>]<[<]>.", "+>+>+>+[>]+", "<<<<+>+[<]+"] {
+                assert_eq!(
+                    run_outcome(&builder, code, b""),
+                    run_outcome(&builder.clone().histogram(true), code, b""),
+                    "{:?} {}",
+                    builder,
+                    code
+                );
+            }
+        }
+
+        insta::assert_debug_snapshot!(run_cells::<u16>("+>+>+<<[>]<[<]>."), @r#"
+        (
+            Ok(
+                (),
+            ),
+            [
+                "1",
+                "1",
+            ],
+            [
+                1,
+            ],
+        )
+        "#);
+    }
+
+    /// Runs `code` with `C` cells on both the fast path and the step loop,
+    /// returning the result, the first and current cells, and the output.
+    fn run_cells<C: Cell>(code: &str) -> (Result<(), String>, [String; 2], Vec<u8>) {
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+        let [fast, hooked] = [false, true].map(|histogram| {
+            let mut interpreter = Interpreter::builder()
+                .histogram(histogram)
+                .build_with_cells::<C>();
+            let start = interpreter.data_pointer;
+            let mut output = Vec::new();
+            let result = interpreter.run_with_io(&tokens, &jump_table, &b"x"[..], &mut output);
+            let cells =
+                [start, interpreter.data_pointer].map(|i| interpreter.memory[i].to_string());
+            (result, cells, output)
+        });
+        assert_eq!(fast, hooked, "{}", code);
+        fast
+    }
+
+    #[test]
+    fn test_cell_sizes() {
+        // 256 is zero in a byte, so the last loop only runs with wider cells.
+        let code = "-.>,.>++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>";
+        insta::assert_debug_snapshot!(
+            (
+                run_cells::<u8>(code),
+                run_cells::<u16>(code),
+                run_cells::<u32>(code),
+                run_cells::<bignum::BigInt>(code)
+            ),
+            @r#"
+        (
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "255",
+                    "0",
+                ],
+                [
+                    255,
+                    120,
+                ],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "65535",
+                    "1",
+                ],
+                [
+                    255,
+                    120,
+                ],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "4294967295",
+                    "1",
+                ],
+                [
+                    255,
+                    120,
+                ],
+            ),
+            (
+                Ok(
+                    (),
+                ),
+                [
+                    "-1",
+                    "1",
+                ],
+                [
+                    255,
+                    120,
+                ],
+            ),
+        )
+        "#
+        );
+    }
+
+    /// Compares the fast path with the step loop (which also counts commands
+    /// here) on tight nested loops; run with
+    /// `cargo test --release -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_fast_path() {
+        let code = "-[>-[>-[>+<-]<-]<-]";
+        for (name, builder) in [
+            ("step loop", Interpreter::builder().histogram(true)),
+            ("fast path", Interpreter::builder()),
+        ] {
+            let start = Instant::now();
+            let outcome = run_outcome(&builder, code, b"");
+            outcome.result.unwrap();
+            let steps = outcome.steps;
+            let elapsed = start.elapsed().as_secs_f64();
+            println!(
+                "{}: {} steps in {:.3}s ({:.0}M steps/s)",
+                name,
+                steps,
+                elapsed,
+                steps as f64 / elapsed / 1e6
+            );
+        }
+    }
+}
@@ -0,0 +1,772 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// How many lines of a history file are loaded.
+const HISTORY_FILE_LIMIT: usize = 1000;
+
+/// Returns the number of `[` not yet closed by a `]` in `line`.
+pub fn bracket_depth(line: &str) -> isize {
+    line.chars().fold(0, |depth, c| match c {
+        '[' => depth + 1,
+        ']' => depth - 1,
+        _ => depth,
+    })
+}
+
+fn bracket_hint(line: &str) -> Option<String> {
+    if line.trim_start().starts_with(':') {
+        return None;
+    }
+
+    match bracket_depth(line) {
+        0 => None,
+        depth => Some(format!("[{}]", depth)),
+    }
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Up,
+    Down,
+    Search,
+    Tab,
+    Interrupt,
+    EndOfFile,
+    Other,
+}
+
+/// Makes the console interpret the ANSI escape sequences used for colors
+/// and cursor movement. Only Windows consoles need to be told.
+pub fn enable_ansi_output() {
+    terminal::enable_ansi_output();
+}
+
+/// Restores the terminal's settings and resets colors and the cursor if
+/// the process panics or is killed by a signal, so a crash in raw mode or
+/// halfway through drawing in color never leaves the shell garbled.
+pub fn install_terminal_cleanup() {
+    terminal::install_cleanup();
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        terminal::restore();
+        default_hook(info);
+    }));
+}
+
+pub(crate) use terminal::{RawMode, read_byte};
+
+/// Returns the width and height of the terminal on stdout in characters,
+/// or 80 by 24 if it cannot be told.
+pub(crate) fn terminal_size() -> (usize, usize) {
+    terminal::size().unwrap_or((80, 24))
+}
+
+/// A minimal line editor for interactive terminals.
+///
+/// While a line is being typed, the number of unclosed brackets is shown
+/// dimmed after the cursor so a missing `]` is noticed before submitting.
+/// Up and Down move through the lines entered before, and Ctrl-R searches
+/// them.
+pub struct LineEditor {
+    /// Given the line up to the cursor, returns where the word being
+    /// completed starts and what it could become.
+    completer: Option<Completer>,
+    /// Lines entered so far, oldest first.
+    history: Vec<String>,
+    /// Where entered lines are appended so later sessions can recall them.
+    history_file: Option<PathBuf>,
+}
+
+pub type Completer = fn(&str) -> (usize, Vec<String>);
+
+impl LineEditor {
+    /// Returns an editor if both stdin and stderr are terminals.
+    pub fn for_terminal() -> Option<Self> {
+        terminal::is_interactive().then(|| Self {
+            completer: None,
+            history: Vec::new(),
+            history_file: None,
+        })
+    }
+
+    /// Completes the word before the cursor with `completer` when Tab is
+    /// pressed.
+    pub fn set_completer(&mut self, completer: Completer) {
+        self.completer = Some(completer);
+    }
+
+    /// Loads the last lines saved in `path`, if it exists, and appends each
+    /// line entered from now on to it. A file that has grown past the lines
+    /// loaded is cut down to them.
+    pub fn set_history_file(&mut self, path: PathBuf) {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            let lines: Vec<&str> = contents.lines().filter(|line| !line.is_empty()).collect();
+            let skipped = lines.len().saturating_sub(HISTORY_FILE_LIMIT);
+            self.history = lines[skipped..]
+                .iter()
+                .map(|line| line.to_string())
+                .collect();
+            if skipped > 0 {
+                let _ = fs::write(&path, self.history.join("\n") + "\n");
+            }
+        }
+        self.history_file = Some(path);
+    }
+
+    /// Reads one line, returning `None` at end of input.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        self.edit_line(prompt, "")
+    }
+
+    /// Like [`read_line`](Self::read_line), but starts with `initial`
+    /// already typed and the cursor at its end.
+    pub fn edit_line(&mut self, prompt: &str, initial: &str) -> io::Result<Option<String>> {
+        let _raw_mode = terminal::RawMode::enable()?;
+        let mut buffer: Vec<char> = initial.chars().collect();
+        let mut cursor = buffer.len();
+        // Which history entry is shown, or `history.len()` for the new line,
+        // which is kept in `draft` while an older one is shown.
+        let mut position = self.history.len();
+        let mut draft = Vec::new();
+        let mut pending = None;
+
+        loop {
+            redraw(prompt, &buffer, cursor)?;
+
+            let key = match pending.take() {
+                Some(key) => key,
+                None => read_key()?,
+            };
+            match key {
+                Key::Char(c) => {
+                    buffer.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => {
+                    let line: String = buffer.iter().collect();
+                    redraw_final(prompt, &line)?;
+                    self.add_history(&line);
+                    return Ok(Some(line));
+                }
+                Key::Up if position > 0 => {
+                    if position == self.history.len() {
+                        draft = std::mem::take(&mut buffer);
+                    }
+                    position -= 1;
+                    buffer = self.history[position].chars().collect();
+                    cursor = buffer.len();
+                }
+                Key::Down if position < self.history.len() => {
+                    position += 1;
+                    buffer = match self.history.get(position) {
+                        Some(line) => line.chars().collect(),
+                        None => std::mem::take(&mut draft),
+                    };
+                    cursor = buffer.len();
+                }
+                Key::Tab => {
+                    if let Some(complete) = self.completer {
+                        let line: String = buffer[..cursor].iter().collect();
+                        let (start, candidates) = complete(&line);
+                        let start = line[..start].chars().count();
+                        let common = common_prefix(&candidates);
+                        if common.chars().count() > cursor - start {
+                            buffer.splice(start..cursor, common.chars());
+                            cursor = start + common.chars().count();
+                        } else if candidates.len() > 1 {
+                            eprint!("\r\n{}\r\n", candidates.join("  "));
+                        }
+                    }
+                }
+                Key::Search => {
+                    if let Some((line, key)) = self.search()? {
+                        buffer = line.chars().collect();
+                        cursor = buffer.len();
+                        pending = Some(key);
+                    }
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    buffer.remove(cursor);
+                }
+                Key::Delete if cursor < buffer.len() => {
+                    buffer.remove(cursor);
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = (cursor + 1).min(buffer.len()),
+                Key::Home => cursor = 0,
+                Key::End => cursor = buffer.len(),
+                Key::Interrupt => {
+                    eprint!("^C\r\n");
+                    buffer.clear();
+                    cursor = 0;
+                }
+                Key::EndOfFile if buffer.is_empty() => {
+                    eprint!("\r\n");
+                    return Ok(None);
+                }
+                Key::EndOfFile
+                | Key::Backspace
+                | Key::Delete
+                | Key::Up
+                | Key::Down
+                | Key::Other => {}
+            }
+        }
+    }
+
+    /// Remembers `line` for Up and Ctrl-R, unless it is blank or repeats
+    /// the line before.
+    fn add_history(&mut self, line: &str) {
+        let line = line.trim();
+        if !line.is_empty() && self.history.last().is_none_or(|last| last != line) {
+            self.history.push(line.to_string());
+
+            // Losing a line of history is not worth interrupting the session.
+            if let Some(path) = &self.history_file
+                && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+            {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    /// Searches the history backwards for the text typed so far, newest
+    /// match first; Ctrl-R again finds the match before. Returns the line
+    /// found and the key that ended the search, or `None` if it was
+    /// cancelled with Ctrl-C.
+    fn search(&self) -> io::Result<Option<(String, Key)>> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+
+        loop {
+            let line = found.map_or("", |i| self.history[i].as_str());
+            let prompt = format!(
+                "({}reverse-i-search)'{}': ",
+                if failed { "failed " } else { "" },
+                query
+            );
+            let chars: Vec<char> = line.chars().collect();
+            redraw(&prompt, &chars, chars.len())?;
+
+            let before = match read_key()? {
+                Key::Char(c) => {
+                    query.push(c);
+                    found.map_or(self.history.len(), |i| i + 1)
+                }
+                Key::Backspace => {
+                    query.pop();
+                    self.history.len()
+                }
+                Key::Search => found.unwrap_or(self.history.len()),
+                Key::Interrupt => {
+                    eprint!("\r\x1b[K");
+                    return Ok(None);
+                }
+                key => {
+                    eprint!("\r\x1b[K");
+                    return Ok(Some((line.to_string(), key)));
+                }
+            };
+            match self.history[..before]
+                .iter()
+                .rposition(|entry| entry.contains(&query))
+            {
+                Some(i) => {
+                    found = Some(i);
+                    failed = false;
+                }
+                None => failed = true,
+            }
+        }
+    }
+}
+
+/// Returns the longest text all of `candidates` start with.
+fn common_prefix(candidates: &[String]) -> &str {
+    let Some((first, rest)) = candidates.split_first() else {
+        return "";
+    };
+    let mut len = first.len();
+    for candidate in rest {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((i, a), b)| *i < len && a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8());
+    }
+    &first[..len]
+}
+
+fn redraw(prompt: &str, buffer: &[char], cursor: usize) -> io::Result<()> {
+    let line: String = buffer.iter().collect();
+    let mut stderr = io::stderr().lock();
+
+    write!(stderr, "\r{}{}", prompt, line)?;
+    if let Some(hint) = bracket_hint(&line) {
+        write!(stderr, " \x1b[2m{}\x1b[0m", hint)?;
+    }
+    write!(stderr, "\x1b[K\r")?;
+
+    let column = prompt.chars().count() + cursor;
+    if column > 0 {
+        write!(stderr, "\x1b[{}C", column)?;
+    }
+    stderr.flush()
+}
+
+fn redraw_final(prompt: &str, line: &str) -> io::Result<()> {
+    let mut stderr = io::stderr().lock();
+    write!(stderr, "\r{}{}\x1b[K\r\n", prompt, line)?;
+    stderr.flush()
+}
+
+fn read_key() -> io::Result<Key> {
+    let Some(byte) = terminal::read_byte()? else {
+        return Ok(Key::EndOfFile);
+    };
+
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x09 => Key::Tab,
+        0x02 => Key::Left,
+        0x03 => Key::Interrupt,
+        0x04 => Key::EndOfFile,
+        #[cfg(windows)]
+        0x1a => Key::EndOfFile,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x12 => Key::Search,
+        0x1b => read_escape_sequence()?,
+        byte if byte >= 0x20 => read_char(byte)?,
+        _ => Key::Other,
+    };
+
+    Ok(key)
+}
+
+fn read_escape_sequence() -> io::Result<Key> {
+    let key = match terminal::read_byte()? {
+        Some(b'[') => match terminal::read_byte()? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            Some(digit @ b'0'..=b'9') => {
+                let mut code = vec![digit];
+                while let Some(byte) = terminal::read_byte()? {
+                    if byte == b'~' {
+                        break;
+                    }
+                    code.push(byte);
+                }
+                match &code[..] {
+                    b"1" | b"7" => Key::Home,
+                    b"3" => Key::Delete,
+                    b"4" | b"8" => Key::End,
+                    _ => Key::Other,
+                }
+            }
+            _ => Key::Other,
+        },
+        Some(b'O') => match terminal::read_byte()? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Other,
+        },
+        _ => Key::Other,
+    };
+
+    Ok(key)
+}
+
+fn read_char(first: u8) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match terminal::read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+
+    Ok(std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next())
+        .map_or(Key::Other, Key::Char))
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::sync::OnceLock;
+
+    /// Written to a terminal on stderr when cleaning up: resets colors and
+    /// shows the cursor.
+    const RESET: &[u8] = b"\x1b[0m\x1b[?25h";
+
+    /// The terminal settings from before anything changed them.
+    static ORIGINAL: OnceLock<libc::termios> = OnceLock::new();
+
+    pub fn is_interactive() -> bool {
+        // SAFETY: isatty only inspects the given file descriptors.
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDERR_FILENO) == 1 }
+    }
+
+    pub fn enable_ansi_output() {}
+
+    pub fn install_cleanup() {
+        let mut termios = MaybeUninit::<libc::termios>::uninit();
+
+        // SAFETY: tcgetattr initializes `termios` when it returns 0, and the
+        // handler only calls async-signal-safe functions.
+        unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) == 0 {
+                let _ = ORIGINAL.set(termios.assume_init());
+            }
+            for signal in [libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGTERM] {
+                libc::signal(signal, handle_signal as *const () as libc::sighandler_t);
+            }
+        }
+    }
+
+    pub fn restore() {
+        // SAFETY: restores settings captured by tcgetattr and writes a
+        // static buffer; both are async-signal-safe.
+        unsafe {
+            if let Some(original) = ORIGINAL.get() {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, original);
+            }
+            if libc::isatty(libc::STDERR_FILENO) == 1 {
+                libc::write(libc::STDERR_FILENO, RESET.as_ptr().cast(), RESET.len());
+            }
+        }
+    }
+
+    extern "C" fn handle_signal(signal: libc::c_int) {
+        restore();
+        // SAFETY: re-raising with the default disposition ends the process
+        // the way the signal would have without the handler.
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+
+    pub fn size() -> Option<(usize, usize)> {
+        let mut size = MaybeUninit::<libc::winsize>::uninit();
+
+        // SAFETY: TIOCGWINSZ initializes `size` when ioctl returns 0.
+        let size = unsafe {
+            if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+                return None;
+            }
+            size.assume_init()
+        };
+        (size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col.into(), size.ws_row.into()))
+    }
+
+    pub fn read_byte() -> io::Result<Option<u8>> {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: reads at most one byte into a valid, writable location.
+            let n = unsafe { libc::read(libc::STDIN_FILENO, (&raw mut byte).cast(), 1) };
+            match n {
+                1 => return Ok(Some(byte)),
+                0 => return Ok(None),
+                _ => {
+                    let error = io::Error::last_os_error();
+                    if error.kind() != io::ErrorKind::Interrupted {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Puts the terminal into raw mode until dropped.
+    pub struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            let mut termios = MaybeUninit::<libc::termios>::uninit();
+
+            // SAFETY: tcgetattr initializes `termios` when it returns 0.
+            let original = unsafe {
+                if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                termios.assume_init()
+            };
+
+            let mut raw = original;
+            raw.c_iflag &= !(libc::ICRNL | libc::IXON | libc::BRKINT | libc::ISTRIP);
+            raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+
+            // SAFETY: `raw` is a valid termios derived from the current settings.
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: restores the settings captured in `enable`.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod terminal {
+    use std::ffi::c_void;
+    use std::io::{self, Read, Write};
+    use std::sync::OnceLock;
+
+    type Handle = *mut c_void;
+
+    const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+
+    const ENABLE_PROCESSED_INPUT: u32 = 0x0001;
+    const ENABLE_LINE_INPUT: u32 = 0x0002;
+    const ENABLE_ECHO_INPUT: u32 = 0x0004;
+    const ENABLE_VIRTUAL_TERMINAL_INPUT: u32 = 0x0200;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    /// The console input mode from before anything changed it.
+    static ORIGINAL_INPUT_MODE: OnceLock<u32> = OnceLock::new();
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetStdHandle(std_handle: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: Handle, mode: u32) -> i32;
+    }
+
+    /// Returns the handle and mode of a standard stream if it is a console.
+    fn console_mode(std_handle: u32) -> Option<(Handle, u32)> {
+        let mut mode = 0;
+        // SAFETY: GetStdHandle has no preconditions, and GetConsoleMode only
+        // writes to `mode`, failing for handles that are not consoles.
+        unsafe {
+            let handle = GetStdHandle(std_handle);
+            (GetConsoleMode(handle, &mut mode) != 0).then_some((handle, mode))
+        }
+    }
+
+    pub fn is_interactive() -> bool {
+        console_mode(STD_INPUT_HANDLE).is_some() && console_mode(STD_ERROR_HANDLE).is_some()
+    }
+
+    pub fn enable_ansi_output() {
+        for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            if let Some((handle, mode)) = console_mode(std_handle) {
+                // SAFETY: `handle` is a console handle, as GetConsoleMode
+                // succeeded on it.
+                unsafe {
+                    SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING);
+                }
+            }
+        }
+    }
+
+    pub fn install_cleanup() {
+        if let Some((_, mode)) = console_mode(STD_INPUT_HANDLE) {
+            let _ = ORIGINAL_INPUT_MODE.set(mode);
+        }
+    }
+
+    pub fn restore() {
+        if let (Some((handle, _)), Some(&mode)) =
+            (console_mode(STD_INPUT_HANDLE), ORIGINAL_INPUT_MODE.get())
+        {
+            // SAFETY: `handle` is the console input handle.
+            unsafe {
+                SetConsoleMode(handle, mode);
+            }
+        }
+        if console_mode(STD_ERROR_HANDLE).is_some() {
+            let _ = io::stderr().write_all(b"\x1b[0m\x1b[?25h");
+        }
+    }
+
+    pub fn size() -> Option<(usize, usize)> {
+        None
+    }
+
+    pub fn read_byte() -> io::Result<Option<u8>> {
+        // The standard library reads the console as UTF-16 and hands out
+        // UTF-8, which is what the key decoder expects.
+        let mut byte = [0u8];
+        match io::stdin().read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Turns off line input, echo, and Ctrl-C processing, and asks for
+    /// arrow keys as escape sequences, until dropped.
+    pub struct RawMode {
+        handle: Handle,
+        original: u32,
+    }
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            let (handle, original) =
+                console_mode(STD_INPUT_HANDLE).ok_or_else(io::Error::last_os_error)?;
+            let raw = original & !(ENABLE_PROCESSED_INPUT | ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT)
+                | ENABLE_VIRTUAL_TERMINAL_INPUT;
+
+            // SAFETY: `handle` is the console input handle.
+            if unsafe { SetConsoleMode(handle, raw) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self { handle, original })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            // SAFETY: restores the mode captured in `enable`.
+            unsafe {
+                SetConsoleMode(self.handle, self.original);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod terminal {
+    use std::io;
+
+    pub fn is_interactive() -> bool {
+        false
+    }
+
+    pub fn enable_ansi_output() {}
+
+    pub fn install_cleanup() {}
+
+    pub fn restore() {}
+
+    pub fn size() -> Option<(usize, usize)> {
+        None
+    }
+
+    pub fn read_byte() -> io::Result<Option<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw terminal input is not supported on this platform",
+        ))
+    }
+
+    pub struct RawMode;
+
+    impl RawMode {
+        pub fn enable() -> io::Result<Self> {
+            Ok(Self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bracket_hint() {
+        insta::assert_debug_snapshot!(
+            ["+[>[-", "+[-]", "-]", ":find \"[\""].map(bracket_hint),
+            @r#"
+        [
+            Some(
+                "[2]",
+            ),
+            None,
+            Some(
+                "[-1]",
+            ),
+            None,
+        ]
+        "#
+        );
+    }
+
+    #[test]
+    fn test_history_file() {
+        let path = std::env::temp_dir().join(format!("bf-repl-history-{}.txt", std::process::id()));
+        fs::write(&path, "+\n\n>\n").unwrap();
+
+        let mut editor = LineEditor {
+            completer: None,
+            history: Vec::new(),
+            history_file: None,
+        };
+        editor.set_history_file(path.clone());
+        editor.add_history(" :mem ");
+        editor.add_history(":mem");
+        editor.add_history("");
+
+        let mut reloaded = LineEditor {
+            completer: None,
+            history: Vec::new(),
+            history_file: None,
+        };
+        reloaded.set_history_file(path.clone());
+        fs::remove_file(&path).unwrap();
+
+        insta::assert_debug_snapshot!((editor.history, reloaded.history), @r#"
+        (
+            [
+                "+",
+                ">",
+                ":mem",
+            ],
+            [
+                "+",
+                ">",
+                ":mem",
+            ],
+        )
+        "#);
+    }
+}
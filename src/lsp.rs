@@ -0,0 +1,335 @@
+//! `bf-repl lsp`: a language server on stdin and stdout that reports
+//! unmatched brackets and loops that never end, highlights matching
+//! brackets, and outlines a program's top-level loops.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::analysis::loop_diagnostics;
+use crate::diagnostic::Severity;
+use crate::json::{self, Value};
+use crate::{Extensions, Token, match_loops, token_for, tokenize};
+
+/// How many instructions of a loop its outline entry shows before cutting
+/// it off.
+const SYMBOL_WIDTH: usize = 30;
+/// LSP's `SymbolKind.Function`; there is no kind for loops.
+const LOOP_SYMBOL_KIND: u64 = 12;
+/// The JSON-RPC error for a request the server does not handle.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Serves the Language Server Protocol on stdin and stdout until the editor
+/// sends `exit`.
+pub fn run_lsp() -> Result<(), String> {
+    let stdin = io::stdin();
+    Server::new(io::stdout().lock())
+        .serve(&mut stdin.lock())
+        .map_err(|e| e.to_string())
+}
+
+struct Server<W: Write> {
+    out: W,
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+}
+
+impl<W: Write> Server<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            documents: HashMap::new(),
+        }
+    }
+
+    fn serve(mut self, input: &mut dyn BufRead) -> io::Result<()> {
+        while let Some(message) = json::read_message(input)? {
+            let Ok(message) = Value::parse(&message) else {
+                continue;
+            };
+            let method = message.get("method").as_str().unwrap_or_default();
+            let params = message.get("params");
+
+            match message.get("id") {
+                _ if method == "exit" => break,
+                Value::Null => self.notification(method, params)?,
+                id => {
+                    let outcome = match self.request(method, params) {
+                        Some(result) => ("result", result),
+                        None => (
+                            "error",
+                            json::object([
+                                ("code", Value::Number(METHOD_NOT_FOUND as f64)),
+                                ("message", format!("Unknown method '{}'", method).into()),
+                            ]),
+                        ),
+                    };
+                    let response =
+                        json::object([("jsonrpc", "2.0".into()), ("id", id.clone()), outcome]);
+                    json::write_message(&mut self.out, &response)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers a request, or returns `None` if the method is unknown.
+    fn request(&self, method: &str, params: &Value) -> Option<Value> {
+        let text = || {
+            let uri = params.get("textDocument").get("uri").as_str()?;
+            self.documents.get(uri)
+        };
+        match method {
+            "initialize" => Some(json::object([
+                (
+                    "capabilities",
+                    json::object([
+                        // Each change sends the whole document.
+                        ("textDocumentSync", 1usize.into()),
+                        ("documentHighlightProvider", true.into()),
+                        ("documentSymbolProvider", true.into()),
+                    ]),
+                ),
+                ("serverInfo", json::object([("name", "bf-repl".into())])),
+            ])),
+            "shutdown" => Some(Value::Null),
+            "textDocument/documentHighlight" => Some(match text() {
+                Some(text) => matching_brackets(text, params.get("position")).into(),
+                None => Value::Null,
+            }),
+            "textDocument/documentSymbol" => Some(match text() {
+                Some(text) => loop_symbols(text).into(),
+                None => Value::Null,
+            }),
+            _ => None,
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let Some(uri) = params.get("textDocument").get("uri").as_str() else {
+            return Ok(());
+        };
+        let text = match method {
+            "textDocument/didOpen" => params.get("textDocument").get("text"),
+            // Whole-document sync sends one change holding the new text.
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .as_array()
+                .last()
+                .map_or(&Value::Null, |change| change.get("text")),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.publish_diagnostics(uri, Vec::new());
+            }
+            _ => return Ok(()),
+        };
+        let Some(text) = text.as_str() else {
+            return Ok(());
+        };
+
+        self.documents.insert(uri.to_string(), text.to_string());
+        self.publish_diagnostics(uri, diagnostics(text))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+        let notification = json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                json::object([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+            ),
+        ]);
+        json::write_message(&mut self.out, &notification)
+    }
+}
+
+/// Returns the byte offset in `text` of each instruction.
+fn instruction_offsets(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .filter(|&(_, c)| token_for(c, Extensions::default()).is_some())
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Finds unmatched brackets, or, if there are none, loops that never end.
+fn diagnostics(text: &str) -> Vec<Value> {
+    let tokens = tokenize(text);
+    let diagnostics = match match_loops(&tokens) {
+        Ok(jump_table) => loop_diagnostics(&tokens, &jump_table),
+        Err(diagnostic) => vec![diagnostic],
+    };
+    let offsets = instruction_offsets(text);
+
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let start = diagnostic.token_index.map_or(0, |index| offsets[index]);
+            let severity = match diagnostic.severity {
+                Severity::Error => 1usize,
+                Severity::Warning => 2,
+            };
+            json::object([
+                ("range", range(text, start, start + 1)),
+                ("severity", severity.into()),
+                ("source", "bf-repl".into()),
+                ("message", diagnostic.message.into()),
+            ])
+        })
+        .collect()
+}
+
+/// Returns the ranges of the bracket at or just before `position` and the
+/// bracket it matches.
+fn matching_brackets(text: &str, position: &Value) -> Vec<Value> {
+    let tokens = tokenize(text);
+    let (Ok(jump_table), Some(cursor)) = (match_loops(&tokens), offset(text, position)) else {
+        return Vec::new();
+    };
+    let offsets = instruction_offsets(text);
+
+    let is_bracket = |&index: &usize| matches!(tokens[index], Token::LoopStart | Token::LoopEnd);
+    let at = offsets.iter().position(|&offset| offset == cursor);
+    let before = offsets.iter().position(|&offset| offset + 1 == cursor);
+    let Some(index) = at.filter(is_bracket).or(before.filter(is_bracket)) else {
+        return Vec::new();
+    };
+
+    [index, jump_table[index]]
+        .into_iter()
+        .map(|index| json::object([("range", range(text, offsets[index], offsets[index] + 1))]))
+        .collect()
+}
+
+/// Lists the loops that are not inside another loop, each named by its
+/// instructions.
+fn loop_symbols(text: &str) -> Vec<Value> {
+    let tokens = tokenize(text);
+    let Ok(jump_table) = match_loops(&tokens) else {
+        return Vec::new();
+    };
+    let offsets = instruction_offsets(text);
+
+    let mut symbols = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if tokens[index] != Token::LoopStart {
+            index += 1;
+            continue;
+        }
+        let end = jump_table[index];
+        let mut name: String = tokens[index..=end]
+            .iter()
+            .take(SYMBOL_WIDTH)
+            .map(|token| token.symbol())
+            .collect();
+        if end - index + 1 > SYMBOL_WIDTH {
+            name.push_str("...");
+        }
+        symbols.push(json::object([
+            ("name", name.into()),
+            ("detail", format!("{} instructions", end - index + 1).into()),
+            ("kind", LOOP_SYMBOL_KIND.into()),
+            ("range", range(text, offsets[index], offsets[end] + 1)),
+            (
+                "selectionRange",
+                range(text, offsets[index], offsets[index] + 1),
+            ),
+        ]));
+        index = end + 1;
+    }
+    symbols
+}
+
+/// Returns the LSP range between byte offsets `start` and `end` of `text`.
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json::object([
+        ("start", position(text, start)),
+        ("end", position(text, end)),
+    ])
+}
+
+/// Returns the LSP position of byte `offset` in `text`: the line, counted
+/// from 0, and the UTF-16 code units before it on that line.
+fn position(text: &str, offset: usize) -> Value {
+    let line = text[..offset].matches('\n').count();
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+    json::object([("line", line.into()), ("character", character.into())])
+}
+
+/// Returns the byte offset in `text` of the LSP `position`.
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position.get("line").as_u64()? as usize;
+    let character = position.get("character").as_u64()? as usize;
+    let line_start = match line {
+        0 => 0,
+        line => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_server() {
+        let uri = "file:///tmp/program.bf";
+        let messages = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_string(),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","text":"é ++[>+<-]\n[>]+[]]"}}}}}}"#,
+                uri
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{}"}},"contentChanges":[{{"text":"é ++[>+<-]\n[>[-]]+[]"}}]}}}}"#,
+                uri
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/documentHighlight","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":0,"character":10}}}}}}"#,
+                uri
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/documentSymbol","params":{{"textDocument":{{"uri":"{}"}}}}}}"#,
+                uri
+            ),
+            r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/hover","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+        ];
+        let input: String = messages
+            .iter()
+            .map(|message| format!("Content-Length: {}\r\n\r\n{}", message.len(), message))
+            .collect();
+
+        let mut output = Vec::new();
+        Server::new(&mut output)
+            .serve(&mut input.as_bytes())
+            .unwrap();
+
+        let transcript = String::from_utf8(output)
+            .unwrap()
+            .split("Content-Length: ")
+            .filter_map(|message| message.split_once("\r\n\r\n"))
+            .map(|(_, body)| format!("{}\n", body))
+            .collect::<String>();
+        insta::assert_snapshot!(transcript, @r#"
+        {"jsonrpc":"2.0","id":1,"result":{"capabilities":{"textDocumentSync":1,"documentHighlightProvider":true,"documentSymbolProvider":true},"serverInfo":{"name":"bf-repl"}}}
+        {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///tmp/program.bf","diagnostics":[{"range":{"start":{"line":1,"character":6},"end":{"line":1,"character":7}},"severity":1,"source":"bf-repl","message":"Unmatched ']' at index 14"}]}}
+        {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///tmp/program.bf","diagnostics":[{"range":{"start":{"line":1,"character":7},"end":{"line":1,"character":8}},"severity":2,"source":"bf-repl","message":"This loop never changes the cell it tests, so it never ends once entered"}]}}
+        {"jsonrpc":"2.0","id":2,"result":[{"range":{"start":{"line":0,"character":9},"end":{"line":0,"character":10}}},{"range":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}}]}
+        {"jsonrpc":"2.0","id":3,"result":[{"name":"[>+<-]","detail":"6 instructions","kind":12,"range":{"start":{"line":0,"character":4},"end":{"line":0,"character":10}},"selectionRange":{"start":{"line":0,"character":4},"end":{"line":0,"character":5}}},{"name":"[>[-]]","detail":"6 instructions","kind":12,"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":6}},"selectionRange":{"start":{"line":1,"character":0},"end":{"line":1,"character":1}}},{"name":"[]","detail":"2 instructions","kind":12,"range":{"start":{"line":1,"character":7},"end":{"line":1,"character":9}},"selectionRange":{"start":{"line":1,"character":7},"end":{"line":1,"character":8}}}]}
+        {"jsonrpc":"2.0","id":4,"error":{"code":-32601,"message":"Unknown method 'textDocument/hover'"}}
+        {"jsonrpc":"2.0","id":5,"result":null}
+        "#);
+    }
+}
@@ -1,350 +1,934 @@
-use std::collections::HashMap;
 use std::env;
-use std::fs;
-use std::io::{self, Read, Write};
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Token {
-    IncrementPointer, // >
-    DecrementPointer, // <
-    IncrementData,    // +
-    DecrementData,    // -
-    Output,           // .
-    Input,            // ,
-    LoopStart,        // [
-    LoopEnd,          // ]
-}
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bf_repl::bignum::BigInt;
+use bf_repl::bytecode::Bytecode;
+use bf_repl::cell::{Cell, CellSize, Overflow};
+use bf_repl::codegen::{BackendKind, Settings};
+use bf_repl::coredump::{CoreDump, DumpFormat};
+use bf_repl::diagnostic::{Diagnostic, ErrorFormat, Sources};
+use bf_repl::framebuffer::Framebuffer;
+use bf_repl::generate::{self, GenOptions};
+use bf_repl::io_log::IoLog;
+use bf_repl::messages::Lang;
+use bf_repl::program_io::{self, CrlfToLf, OutputMode, ProgramIo};
+use bf_repl::repl::Repl;
+use bf_repl::summary::SummaryFormat;
+use bf_repl::trace::{self, Trace, TraceFormat};
+use bf_repl::{
+    Engine, EofBehavior, Interpreter, InterpreterBuilder, analysis, annotate, batch, build, check,
+    dap, inspect, jupyter, line_editor, lsp, match_loops, obfuscate, server, source, tokenize_with,
+    tui, visualize,
+};
+
+/// Parses a duration such as `2s`, `1.5s`, or `500ms`; a bare number is in
+/// seconds.
+fn parse_duration(spec: &str) -> Result<Duration, String> {
+    let (number, scale) = match spec.strip_suffix("ms") {
+        Some(millis) => (millis, 1e-3),
+        None => (spec.strip_suffix('s').unwrap_or(spec), 1.0),
+    };
 
-pub fn tokenize(code: &str) -> Vec<Token> {
-    code.chars()
-        .filter_map(|c| match c {
-            '>' => Some(Token::IncrementPointer),
-            '<' => Some(Token::DecrementPointer),
-            '+' => Some(Token::IncrementData),
-            '-' => Some(Token::DecrementData),
-            '.' => Some(Token::Output),
-            ',' => Some(Token::Input),
-            '[' => Some(Token::LoopStart),
-            ']' => Some(Token::LoopEnd),
-            _ => None,
-        })
-        .collect()
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .ok_or_else(|| format!("Invalid duration '{}' (expected e.g. 2s or 500ms)", spec))
 }
 
-pub type JumpTable = HashMap<usize, usize>;
+/// How many steps apart `--render-gif` takes frames by default.
+const DEFAULT_SAMPLE_INTERVAL: u64 = 1000;
 
-pub fn parse_loops(tokens: &[Token]) -> Result<JumpTable, String> {
-    let mut jump_table: JumpTable = HashMap::new();
-    let mut loop_stack: Vec<usize> = Vec::new();
+fn run_files(filenames: &[String], options: &Options) -> Result<(), String> {
+    if filenames.len() > 1 && !options.concat {
+        return Err(
+            "Multiple files given; pass --concat to join them into one program".to_string(),
+        );
+    }
 
-    for (i, token) in tokens.iter().enumerate() {
-        match token {
-            Token::LoopStart => {
-                loop_stack.push(i);
-            }
-            Token::LoopEnd => {
-                if let Some(start_index) = loop_stack.pop() {
-                    jump_table.insert(start_index, i);
-                    jump_table.insert(i, start_index);
-                } else {
-                    return Err(format!("Unmatched ']' at index {}", i));
-                }
-            }
-            _ => {}
+    if let [filename] = filenames
+        && let Some(bytecode) = Bytecode::load(filename)?
+    {
+        // Errors are reported against the program's symbols.
+        let code: String = bytecode.tokens.iter().map(|token| token.symbol()).collect();
+        if options.debug {
+            return debug_program(&code, options);
+        }
+        if options.tui {
+            return tui_program(&code, options);
         }
+        let sources = [(filename.as_str(), code.as_str())];
+        return match options.cell_size {
+            CellSize::Bits8 => run_program::<u8>(&code, &sources, Some(&bytecode), options),
+            CellSize::Bits16 => run_program::<u16>(&code, &sources, Some(&bytecode), options),
+            CellSize::Bits32 => run_program::<u32>(&code, &sources, Some(&bytecode), options),
+            CellSize::Big => run_program::<BigInt>(&code, &sources, Some(&bytecode), options),
+        };
     }
 
-    if loop_stack.is_empty() {
-        Ok(jump_table)
-    } else {
-        Err(format!("Unmatched '[' at index {}", loop_stack[0]))
+    let contents = filenames
+        .iter()
+        .map(|filename| source::read_source(filename))
+        .collect::<Result<Vec<String>, String>>()?;
+    let bf_code = contents.concat();
+
+    if options.debug {
+        return debug_program(&bf_code, options);
+    }
+    if options.tui {
+        return tui_program(&bf_code, options);
+    }
+    let sources: Vec<(&str, &str)> = filenames
+        .iter()
+        .map(String::as_str)
+        .zip(contents.iter().map(String::as_str))
+        .collect();
+
+    match options.cell_size {
+        CellSize::Bits8 => run_program::<u8>(&bf_code, &sources, None, options),
+        CellSize::Bits16 => run_program::<u16>(&bf_code, &sources, None, options),
+        CellSize::Bits32 => run_program::<u32>(&bf_code, &sources, None, options),
+        CellSize::Big => run_program::<BigInt>(&bf_code, &sources, None, options),
     }
 }
 
-pub struct Interpreter {
-    memory: Vec<u8>,
-    data_pointer: usize,
-    instruction_pointer: usize,
+/// Runs `bf_code` in the REPL's debugger, stopping at `#` breakpoints.
+fn debug_program(bf_code: &str, options: &Options) -> Result<(), String> {
+    let mut repl = Repl::stdio().with_interpreter(options.interpreter_builder()?);
+    repl.set_lang(options.lang);
+    repl.set_translate_crlf(options.translate_crlf);
+    repl.run_debugger(bf_code).map_err(|e| e.to_string())
 }
 
-impl Default for Interpreter {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Runs `bf_code` full-screen, updating the view as it runs.
+fn tui_program(bf_code: &str, options: &Options) -> Result<(), String> {
+    tui::run_tui(
+        bf_code,
+        options.interpreter_builder()?.build(),
+        options.tui_speed.unwrap_or(tui::DEFAULT_SPEED),
+    )
 }
 
-impl Interpreter {
-    const MEMORY_SIZE: usize = 30000;
-
-    pub fn new() -> Self {
-        Self {
-            memory: vec![0; Self::MEMORY_SIZE],
-            data_pointer: Self::MEMORY_SIZE / 2,
-            instruction_pointer: 0,
+/// Runs the program joined from `sources` on a tape of `C` cells, using
+/// `bytecode` instead of tokenizing `bf_code` if it was loaded from a `.bfc`
+/// file.
+fn run_program<C: Cell>(
+    bf_code: &str,
+    sources: &Sources,
+    bytecode: Option<&Bytecode>,
+    options: &Options,
+) -> Result<(), String> {
+    let error_format = options.error_format;
+
+    let mut interpreter = options
+        .interpreter_builder()?
+        .histogram(options.histogram)
+        .profile(options.profile)
+        .record_tape(
+            (options.render_gif.is_some() || options.render_svg.is_some())
+                .then(|| options.sample_every.unwrap_or(DEFAULT_SAMPLE_INTERVAL)),
+        )
+        .build_with_cells::<C>();
+
+    let compiled;
+    let (tokens, jump_table) = match bytecode {
+        Some(bytecode) => (&bytecode.tokens, &bytecode.jump_table),
+        None => {
+            let tokens = tokenize_with(bf_code, interpreter.extensions());
+            let jump_table = match_loops(&tokens)
+                .map_err(|diagnostic| error_format.report_error(diagnostic, sources))?;
+            compiled = (tokens, jump_table);
+            (&compiled.0, &compiled.1)
         }
-    }
-
-    pub fn run(&mut self, tokens: &[Token], jump_table: &JumpTable) -> Result<(), String> {
-        let tokens_len = tokens.len();
-        self.instruction_pointer = 0;
-
-        while self.instruction_pointer < tokens_len {
-            let token = tokens[self.instruction_pointer];
+    };
 
-            match token {
-                Token::IncrementPointer => {
-                    self.data_pointer += 1;
-                    if self.data_pointer >= Self::MEMORY_SIZE {
-                        return Err("Data pointer out of bounds (right)".to_string());
-                    }
-                }
-                Token::DecrementPointer => {
-                    if self.data_pointer == 0 {
-                        return Err("Data pointer out of bounds (left)".to_string());
-                    }
-                    self.data_pointer -= 1;
-                }
-                Token::IncrementData => {
-                    self.memory[self.data_pointer] = self.memory[self.data_pointer].wrapping_add(1);
+    if !options.quiet && bytecode.is_none() {
+        match error_format {
+            ErrorFormat::Human => {
+                for warning in analysis::loop_warnings(bf_code, tokens, jump_table) {
+                    eprintln!("{}", options.lang.translate(&warning));
                 }
-                Token::DecrementData => {
-                    self.memory[self.data_pointer] = self.memory[self.data_pointer].wrapping_sub(1);
-                }
-                Token::Output => {
-                    print!("{}", self.memory[self.data_pointer] as char);
-                    io::stdout().flush().map_err(|e| e.to_string())?;
-                }
-                Token::Input => match io::stdin().bytes().next() {
-                    Some(Ok(byte)) => self.memory[self.data_pointer] = byte,
-                    Some(Err(e)) => return Err(e.to_string()),
-                    None => self.memory[self.data_pointer] = 0,
-                },
-                Token::LoopStart => {
-                    if self.memory[self.data_pointer] == 0 {
-                        self.instruction_pointer =
-                            *jump_table.get(&self.instruction_pointer).ok_or_else(|| {
-                                format!(
-                                    "Jump table missing entry for '[' at {}",
-                                    self.instruction_pointer
-                                )
-                            })?;
-                    }
-                }
-                Token::LoopEnd => {
-                    if self.memory[self.data_pointer] != 0 {
-                        self.instruction_pointer =
-                            *jump_table.get(&self.instruction_pointer).ok_or_else(|| {
-                                format!(
-                                    "Jump table missing entry for ']' at {}",
-                                    self.instruction_pointer
-                                )
-                            })?;
-                    }
+            }
+            ErrorFormat::Json => {
+                for diagnostic in analysis::loop_diagnostics(tokens, jump_table) {
+                    eprintln!("{}", diagnostic.to_json(sources));
                 }
             }
-
-            self.instruction_pointer += 1;
         }
+    }
 
-        Ok(())
+    let (input, mut output) = options.io.open()?;
+    let mut input: Box<dyn Read> = match options.input_timeout {
+        Some(timeout) => {
+            program_io::with_timeout(input, timeout, options.input_default.unwrap_or(0))
+        }
+        None => input,
+    };
+    if options.translate_crlf {
+        input = Box::new(CrlfToLf::new(input));
+    }
+    if let Some(redirected) = options.output_mode.open(options.output_file.as_deref())? {
+        output = redirected;
     }
 
-    pub fn print_memory_snapshot(&self, range: usize) {
-        let start = self.data_pointer.saturating_sub(range);
-        let end = (self.data_pointer + range + 1).min(Self::MEMORY_SIZE);
+    let result = match bytecode {
+        Some(bytecode) => interpreter.run_bytecode_with_io(bytecode, &mut input, &mut output),
+        None => interpreter.run_with_io(tokens, jump_table, &mut input, &mut output),
+    };
 
-        eprint!("Addr:");
-        for i in start..end {
-            eprint!("{:>7}", i);
+    if let (Some(path), Some(recording)) = (&options.render_gif, interpreter.tape_recording()) {
+        recording.save(path)?;
+        if !options.quiet {
+            eprintln!();
+            eprintln!("Wrote {} frames to {}", recording.frame_count(), path);
         }
-        eprintln!();
-
-        eprint!("Data:");
-        for i in start..end {
-            eprint!("{:>7}", self.memory[i]);
+    }
+    if let (Some(path), Some(recording)) = (&options.render_svg, interpreter.tape_recording()) {
+        recording.save_svg(path)?;
+        if !options.quiet {
+            eprintln!();
+            eprintln!("Wrote {} frames to {}", recording.frame_count(), path);
         }
-        eprintln!();
+    }
 
-        eprint!("Ptrs:");
-        for i in start..end {
-            if i == self.data_pointer {
-                eprint!("  ^^^^^");
-            } else {
-                eprint!("       ");
-            }
+    if let Err(e) = result {
+        // Profiles of runs that failed, such as ones stopped by --timeout,
+        // still show where the time went.
+        if let Some(profile) = interpreter.profile() {
+            eprintln!();
+            eprint!("{}", profile.render(bf_code, tokens, jump_table));
         }
-        eprintln!();
+        if let Some(core_path) = &options.coredump_on_error {
+            CoreDump::capture(&interpreter, bf_code, &e)
+                .save(core_path, options.coredump_format)?;
+            eprintln!();
+            eprintln!("Core dump written to {}", core_path);
+        }
+        let diagnostic = Diagnostic::error(e, Some(interpreter.instruction_pointer()));
+        return Err(error_format.report_error(diagnostic, sources));
     }
 
-    fn print_cell(&self, data_pointer: usize) {
-        eprintln!("Cell[{}] = {}", data_pointer, self.memory[data_pointer]);
+    if !options.quiet {
+        eprintln!();
     }
 
-    fn print_current_cell(&self) {
-        self.print_cell(self.data_pointer);
+    if let Some(summary_format) = &options.summary_format {
+        eprintln!("{}", summary_format.render(&interpreter));
     }
-}
 
-fn run_repl() -> Result<(), String> {
-    let mut interpreter = Interpreter::new();
+    if let Some(histogram) = interpreter.histogram() {
+        eprint!("{}", histogram.render());
+    }
 
-    eprintln!("Brainfuck REPL");
-    eprintln!("Type 'exit' to exit, or 'mem' to show memory snapshot.");
+    if let Some(profile) = interpreter.profile() {
+        eprint!("{}", profile.render(bf_code, tokens, jump_table));
+    }
 
-    loop {
-        eprint!("> ");
-        io::stdout().flush().map_err(|e| e.to_string())?;
+    if let Some(heatmap) = interpreter.heatmap() {
+        eprint!("{}", heatmap.render());
+    }
 
-        let mut input = String::new();
+    Ok(())
+}
 
-        let bytes_read = io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| e.to_string())?;
+fn run_repl(options: &Options) -> Result<(), String> {
+    let mut repl = Repl::stdio().with_interpreter(options.interpreter_builder()?);
+    repl.set_quiet(options.quiet);
+    repl.set_summary_format(options.summary_format.clone());
+    repl.set_translate_crlf(options.translate_crlf);
+    repl.set_lang(options.lang);
+    if let Some(path) = history_file(options.history_file.as_deref()) {
+        repl.set_history_file(path);
+    }
 
-        if bytes_read == 0 {
-            eprintln!();
-            break;
-        }
+    if options.io != ProgramIo::Stdio {
+        let (input, output) = options.io.open()?;
+        repl = repl.with_program_io(input, output);
+    }
 
-        let bf_code = input.trim();
+    if let Some(output) = options.output_mode.open(None)? {
+        repl = repl.with_output(output);
+    }
 
-        if bf_code.is_empty() {
-            continue;
-        }
+    repl.run().map_err(|e| e.to_string())
+}
 
-        let parts: Vec<&str> = bf_code.split_whitespace().collect();
-        let command = parts.first().unwrap_or(&"");
-
-        match *command {
-            "quit" | "exit" => {
-                break;
-            }
-            "mem" | "memory" => {
-                interpreter.print_memory_snapshot(5);
-                continue;
-            }
-            "show" => {
-                if let Some(addr_str) = parts.get(1) {
-                    match addr_str.parse::<usize>() {
-                        Ok(addr) if addr < Interpreter::MEMORY_SIZE => {
-                            interpreter.print_cell(addr);
-                        }
-                        Ok(addr) => {
-                            eprintln!(
-                                "Address {} is out of bounds (0-{})",
-                                addr,
-                                Interpreter::MEMORY_SIZE - 1
-                            );
-                        }
-                        Err(_) => {
-                            eprintln!("Invalid address format");
-                        }
-                    }
-                } else {
-                    interpreter.print_current_cell();
-                }
-                continue;
-            }
-            "jump" => {
-                if let Some(addr_str) = parts.get(1) {
-                    match addr_str.parse::<usize>() {
-                        Ok(addr) if addr < Interpreter::MEMORY_SIZE => {
-                            interpreter.data_pointer = addr;
-                            interpreter.print_current_cell();
-                        }
-                        Ok(addr) => {
-                            eprintln!(
-                                "Address {} is out of bounds (0-{})",
-                                addr,
-                                Interpreter::MEMORY_SIZE - 1
-                            );
-                        }
-                        Err(_) => {
-                            eprintln!("Invalid address format");
-                        }
-                    }
-                } else {
-                    eprintln!("Usage: jump <address>");
-                }
-                continue;
-            }
-            "set" => {
-                if let Some(value_str) = parts.get(1) {
-                    match value_str.parse::<u8>() {
-                        Ok(value) => {
-                            interpreter.memory[interpreter.data_pointer] = value;
-                            interpreter.print_current_cell();
-                        }
-                        Err(_) => {
-                            eprintln!("Invalid value format or value is out of u8 range (0-255)");
-                        }
-                    }
-                } else {
-                    eprintln!("Usage: set <value>");
-                }
-                continue;
+/// Where the REPL keeps its line history: `flag` if given, then
+/// `BF_REPL_HISTORY_FILE`, then `.bf_repl_history` in the home directory.
+/// `off` keeps none.
+fn history_file(flag: Option<&str>) -> Option<PathBuf> {
+    let path = match flag {
+        Some(path) => PathBuf::from(path),
+        None => match env::var_os("BF_REPL_HISTORY_FILE") {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })?;
+                PathBuf::from(home).join(".bf_repl_history")
             }
-            _ => {}
+        },
+    };
+    (path.as_os_str() != "off").then_some(path)
+}
+
+#[derive(Debug, Default)]
+enum Command {
+    #[default]
+    Repl,
+    Run(Vec<String>),
+    Inspect(String),
+    Annotate(String),
+    Check(String),
+    Build(String),
+    /// The file is read from wherever it falls among the flags, so it
+    /// can follow `--target`.
+    Compile(Option<String>),
+    Map(String),
+    Obfuscate(String),
+    GenRandom,
+    Dap,
+    Lsp,
+    JupyterKernel(String),
+    InstallKernel,
+    Visualize(String),
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    command: Command,
+    coredump_on_error: Option<String>,
+    coredump_format: DumpFormat,
+    listen: Option<String>,
+    history_file: Option<String>,
+    metrics_listen: Option<String>,
+    io: ProgramIo,
+    progress_interval: Option<u64>,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    tape_size: Option<usize>,
+    cell_size: CellSize,
+    grow: bool,
+    wrap_pointer: bool,
+    eof: EofBehavior,
+    overflow: Overflow,
+    engine: Engine,
+    quiet: bool,
+    summary_format: Option<SummaryFormat>,
+    concat: bool,
+    output_file: Option<String>,
+    output_mode: OutputMode,
+    framebuffer: Option<Framebuffer>,
+    analyze: bool,
+    error_format: ErrorFormat,
+    lang: Lang,
+    detect_cycles: bool,
+    io_log: Option<String>,
+    debug: bool,
+    tui: bool,
+    tui_speed: Option<u64>,
+    speed: Option<u64>,
+    trace: Option<String>,
+    trace_format: TraceFormat,
+    trace_limit: Option<u64>,
+    histogram: bool,
+    profile: bool,
+    heatmap: bool,
+    render_gif: Option<String>,
+    render_svg: Option<String>,
+    sample_every: Option<u64>,
+    seed: Option<u64>,
+    input_timeout: Option<Duration>,
+    input_default: Option<u8>,
+    translate_crlf: bool,
+    gen_options: GenOptions,
+    keep_c: bool,
+    backend: BackendKind,
+    inputs_dir: Option<String>,
+    out_dir: Option<String>,
+}
+
+impl Options {
+    /// Creates the configured interpreter builder, creating the I/O log file
+    /// if one was requested.
+    fn interpreter_builder(&self) -> Result<InterpreterBuilder, String> {
+        let io_log = self.io_log.as_deref().map(IoLog::create).transpose()?;
+        let trace = self
+            .trace
+            .as_deref()
+            .map(|path| {
+                Trace::create(
+                    path,
+                    self.trace_format,
+                    self.trace_limit.unwrap_or(trace::DEFAULT_LIMIT),
+                )
+            })
+            .transpose()?;
+
+        let mut builder = Interpreter::builder();
+        if let Some(tape_size) = self.tape_size {
+            builder = builder.tape_size(tape_size);
         }
 
-        let tokens = tokenize(bf_code);
+        Ok(builder
+            .grow(self.grow)
+            .wrap_pointer(self.wrap_pointer)
+            .eof(self.eof)
+            .overflow(self.overflow)
+            .engine(self.engine)
+            .max_steps(self.max_steps)
+            .timeout(self.timeout)
+            .progress_interval(self.progress_interval)
+            .framebuffer(self.framebuffer.clone())
+            .detect_cycles(self.detect_cycles)
+            .heatmap(self.heatmap)
+            .io_log(io_log)
+            .trace(trace))
+    }
+}
 
-        if tokens.is_empty() {
+const DEFAULT_PROGRESS_INTERVAL: u64 = 10_000_000;
+const MAP_USAGE: &str = "Usage: bf-repl map <file> --inputs-dir <dir> --out-dir <dir>";
+const COMPILE_USAGE: &str =
+    "Usage: bf-repl compile [--target c|llvm|rust|wasm|bfc] <file> [-o <file>]";
+
+/// Environment variables that set options, the flag each one stands for,
+/// and whether that flag takes a value. Flags given on the command line
+/// take precedence.
+const ENV_OPTIONS: [(&str, &str, bool); 12] = [
+    ("BF_REPL_QUIET", "--quiet", false),
+    ("BF_REPL_TAPE_SIZE", "--tape-size", true),
+    ("BF_REPL_SUMMARY_FORMAT", "--summary-format", true),
+    ("BF_REPL_PROGRESS_EVERY", "--progress-every", true),
+    ("BF_REPL_MAX_STEPS", "--max-steps", true),
+    ("BF_REPL_OUTPUT_MODE", "--output-mode", true),
+    ("BF_REPL_IO", "--io", true),
+    ("BF_REPL_DETECT_CYCLES", "--detect-cycles", false),
+    ("BF_REPL_COREDUMP_ON_ERROR", "--coredump-on-error", true),
+    ("BF_REPL_COREDUMP_FORMAT", "--coredump-format", true),
+    ("BF_REPL_BACKEND", "--backend", true),
+    ("BF_REPL_LANG", "--lang", true),
+];
+
+/// Translates the `BF_REPL_*` variables found by `var` into flags. Switches
+/// are on for `1`, `true`, or `yes`.
+fn env_flags(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    for (name, flag, takes_value) in ENV_OPTIONS {
+        let Some(value) = var(name) else {
             continue;
+        };
+        if takes_value {
+            flags.extend([flag.to_string(), value]);
+        } else if matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes") {
+            flags.push(flag.to_string());
         }
+    }
 
-        let jump_table = match parse_loops(&tokens) {
-            Ok(jump_table) => jump_table,
-            Err(e) => {
-                eprintln!("{}", e);
-                continue;
+    flags
+}
+
+fn parse_args(args: &[String], env_flags: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        translate_crlf: cfg!(windows),
+        ..Options::default()
+    };
+    let mut args = args.iter().peekable();
+    let mut explicit_command = true;
+
+    if args.next_if(|arg| *arg == "repl").is_some() {
+        options.command = Command::Repl;
+    } else if args.next_if(|arg| *arg == "run").is_some() {
+        options.command = Command::Run(Vec::new());
+    } else if args.next_if(|arg| *arg == "inspect").is_some() {
+        let core_path = args.next().ok_or("Usage: bf-repl inspect <core>")?;
+        options.command = Command::Inspect(core_path.clone());
+    } else if args.next_if(|arg| *arg == "annotate").is_some() {
+        let filename = args.next().ok_or("Usage: bf-repl annotate <file>")?;
+        options.command = Command::Annotate(filename.clone());
+    } else if args.next_if(|arg| *arg == "build").is_some() {
+        let filename = args.next().ok_or(
+            "Usage: bf-repl build <file> [-o <executable>] [--backend c|llvm|rust] [--keep-c]",
+        )?;
+        options.command = Command::Build(filename.clone());
+    } else if args.next_if(|arg| *arg == "compile").is_some() {
+        options.command = Command::Compile(None);
+    } else if args.next_if(|arg| *arg == "map").is_some() {
+        let filename = args.next().ok_or(MAP_USAGE)?;
+        options.command = Command::Map(filename.clone());
+    } else if args.next_if(|arg| *arg == "check").is_some() {
+        let filename = args
+            .next()
+            .ok_or("Usage: bf-repl check <file> [--analyze]")?;
+        options.command = Command::Check(filename.clone());
+    } else if args.next_if(|arg| *arg == "obfuscate").is_some() {
+        let filename = args
+            .next()
+            .ok_or("Usage: bf-repl obfuscate <file> [-o <file>] [--seed <n>]")?;
+        options.command = Command::Obfuscate(filename.clone());
+    } else if args.next_if(|arg| *arg == "gen-random").is_some() {
+        options.command = Command::GenRandom;
+    } else if args.next_if(|arg| *arg == "visualize").is_some() {
+        let filename = args
+            .next()
+            .ok_or("Usage: bf-repl visualize <file> [--speed <instructions per second>]")?;
+        options.command = Command::Visualize(filename.clone());
+    } else if args.next_if(|arg| *arg == "dap").is_some() {
+        options.command = Command::Dap;
+    } else if args.next_if(|arg| *arg == "lsp").is_some() {
+        options.command = Command::Lsp;
+    } else if args.next_if(|arg| *arg == "jupyter-kernel").is_some() {
+        options.command = match args.next() {
+            Some(arg) if arg == "--install" => Command::InstallKernel,
+            Some(connection_file) => Command::JupyterKernel(connection_file.clone()),
+            None => {
+                return Err(
+                    "Usage: bf-repl jupyter-kernel <connection-file> | --install".to_string(),
+                );
             }
         };
+    } else {
+        explicit_command = false;
+    }
 
-        match interpreter.run(&tokens, &jump_table) {
-            Ok(_) => {
-                if tokens.contains(&Token::Output) {
-                    eprintln!();
-                } else {
-                    interpreter.print_current_cell();
-                }
+    // Flags from the environment come first so the command line overrides
+    // them.
+    let mut args = env_flags.iter().chain(args);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--coredump-on-error" => {
+                let path = args.next().ok_or("Usage: --coredump-on-error <file>")?;
+                options.coredump_on_error = Some(path.clone());
+            }
+            "--coredump-format" => {
+                let name = args
+                    .next()
+                    .ok_or("Usage: --coredump-format plain|sparse|gzip")?;
+                options.coredump_format = DumpFormat::parse(name)?;
+            }
+            "--io" => {
+                let spec = args.next().ok_or("Usage: --io stdio|tcp:HOST:PORT")?;
+                options.io = ProgramIo::parse(spec)?;
+            }
+            "--tape-size" => {
+                let cells = args
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("Usage: --tape-size <cells>")?;
+                options.tape_size = Some(cells);
+            }
+            "--max-steps" => {
+                let steps = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .ok_or("Usage: --max-steps <N>")?;
+                options.max_steps = Some(steps);
+            }
+            "--timeout" => {
+                let spec = args.next().ok_or("Usage: --timeout <duration>")?;
+                options.timeout = Some(parse_duration(spec)?);
+            }
+            "--cell-size" => {
+                let bits = args.next().ok_or("Usage: --cell-size 8|16|32|bignum")?;
+                options.cell_size = CellSize::parse(bits)?;
+            }
+            "--grow" => {
+                options.grow = true;
+            }
+            "--wrap-pointer" => {
+                options.wrap_pointer = true;
+            }
+            "--eof" => {
+                let name = args.next().ok_or("Usage: --eof 0|minus-one|unchanged")?;
+                options.eof = EofBehavior::parse(name)?;
+            }
+            "--overflow" => {
+                let name = args.next().ok_or("Usage: --overflow wrap|saturate|error")?;
+                options.overflow = Overflow::parse(name)?;
+            }
+            "--engine" => {
+                let name = args.next().ok_or("Usage: --engine interp|jit")?;
+                options.engine = Engine::parse(name)?;
+            }
+            "-q" | "--quiet" => {
+                options.quiet = true;
+            }
+            "--summary-format" => {
+                let template = args.next().ok_or("Usage: --summary-format <template>")?;
+                options.summary_format = Some(SummaryFormat::parse(template)?);
+            }
+            "--progress" => {
+                options
+                    .progress_interval
+                    .get_or_insert(DEFAULT_PROGRESS_INTERVAL);
+            }
+            "--progress-every" => {
+                let steps = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .filter(|&n| n > 0)
+                    .and_then(|n| n.checked_mul(1_000_000))
+                    .ok_or("Usage: --progress-every <millions of steps>")?;
+                options.progress_interval = Some(steps);
+            }
+            "-o" | "--output" => {
+                let path = args.next().ok_or("Usage: --output <file>")?;
+                options.output_file = Some(path.clone());
+            }
+            "--output-mode" => {
+                let mode = args.next().ok_or("Usage: --output-mode text|audio")?;
+                options.output_mode = OutputMode::parse(mode)?;
+            }
+            "--framebuffer" => {
+                let spec = args
+                    .next()
+                    .ok_or("Usage: --framebuffer WIDTHxHEIGHT[@ADDRESS]")?;
+                options.framebuffer = Some(Framebuffer::parse(spec)?);
+            }
+            "--detect-cycles" => {
+                options.detect_cycles = true;
             }
-            Err(e) => {
-                eprintln!("{}", e);
+            "--seed" => {
+                let seed = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .ok_or("Usage: --seed <number>")?;
+                options.seed = Some(seed);
             }
+            "--len" => {
+                options.gen_options.len = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("Usage: --len <instructions>")?;
+            }
+            "--max-depth" => {
+                options.gen_options.max_depth = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("Usage: --max-depth <loops>")?;
+            }
+            "--mix" => {
+                let spec = args.next().ok_or("Usage: --mix \"+3 -3 >2 <2 .1 ,1 [1\"")?;
+                options.gen_options.mix = GenOptions::parse_mix(spec)?;
+            }
+            "--crlf" => {
+                options.translate_crlf = match args.next().map(String::as_str) {
+                    Some("translate") => true,
+                    Some("keep") => false,
+                    _ => return Err("Usage: --crlf translate|keep".to_string()),
+                };
+            }
+            "--input-timeout" => {
+                let spec = args.next().ok_or("Usage: --input-timeout <duration>")?;
+                options.input_timeout = Some(parse_duration(spec)?);
+            }
+            "--input-default" => {
+                let byte = args
+                    .next()
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .ok_or("Usage: --input-default <byte (0-255)>")?;
+                options.input_default = Some(byte);
+            }
+            "--histogram" => {
+                options.histogram = true;
+            }
+            "--profile" => {
+                options.profile = true;
+            }
+            "--heatmap" => {
+                options.heatmap = true;
+            }
+            "--render-gif" => {
+                let path = args.next().ok_or("Usage: --render-gif <file>")?;
+                options.render_gif = Some(path.clone());
+            }
+            "--render-svg" => {
+                let path = args.next().ok_or("Usage: --render-svg <file>")?;
+                options.render_svg = Some(path.clone());
+            }
+            "--sample-every" => {
+                let interval = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("Usage: --sample-every <steps>")?;
+                options.sample_every = Some(interval);
+            }
+            "--io-log" => {
+                let path = args.next().ok_or("Usage: --io-log <file>")?;
+                options.io_log = Some(path.clone());
+            }
+            "--debug" => {
+                options.debug = true;
+            }
+            "--tui" => {
+                options.tui = true;
+            }
+            "--speed" => {
+                let speed = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("Usage: --speed <instructions per second>")?;
+                options.speed = Some(speed);
+            }
+            "--tui-speed" => {
+                let speed = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("Usage: --tui-speed <instructions per second>")?;
+                options.tui_speed = Some(speed);
+            }
+            "--trace" => {
+                let path = args.next().ok_or("Usage: --trace <file>")?;
+                options.trace = Some(path.clone());
+            }
+            "--trace-format" => {
+                let name = args.next().ok_or("Usage: --trace-format text|jsonl")?;
+                options.trace_format = TraceFormat::parse(name)?;
+            }
+            "--trace-limit" => {
+                let limit = args
+                    .next()
+                    .and_then(|n| n.parse::<u64>().ok())
+                    .ok_or("Usage: --trace-limit <instructions>")?;
+                options.trace_limit = Some(limit);
+            }
+            "--backend" | "--target" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| format!("Usage: {} c|llvm|rust|wasm|bfc", arg))?;
+                options.backend = BackendKind::parse(name)?;
+            }
+            "--inputs-dir" => {
+                let dir = args.next().ok_or(MAP_USAGE)?;
+                options.inputs_dir = Some(dir.clone());
+            }
+            "--out-dir" => {
+                let dir = args.next().ok_or(MAP_USAGE)?;
+                options.out_dir = Some(dir.clone());
+            }
+            "--keep-c" => {
+                options.keep_c = true;
+            }
+            "--analyze" => {
+                options.analyze = true;
+            }
+            "--lang" => {
+                let name = args.next().ok_or("Usage: --lang en|ja")?;
+                options.lang = Lang::parse(name)?;
+            }
+            "--error-format" => {
+                let name = args.next().ok_or("Usage: --error-format human|json")?;
+                options.error_format = ErrorFormat::parse(name)?;
+            }
+            "--concat" => {
+                options.concat = true;
+            }
+            "--listen" => {
+                let addr = args.next().ok_or("Usage: --listen <address:port>")?;
+                options.listen = Some(addr.clone());
+            }
+            "--history-file" => {
+                let path = args.next().ok_or("Usage: --history-file <file>|off")?;
+                options.history_file = Some(path.clone());
+            }
+            "--metrics-listen" => {
+                let addr = args
+                    .next()
+                    .ok_or("Usage: --metrics-listen <address:port>")?;
+                options.metrics_listen = Some(addr.clone());
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("Unknown option {}", arg));
+            }
+            _ => match &mut options.command {
+                Command::Run(filenames) => filenames.push(arg.clone()),
+                Command::Compile(filename @ None) => *filename = Some(arg.clone()),
+                Command::Repl if !explicit_command => {
+                    options.command = Command::Run(vec![arg.clone()]);
+                }
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            },
         }
     }
 
-    Ok(())
-}
+    if matches!(&options.command, Command::Run(filenames) if filenames.is_empty()) {
+        return Err("Usage: bf-repl run <file>... [--concat]".to_string());
+    }
 
-fn run_file(filename: &str) -> Result<(), String> {
-    let bf_code =
-        fs::read_to_string(filename).map_err(|e| format!("Cannot read {}: {}", filename, e))?;
+    if matches!(options.command, Command::Map(_))
+        && (options.inputs_dir.is_none() || options.out_dir.is_none())
+    {
+        return Err(MAP_USAGE.to_string());
+    }
 
-    let tokens = tokenize(&bf_code);
+    if options.listen.is_some() && !matches!(options.command, Command::Repl) {
+        return Err("--listen can only be used with the repl command".to_string());
+    }
 
-    let jump_table = parse_loops(&tokens)?;
+    if options.history_file.is_some() && !matches!(options.command, Command::Repl) {
+        return Err("--history-file can only be used with the repl command".to_string());
+    }
 
-    let mut interpreter = Interpreter::new();
+    if options.input_timeout.is_some() && !matches!(options.command, Command::Run(_)) {
+        return Err("--input-timeout can only be used when running files".to_string());
+    }
 
-    interpreter.run(&tokens, &jump_table)?;
-    eprintln!();
+    if options.debug && !matches!(options.command, Command::Run(_)) {
+        return Err("--debug can only be used when running files".to_string());
+    }
 
-    Ok(())
+    if options.tui && !matches!(options.command, Command::Run(_)) {
+        return Err("--tui can only be used when running files".to_string());
+    }
+
+    if options.tui && options.debug {
+        return Err("--tui and --debug cannot be used together".to_string());
+    }
+
+    if options.speed.is_some() && !matches!(options.command, Command::Visualize(_)) {
+        return Err("--speed can only be used with the visualize command".to_string());
+    }
+
+    if options.tui_speed.is_some() && !options.tui {
+        return Err("--tui-speed requires --tui".to_string());
+    }
+
+    if options.render_gif.is_some() && !matches!(options.command, Command::Run(_)) {
+        return Err("--render-gif can only be used when running files".to_string());
+    }
+
+    if options.render_svg.is_some() && !matches!(options.command, Command::Run(_)) {
+        return Err("--render-svg can only be used when running files".to_string());
+    }
+
+    if options.cell_size != CellSize::Bits8 {
+        if !matches!(options.command, Command::Run(_) | Command::Compile(_)) {
+            return Err("--cell-size can only be used when running or compiling files".to_string());
+        }
+        if options.coredump_on_error.is_some() {
+            return Err("--coredump-on-error requires 8-bit cells".to_string());
+        }
+        if options.debug {
+            return Err("--debug requires 8-bit cells".to_string());
+        }
+        if options.tui {
+            return Err("--tui requires 8-bit cells".to_string());
+        }
+    }
+
+    if options.sample_every.is_some()
+        && options.render_gif.is_none()
+        && options.render_svg.is_none()
+    {
+        return Err("--sample-every requires --render-gif or --render-svg".to_string());
+    }
+
+    if options.input_default.is_some() && options.input_timeout.is_none() {
+        return Err("--input-default requires --input-timeout".to_string());
+    }
+
+    if options.grow && options.wrap_pointer {
+        return Err("--grow and --wrap-pointer cannot be used together".to_string());
+    }
+
+    if options.metrics_listen.is_some() && options.listen.is_none() {
+        return Err("--metrics-listen can only be used with --listen".to_string());
+    }
+
+    Ok(options)
+}
+
+/// Runs the command chosen on the command line.
+fn run(options: &Options) -> Result<(), String> {
+    match &options.command {
+        Command::Repl => match &options.listen {
+            Some(addr) => server::serve(
+                addr,
+                options.metrics_listen.as_deref(),
+                options.interpreter_builder()?,
+            ),
+            None => run_repl(options),
+        },
+        Command::Run(filenames) => run_files(filenames, options),
+        Command::Inspect(core_path) => inspect::run_inspector(core_path),
+        Command::Annotate(filename) => annotate::run_annotate(filename),
+        Command::Map(filename) => batch::run_map(
+            filename,
+            options.inputs_dir.as_deref().unwrap_or_default(),
+            options.out_dir.as_deref().unwrap_or_default(),
+            &options.interpreter_builder()?,
+        ),
+        Command::Check(filename) => {
+            check::run_check(filename, options.analyze, options.error_format)
+        }
+        Command::Obfuscate(filename) => {
+            obfuscate::run_obfuscate(filename, options.output_file.as_deref(), options.seed)
+        }
+        Command::Dap => dap::run_dap(&options.interpreter_builder()?),
+        Command::Lsp => lsp::run_lsp(),
+        Command::JupyterKernel(connection_file) => {
+            jupyter::run_kernel(connection_file, &options.interpreter_builder()?)
+        }
+        Command::InstallKernel => jupyter::install_kernel(),
+        Command::Visualize(filename) => visualize::run_visualize(
+            filename,
+            &options.interpreter_builder()?,
+            options.speed.unwrap_or(visualize::DEFAULT_SPEED),
+        ),
+        Command::GenRandom => generate::run_gen_random(
+            &options.gen_options,
+            options.output_file.as_deref(),
+            options.seed,
+        ),
+        Command::Build(filename) => build::run_build(
+            filename,
+            options.output_file.as_deref(),
+            options.backend,
+            options.keep_c,
+        ),
+        Command::Compile(filename) => build::run_compile(
+            filename.as_deref().ok_or(COMPILE_USAGE)?,
+            options.output_file.as_deref(),
+            options.backend,
+            &Settings {
+                tape_size: options
+                    .tape_size
+                    .unwrap_or(Interpreter::DEFAULT_MEMORY_SIZE),
+                cell_size: options.cell_size,
+                eof: options.eof,
+            },
+        ),
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let result = if args.len() > 1 {
-        run_file(&args[1])
-    } else {
-        run_repl()
+    line_editor::enable_ansi_output();
+    line_editor::install_terminal_cleanup();
+    let env_flags = env_flags(|name| env::var(name).ok());
+
+    let options = match parse_args(&args[1..], &env_flags) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     };
 
-    if let Err(e) = result {
-        eprintln!("{}", e);
+    if let Err(e) = run(&options) {
+        // An empty error was already reported as a JSON record.
+        match options.error_format {
+            ErrorFormat::Human => eprintln!("{}", options.lang.translate(&e)),
+            ErrorFormat::Json if e.is_empty() => {}
+            ErrorFormat::Json => eprintln!("{}", Diagnostic::error(e, None).to_json(&[])),
+        }
         std::process::exit(1);
     }
 }
@@ -352,78 +936,84 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use insta;
 
     #[test]
-    fn test_tokenize_basic() {
-        let code = "+-><.,[]";
-        let tokens = tokenize(code);
-
-        insta::assert_debug_snapshot!(
-            tokens,
-            @r"
-        [
-            IncrementData,
-            DecrementData,
-            IncrementPointer,
-            DecrementPointer,
-            Output,
-            Input,
-            LoopStart,
-            LoopEnd,
-        ]
-        "
-        );
+    fn test_env_flags() {
+        let flags = env_flags(|name| match name {
+            "BF_REPL_QUIET" => Some("yes".to_string()),
+            "BF_REPL_DETECT_CYCLES" => Some("0".to_string()),
+            "BF_REPL_SUMMARY_FORMAT" => Some("{dp}".to_string()),
+            _ => None,
+        });
+        let args = ["run", "a.bf", "--summary-format", "{cell}"].map(String::from);
+        let options = parse_args(&args, &flags).unwrap();
+
+        insta::assert_debug_snapshot!((flags, options.quiet, options.detect_cycles, options.summary_format), @r#"
+        (
+            [
+                "--quiet",
+                "--summary-format",
+                "{dp}",
+            ],
+            true,
+            false,
+            Some(
+                SummaryFormat {
+                    segments: [
+                        Field(
+                            Cell,
+                        ),
+                    ],
+                },
+            ),
+        )
+        "#);
     }
 
     #[test]
-    fn test_tokenize_with_comments() {
-        let code = "++ Hello World! [<]";
-        let tokens = tokenize(code);
+    fn test_progress_every() {
+        let intervals = ["2", "18446744073709551615", "0"].map(|millions| {
+            let args = ["run", "a.bf", "--progress-every", millions].map(String::from);
+            parse_args(&args, &[]).map(|options| options.progress_interval)
+        });
 
-        insta::assert_debug_snapshot!(
-            tokens,
-            @r"
+        insta::assert_debug_snapshot!(intervals, @r#"
         [
-            IncrementData,
-            IncrementData,
-            LoopStart,
-            DecrementPointer,
-            LoopEnd,
+            Ok(
+                Some(
+                    2000000,
+                ),
+            ),
+            Err(
+                "Usage: --progress-every <millions of steps>",
+            ),
+            Err(
+                "Usage: --progress-every <millions of steps>",
+            ),
         ]
-        "
-        );
-    }
-
-    #[test]
-    fn test_tokenize_empty() {
-        let code = "";
-        let tokens = tokenize(code);
-
-        insta::assert_debug_snapshot!(tokens, @"[]");
-    }
-
-    #[test]
-    fn test_parse_loops_unmatched_loop_start() {
-        let tokens = tokenize("[<>]++[");
-        let result = parse_loops(&tokens);
-
-        insta::assert_debug_snapshot!(result, @r#"
-        Err(
-            "Unmatched '[' at index 6",
-        )
         "#);
     }
 
     #[test]
-    fn test_parse_loops_unmatched_loop_end() {
-        let tokens = tokenize("[<>]++[]]");
-        let result = parse_loops(&tokens);
-
-        insta::assert_debug_snapshot!(result, @r#"
-        Err(
-            "Unmatched ']' at index 8",
-        )
+    fn test_parse_duration() {
+        insta::assert_debug_snapshot!(["2s", "1.5", "250ms", "-1s", "soon"].map(parse_duration), @r#"
+        [
+            Ok(
+                2s,
+            ),
+            Ok(
+                1.5s,
+            ),
+            Ok(
+                250ms,
+            ),
+            Err(
+                "Invalid duration '-1s' (expected e.g. 2s or 500ms)",
+            ),
+            Err(
+                "Invalid duration 'soon' (expected e.g. 2s or 500ms)",
+            ),
+        ]
         "#);
     }
 }
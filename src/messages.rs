@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+
+/// The language user-facing messages are shown in.
+///
+/// Messages are written in English throughout the code and translated when
+/// shown, by looking them up in a catalog. A `{}` in a catalog entry stands
+/// for a part of the message that varies, such as a number or file name;
+/// messages without an entry are shown in English.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+}
+
+/// English messages and their Japanese translations, with the variable
+/// parts in the same order.
+const JA: &[(&str, &str)] = &[
+    ("Brainfuck REPL", "Brainfuck REPL"),
+    (
+        "Type 'exit' to exit, or 'mem' to show memory snapshot.",
+        "'exit' で終了、'mem' でメモリの様子を表示します。",
+    ),
+    ("input> ", "入力> "),
+    (
+        "Data pointer out of bounds (left)",
+        "データポインタがテープの左端を越えました",
+    ),
+    (
+        "Data pointer out of bounds (right)",
+        "データポインタがテープの右端を越えました",
+    ),
+    (
+        "Unmatched '[' at index {}",
+        "位置 {} の '[' に対応する ']' がありません",
+    ),
+    (
+        "Unmatched ']' at index {}",
+        "位置 {} の ']' に対応する '[' がありません",
+    ),
+    (
+        "Cell overflow at index {}",
+        "位置 {} でセルの値が上限を超えました",
+    ),
+    (
+        "Cell underflow at index {}",
+        "位置 {} でセルの値が下限を下回りました",
+    ),
+    (
+        "Infinite loop detected: state repeated",
+        "無限ループを検出しました: 同じ状態に戻りました",
+    ),
+    (
+        "Warning: the loop at line {}, column {} never changes the cell it tests, so it never ends once entered",
+        "警告: {} 行 {} 列のループは判定するセルを変更しないため、一度入ると終わりません",
+    ),
+    ("Invalid address format", "アドレスの形式が正しくありません"),
+    (
+        "Address {} is out of bounds (0-{})",
+        "アドレス {} は範囲外です (0-{})",
+    ),
+    (
+        "Invalid value format or value is out of u8 range (0-255)",
+        "値の形式が正しくないか、範囲 (0-255) の外です",
+    ),
+    ("Usage: jump <address>", "使い方: jump <アドレス>"),
+    (
+        "Usage: set [<address>] <value>",
+        "使い方: set [<アドレス>] <値>",
+    ),
+    ("Cannot read {}: {}", "{} を読み込めません: {}"),
+    ("Cannot write {}: {}", "{} に書き込めません: {}"),
+    (
+        "Multiple files given; pass --concat to join them into one program",
+        "複数のファイルが指定されました。1 つのプログラムとして連結するには --concat を付けてください",
+    ),
+    ("1 problem found in {}", "{} に問題が 1 件見つかりました"),
+    ("{} problems found in {}", "問題が {} 件見つかりました ({})"),
+];
+
+impl Lang {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "en" => Ok(Self::En),
+            "ja" => Ok(Self::Ja),
+            _ => Err(format!(
+                "Unknown language '{}' (expected 'en' or 'ja')",
+                name
+            )),
+        }
+    }
+
+    /// Translates an English `message` into this language.
+    pub fn translate(self, message: &str) -> Cow<'_, str> {
+        let catalog = match self {
+            Self::En => return Cow::Borrowed(message),
+            Self::Ja => JA,
+        };
+
+        catalog
+            .iter()
+            .find_map(|&(english, translation)| {
+                let parts = match_template(english, message)?;
+                Some(Cow::Owned(fill_template(translation, &parts)))
+            })
+            .unwrap_or(Cow::Borrowed(message))
+    }
+}
+
+/// Matches `message` against `template`, returning the text standing in for
+/// each `{}`.
+fn match_template<'a>(template: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let mut pieces = template.split("{}");
+    let mut rest = message.strip_prefix(pieces.next()?)?;
+    let mut parts = Vec::new();
+    let mut pieces = pieces.peekable();
+
+    while let Some(piece) = pieces.next() {
+        let end = match pieces.peek() {
+            Some(_) => rest.find(piece)?,
+            None => rest.strip_suffix(piece)?.len(),
+        };
+        parts.push(&rest[..end]);
+        rest = &rest[end + piece.len()..];
+    }
+
+    rest.is_empty().then_some(parts)
+}
+
+fn fill_template(template: &str, parts: &[&str]) -> String {
+    let mut filled = String::new();
+    for (i, piece) in template.split("{}").enumerate() {
+        if i > 0 {
+            filled.push_str(parts.get(i - 1).copied().unwrap_or_default());
+        }
+        filled.push_str(piece);
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let messages = [
+            "Data pointer out of bounds (left)",
+            "Unmatched ']' at index 12",
+            "Cannot read a.bf: No such file or directory (os error 2)",
+            "12 problems found in a.bf",
+            "Unmatched ']' at index",
+            "Something new",
+        ];
+
+        insta::assert_debug_snapshot!(messages.map(|m| Lang::Ja.translate(m)), @r#"
+        [
+            "データポインタがテープの左端を越えました",
+            "位置 12 の ']' に対応する '[' がありません",
+            "a.bf を読み込めません: No such file or directory (os error 2)",
+            "問題が 12 件見つかりました (a.bf)",
+            "Unmatched ']' at index",
+            "Something new",
+        ]
+        "#);
+        assert!(messages.iter().all(|m| Lang::En.translate(m) == *m));
+    }
+}
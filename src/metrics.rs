@@ -0,0 +1,220 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// How long a scrape may take to send its request or read the response, so
+/// a client that stalls cannot hold up the ones after it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a program run ended, as far as the counters are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Finished,
+    /// Stopped by the interpreter's timeout.
+    TimedOut,
+    /// Ended in any other error.
+    Failed,
+}
+
+impl RunOutcome {
+    pub fn of<T>(result: &Result<T, String>) -> Self {
+        match result {
+            Ok(_) => Self::Finished,
+            Err(e) if e.starts_with("Timed out after ") => Self::TimedOut,
+            Err(_) => Self::Failed,
+        }
+    }
+}
+
+/// Counters shared by every session of a server.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    instructions: AtomicU64,
+    errors: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+impl Metrics {
+    /// Counts one program run of `steps` instructions.
+    pub fn record_run(&self, steps: u64, outcome: RunOutcome) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.instructions.fetch_add(steps, Ordering::Relaxed);
+        match outcome {
+            RunOutcome::Finished => {}
+            RunOutcome::TimedOut => {
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+            RunOutcome::Failed => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Renders the counters in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let counters = [
+            ("requests", "Programs run", &self.requests),
+            ("instructions", "Instructions executed", &self.instructions),
+            (
+                "errors",
+                "Programs that ended in an error other than a timeout",
+                &self.errors,
+            ),
+            (
+                "timeouts",
+                "Programs stopped by the timeout",
+                &self.timeouts,
+            ),
+        ];
+
+        counters
+            .iter()
+            .map(|(name, help, counter)| {
+                format!(
+                    "# HELP bf_repl_{name}_total {help}.\n# TYPE bf_repl_{name}_total counter\nbf_repl_{name}_total {}\n",
+                    counter.load(Ordering::Relaxed)
+                )
+            })
+            .collect()
+    }
+}
+
+/// Serves `metrics` over HTTP at `/metrics` on `addr` from a background
+/// thread.
+pub fn serve(addr: &str, metrics: Arc<Metrics>) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+
+    eprintln!("Serving metrics on http://{}/metrics", local_addr);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(REQUEST_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            if let Err(e) = respond(&mut BufReader::new(reader), &mut &stream, &metrics) {
+                eprintln!("Metrics request failed: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answers one HTTP request read from `request`.
+fn respond(
+    request: &mut dyn BufRead,
+    response: &mut dyn Write,
+    metrics: &Metrics,
+) -> io::Result<()> {
+    let mut request_line = String::new();
+    request.read_line(&mut request_line)?;
+
+    // Skip the headers; no request needs a body.
+    let mut header = String::new();
+    while request.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        (Some("GET"), _) => ("404 Not Found", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method not allowed\n".to_string()),
+    };
+
+    write!(
+        response,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    response.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let metrics = Metrics::default();
+        metrics.record_run(120, RunOutcome::Finished);
+        metrics.record_run(7, RunOutcome::Failed);
+        metrics.record_run(30, RunOutcome::TimedOut);
+
+        let mut response = Vec::new();
+        respond(
+            &mut &b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"[..],
+            &mut response,
+            &metrics,
+        )
+        .unwrap();
+
+        let mut not_found = Vec::new();
+        respond(
+            &mut &b"GET / HTTP/1.1\r\n\r\n"[..],
+            &mut not_found,
+            &metrics,
+        )
+        .unwrap();
+
+        insta::assert_snapshot!(
+            String::from_utf8_lossy(&response) + String::from_utf8_lossy(&not_found),
+            @r"
+        HTTP/1.1 200 OK
+        Content-Type: text/plain; version=0.0.4
+        Content-Length: 504
+        Connection: close
+
+        # HELP bf_repl_requests_total Programs run.
+        # TYPE bf_repl_requests_total counter
+        bf_repl_requests_total 3
+        # HELP bf_repl_instructions_total Instructions executed.
+        # TYPE bf_repl_instructions_total counter
+        bf_repl_instructions_total 157
+        # HELP bf_repl_errors_total Programs that ended in an error other than a timeout.
+        # TYPE bf_repl_errors_total counter
+        bf_repl_errors_total 1
+        # HELP bf_repl_timeouts_total Programs stopped by the timeout.
+        # TYPE bf_repl_timeouts_total counter
+        bf_repl_timeouts_total 1
+        HTTP/1.1 404 Not Found
+        Content-Type: text/plain; version=0.0.4
+        Content-Length: 10
+        Connection: close
+
+        Not found
+        "
+        );
+    }
+
+    #[test]
+    fn test_run_outcome() {
+        let results: [Result<(), String>; 3] = [
+            Ok(()),
+            Err("Timed out after 10ms (65536 steps executed)".to_string()),
+            Err("Step limit of 10 reached".to_string()),
+        ];
+
+        assert_eq!(
+            results.each_ref().map(RunOutcome::of),
+            [
+                RunOutcome::Finished,
+                RunOutcome::TimedOut,
+                RunOutcome::Failed
+            ]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+
+use crate::rng::Rng;
+use crate::source::read_source;
+use crate::{Token, equivalence, parse_loops, tokenize};
+
+/// Filler words; they contain no instruction characters.
+const NOISE_WORDS: [&str; 12] = [
+    "brain", "fun", "hello", "zap", "quirk", "tape", "cell", "void", "hmm", "why", "ok", "so",
+];
+/// Instructions for dead loops, which never run.
+const DEAD_CODE: [char; 6] = ['+', '-', '>', '<', '.', ','];
+const LINE_WIDTH: usize = 64;
+/// The longest a test run of the original program may take.
+const MAX_STEPS: u64 = 10_000_000;
+/// How many times more steps the obfuscated program may take; each `+` or
+/// `-` becomes at most seven, and dead loops cost one step each.
+const SLOWDOWN: u64 = 8;
+const TEST_INPUTS: [&[u8]; 3] = [b"", b"hello\n", b"42 17\n\x00\xff"];
+
+/// Writes an obfuscated version of `filename` to `output` (or stdout),
+/// after checking that it behaves like the original on a few inputs.
+pub fn run_obfuscate(
+    filename: &str,
+    output: Option<&str>,
+    seed: Option<u64>,
+) -> Result<(), String> {
+    let code = read_source(filename)?;
+    let tokens = tokenize(&code);
+    parse_loops(&tokens)?;
+
+    let seed = seed.unwrap_or_else(Rng::time_seed);
+    let obfuscated = obfuscate(&tokens, &mut Rng::new(seed));
+
+    let checked = equivalence::check(
+        &tokens,
+        &tokenize(&obfuscated),
+        &TEST_INPUTS,
+        MAX_STEPS,
+        SLOWDOWN,
+    )
+    .map_err(|e| format!("Obfuscation with seed {} changed the program: {}", seed, e))?;
+
+    match checked {
+        0 => eprintln!(
+            "Warning: not verified; the program did not finish within {} steps on any test input",
+            MAX_STEPS
+        ),
+        _ => eprintln!(
+            "Verified on {} of {} test inputs (seed {})",
+            checked,
+            TEST_INPUTS.len(),
+            seed
+        ),
+    }
+
+    match output {
+        Some(path) => {
+            fs::write(path, obfuscated).map_err(|e| format!("Cannot write {}: {}", path, e))
+        }
+        None => {
+            print!("{}", obfuscated);
+            Ok(())
+        }
+    }
+}
+
+/// Rewrites `tokens` into equivalent, harder-to-read source:
+///
+/// - runs of `+` and `-` are replaced by shuffled runs with the same net
+///   effect (cells wrap, so `+` may become 255 `-`s and vice versa);
+/// - dead loops are inserted right after `]`, where the cell is always 0;
+/// - filler words are scattered between instructions.
+pub fn obfuscate(tokens: &[Token], rng: &mut Rng) -> String {
+    let mut text = Text::default();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if rng.chance(1, 4) {
+            let word = *rng.choose(&NOISE_WORDS);
+            text.push_word(word);
+        }
+
+        let run = tokens[i..]
+            .iter()
+            .take_while(|&&token| matches!(token, Token::IncrementData | Token::DecrementData))
+            .count();
+        if run > 0 {
+            let delta = tokens[i..i + run].iter().fold(0u8, |delta, &token| {
+                if token == Token::IncrementData {
+                    delta.wrapping_add(1)
+                } else {
+                    delta.wrapping_sub(1)
+                }
+            });
+            text.push_code(&scramble_delta(delta, run, rng));
+            i += run;
+            continue;
+        }
+
+        text.push_code(&tokens[i].symbol().to_string());
+        if tokens[i] == Token::LoopEnd && rng.chance(1, 3) {
+            text.push_code(&dead_loop(rng));
+        }
+        i += 1;
+    }
+
+    text.finish()
+}
+
+/// Returns a shuffled run of `+` and `-` that adds `delta` to a cell,
+/// roughly as long as the `original_len` instructions it replaces.
+fn scramble_delta(delta: u8, original_len: usize, rng: &mut Rng) -> String {
+    // Take the shorter way around unless the original went the long way.
+    let (symbol, count) = if delta <= 128 || original_len > 128 {
+        ('+', delta as usize)
+    } else {
+        ('-', 256 - delta as usize)
+    };
+    let (opposite, pairs) = (if symbol == '+' { '-' } else { '+' }, rng.below(4));
+
+    let mut run: Vec<char> = std::iter::repeat_n(symbol, count + pairs)
+        .chain(std::iter::repeat_n(opposite, pairs))
+        .collect();
+    rng.shuffle(&mut run);
+    run.into_iter().collect()
+}
+
+/// Returns a loop whose body is random, balanced code.
+fn dead_loop(rng: &mut Rng) -> String {
+    let mut body: String = (0..2 + rng.below(6))
+        .map(|_| *rng.choose(&DEAD_CODE))
+        .collect();
+    if rng.chance(1, 2) {
+        let split = rng.below(body.len() + 1);
+        body.insert_str(split, &format!("[{}]", rng.choose(&DEAD_CODE)));
+    }
+    format!("[{}]", body)
+}
+
+/// Source text wrapped at [`LINE_WIDTH`].
+#[derive(Default)]
+struct Text {
+    lines: Vec<String>,
+    line: String,
+}
+
+impl Text {
+    fn push_code(&mut self, code: &str) {
+        for c in code.chars() {
+            if self.line.len() >= LINE_WIDTH {
+                self.break_line();
+            }
+            self.line.push(c);
+        }
+    }
+
+    fn push_word(&mut self, word: &str) {
+        if self.line.len() + word.len() + 2 > LINE_WIDTH {
+            self.break_line();
+        }
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+        self.line.push(' ');
+    }
+
+    fn break_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.lines.push(line.trim_end().to_string());
+    }
+
+    fn finish(mut self) -> String {
+        self.break_line();
+        self.lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obfuscate() {
+        let code = "++++++++[>++++[>++>+++<<-]>+<<-]>>.>+.-----------.";
+        let tokens = tokenize(code);
+        let obfuscated = obfuscate(&tokens, &mut Rng::new(7));
+        let checked = equivalence::check(
+            &tokens,
+            &tokenize(&obfuscated),
+            &TEST_INPUTS,
+            MAX_STEPS,
+            SLOWDOWN,
+        );
+
+        insta::assert_snapshot!(format!("{:?}\n{}", checked, obfuscated), @r"
+        Ok(3)
+        ++++++++[> cell ++++[> void ++-+>++---++++ why <<+-- cell ]>-+-+
+        +<<-+-+-] why >>.>+--++ tape .-----------.
+        ");
+    }
+}
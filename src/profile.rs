@@ -0,0 +1,186 @@
+use std::cmp::Reverse;
+
+use crate::{JumpTable, Token};
+
+/// How many loops the report lists.
+const HOT_LOOPS: usize = 5;
+/// How many instructions of a loop the report shows before cutting it off.
+const SNIPPET_WIDTH: usize = 30;
+
+/// How many times each instruction of a program was executed in a run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Profile {
+    counts: Vec<u64>,
+}
+
+impl Profile {
+    /// Counts one execution of the instruction at `token_index`.
+    pub fn record(&mut self, token_index: usize) {
+        if token_index >= self.counts.len() {
+            self.counts.resize(token_index + 1, 0);
+        }
+        self.counts[token_index] += 1;
+    }
+
+    /// Returns the number of times the instruction at `token_index` was
+    /// executed.
+    pub fn count(&self, token_index: usize) -> u64 {
+        self.counts.get(token_index).copied().unwrap_or(0)
+    }
+
+    /// Renders a report on the run of `tokens`, read from `code`: the loops
+    /// that executed the most instructions, then `code` with each line
+    /// followed by the counts of its instructions. Each count starts under
+    /// the first of a group of neighbouring instructions that ran equally
+    /// often, moving to a row of its own when it would touch the count
+    /// before it.
+    pub fn render(&self, code: &str, tokens: &[Token], jump_table: &JumpTable) -> String {
+        let positions = token_positions(code, tokens);
+        let mut report = String::new();
+
+        let mut loops: Vec<(u64, u64, usize, usize)> = tokens
+            .iter()
+            .enumerate()
+            .filter(|&(_, &token)| token == Token::LoopStart)
+            .map(|(start, _)| {
+                let end = jump_table[start];
+                let steps = (start..=end).map(|index| self.count(index)).sum();
+                (steps, self.count(end), start, end)
+            })
+            .filter(|&(steps, ..)| steps > 0)
+            .collect();
+        loops.sort_by_key(|&(steps, _, start, _)| (Reverse(steps), start));
+        loops.truncate(HOT_LOOPS);
+
+        if !loops.is_empty() {
+            let locations: Vec<String> = loops
+                .iter()
+                .map(|&(_, _, start, _)| {
+                    let (line, column) = positions[start];
+                    format!("{}:{}", line + 1, column + 1)
+                })
+                .collect();
+            let steps_width = loops[0].0.to_string().len().max("steps".len());
+            let iterations_width = loops
+                .iter()
+                .map(|&(_, iterations, ..)| iterations.to_string().len())
+                .chain(["iterations".len()])
+                .max()
+                .unwrap_or(0);
+            let location_width = locations
+                .iter()
+                .map(String::len)
+                .chain(["at".len()])
+                .max()
+                .unwrap_or(0);
+
+            report.push_str(&format!(
+                "{:>steps_width$}  {:>iterations_width$}  {:<location_width$}  hot loop\n",
+                "steps", "iterations", "at"
+            ));
+            for (&(steps, iterations, start, end), location) in loops.iter().zip(&locations) {
+                let mut snippet: String = tokens[start..=end]
+                    .iter()
+                    .take(SNIPPET_WIDTH)
+                    .map(|token| token.symbol())
+                    .collect();
+                if end - start + 1 > SNIPPET_WIDTH {
+                    snippet.push_str("...");
+                }
+                report.push_str(&format!(
+                    "{:>steps_width$}  {:>iterations_width$}  {:<location_width$}  {}\n",
+                    steps, iterations, location, snippet
+                ));
+            }
+            report.push('\n');
+        }
+
+        let mut groups: Vec<Vec<(usize, u64)>> = vec![Vec::new(); code.split('\n').count()];
+        for (index, &(line, column)) in positions.iter().enumerate() {
+            let count = self.count(index);
+            let groups = &mut groups[line];
+            if groups.last().is_none_or(|&(_, last)| last != count) {
+                groups.push((column, count));
+            }
+        }
+
+        for (line, groups) in code.split('\n').zip(groups) {
+            report.push_str(line.trim_end());
+            report.push('\n');
+
+            let mut rows: Vec<String> = Vec::new();
+            for (column, count) in groups {
+                let row = match rows
+                    .iter_mut()
+                    .find(|row| row.is_empty() || row.chars().count() < column)
+                {
+                    Some(row) => row,
+                    None => {
+                        rows.push(String::new());
+                        rows.last_mut().unwrap()
+                    }
+                };
+                let padding = column - row.chars().count().min(column);
+                row.push_str(&" ".repeat(padding));
+                row.push_str(&count.to_string());
+            }
+            for row in rows {
+                report.push_str(&row);
+                report.push('\n');
+            }
+        }
+
+        // A trailing newline in `code` leaves an empty last line.
+        if code.ends_with('\n') {
+            report.pop();
+        }
+        report
+    }
+}
+
+/// Returns the line and column, both counted from 0, of each of `tokens` in
+/// `code`.
+fn token_positions(code: &str, tokens: &[Token]) -> Vec<(usize, usize)> {
+    let mut positions = Vec::with_capacity(tokens.len());
+    for (line, text) in code.split('\n').enumerate() {
+        for (column, c) in text.chars().enumerate() {
+            if tokens.get(positions.len()).map(|token| token.symbol()) == Some(c) {
+                positions.push((line, column));
+            }
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, parse_loops, tokenize};
+
+    #[test]
+    fn test_render_profile() {
+        let code = "Print A\n++++++++[>++++++++<-]>+.\n+[-]-[+[-]>+++[-]<]\n";
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+
+        let mut interpreter = Interpreter::builder().profile(true).build();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b""[..], Vec::new())
+            .unwrap();
+
+        insta::assert_snapshot!(interpreter.profile().unwrap().render(code, &tokens, &jump_table), @r"
+        steps  iterations  at    hot loop
+          133          66  3:2   [-]
+           97           8  2:9   [>++++++++<-]
+           16           1  3:6   [+[-]>+++[-]<]
+            7           3  3:15  [-]
+            1           0  3:8   [-]
+
+        Print A
+        ++++++++[>++++++++<-]>+.
+        1        8           1
+        +[-]-[+[-]>+++[-]<]
+        1 66    0 1    3 1
+            1
+        ");
+    }
+}
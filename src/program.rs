@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use crate::{Interpreter, JumpTable, Token, parse_loops, tokenize_with};
+
+/// What a [`Program`] is doing after a slice of execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// The fuel ran out; call `run_for` again to continue.
+    Running,
+    /// The next instruction is `,` and no input is queued.
+    NeedsInput,
+    /// The program has finished.
+    Halted,
+}
+
+/// A program loaded into an interpreter and executed in time slices.
+///
+/// Input is queued with [`Program::push_input`] and output is collected for
+/// the host to drain with [`Program::take_output`], so execution never blocks.
+pub struct Program {
+    interpreter: Interpreter,
+    tokens: Vec<Token>,
+    jump_table: JumpTable,
+    input: VecDeque<u8>,
+    input_closed: bool,
+    output: Vec<u8>,
+}
+
+impl Program {
+    pub fn new(code: &str) -> Result<Self, String> {
+        Self::with_interpreter(code, Interpreter::new())
+    }
+
+    pub fn with_interpreter(code: &str, mut interpreter: Interpreter) -> Result<Self, String> {
+        let tokens = tokenize_with(code, interpreter.extensions());
+        let jump_table = parse_loops(&tokens)?;
+        interpreter.instruction_pointer = 0;
+        interpreter.steps = 0;
+
+        Ok(Self {
+            interpreter,
+            tokens,
+            jump_table,
+            input: VecDeque::new(),
+            input_closed: false,
+            output: Vec::new(),
+        })
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        self.input.extend(bytes);
+    }
+
+    /// Marks the end of input; once the queue is drained, `,` sees EOF.
+    pub fn close_input(&mut self) {
+        self.input_closed = true;
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn state(&self) -> RunState {
+        match self.tokens.get(self.interpreter.instruction_pointer) {
+            None => RunState::Halted,
+            Some(Token::Input) if self.input.is_empty() && !self.input_closed => {
+                RunState::NeedsInput
+            }
+            Some(_) => RunState::Running,
+        }
+    }
+
+    /// Executes at most `fuel` instructions.
+    pub fn run_for(&mut self, fuel: u64) -> Result<RunState, String> {
+        for _ in 0..fuel {
+            match self.state() {
+                RunState::Running => self.interpreter.step(
+                    &self.tokens,
+                    &self.jump_table,
+                    &mut self.input,
+                    &mut self.output,
+                )?,
+                state => return Ok(state),
+            }
+        }
+
+        Ok(self.state())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_for_time_slices() {
+        let mut program = Program::new(",[.,]").unwrap();
+        let mut states = vec![program.run_for(10)];
+
+        program.push_input(b"hi");
+        states.push(program.run_for(3));
+        states.push(program.run_for(100));
+        let output = String::from_utf8(program.take_output()).unwrap();
+
+        program.close_input();
+        states.push(program.run_for(100));
+
+        insta::assert_debug_snapshot!((states, output), @r#"
+        (
+            [
+                Ok(
+                    NeedsInput,
+                ),
+                Ok(
+                    Running,
+                ),
+                Ok(
+                    NeedsInput,
+                ),
+                Ok(
+                    Halted,
+                ),
+            ],
+            "hi",
+        )
+        "#);
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+pub type Streams = (Box<dyn Read + Send>, Box<dyn Write>);
+
+/// Where `,` reads from and `.` writes to.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum ProgramIo {
+    #[default]
+    Stdio,
+    Tcp(String),
+}
+
+impl ProgramIo {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once(':') {
+            _ if spec == "stdio" => Ok(Self::Stdio),
+            Some(("tcp", addr)) if addr.contains(':') => Ok(Self::Tcp(addr.to_string())),
+            _ => Err(format!(
+                "Invalid I/O specification '{}' (expected 'stdio' or 'tcp:HOST:PORT')",
+                spec
+            )),
+        }
+    }
+
+    pub fn open(&self) -> Result<Streams, String> {
+        match self {
+            Self::Stdio => Ok((Box::new(io::stdin()), Box::new(io::stdout()))),
+            Self::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)
+                    .map_err(|e| format!("Cannot connect to {}: {}", addr, e))?;
+                let input = stream.try_clone().map_err(|e| e.to_string())?;
+                Ok((Box::new(BufReader::new(input)), Box::new(stream)))
+            }
+        }
+    }
+}
+
+/// Wraps `input` so that a read waiting longer than `timeout` yields
+/// `default` instead of blocking forever.
+///
+/// `input` is read on a background thread, which stays blocked after the
+/// program ends if no more input arrives.
+pub fn with_timeout(input: Box<dyn Read + Send>, timeout: Duration, default: u8) -> Box<dyn Read> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for byte in BufReader::new(input).bytes() {
+            if sender.send(byte).is_err() {
+                break;
+            }
+        }
+    });
+
+    Box::new(TimedInput {
+        receiver,
+        timeout,
+        default,
+    })
+}
+
+struct TimedInput {
+    receiver: Receiver<io::Result<u8>>,
+    timeout: Duration,
+    default: u8,
+}
+
+impl Read for TimedInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.receiver.recv_timeout(self.timeout) {
+            Ok(byte) => buf[0] = byte?,
+            Err(RecvTimeoutError::Timeout) => buf[0] = self.default,
+            Err(RecvTimeoutError::Disconnected) => return Ok(0),
+        }
+        Ok(1)
+    }
+}
+
+/// Reads `\r\n` from `inner` as a single `\n`, as typed on Windows
+/// consoles and found in files saved there. Other bytes pass through.
+pub struct CrlfToLf<R> {
+    inner: R,
+    pending: Option<u8>,
+}
+
+impl<R: Read> CrlfToLf<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: None,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0];
+        Ok((self.inner.read(&mut byte)? == 1).then_some(byte[0]))
+    }
+}
+
+impl<R: Read> Read for CrlfToLf<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = match self.next_byte()? {
+            None => return Ok(0),
+            Some(b'\r') => match self.next_byte()? {
+                Some(b'\n') => b'\n',
+                next => {
+                    self.pending = next;
+                    b'\r'
+                }
+            },
+            Some(byte) => byte,
+        };
+        Ok(1)
+    }
+}
+
+/// How bytes written by `.` are presented.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
+    Text,
+    #[cfg(feature = "audio")]
+    Audio,
+}
+
+impl OutputMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "text" => Ok(Self::Text),
+            #[cfg(feature = "audio")]
+            "audio" => Ok(Self::Audio),
+            #[cfg(not(feature = "audio"))]
+            "audio" => Err("Audio output requires building with --features audio".to_string()),
+            _ => Err(format!(
+                "Invalid output mode '{}' (expected 'text' or 'audio')",
+                mode
+            )),
+        }
+    }
+
+    /// Opens the writer for this mode, or `None` to keep the default output.
+    pub fn open(self, path: Option<&str>) -> Result<Option<Box<dyn Write>>, String> {
+        match (self, path) {
+            (Self::Text, None) => Ok(None),
+            (Self::Text, Some(path)) => create_output_file(path).map(Some),
+            #[cfg(feature = "audio")]
+            (Self::Audio, path) => crate::audio::tone_writer(path).map(Some),
+        }
+    }
+}
+
+/// Creates (or truncates) `path` for program output, written byte for byte.
+pub fn create_output_file(path: &str) -> Result<Box<dyn Write>, String> {
+    let file = File::create(path).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_program_io() {
+        insta::assert_debug_snapshot!(
+            ["stdio", "tcp:localhost:4000", "tcp:4000", "udp:localhost:4000"].map(ProgramIo::parse),
+            @r#"
+        [
+            Ok(
+                Stdio,
+            ),
+            Ok(
+                Tcp(
+                    "localhost:4000",
+                ),
+            ),
+            Err(
+                "Invalid I/O specification 'tcp:4000' (expected 'stdio' or 'tcp:HOST:PORT')",
+            ),
+            Err(
+                "Invalid I/O specification 'udp:localhost:4000' (expected 'stdio' or 'tcp:HOST:PORT')",
+            ),
+        ]
+        "#
+        );
+    }
+
+    #[test]
+    fn test_crlf_to_lf() {
+        let mut translated = Vec::new();
+        CrlfToLf::new(&b"a\r\nb\rc\r"[..])
+            .read_to_end(&mut translated)
+            .unwrap();
+
+        assert_eq!(translated, b"a\nb\rc\r");
+    }
+
+    #[test]
+    fn test_with_timeout() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        writer.write_all(b"a").unwrap();
+        let mut input = with_timeout(Box::new(reader), Duration::from_millis(50), b'?');
+
+        let mut reads = Vec::new();
+        let mut byte = [0];
+        for _ in 0..2 {
+            input.read_exact(&mut byte).unwrap();
+            reads.push(byte[0]);
+        }
+        drop(writer);
+        let at_end = input.read(&mut byte).unwrap();
+
+        assert_eq!((reads, at_end), (b"a?".to_vec(), 0));
+    }
+}
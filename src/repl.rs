@@ -0,0 +1,2332 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::analysis::loop_warnings;
+use crate::ascii;
+use crate::cell_format::CellFormat;
+use crate::clipboard;
+use crate::commands;
+use crate::completion;
+use crate::debugger::{Debugger, Stop};
+use crate::display::DisplayExpr;
+use crate::hexdump;
+use crate::histogram::Histogram;
+use crate::interrupt;
+use crate::line_editor::{LineEditor, bracket_depth};
+use crate::messages::Lang;
+use crate::metrics::{Metrics, RunOutcome};
+use crate::program_io::{self, CrlfToLf};
+use crate::search::Search;
+use crate::source::read_source;
+use crate::summary::SummaryFormat;
+use crate::{Interpreter, InterpreterBuilder, Token, parse_loops, tokenize_with};
+
+pub(crate) fn parse_address(addr_str: &str, memory_size: usize) -> Result<usize, String> {
+    match addr_str.parse::<usize>() {
+        Ok(addr) if addr < memory_size => Ok(addr),
+        Ok(addr) => Err(format!(
+            "Address {} is out of bounds (0-{})",
+            addr,
+            memory_size - 1
+        )),
+        Err(_) => Err("Invalid address format".to_string()),
+    }
+}
+
+/// Parses `A..B`, the cells from `A` up to but excluding `B`.
+pub(crate) fn parse_range(range: &str, memory_size: usize) -> Result<Range<usize>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| format!("Invalid range '{}' (expected A..B)", range))?;
+    let start = parse_address(start, memory_size)?;
+    let end = parse_address(end, memory_size + 1)?;
+    if end < start {
+        return Err(format!("Invalid range {}..{}", start, end));
+    }
+    Ok(start..end)
+}
+
+/// How many cells on each side `mem` shows.
+const MEMORY_WINDOW: usize = 5;
+/// How many bytes `dump` shows unless told otherwise.
+const DEFAULT_DUMP_LENGTH: usize = 64;
+/// How many changes `undo` can take back.
+const UNDO_LIMIT: usize = 20;
+/// The commands that can change the tape or the data pointer, and so can be
+/// undone.
+const UNDOABLE_COMMANDS: [&str; 9] = [
+    "jump", "set", "fill", "clear", "load", "rerun", "run", "reset", "rollback",
+];
+const MAX_DIFF_CELLS: usize = 20;
+const MAX_FIND_RESULTS: usize = 50;
+const DEFAULT_SESSION: &str = "main";
+/// The commands a restricted session refuses: ones that touch files or the
+/// clipboard, and `debug`, whose runs have no step limit.
+const RESTRICTED_COMMANDS: [&str; 6] = [
+    "load",
+    "save",
+    "output",
+    "copy-output",
+    "copy-source",
+    "debug",
+];
+/// How many sessions, checkpoints, and slots a restricted session may keep.
+const MAX_RESTRICTED_ENTRIES: usize = 16;
+/// How many lines of history a restricted session keeps.
+const MAX_RESTRICTED_HISTORY: usize = 1000;
+
+/// The tape and data pointer as they were before a line ran, or when a
+/// checkpoint was saved.
+#[derive(Clone)]
+struct Snapshot {
+    memory: Vec<u8>,
+    data_pointer: usize,
+    /// How many lines `save` would have written.
+    program_len: usize,
+}
+
+/// An interactive session reading commands from `input`.
+///
+/// Program output goes to `output`; prompts and diagnostics go to `console`.
+/// Programs read from `input` too unless a separate program input is given.
+pub struct Repl {
+    builder: InterpreterBuilder,
+    interpreter: Interpreter,
+    session: String,
+    other_sessions: BTreeMap<String, Interpreter>,
+    input: Box<dyn BufRead>,
+    editor: Option<LineEditor>,
+    program_input: Option<Box<dyn Read>>,
+    output: Box<dyn Write>,
+    redirected_output: Option<(String, Box<dyn Write>)>,
+    console: Box<dyn Write>,
+    quiet: bool,
+    summary_format: Option<SummaryFormat>,
+    displays: BTreeMap<usize, DisplayExpr>,
+    next_display: usize,
+    last_source: String,
+    /// Every line of code run in this REPL, oldest first.
+    history: Vec<String>,
+    /// The lines of code that ran without an error since the tape was last
+    /// reset, which `save` writes out as a program.
+    program: Vec<String>,
+    /// A line from `history` to offer for editing at the next prompt.
+    pending_edit: Option<String>,
+    last_output: Vec<u8>,
+    metrics: Option<Arc<Metrics>>,
+    input_prompt: bool,
+    translate_crlf: bool,
+    /// Whether Ctrl-C stops the running program rather than the process.
+    catch_interrupts: bool,
+    /// Whether commands that reach outside the session are refused.
+    restricted: bool,
+    /// The program loaded with `debug`, if any.
+    debugger: Option<Debugger>,
+    /// How `mem` shows cell values unless told otherwise.
+    cell_format: CellFormat,
+    /// The state before each of the last lines that changed it, newest
+    /// last.
+    undo_stack: Vec<Snapshot>,
+    /// States saved with `checkpoint` in this session, by name.
+    checkpoints: BTreeMap<String, Snapshot>,
+    /// The checkpoints of the sessions in `other_sessions`.
+    other_checkpoints: BTreeMap<String, BTreeMap<String, Snapshot>>,
+    /// Programs saved with `store`, by slot number.
+    slots: BTreeMap<usize, String>,
+    lang: Lang,
+}
+
+impl Repl {
+    pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>, console: Box<dyn Write>) -> Self {
+        Self {
+            builder: InterpreterBuilder::default(),
+            interpreter: Interpreter::new(),
+            session: DEFAULT_SESSION.to_string(),
+            other_sessions: BTreeMap::new(),
+            input,
+            editor: None,
+            program_input: None,
+            output,
+            redirected_output: None,
+            console,
+            quiet: false,
+            summary_format: None,
+            displays: BTreeMap::new(),
+            next_display: 1,
+            last_source: String::new(),
+            history: Vec::new(),
+            program: Vec::new(),
+            pending_edit: None,
+            last_output: Vec::new(),
+            metrics: None,
+            input_prompt: false,
+            translate_crlf: false,
+            catch_interrupts: false,
+            restricted: false,
+            debugger: None,
+            cell_format: CellFormat::default(),
+            undo_stack: Vec::new(),
+            checkpoints: BTreeMap::new(),
+            other_checkpoints: BTreeMap::new(),
+            slots: BTreeMap::new(),
+            lang: Lang::default(),
+        }
+    }
+
+    /// Uses `builder` for this session's interpreter and any created later.
+    pub fn with_interpreter(mut self, builder: InterpreterBuilder) -> Self {
+        self.interpreter = builder.build();
+        self.builder = builder;
+        self
+    }
+
+    pub fn with_program_io(mut self, input: Box<dyn Read>, output: Box<dyn Write>) -> Self {
+        self.program_input = Some(input);
+        self.output = output;
+        self
+    }
+
+    /// Sends program output to `output` while programs keep reading from
+    /// the REPL's input.
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Counts this session's runs in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Suppresses the banner and the cell summary printed after each run.
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Prints `input> ` whenever a program waits for a new line of input from
+    /// the REPL's own input.
+    pub fn set_input_prompt(&mut self, input_prompt: bool) {
+        self.input_prompt = input_prompt;
+    }
+
+    /// Makes programs read `\r\n` as `\n`.
+    pub fn set_translate_crlf(&mut self, translate_crlf: bool) {
+        self.translate_crlf = translate_crlf;
+    }
+
+    /// Refuses commands that read or write files or the clipboard, and
+    /// changes to the step limit, for sessions run on behalf of others.
+    /// Such sessions also keep a bounded number of sessions, checkpoints,
+    /// slots, and history lines.
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+    }
+
+    /// Shows the banner, prompts, and error messages in `lang`.
+    pub fn set_lang(&mut self, lang: Lang) {
+        self.lang = lang;
+    }
+
+    /// Loads the line editor's history from `path` and saves entered lines
+    /// to it. Does nothing without a terminal.
+    pub fn set_history_file(&mut self, path: PathBuf) {
+        if let Some(editor) = &mut self.editor {
+            editor.set_history_file(path);
+        }
+    }
+
+    /// Replaces the cell summary printed after each run with `summary_format`.
+    pub fn set_summary_format(&mut self, summary_format: Option<SummaryFormat>) {
+        self.summary_format = summary_format;
+    }
+
+    pub fn stdio() -> Self {
+        let mut repl = Self::new(
+            Box::new(io::stdin().lock()),
+            Box::new(io::stdout()),
+            Box::new(io::stderr()),
+        );
+        repl.editor = LineEditor::for_terminal().map(|mut editor| {
+            editor.set_completer(completion::complete);
+            editor
+        });
+        repl.input_prompt = repl.editor.is_some();
+        repl.catch_interrupts = true;
+        repl
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        if !self.quiet {
+            writeln!(self.console, "{}", self.lang.translate("Brainfuck REPL"))?;
+            writeln!(
+                self.console,
+                "{}",
+                self.lang
+                    .translate("Type 'exit' to exit, or 'mem' to show memory snapshot.")
+            )?;
+        }
+
+        while let Some(input) = self.read_line("> ")? {
+            let input = self.read_continuation(input.trim())?;
+            let bf_code = input.as_str();
+
+            if bf_code.is_empty() {
+                continue;
+            }
+
+            if !self.eval(bf_code)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `code` until it reaches a `#` breakpoint, then takes commands
+    /// at a `debug> ` prompt until the program finishes or the user exits.
+    pub fn run_debugger(&mut self, code: &str) -> io::Result<()> {
+        match Debugger::load(code, &self.interpreter) {
+            Ok(debugger) if debugger.at_breakpoint() => {
+                writeln!(self.console, "Breakpoint")?;
+                debugger.write_location(&mut self.console, &self.interpreter)?;
+                self.debugger = Some(debugger);
+            }
+            Ok(debugger) => {
+                self.debugger = Some(debugger);
+                self.eval_debugger("continue")?;
+            }
+            Err(e) => return writeln!(self.console, "{}", self.lang.translate(&e)),
+        }
+
+        while self.debugger.is_some()
+            && let Some(input) = self.read_line("debug> ")?
+        {
+            let command = input.trim();
+            if !command.is_empty() && !self.eval(command)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keeps reading lines at a `... ` prompt while `first` and the lines
+    /// after it have more `[` than `]`, and returns them joined.
+    fn read_continuation(&mut self, first: &str) -> io::Result<String> {
+        let mut code = first.to_string();
+        while !code.starts_with(':') && bracket_depth(&code) > 0 {
+            let Some(line) = self.read_line("... ")? else {
+                break;
+            };
+            code.push('\n');
+            code.push_str(line.trim());
+        }
+        Ok(code)
+    }
+
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if let Some(editor) = &mut self.editor {
+            return match self.pending_edit.take() {
+                Some(initial) => editor.edit_line(prompt, &initial),
+                None => editor.read_line(prompt),
+            };
+        }
+
+        write!(self.console, "{}", prompt)?;
+        self.console.flush()?;
+
+        let mut input = String::new();
+
+        if self.input.read_line(&mut input)? == 0 {
+            writeln!(self.console)?;
+            return Ok(None);
+        }
+
+        Ok(Some(input))
+    }
+
+    /// Handles one line of input, returning `false` when the session should end.
+    fn eval(&mut self, bf_code: &str) -> io::Result<bool> {
+        let invocation = match commands::parse(bf_code) {
+            Ok(Some(invocation)) => invocation,
+            Ok(None) => {
+                let before = self.snapshot();
+                self.run_code(bf_code)?;
+                self.record_undo(before);
+                return Ok(true);
+            }
+            Err(e) => {
+                writeln!(self.console, "{}", self.lang.translate(&e))?;
+                return Ok(true);
+            }
+        };
+        let args = invocation.args.as_slice();
+        let usage = invocation.command.usage();
+        if self.restricted && RESTRICTED_COMMANDS.contains(&invocation.command.name) {
+            writeln!(
+                self.console,
+                "'{}' is not available in this session",
+                invocation.command.name
+            )?;
+            return Ok(true);
+        }
+        let before = UNDOABLE_COMMANDS
+            .contains(&invocation.command.name)
+            .then(|| self.snapshot());
+
+        match invocation.command.name {
+            "exit" => {
+                return Ok(false);
+            }
+            "help" => self.eval_help(args)?,
+            "mem" => self.eval_mem(args, &usage)?,
+            "dump" => self.eval_dump(args, &usage)?,
+            "fill" => match args {
+                [start, end, value] => self.fill(start, end, Some(value))?,
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "clear" => match args {
+                [start, end] => self.fill(start, end, None)?,
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "show" => {
+                if let Some(addr_str) = args.first() {
+                    match parse_address(addr_str, self.interpreter.memory_size()) {
+                        Ok(addr) => self.interpreter.write_cell(&mut self.console, addr)?,
+                        Err(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+                    }
+                } else {
+                    self.interpreter.write_current_cell(&mut self.console)?;
+                }
+            }
+            "jump" => {
+                if let Some(addr_str) = args.first() {
+                    match parse_address(addr_str, self.interpreter.memory_size()) {
+                        Ok(addr) => {
+                            self.interpreter.data_pointer = addr;
+                            self.interpreter.write_current_cell(&mut self.console)?;
+                        }
+                        Err(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+                    }
+                } else {
+                    writeln!(self.console, "{}", self.lang.translate(&usage))?;
+                }
+            }
+            "set" if args.first() == Some(&"max-steps") => self.eval_max_steps(&args[1..])?,
+            "set" if args.first() == Some(&"display") => self.eval_cell_format(&args[1..])?,
+            "set" => self.eval_set(args, &usage)?,
+            "load" => match args {
+                [path] => match read_source(path) {
+                    Ok(code) => self.run_code(&code)?,
+                    Err(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+                },
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "reset" => match args {
+                [] => self.reset(false)?,
+                ["all"] => self.reset(true)?,
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "session" => self.eval_session(args)?,
+            "output" => self.eval_output(args)?,
+            "display" => self.eval_display(invocation.rest)?,
+            "undisplay" => self.eval_undisplay(args)?,
+            "ascii" => match args.first() {
+                Some(arg) => match ascii::lookup(arg) {
+                    Ok(description) => writeln!(self.console, "{}", description)?,
+                    Err(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+                },
+                None => write!(self.console, "{}", ascii::table())?,
+            },
+            "histogram" => {
+                let code = invocation.rest.unwrap_or(&self.last_source);
+                let tokens = tokenize_with(code, self.interpreter.extensions());
+                write!(self.console, "{}", Histogram::from_tokens(&tokens).render())?;
+            }
+            "heatmap" => self.eval_heatmap(args)?,
+            "find" => match invocation.rest {
+                Some(query) => self.eval_find(query)?,
+                None => writeln!(self.console, "{}", usage)?,
+            },
+            "copy-output" => {
+                let output = std::mem::take(&mut self.last_output);
+                self.copy_to_clipboard(&output, "output")?;
+                self.last_output = output;
+            }
+            "copy-source" => {
+                let source = std::mem::take(&mut self.last_source);
+                self.copy_to_clipboard(source.as_bytes(), "source")?;
+                self.last_source = source;
+            }
+            "history" => {
+                for (number, code) in self.history.iter().enumerate() {
+                    writeln!(self.console, "{:>4}  {}", number + 1, code)?;
+                }
+            }
+            "rerun" => {
+                if let Some(code) = self.history_entry(args.first(), "rerun")? {
+                    writeln!(self.console, "{}", code)?;
+                    self.run_code(&code)?;
+                }
+            }
+            "edit" => {
+                if let Some(code) = self.history_entry(args.first(), "edit")? {
+                    if self.editor.is_some() {
+                        self.pending_edit = Some(code);
+                    } else {
+                        // Without a line editor, show it for copying instead.
+                        writeln!(self.console, "{}", code)?;
+                    }
+                }
+            }
+            "save" => match args {
+                [path] => self.save(path)?,
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "dry" => match invocation.rest {
+                Some(code) => self.dry_run(code)?,
+                None => writeln!(self.console, "{}", usage)?,
+            },
+            "debug" => match invocation.rest {
+                Some(code) => self.eval_debug(code)?,
+                None => writeln!(self.console, "{}", usage)?,
+            },
+            name @ ("step" | "next" | "continue" | "where") => self.eval_debugger(name)?,
+            "break" => self.eval_break(args)?,
+            "watch" => self.eval_watch(args)?,
+            "back" => self.eval_back(args)?,
+            "undo" => self.undo()?,
+            "store" => match args.first().map(|slot| slot.parse()) {
+                Some(Ok(slot)) if args.len() == 1 => {
+                    if self.last_source.is_empty() {
+                        writeln!(self.console, "No code has been run yet")?;
+                    } else if self.restricted_full(self.slots.len())
+                        && !self.slots.contains_key(&slot)
+                    {
+                        writeln!(
+                            self.console,
+                            "This session can keep at most {} slots",
+                            MAX_RESTRICTED_ENTRIES
+                        )?;
+                    } else {
+                        self.slots.insert(slot, self.last_source.clone());
+                        writeln!(self.console, "Stored in slot {}", slot)?;
+                    }
+                }
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "run" => match args.first().map(|slot| slot.parse::<usize>()) {
+                Some(Ok(slot)) if args.len() == 1 => match self.slots.get(&slot).cloned() {
+                    Some(code) => {
+                        writeln!(self.console, "{}", code)?;
+                        self.run_code(&code)?;
+                    }
+                    None => writeln!(self.console, "Slot {} is empty", slot)?,
+                },
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "list" => {
+                for (slot, code) in &self.slots {
+                    writeln!(self.console, "{:>4}  {}", slot, code)?;
+                }
+            }
+            "checkpoint" => match args {
+                [] => {
+                    for name in self.checkpoints.keys() {
+                        writeln!(self.console, "{}", name)?;
+                    }
+                }
+                [name]
+                    if self.restricted_full(self.checkpoints.len())
+                        && !self.checkpoints.contains_key(*name) =>
+                {
+                    writeln!(
+                        self.console,
+                        "This session can keep at most {} checkpoints",
+                        MAX_RESTRICTED_ENTRIES
+                    )?
+                }
+                [name] => {
+                    let snapshot = self.snapshot();
+                    self.checkpoints.insert(name.to_string(), snapshot);
+                    writeln!(self.console, "Saved checkpoint '{}'", name)?;
+                }
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            "rollback" => match args {
+                [name] => match self.checkpoints.get(*name).cloned() {
+                    Some(snapshot) => self.restore(snapshot)?,
+                    None => writeln!(self.console, "No checkpoint named '{}'", name)?,
+                },
+                _ => writeln!(self.console, "{}", usage)?,
+            },
+            name => unreachable!("command '{}' has no handler", name),
+        }
+
+        if let Some(before) = before {
+            self.record_undo(before);
+        }
+        Ok(true)
+    }
+
+    /// Whether a restricted session already keeps `count` of something, as
+    /// many as [`MAX_RESTRICTED_ENTRIES`] allows.
+    fn restricted_full(&self, count: usize) -> bool {
+        self.restricted && count >= MAX_RESTRICTED_ENTRIES
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.interpreter.memory.clone(),
+            data_pointer: self.interpreter.data_pointer,
+            program_len: self.program.len(),
+        }
+    }
+
+    /// Remembers the state from `before` a line for `undo`, if the line
+    /// changed it.
+    fn record_undo(&mut self, before: Snapshot) {
+        if before.memory == self.interpreter.memory
+            && before.data_pointer == self.interpreter.data_pointer
+        {
+            return;
+        }
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(before);
+    }
+
+    fn undo(&mut self) -> io::Result<()> {
+        match self.undo_stack.pop() {
+            Some(snapshot) => self.restore(snapshot),
+            None => writeln!(self.console, "Nothing to undo"),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        self.interpreter.memory = snapshot.memory;
+        self.interpreter.data_pointer = snapshot.data_pointer;
+        // Lines run since then are taken back from the program too.
+        self.program.truncate(snapshot.program_len);
+        // A program being debugged would no longer match the tape.
+        self.debugger = None;
+        self.interpreter.write_current_cell(&mut self.console)
+    }
+
+    fn eval_help(&mut self, args: &[&str]) -> io::Result<()> {
+        let [name] = args else {
+            return commands::write_help(&mut self.console);
+        };
+        let name = name.trim_start_matches(':');
+        match commands::lookup(name) {
+            Some(command) => {
+                writeln!(self.console, "{}", command.usage())?;
+                writeln!(self.console, "{}", command.help)?;
+                if !command.aliases.is_empty() {
+                    writeln!(self.console, "Also: {}", command.aliases.join(", "))?;
+                }
+                Ok(())
+            }
+            None => writeln!(self.console, "{}", commands::unknown_command(name)),
+        }
+    }
+
+    fn eval_mem(&mut self, args: &[&str], usage: &str) -> io::Result<()> {
+        let mut format = self.cell_format;
+        let mut positional = Vec::new();
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(name) => match CellFormat::parse(name) {
+                    Ok(named) => format = named,
+                    Err(e) => return writeln!(self.console, "{}", self.lang.translate(&e)),
+                },
+                None => positional.push(*arg),
+            }
+        }
+
+        let memory_size = self.interpreter.memory_size();
+        match positional[..] {
+            [] => self.interpreter.write_memory_around(
+                &mut self.console,
+                self.interpreter.data_pointer,
+                MEMORY_WINDOW,
+                format,
+            ),
+            [range] if range.contains("..") => match parse_range(range, memory_size) {
+                Ok(cells) => self
+                    .interpreter
+                    .write_memory_cells(&mut self.console, cells, format),
+                Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+            },
+            [address] => match parse_address(address, memory_size) {
+                Ok(address) => self.interpreter.write_memory_around(
+                    &mut self.console,
+                    address,
+                    MEMORY_WINDOW,
+                    format,
+                ),
+                Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+            },
+            _ => writeln!(self.console, "{}", usage),
+        }
+    }
+
+    fn eval_set(&mut self, args: &[&str], usage: &str) -> io::Result<()> {
+        let (address, value) = match args {
+            [value] => (Ok(self.interpreter.data_pointer), value),
+            [address, value] => (
+                parse_address(address, self.interpreter.memory_size()),
+                value,
+            ),
+            _ => return writeln!(self.console, "{}", self.lang.translate(usage)),
+        };
+        let address = match address {
+            Ok(address) => address,
+            Err(e) => return writeln!(self.console, "{}", self.lang.translate(&e)),
+        };
+
+        match value.parse::<u8>() {
+            Ok(value) => {
+                self.interpreter.memory[address] = value;
+                self.interpreter.write_cell(&mut self.console, address)
+            }
+            Err(_) => writeln!(
+                self.console,
+                "{}",
+                self.lang
+                    .translate("Invalid value format or value is out of u8 range (0-255)")
+            ),
+        }
+    }
+
+    /// Sets the cells from `start` up to but excluding `end` to `value`, or
+    /// to zero if there is none.
+    fn fill(&mut self, start: &str, end: &str, value: Option<&str>) -> io::Result<()> {
+        let cells = match parse_range(
+            &format!("{}..{}", start, end),
+            self.interpreter.memory_size(),
+        ) {
+            Ok(cells) => cells,
+            Err(e) => return writeln!(self.console, "{}", self.lang.translate(&e)),
+        };
+        let byte = match value.map(str::parse::<u8>) {
+            None => 0,
+            Some(Ok(byte)) => byte,
+            Some(Err(_)) => {
+                return writeln!(
+                    self.console,
+                    "{}",
+                    self.lang
+                        .translate("Invalid value format or value is out of u8 range (0-255)")
+                );
+            }
+        };
+
+        self.interpreter.memory[cells.clone()].fill(byte);
+        match value {
+            Some(_) => writeln!(
+                self.console,
+                "Filled cells {}..{} with {}",
+                cells.start, cells.end, byte
+            ),
+            None => writeln!(self.console, "Cleared cells {}..{}", cells.start, cells.end),
+        }
+    }
+
+    fn eval_dump(&mut self, args: &[&str], usage: &str) -> io::Result<()> {
+        let (start, len) = match args {
+            [start] => (start, DEFAULT_DUMP_LENGTH),
+            [start, len] => match len.parse() {
+                Ok(len) => (start, len),
+                Err(_) => return writeln!(self.console, "{}", usage),
+            },
+            _ => return writeln!(self.console, "{}", usage),
+        };
+        let memory = &self.interpreter.memory;
+        match parse_address(start, memory.len()) {
+            Ok(start) => {
+                let end = start.saturating_add(len).min(memory.len());
+                hexdump::write_hexdump(&mut self.console, &memory[start..end], start)
+            }
+            Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+        }
+    }
+
+    fn eval_cell_format(&mut self, args: &[&str]) -> io::Result<()> {
+        match args {
+            [] => writeln!(
+                self.console,
+                "Cells are shown as {}",
+                self.cell_format.name()
+            ),
+            [name] => match CellFormat::parse(name) {
+                Ok(format) => {
+                    self.cell_format = format;
+                    Ok(())
+                }
+                Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+            },
+            _ => writeln!(self.console, "Usage: set display dec|hex|ascii|bin"),
+        }
+    }
+
+    fn eval_session(&mut self, args: &[&str]) -> io::Result<()> {
+        match args {
+            ["list"] | [] => {
+                writeln!(self.console, "* {}", self.session)?;
+                for name in self.other_sessions.keys() {
+                    writeln!(self.console, "  {}", name)?;
+                }
+                Ok(())
+            }
+            ["new", name] => {
+                if *name == self.session || self.other_sessions.contains_key(*name) {
+                    return writeln!(self.console, "Session '{}' already exists", name);
+                }
+                if self.restricted_full(self.other_sessions.len() + 1) {
+                    return writeln!(
+                        self.console,
+                        "This session can keep at most {} sessions",
+                        MAX_RESTRICTED_ENTRIES
+                    );
+                }
+                let interpreter = self.builder.build();
+                self.switch_session(name, interpreter)
+            }
+            ["switch", name] => match self.other_sessions.remove(*name) {
+                Some(interpreter) => self.switch_session(name, interpreter),
+                None if *name == self.session => {
+                    writeln!(self.console, "Already in session '{}'", name)
+                }
+                None => writeln!(self.console, "No session named '{}'", name),
+            },
+            _ => writeln!(
+                self.console,
+                "Usage: session list | session new <name> | session switch <name>"
+            ),
+        }
+    }
+
+    fn eval_max_steps(&mut self, args: &[&str]) -> io::Result<()> {
+        if self.restricted && !args.is_empty() {
+            return writeln!(
+                self.console,
+                "The step limit cannot be changed in this session"
+            );
+        }
+        match args {
+            [] => match self.interpreter.max_steps() {
+                Some(max_steps) => writeln!(self.console, "Runs stop after {} steps", max_steps),
+                None => writeln!(self.console, "Runs have no step limit"),
+            },
+            ["off"] => {
+                self.interpreter.set_max_steps(None);
+                Ok(())
+            }
+            [steps] => match steps.parse() {
+                Ok(steps) => {
+                    self.interpreter.set_max_steps(Some(steps));
+                    Ok(())
+                }
+                Err(_) => writeln!(self.console, "Usage: set max-steps <N>|off"),
+            },
+            _ => writeln!(self.console, "Usage: set max-steps <N>|off"),
+        }
+    }
+
+    fn eval_heatmap(&mut self, args: &[&str]) -> io::Result<()> {
+        match args {
+            [] => match self.interpreter.heatmap() {
+                Some(heatmap) => write!(self.console, "{}", heatmap.render()),
+                None => writeln!(
+                    self.console,
+                    "Cell accesses are not being counted; use 'heatmap on' to start"
+                ),
+            },
+            ["on"] => {
+                self.interpreter.set_heatmap(true);
+                writeln!(self.console, "Counting cell accesses from the next run")
+            }
+            ["off"] => {
+                self.interpreter.set_heatmap(false);
+                Ok(())
+            }
+            _ => writeln!(self.console, "Usage: heatmap [on|off]"),
+        }
+    }
+
+    fn eval_debug(&mut self, code: &str) -> io::Result<()> {
+        match Debugger::load(code, &self.interpreter) {
+            Ok(debugger) => {
+                debugger.write_location(&mut self.console, &self.interpreter)?;
+                self.debugger = Some(debugger);
+                Ok(())
+            }
+            Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+        }
+    }
+
+    /// Handles `step`, `next`, `continue`, and `where`, which control the
+    /// program loaded with `debug`.
+    fn eval_debugger(&mut self, command: &str) -> io::Result<()> {
+        let Some(mut debugger) = self.debugger.take() else {
+            return writeln!(
+                self.console,
+                "No program is being debugged; start one with 'debug <code>'"
+            );
+        };
+        if command == "where" {
+            debugger.write_location(&mut self.console, &self.interpreter)?;
+            self.debugger = Some(debugger);
+            return Ok(());
+        }
+
+        self.last_output.clear();
+        let catch = self.catch_interrupts.then(interrupt::Catch::new);
+        let stop = self.run_with_program_io(|interpreter, input, output| match command {
+            "step" => debugger.step(interpreter, input, output),
+            "next" => debugger.next(interpreter, input, output),
+            _ => debugger.resume(interpreter, input, output),
+        });
+        drop(catch);
+
+        // Stops are reported on a line of their own.
+        if self.redirected_output.is_none()
+            && !self.last_output.is_empty()
+            && !self.last_output.ends_with(b"\n")
+        {
+            writeln!(self.console)?;
+        }
+        match stop {
+            Stop::Finished => {
+                return writeln!(
+                    self.console,
+                    "Program finished after {} steps",
+                    debugger.steps()
+                );
+            }
+            Stop::Failed(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+            Stop::Interrupted => writeln!(self.console, "Interrupted")?,
+            Stop::Breakpoint => writeln!(self.console, "Breakpoint")?,
+            Stop::Watch {
+                address,
+                old,
+                new,
+                instruction,
+            } => writeln!(
+                self.console,
+                "Cell {} changed from {} to {} by instruction {}",
+                address, old, new, instruction
+            )?,
+            Stop::Paused => {}
+        }
+        debugger.write_location(&mut self.console, &self.interpreter)?;
+        self.debugger = Some(debugger);
+        Ok(())
+    }
+
+    fn eval_break(&mut self, args: &[&str]) -> io::Result<()> {
+        let Some(debugger) = &mut self.debugger else {
+            return writeln!(
+                self.console,
+                "No program is being debugged; start one with 'debug <code>'"
+            );
+        };
+
+        match args {
+            [] => {
+                let breakpoints: Vec<String> = debugger
+                    .breakpoints()
+                    .map(|index| index.to_string())
+                    .collect();
+                if breakpoints.is_empty() {
+                    writeln!(self.console, "No breakpoints")
+                } else {
+                    writeln!(self.console, "Breakpoints at {}", breakpoints.join(", "))
+                }
+            }
+            ["clear"] => {
+                debugger.clear_breakpoints();
+                writeln!(self.console, "Breakpoints cleared")
+            }
+            [index] => match index.parse() {
+                Ok(index) => match debugger.set_breakpoint(index) {
+                    Ok(()) => writeln!(self.console, "Breakpoint at instruction {}", index),
+                    Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+                },
+                Err(_) => writeln!(self.console, "Usage: break <instruction>|clear"),
+            },
+            _ => writeln!(self.console, "Usage: break <instruction>|clear"),
+        }
+    }
+
+    fn eval_watch(&mut self, args: &[&str]) -> io::Result<()> {
+        let Some(debugger) = &mut self.debugger else {
+            return writeln!(
+                self.console,
+                "No program is being debugged; start one with 'debug <code>'"
+            );
+        };
+
+        match args {
+            [] => {
+                let watches: Vec<String> = debugger
+                    .watches()
+                    .map(|address| address.to_string())
+                    .collect();
+                if watches.is_empty() {
+                    writeln!(self.console, "No cells are watched")
+                } else {
+                    writeln!(self.console, "Watched cells: {}", watches.join(", "))
+                }
+            }
+            ["clear"] => {
+                debugger.clear_watches();
+                writeln!(self.console, "Watches cleared")
+            }
+            [addr_str] => match parse_address(addr_str, self.interpreter.memory_size()) {
+                Ok(addr) => {
+                    debugger.watch(addr);
+                    writeln!(self.console, "Watching cell {}", addr)
+                }
+                Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+            },
+            _ => writeln!(self.console, "Usage: watch <address>|clear"),
+        }
+    }
+
+    fn eval_back(&mut self, args: &[&str]) -> io::Result<()> {
+        let Some(debugger) = &mut self.debugger else {
+            return writeln!(
+                self.console,
+                "No program is being debugged; start one with 'debug <code>'"
+            );
+        };
+
+        let count = match args {
+            [] => 1,
+            [count] => match count.parse() {
+                Ok(count) => count,
+                Err(_) => return writeln!(self.console, "Usage: back [steps]"),
+            },
+            _ => return writeln!(self.console, "Usage: back [steps]"),
+        };
+        if debugger.back(&mut self.interpreter, count) == 0 {
+            return writeln!(self.console, "No earlier state recorded");
+        }
+        debugger.write_location(&mut self.console, &self.interpreter)
+    }
+
+    fn eval_output(&mut self, args: &[&str]) -> io::Result<()> {
+        match args {
+            [] => match &self.redirected_output {
+                Some((path, _)) => writeln!(self.console, "Program output goes to {}", path),
+                None => writeln!(self.console, "Program output goes to the terminal"),
+            },
+            ["off" | "-"] => {
+                self.redirected_output = None;
+                writeln!(self.console, "Program output goes to the terminal")
+            }
+            [path] => match program_io::create_output_file(path) {
+                Ok(file) => {
+                    self.redirected_output = Some((path.to_string(), file));
+                    writeln!(self.console, "Program output goes to {}", path)
+                }
+                Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+            },
+            _ => writeln!(self.console, "Usage: output <file> | output off"),
+        }
+    }
+
+    fn eval_display(&mut self, expr: Option<&str>) -> io::Result<()> {
+        let Some(expr) = expr else {
+            return self.write_displays();
+        };
+
+        match DisplayExpr::parse(expr, self.interpreter.memory_size()) {
+            Ok(expr) => {
+                let number = self.next_display;
+                self.next_display += 1;
+                self.displays.insert(number, expr);
+                let value = expr.eval(&self.interpreter);
+                writeln!(self.console, "{}: {} = {}", number, expr, value)
+            }
+            Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+        }
+    }
+
+    fn eval_find(&mut self, query: &str) -> io::Result<()> {
+        let search = match Search::parse(query, self.interpreter.memory_size()) {
+            Ok(search) => search,
+            Err(e) => return writeln!(self.console, "{}", self.lang.translate(&e)),
+        };
+
+        let matches = search.find(&self.interpreter.memory);
+        if matches.is_empty() {
+            return writeln!(self.console, "No matches");
+        }
+
+        let shown: Vec<String> = matches
+            .iter()
+            .take(MAX_FIND_RESULTS)
+            .map(|addr| addr.to_string())
+            .collect();
+        write!(
+            self.console,
+            "{} match{} at {}",
+            matches.len(),
+            if matches.len() == 1 { "" } else { "es" },
+            shown.join(", ")
+        )?;
+        if matches.len() > MAX_FIND_RESULTS {
+            write!(
+                self.console,
+                ", ... and {} more",
+                matches.len() - MAX_FIND_RESULTS
+            )?;
+        }
+        writeln!(self.console)
+    }
+
+    fn eval_undisplay(&mut self, args: &[&str]) -> io::Result<()> {
+        match args {
+            [] => {
+                self.displays.clear();
+                Ok(())
+            }
+            [number] => match number.parse().ok().and_then(|n| self.displays.remove(&n)) {
+                Some(_) => Ok(()),
+                None => writeln!(self.console, "No display number {}", number),
+            },
+            _ => writeln!(self.console, "Usage: undisplay [<number>]"),
+        }
+    }
+
+    fn write_displays(&mut self) -> io::Result<()> {
+        for (number, expr) in &self.displays {
+            let value = expr.eval(&self.interpreter);
+            writeln!(self.console, "{}: {} = {}", number, expr, value)?;
+        }
+        Ok(())
+    }
+
+    fn copy_to_clipboard(&mut self, data: &[u8], what: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return writeln!(self.console, "No {} to copy", what);
+        }
+
+        match clipboard::copy(data, &mut self.console) {
+            Ok(method) => writeln!(
+                self.console,
+                "Copied {} bytes of {} to the clipboard using {}",
+                data.len(),
+                what,
+                method
+            ),
+            Err(e) => writeln!(self.console, "{}", self.lang.translate(&e)),
+        }
+    }
+
+    /// Looks up the history entry numbered `number`, printing a message if
+    /// there is none.
+    fn history_entry(
+        &mut self,
+        number: Option<&&str>,
+        command: &str,
+    ) -> io::Result<Option<String>> {
+        let Some(number) = number else {
+            writeln!(self.console, "Usage: {} <number>", command)?;
+            return Ok(None);
+        };
+
+        let entry = number
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| self.history.get(n.checked_sub(1)?));
+        match entry {
+            Some(code) => Ok(Some(code.clone())),
+            None => {
+                writeln!(self.console, "No history entry {}", number)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn save(&mut self, path: &str) -> io::Result<()> {
+        if self.program.is_empty() {
+            return writeln!(self.console, "No code has been run yet");
+        }
+
+        let mut source = self.program.join("\n");
+        source.push('\n');
+        match fs::write(path, source) {
+            Ok(()) => writeln!(
+                self.console,
+                "Wrote {} lines of code to {}",
+                self.program.len(),
+                path
+            ),
+            Err(e) => writeln!(self.console, "Cannot write {}: {}", path, e),
+        }
+    }
+
+    /// Replaces the tape with a fresh one, with the data pointer where it
+    /// starts, and starts a new program for `save`. The step limit, heatmap,
+    /// and display format are kept unless `all` is set, which also forgets
+    /// the history and displays.
+    fn reset(&mut self, all: bool) -> io::Result<()> {
+        let previous = std::mem::replace(&mut self.interpreter, self.builder.build());
+        self.debugger = None;
+        self.program.clear();
+
+        if all {
+            self.history.clear();
+            self.displays.clear();
+            self.next_display = 1;
+            self.cell_format = CellFormat::default();
+            self.last_source.clear();
+            self.last_output.clear();
+            writeln!(self.console, "Session reset")
+        } else {
+            self.interpreter.set_max_steps(previous.max_steps());
+            self.interpreter.set_heatmap(previous.heatmap().is_some());
+            writeln!(self.console, "Tape reset")
+        }
+    }
+
+    fn switch_session(&mut self, name: &str, interpreter: Interpreter) -> io::Result<()> {
+        let previous = std::mem::replace(&mut self.interpreter, interpreter);
+        let previous_name = std::mem::replace(&mut self.session, name.to_string());
+        // Changes made to the other tape cannot be undone on this one, and a
+        // program being debugged belongs to the other tape.
+        self.undo_stack.clear();
+        self.debugger = None;
+        let checkpoints = self.other_checkpoints.remove(name).unwrap_or_default();
+        let previous_checkpoints = std::mem::replace(&mut self.checkpoints, checkpoints);
+        self.other_checkpoints
+            .insert(previous_name.clone(), previous_checkpoints);
+        self.other_sessions.insert(previous_name, previous);
+        writeln!(self.console, "Switched to session '{}'", name)
+    }
+
+    fn run_code(&mut self, bf_code: &str) -> io::Result<()> {
+        let tokens = tokenize_with(bf_code, self.interpreter.extensions());
+
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let jump_table = match parse_loops(&tokens) {
+            Ok(jump_table) => jump_table,
+            Err(e) => {
+                return writeln!(self.console, "{}", self.lang.translate(&e));
+            }
+        };
+
+        if !self.quiet {
+            for warning in loop_warnings(bf_code, &tokens, &jump_table) {
+                writeln!(self.console, "{}", self.lang.translate(&warning))?;
+            }
+        }
+
+        self.last_source = bf_code.to_string();
+        self.history.push(bf_code.to_string());
+        if self.restricted && self.history.len() > MAX_RESTRICTED_HISTORY {
+            self.history.remove(0);
+        }
+        self.last_output.clear();
+
+        let catch = self.catch_interrupts.then(interrupt::Catch::new);
+        let result = self.run_with_program_io(|interpreter, input, output| {
+            interpreter.run_with_io(&tokens, &jump_table, input, output)
+        });
+        drop(catch);
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_run(self.interpreter.steps(), RunOutcome::of(&result));
+        }
+
+        match result {
+            Ok(_) => {
+                // Restricted sessions cannot save, so they keep no program.
+                if !self.restricted {
+                    self.program.push(bf_code.to_string());
+                }
+                let produced_output =
+                    self.redirected_output.is_none() && tokens.contains(&Token::Output);
+                self.write_summary(produced_output)?;
+            }
+            Err(e) => writeln!(self.console, "{}", self.lang.translate(&e))?,
+        }
+
+        self.write_displays()
+    }
+
+    /// Calls `run` with the session's interpreter and the program's input and
+    /// output, copying the output to `last_output`.
+    fn run_with_program_io<T>(
+        &mut self,
+        run: impl FnOnce(&mut Interpreter, &mut dyn Read, &mut dyn Write) -> T,
+    ) -> T {
+        let mut prompting_input;
+        let program_input: &mut dyn Read = match &mut self.program_input {
+            Some(program_input) => program_input,
+            None if self.input_prompt => {
+                prompting_input = PromptingInput {
+                    inner: &mut self.input,
+                    console: &mut self.console,
+                    prompt: self.lang.translate("input> "),
+                    at_line_start: true,
+                };
+                &mut prompting_input
+            }
+            None => &mut self.input,
+        };
+        let mut translated_input;
+        let program_input: &mut dyn Read = if self.translate_crlf {
+            translated_input = CrlfToLf::new(program_input);
+            &mut translated_input
+        } else {
+            program_input
+        };
+
+        let output: &mut dyn Write = match &mut self.redirected_output {
+            Some((_, file)) => file,
+            None => &mut self.output,
+        };
+
+        let mut output = Tee {
+            inner: output,
+            copy: &mut self.last_output,
+        };
+        run(&mut self.interpreter, program_input, &mut output)
+    }
+
+    /// Runs `bf_code` on a copy of the interpreter and reports its output and
+    /// tape changes, leaving the session untouched. `,` reads end of input.
+    fn dry_run(&mut self, bf_code: &str) -> io::Result<()> {
+        let tokens = tokenize_with(bf_code, self.interpreter.extensions());
+
+        let jump_table = match parse_loops(&tokens) {
+            Ok(jump_table) => jump_table,
+            Err(e) => {
+                return writeln!(self.console, "{}", self.lang.translate(&e));
+            }
+        };
+
+        let mut scratch = self.interpreter.clone();
+        // Discarded runs stay out of the I/O log and trace, and do not
+        // report progress.
+        scratch.io_log = None;
+        scratch.trace = None;
+        scratch.progress_interval = None;
+        let mut output = Vec::new();
+        let catch = self.catch_interrupts.then(interrupt::Catch::new);
+        let result = scratch.run_with_io(&tokens, &jump_table, &mut io::empty(), &mut output);
+        drop(catch);
+
+        if !output.is_empty() {
+            writeln!(self.console, "Output: \"{}\"", output.escape_ascii())?;
+        }
+        if let Err(e) = result {
+            writeln!(self.console, "Error: {}", e)?;
+        }
+        self.write_diff(&scratch)?;
+        writeln!(self.console, "Changes discarded")
+    }
+
+    fn write_diff(&mut self, after: &Interpreter) -> io::Result<()> {
+        let before = &self.interpreter;
+        // Cells a growing tape gained during the run were zero before it.
+        let cell = |interpreter: &Interpreter, addr| {
+            interpreter.memory.get(addr).copied().unwrap_or_default()
+        };
+        let changed: Vec<usize> = (0..before.memory.len().max(after.memory.len()))
+            .filter(|&addr| cell(before, addr) != cell(after, addr))
+            .collect();
+
+        if before.data_pointer != after.data_pointer {
+            writeln!(
+                self.console,
+                "Data pointer: {} -> {}",
+                before.data_pointer, after.data_pointer
+            )?;
+        } else if changed.is_empty() {
+            writeln!(self.console, "No changes to the tape")?;
+        }
+
+        for &addr in changed.iter().take(MAX_DIFF_CELLS) {
+            writeln!(
+                self.console,
+                "Cell {}: {} -> {}",
+                addr,
+                cell(before, addr),
+                cell(after, addr)
+            )?;
+        }
+        if changed.len() > MAX_DIFF_CELLS {
+            writeln!(
+                self.console,
+                "... and {} more cells",
+                changed.len() - MAX_DIFF_CELLS
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn write_summary(&mut self, produced_output: bool) -> io::Result<()> {
+        if let Some(summary_format) = &self.summary_format {
+            if produced_output {
+                writeln!(self.console)?;
+            }
+            writeln!(self.console, "{}", summary_format.render(&self.interpreter))
+        } else if self.quiet {
+            Ok(())
+        } else if produced_output {
+            writeln!(self.console)
+        } else {
+            self.interpreter.write_current_cell(&mut self.console)
+        }
+    }
+}
+
+/// Reads from `inner`, prompting on `console` before each new line is read so
+/// a program waiting for input can be told apart from one that is busy.
+struct PromptingInput<'a> {
+    inner: &'a mut dyn BufRead,
+    console: &'a mut dyn Write,
+    prompt: Cow<'static, str>,
+    at_line_start: bool,
+}
+
+impl Read for PromptingInput<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.at_line_start {
+            write!(self.console, "{}", self.prompt)?;
+            self.console.flush()?;
+        }
+
+        let read = self.inner.read(buf)?;
+        self.at_line_start = buf[..read].last() == Some(&b'\n');
+        Ok(read)
+    }
+}
+
+/// Writes to `inner` while keeping a copy of everything written.
+struct Tee<'a> {
+    inner: &'a mut dyn Write,
+    copy: &'a mut Vec<u8>,
+}
+
+impl Write for Tee<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.copy.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{Trace, TraceFormat};
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `script` through a quiet REPL and returns everything it printed.
+    fn run_script(script: &str) -> String {
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(script.to_string())),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        );
+        repl.set_quiet(true);
+        repl.set_summary_format(Some(SummaryFormat::parse("{dp}: {cell}").unwrap()));
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| format!("{}\n", line.trim_start_matches("> ")))
+            .collect()
+    }
+
+    #[test]
+    fn test_output_redirection() {
+        let path = std::env::temp_dir().join(format!("bf-repl-output-{}.txt", std::process::id()));
+        let transcript = run_script(&format!(
+            "++++++++[>++++++++<-]>+.\n:output {}\n.+.\n:output off\n+.\n",
+            path.display()
+        ));
+        let file_contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(
+            transcript.replace(&path.display().to_string(), "out.txt"),
+            @r"
+        A
+        15001: 65
+        Program output goes to out.txt
+        15001: 66
+        Program output goes to the terminal
+        C
+        15001: 67
+        "
+        );
+        insta::assert_snapshot!(file_contents, @"AB");
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let transcript = run_script("+++\n:dry ++>-<.,\n:dry [\n:dry <<\n:dry\n+\n");
+
+        insta::assert_snapshot!(transcript, @r#"
+        15000: 3
+        Output: "\x05"
+        Cell 15000: 3 -> 0
+        Cell 15001: 0 -> 255
+        Changes discarded
+        Unmatched '[' at index 0
+        Data pointer: 15000 -> 14998
+        Changes discarded
+        Usage: dry <code>
+        15000: 4
+        "#);
+    }
+
+    #[test]
+    fn test_dry_run_on_growing_tape() {
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(":dry >+\n")),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        )
+        .with_interpreter(InterpreterBuilder::default().tape_size(1).grow(true));
+        repl.set_quiet(true);
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        let transcript: String = String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| format!("{}\n", line.trim_start_matches("> ")))
+            .collect();
+        insta::assert_snapshot!(transcript, @r"
+        Data pointer: 0 -> 1
+        Cell 1: 0 -> 1
+        Changes discarded
+        ");
+    }
+
+    #[test]
+    fn test_dry_run_skips_trace() {
+        let path =
+            std::env::temp_dir().join(format!("bf-repl-dry-trace-{}.txt", std::process::id()));
+        let trace = Trace::create(&path.display().to_string(), TraceFormat::Text, 100).unwrap();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(":dry +>+\n+\n")),
+            Box::new(io::sink()),
+            Box::new(io::sink()),
+        )
+        .with_interpreter(InterpreterBuilder::default().trace(Some(trace)));
+        repl.set_quiet(true);
+        repl.run().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(contents, @r"
+        step	ip	op	dp	cell
+        1	0	+	15000	1
+        ");
+    }
+
+    #[test]
+    fn test_display() {
+        let transcript = run_script(
+            ":display cell(15001)\n:display sum(15000..15002)\n+>++\n:undisplay 1\n-\n:display dp + 1\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        1: cell(15001) = 0
+        2: sum(15000..15002) = 0
+        15001: 2
+        1: cell(15001) = 2
+        2: sum(15000..15002) = 3
+        15001: 1
+        2: sum(15000..15002) = 2
+        Unknown display expression 'dp+1' (expected dp, cell, cell(N), or sum(A..B))
+        ");
+    }
+
+    #[test]
+    fn test_input_prompt() {
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(",.,.,.,.\na\nb\n")),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        );
+        repl.set_quiet(true);
+        repl.set_input_prompt(true);
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        insta::assert_snapshot!(String::from_utf8(bytes).unwrap(), @r"
+        > input> a
+        input> b
+        >
+        ");
+    }
+
+    #[test]
+    fn test_find() {
+        let transcript = run_script(":find 0 0..3\n+>+>+\n:find 1\n:find \"hi\"\n:find\n");
+
+        insta::assert_snapshot!(transcript, @r#"
+        3 matches at 0, 1, 2
+        15002: 1
+        3 matches at 15000, 15001, 15002
+        No matches
+        Usage: find <byte|"text"> [<start>..<end>]
+        "#);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let transcript =
+            run_script(":set max-steps 100\n:set max-steps\n+[]\n:set max-steps off\n+++\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Runs stop after 100 steps
+        Step limit of 100 reached
+        15000: 4
+        ");
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let transcript =
+            run_script(":heatmap\n:heatmap on\n++[>+<-]\n:heatmap\n:heatmap off\n:heatmap\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Cell accesses are not being counted; use 'heatmap on' to start
+        Counting cell accesses from the next run
+        15000: 0
+        cells  reads  writes
+        15000      7       4  ########################################
+        15001      2       2  ###############
+        Cell accesses are not being counted; use 'heatmap on' to start
+        ");
+    }
+
+    #[test]
+    fn test_debugger() {
+        let transcript = run_script(
+            ":step\n:debug ++[>+<-]>.\n:step\n:step\n:next\n:where\n:continue\n:where\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        No program is being debugged; start one with 'debug <code>'
+        Step 0: paused at instruction 0 of 10 ('+')
+          ++[>+<-]>.
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Step 1: paused at instruction 1 of 10 ('+')
+          ++[>+<-]>.
+           ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      1      0      0      0
+        Ptrs:                       ^^^^^                     
+        Step 2: paused at instruction 2 of 10 ('[')
+          ++[>+<-]>.
+            ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      2      0      0      0
+        Ptrs:                       ^^^^^                     
+        Step 13: paused at instruction 8 of 10 ('>')
+          ++[>+<-]>.
+                  ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      2      0      0
+        Ptrs:                       ^^^^^                     
+        Step 13: paused at instruction 8 of 10 ('>')
+          ++[>+<-]>.
+                  ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      2      0      0
+        Ptrs:                       ^^^^^                     
+        
+        Program finished after 15 steps
+        No program is being debugged; start one with 'debug <code>'
+        ");
+    }
+
+    #[test]
+    fn test_breakpoint_markers() {
+        let transcript = run_script(":debug +#[-#]+\n:continue\n:continue\n:continue\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Step 0: paused at instruction 0 of 5 ('+')
+          +#[-#]+
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Breakpoint
+        Step 1: paused at instruction 1 of 5 ('[')
+          +#[-#]+
+            ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      1      0      0      0
+        Ptrs:                       ^^^^^                     
+        Breakpoint
+        Step 3: paused at instruction 3 of 5 (']')
+          +#[-#]+
+               ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Program finished after 5 steps
+        ");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let transcript = run_script(
+            ":break 1\n:debug ++[-]>+\n:break 9\n:break 3\n:break 5\n:break\n:continue\n:continue\n:break clear\n:break\n:continue\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        No program is being debugged; start one with 'debug <code>'
+        Step 0: paused at instruction 0 of 7 ('+')
+          ++[-]>+
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Instruction 9 is out of range (0-6)
+        Breakpoint at instruction 3
+        Breakpoint at instruction 5
+        Breakpoints at 3, 5
+        Breakpoint
+        Step 3: paused at instruction 3 of 7 ('-')
+          ++[-]>+
+             ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      2      0      0      0
+        Ptrs:                       ^^^^^                     
+        Breakpoint
+        Step 5: paused at instruction 3 of 7 ('-')
+          ++[-]>+
+             ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      1      0      0      0
+        Ptrs:                       ^^^^^                     
+        Breakpoints cleared
+        No breakpoints
+        Program finished after 9 steps
+        ");
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let transcript = run_script(
+            ":debug ++[>+<-]\n:watch 15001\n:watch\n:continue\n:watch clear\n:continue\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        Step 0: paused at instruction 0 of 8 ('+')
+          ++[>+<-]
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Watching cell 15001
+        Watched cells: 15001
+        Cell 15001 changed from 0 to 1 by instruction 4
+        Step 5: paused at instruction 5 of 8 ('<')
+          ++[>+<-]
+               ^
+        Addr:  14998  14999  15000  15001  15002  15003  15004
+        Data:      0      0      2      1      0      0      0
+        Ptrs:                       ^^^^^                     
+        Watches cleared
+        Program finished after 13 steps
+        ");
+    }
+
+    #[test]
+    fn test_mem() {
+        let transcript =
+            run_script(":mem 2\n+>++\n:mem 14998..15003\n:mem 3..1\n:mem 40000\n:mem 1 2\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Addr:      0      1      2      3      4      5      6      7
+        Data:      0      0      0      0      0      0      0      0
+        Ptrs:                                                        
+        15001: 2
+        Addr:  14998  14999  15000  15001  15002
+        Data:      0      0      1      2      0
+        Ptrs:                       ^^^^^       
+        Invalid range 3..1
+        Address 40000 is out of bounds (0-29999)
+        Usage: mem [<address> | <start>..<end>] [--hex|--ascii|--bin|--dec]
+        ");
+    }
+
+    #[test]
+    fn test_mem_formats() {
+        let transcript = run_script(
+            "++++++++[>++++++++<-]>+>++++++++++\n:mem 15001..15004 --hex\n:mem 15001..15004 --bin\n:set display ascii\n:mem 15001..15004\n:set display\n:mem --oct\n:set display dec\n:mem 15001..15004\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15002: 10
+        Addr:  15001  15002  15003
+        Data:     41     0A     00
+        Ptrs:         ^^^^^       
+        Addr:    15001    15002    15003
+        Data: 01000001 00001010 00000000
+        Ptrs:           ^^^^^^^         
+        Addr:  15001  15002  15003
+        Data:    'A'     LF    NUL
+        Ptrs:         ^^^^^       
+        Cells are shown as ascii
+        Unknown display format 'oct' (expected dec, hex, ascii, or bin)
+        Addr:  15001  15002  15003
+        Data:     65     10      0
+        Ptrs:         ^^^^^
+        ");
+    }
+
+    #[test]
+    fn test_dump() {
+        let transcript = run_script(
+            "++++++++[>++++++++<-]>+>++++++++++\n:dump 14998 20\n:dump 29990\n:dump 30000\n:dump 1 x\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15002: 10
+         14998  00 00 00 41 0a 00 00 00  00 00 00 00 00 00 00 00  |...A............|
+         15014  00 00 00 00                                       |....|
+         29990  00 00 00 00 00 00 00 00  00 00                    |..........|
+        Address 30000 is out of bounds (0-29999)
+        Usage: dump <start> [<length>]
+        ");
+    }
+
+    #[test]
+    fn test_set_and_ptr() {
+        let transcript =
+            run_script(":set 15010 72\n:ptr 15010\n.\n:set 15010 300\n:set 40000 1\n:set 1 2 3\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Cell[15010] = 72 'H'
+        Cell[15010] = 72 'H'
+        H
+        15010: 72
+        Invalid value format or value is out of u8 range (0-255)
+        Address 40000 is out of bounds (0-29999)
+        Usage: set [<address>] <value>
+        ");
+    }
+
+    #[test]
+    fn test_fill_and_clear() {
+        let transcript = run_script(
+            ":fill 14998 15003 7\n:clear 15000 15002\n:mem 14997..15004\n:fill 3 1 0\n:fill 0 5 256\n:clear 0\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        Filled cells 14998..15003 with 7
+        Cleared cells 15000..15002
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      7      7      0      0      7      0
+        Ptrs:                       ^^^^^                     
+        Invalid range 3..1
+        Invalid value format or value is out of u8 range (0-255)
+        Usage: clear <start> <end>
+        ");
+    }
+
+    #[test]
+    fn test_undo() {
+        let transcript = run_script(
+            "+++\n>++\n:show\n:fill 0 3 9\n:undo\n:undo\n:mem 14999..15002\n:undo\n:undo\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15000: 3
+        15001: 2
+        Cell[15001] = 2 STX
+        Filled cells 0..3 with 9
+        Cell[15001] = 2 STX
+        Cell[15000] = 3 ETX
+        Addr:  14999  15000  15001
+        Data:      0      3      0
+        Ptrs:         ^^^^^       
+        Cell[15000] = 0 NUL
+        Nothing to undo
+        ");
+    }
+
+    #[test]
+    fn test_undo_then_save() {
+        let path =
+            std::env::temp_dir().join(format!("bf-repl-undo-save-{}.bf", std::process::id()));
+        let transcript = run_script(&format!(
+            "+++\n>++\n:undo\n:checkpoint one\n-\n:rollback one\n:save {}\n",
+            path.display()
+        ));
+        let file_contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(transcript.replace(&path.display().to_string(), "out.bf"), @r"
+        15000: 3
+        15001: 2
+        Cell[15000] = 3 ETX
+        Saved checkpoint 'one'
+        15000: 2
+        Cell[15000] = 3 ETX
+        Wrote 1 lines of code to out.bf
+        ");
+        insta::assert_snapshot!(file_contents, @"+++");
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let transcript = run_script(
+            "++\n:checkpoint two\n>+++\n:checkpoint three\n:checkpoint\n:rollback two\n:mem 15000..15002\n:undo\n:rollback four\n:rollback\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15000: 2
+        Saved checkpoint 'two'
+        15001: 3
+        Saved checkpoint 'three'
+        three
+        two
+        Cell[15000] = 2 STX
+        Addr:  15000  15001
+        Data:      2      0
+        Ptrs:  ^^^^^       
+        Cell[15001] = 3 ETX
+        No checkpoint named 'four'
+        Usage: rollback <name>
+        ");
+    }
+
+    #[test]
+    fn test_session_switch_stops_debugging() {
+        let transcript = run_script(":debug +++\n:session new other\n:step\n:show\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        Step 0: paused at instruction 0 of 3 ('+')
+          +++
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Switched to session 'other'
+        No program is being debugged; start one with 'debug <code>'
+        Cell[15000] = 0 NUL
+        ");
+    }
+
+    #[test]
+    fn test_checkpoints_per_session() {
+        let transcript = run_script(
+            "+\n:checkpoint one\n:session new other\n:checkpoint\n:rollback one\n:session switch main\n:checkpoint\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15000: 1
+        Saved checkpoint 'one'
+        Switched to session 'other'
+        No checkpoint named 'one'
+        Switched to session 'main'
+        one
+        ");
+    }
+
+    #[test]
+    fn test_slots() {
+        let transcript = run_script(
+            ":store 1\n+++ add three\n:store 1\n>+\n:store 7\n:list\n:run 1\n:run 2\n:run x\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        No code has been run yet
+        15000: 3
+        Stored in slot 1
+        15001: 1
+        Stored in slot 7
+           1  +++ add three
+           7  >+
+        +++ add three
+        15001: 4
+        Slot 2 is empty
+        Usage: run <slot>
+        ");
+    }
+
+    #[test]
+    fn test_restricted() {
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(
+                "+\n:save /tmp/bf-repl-restricted.bf\nwrite-session x\n:output x\n:load x\n\
+                 :copy-output\n:debug +\n:set max-steps off\n:set max-steps\n",
+            )),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        )
+        .with_interpreter(InterpreterBuilder::default().max_steps(Some(1000)));
+        repl.set_quiet(true);
+        repl.set_restricted(true);
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        insta::assert_snapshot!(String::from_utf8(bytes).unwrap().replace("> ", ""), @r"
+        'save' is not available in this session
+        'save' is not available in this session
+        'output' is not available in this session
+        'load' is not available in this session
+        'copy-output' is not available in this session
+        'debug' is not available in this session
+        The step limit cannot be changed in this session
+        Runs stop after 1000 steps
+        ");
+    }
+
+    #[test]
+    fn test_restricted_limits() {
+        let mut script = String::from("+\n");
+        for i in 0..=MAX_RESTRICTED_ENTRIES {
+            script += &format!(":checkpoint c{0}\n:store {0}\n", i);
+        }
+        script += ":checkpoint c0\n:store 0\n";
+        for i in 1..=MAX_RESTRICTED_ENTRIES {
+            script += &format!(":session new s{}\n", i);
+        }
+        for _ in 0..=MAX_RESTRICTED_HISTORY {
+            script += ">\n";
+        }
+        script += ":history\n";
+
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(script)),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        );
+        repl.set_quiet(true);
+        repl.set_restricted(true);
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        let transcript = String::from_utf8(bytes).unwrap().replace("> ", "");
+        let refusals: Vec<&str> = transcript
+            .lines()
+            .filter(|line| line.starts_with("This session"))
+            .collect();
+        insta::assert_debug_snapshot!(refusals, @r#"
+        [
+            "This session can keep at most 16 checkpoints",
+            "This session can keep at most 16 slots",
+            "This session can keep at most 16 sessions",
+        ]
+        "#);
+        assert_eq!(transcript.matches("Saved checkpoint 'c0'").count(), 2);
+        assert_eq!(repl.history.len(), MAX_RESTRICTED_HISTORY);
+        assert!(repl.program.is_empty());
+    }
+
+    #[test]
+    fn test_help() {
+        let transcript = run_script(":help\n:help show\nhelp :memory\n:sho\n:help nope\n");
+
+        insta::assert_snapshot!(transcript, @r"
+          help         List the commands, or explain one
+          mem          Show the cells around the data pointer or <address>, or a range
+          show         Show the current cell or the cell at <address>
+          jump         Move the data pointer to <address>
+          set          Set the current cell; 'set max-steps' and 'set display' change settings
+          dump         Show the cells from <start> as a hexdump with their characters
+          fill         Set the cells from <start> up to <end> to <value>
+          clear        Set the cells from <start> up to <end> to 0
+          ascii        Show the ASCII table, or one character and its value
+          output       Write program output to <file>, or to the terminal again
+          dry          Run <code> on a copy of the tape and show what it would change
+          debug        Load <code> paused before its first instruction
+          step         Run one instruction of the debugged program
+          next         Run one instruction, or the whole loop at a '['
+          continue     Run the debugged program until it stops
+          where        Show where the debugged program is paused
+          break        Stop before instruction <n>, or list or clear breakpoints
+          watch        Stop when the cell at <address> changes, or list or clear watches
+          back         Undo the last steps of the debugged program
+          undo         Take back the last change to the tape or the data pointer
+          store        Keep the last line of code in slot <slot>
+          run          Run the code kept in slot <slot>
+          list         List the code kept in slots
+          checkpoint   Save the tape and data pointer as <name>, or list checkpoints
+          rollback     Restore the tape and data pointer saved as <name>
+          load         Run the program in <file> on the current tape
+          reset        Start again with a fresh tape; 'all' also forgets history and displays
+          session      Keep several tapes and switch between them
+          display      Show <expr> after every run, or show all displays
+          undisplay    Remove display <n>, or all of them
+          histogram    Chart how often each command occurs
+          heatmap      Count and chart how often each cell is accessed
+          find         List the addresses where a byte or text is stored
+          copy-output  Copy the last run's output to the clipboard
+          copy-source  Copy the last line of code to the clipboard
+          history      List the lines of code run so far
+          rerun        Run line <n> of the history again
+          edit         Put line <n> of the history on the next prompt
+          save         Write the code that ran without errors to <file> as a program
+          exit         Leave the REPL
+        Commands may start with ':'. Anything else runs as code; 'help <command>' explains one.
+        Usage: show [<address>]
+        Show the current cell or the cell at <address>
+        Usage: mem [<address> | <start>..<end>] [--hex|--ascii|--bin|--dec]
+        Show the cells around the data pointer or <address>, or a range
+        Also: memory
+        Unknown command ':sho'; did you mean ':show'?
+        Unknown command ':nope'; type ':help' for a list
+        ");
+    }
+
+    #[test]
+    fn test_reset() {
+        let transcript = run_script(
+            "+++>++\n:display cell(15000)\n:set max-steps 100\n:reset\n:mem\n:set max-steps\n:history\n:reset all\n:history\n:display\n:set max-steps\n:reset x\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15001: 2
+        1: cell(15000) = 3
+        Tape reset
+        Addr:  14995  14996  14997  14998  14999  15000  15001  15002  15003  15004  15005
+        Data:      0      0      0      0      0      0      0      0      0      0      0
+        Ptrs:                                     ^^^^^                                   
+        Runs stop after 100 steps
+           1  +++>++
+        Session reset
+        Runs have no step limit
+        Usage: reset [all]
+        ");
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("bf-repl-load-{}.bf", std::process::id()));
+        std::fs::write(&path, "Print A\n++++++++[>++++++++<-]>+.\n").unwrap();
+        let transcript = run_script(&format!(":load {}\n+.\n:load\n", path.display()));
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(transcript.replace(&path.display().to_string(), "a.bf"), @r"
+        A
+        15001: 65
+        B
+        15001: 66
+        Usage: load <file>
+        ");
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let transcript = run_script("++[>\n+++<\n-]\n:show 15001\n+[\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        ... ... 15000: 0
+        Cell[15001] = 6 ACK
+        ... 
+        Unmatched '[' at index 1
+        ");
+    }
+
+    #[test]
+    fn test_back() {
+        let transcript = run_script(
+            ":back\n:debug ++[>+<-]\n:watch 15001\n:continue\n:back 2\n:back x\n:back 20\n:back\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        No program is being debugged; start one with 'debug <code>'
+        Step 0: paused at instruction 0 of 8 ('+')
+          ++[>+<-]
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        Watching cell 15001
+        Cell 15001 changed from 0 to 1 by instruction 4
+        Step 5: paused at instruction 5 of 8 ('<')
+          ++[>+<-]
+               ^
+        Addr:  14998  14999  15000  15001  15002  15003  15004
+        Data:      0      0      2      1      0      0      0
+        Ptrs:                       ^^^^^                     
+        Step 3: paused at instruction 3 of 8 ('>')
+          ++[>+<-]
+             ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      2      0      0      0
+        Ptrs:                       ^^^^^                     
+        Usage: back [steps]
+        Step 0: paused at instruction 0 of 8 ('+')
+          ++[>+<-]
+          ^
+        Addr:  14997  14998  14999  15000  15001  15002  15003
+        Data:      0      0      0      0      0      0      0
+        Ptrs:                       ^^^^^                     
+        No earlier state recorded
+        ");
+    }
+
+    #[test]
+    fn test_sessions() {
+        let transcript = run_script(
+            "+++\n:session new scratch\n+\n:session list\n:session switch main\n+\n:session switch nope\n",
+        );
+
+        insta::assert_snapshot!(transcript, @r"
+        15000: 3
+        Switched to session 'scratch'
+        15000: 1
+        * scratch
+          main
+        Switched to session 'main'
+        15000: 4
+        No session named 'nope'
+        ");
+    }
+
+    #[test]
+    fn test_write_session() {
+        let path = std::env::temp_dir().join(format!("bf-repl-session-{}.bf", std::process::id()));
+        let transcript = run_script(&format!(
+            ":write-session {0}\n++\n:show\n]\n:dry +\n>+ add one\n:write-session {0}\n",
+            path.display()
+        ));
+        let file_contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(
+            transcript.replace(&path.display().to_string(), "session.bf"),
+            @r"
+        No code has been run yet
+        15000: 2
+        Cell[15000] = 2 STX
+        Unmatched ']' at index 0
+        Cell 15000: 2 -> 3
+        Changes discarded
+        15001: 1
+        Wrote 2 lines of code to session.bf
+        "
+        );
+        insta::assert_snapshot!(file_contents, @r"
+        ++
+        >+ add one
+        ");
+    }
+
+    #[test]
+    fn test_save() {
+        let path = std::env::temp_dir().join(format!("bf-repl-save-{}.bf", std::process::id()));
+        let transcript = run_script(&format!(
+            "++\n:set max-steps 10\n+[]\n>+\n:save {0}\n:reset\n:save {0}\n",
+            path.display()
+        ));
+        let file_contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        insta::assert_snapshot!(transcript.replace(&path.display().to_string(), "out.bf"), @r"
+        15000: 2
+        Step limit of 10 reached
+        15001: 1
+        Wrote 2 lines of code to out.bf
+        Tape reset
+        No code has been run yet
+        ");
+        insta::assert_snapshot!(file_contents, @r"
+        ++
+        >+
+        ");
+    }
+
+    #[test]
+    fn test_history() {
+        let transcript =
+            run_script("+\n:show\n>++ two\n:history\n:rerun 2\n:edit 1\n:rerun 9\n:edit\n");
+
+        insta::assert_snapshot!(transcript, @r"
+        15000: 1
+        Cell[15000] = 1 SOH
+        15001: 2
+           1  +
+           2  >++ two
+        >++ two
+        15002: 2
+        +
+        No history entry 9
+        Usage: edit <number>
+        ");
+    }
+
+    #[test]
+    fn test_lang() {
+        let transcript = SharedBuffer::default();
+        let mut repl = Repl::new(
+            Box::new(Cursor::new(":jump 0\n<\n]\n:set x\n")),
+            Box::new(transcript.clone()),
+            Box::new(transcript.clone()),
+        );
+        repl.set_lang(Lang::Ja);
+        repl.run().unwrap();
+
+        let bytes = transcript.0.borrow().clone();
+        insta::assert_snapshot!(String::from_utf8(bytes).unwrap(), @r"
+        Brainfuck REPL
+        'exit' で終了、'mem' でメモリの様子を表示します。
+        > Cell[0] = 0 NUL
+        > データポインタがテープの左端を越えました
+        > 位置 0 の ']' に対応する '[' がありません
+        > 値の形式が正しくないか、範囲 (0-255) の外です
+        >
+        ");
+    }
+}
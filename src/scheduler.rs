@@ -0,0 +1,162 @@
+use crate::program::{Program, RunState};
+
+/// Identifies a program spawned on a [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgramId(usize);
+
+struct Task {
+    program: Program,
+    error: Option<String>,
+}
+
+/// Runs many [`Program`]s in one thread by giving each a slice of fuel in
+/// turn.
+///
+/// Each program keeps its own input queue and output buffer, reached through
+/// [`Scheduler::program_mut`]. A program that fails stops being scheduled and
+/// keeps its error for [`Scheduler::error`].
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    quantum: u64,
+}
+
+impl Scheduler {
+    /// Creates a scheduler that runs each program for at most `quantum`
+    /// instructions per turn.
+    pub fn new(quantum: u64) -> Self {
+        Self {
+            tasks: Vec::new(),
+            quantum: quantum.max(1),
+        }
+    }
+
+    pub fn spawn(&mut self, program: Program) -> ProgramId {
+        self.tasks.push(Task {
+            program,
+            error: None,
+        });
+        ProgramId(self.tasks.len() - 1)
+    }
+
+    pub fn program(&self, id: ProgramId) -> &Program {
+        &self.tasks[id.0].program
+    }
+
+    /// Gives access to a program's input queue and output buffer.
+    pub fn program_mut(&mut self, id: ProgramId) -> &mut Program {
+        &mut self.tasks[id.0].program
+    }
+
+    /// Returns the error that stopped a program, if any.
+    pub fn error(&self, id: ProgramId) -> Option<&str> {
+        self.tasks[id.0].error.as_deref()
+    }
+
+    /// Returns true if no program can run until input arrives.
+    pub fn is_idle(&self) -> bool {
+        !self.tasks.iter().any(Task::is_runnable)
+    }
+
+    /// Gives every runnable program one turn, in the order they were
+    /// spawned. Returns the number of instructions executed.
+    pub fn run_round(&mut self) -> u64 {
+        let mut executed = 0;
+
+        for task in self.tasks.iter_mut().filter(|task| task.is_runnable()) {
+            let steps_before = task.program.interpreter().steps();
+            if let Err(e) = task.program.run_for(self.quantum) {
+                task.error = Some(e);
+            }
+            executed += task.program.interpreter().steps() - steps_before;
+        }
+
+        executed
+    }
+
+    /// Runs rounds until every program is halted, failed, or waiting for
+    /// input, or until `max_steps` instructions have run in total (checked
+    /// between rounds). Returns the number of instructions executed.
+    pub fn run_until_idle(&mut self, max_steps: u64) -> u64 {
+        let mut executed = 0;
+
+        while executed < max_steps && !self.is_idle() {
+            executed += self.run_round();
+        }
+
+        executed
+    }
+}
+
+impl Task {
+    fn is_runnable(&self) -> bool {
+        self.error.is_none() && self.program.state() == RunState::Running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InterpreterBuilder;
+
+    #[test]
+    fn test_round_robin() {
+        let mut scheduler = Scheduler::new(4);
+        let echo = scheduler.spawn(Program::new(",[.,]").unwrap());
+        let count = scheduler.spawn(Program::new("+++[.-]").unwrap());
+        let small_tape = InterpreterBuilder::default().tape_size(8).build();
+        let fail = scheduler.spawn(Program::with_interpreter("+[<+]", small_tape).unwrap());
+
+        let mut rounds = vec![scheduler.run_round(), scheduler.run_until_idle(1000)];
+        scheduler.program_mut(echo).push_input(b"ok");
+        scheduler.program_mut(echo).close_input();
+        rounds.push(scheduler.run_until_idle(1000));
+
+        let results: Vec<_> = [echo, count, fail]
+            .into_iter()
+            .map(|id| {
+                (
+                    scheduler.program(id).state(),
+                    scheduler.error(id).map(str::to_string),
+                    scheduler.program_mut(id).take_output(),
+                )
+            })
+            .collect();
+
+        insta::assert_debug_snapshot!((rounds, scheduler.is_idle(), results), @r#"
+        (
+            [
+                8,
+                19,
+                8,
+            ],
+            true,
+            [
+                (
+                    Halted,
+                    None,
+                    [
+                        111,
+                        107,
+                    ],
+                ),
+                (
+                    Halted,
+                    None,
+                    [
+                        3,
+                        2,
+                        1,
+                    ],
+                ),
+                (
+                    Running,
+                    Some(
+                        "Data pointer out of bounds (left)",
+                    ),
+                    [],
+                ),
+            ],
+        )
+        "#);
+    }
+}
@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use crate::repl::parse_range;
+
+/// A byte sequence to look for on the tape and the addresses to search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    pub pattern: Vec<u8>,
+    pub range: Range<usize>,
+}
+
+impl Search {
+    /// Parses `<byte|"text"> [start..end]`, where `end` is excluded and the
+    /// whole tape is searched by default. Text may use the escapes `\n`,
+    /// `\t`, `\0`, `\"`, and `\\`.
+    pub fn parse(query: &str, memory_size: usize) -> Result<Self, String> {
+        let query = query.trim();
+        let (pattern, rest) = match query.strip_prefix('"') {
+            Some(text) => parse_text(text)?,
+            None => {
+                let (value, rest) = query.split_once(char::is_whitespace).unwrap_or((query, ""));
+                let byte = value
+                    .parse::<u8>()
+                    .map_err(|_| format!("Invalid byte '{}' (expected 0-255)", value))?;
+                (vec![byte], rest)
+            }
+        };
+
+        if pattern.is_empty() {
+            return Err("Cannot search for empty text".to_string());
+        }
+
+        let range = match rest.trim() {
+            "" => 0..memory_size,
+            range => parse_range(range, memory_size)?,
+        };
+
+        Ok(Self { pattern, range })
+    }
+
+    /// Returns the address of every match that lies within the range,
+    /// including overlapping ones.
+    pub fn find(&self, memory: &[u8]) -> Vec<usize> {
+        memory[self.range.clone()]
+            .windows(self.pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == self.pattern)
+            .map(|(offset, _)| self.range.start + offset)
+            .collect()
+    }
+}
+
+/// Parses quoted text up to its closing quote, returning its bytes and what
+/// follows it.
+fn parse_text(text: &str) -> Result<(Vec<u8>, &str), String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((bytes, &text[i + 1..])),
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => b'\n',
+                    Some((_, 't')) => b'\t',
+                    Some((_, '0')) => 0,
+                    Some((_, '"')) => b'"',
+                    Some((_, '\\')) => b'\\',
+                    Some((_, other)) => return Err(format!("Unknown escape '\\{}'", other)),
+                    None => break,
+                };
+                bytes.push(escaped);
+            }
+            _ => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    Err("Unterminated text (missing closing '\"')".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut memory = vec![0u8; 16];
+        memory[2..5].copy_from_slice(b"aaa");
+        memory[8..11].copy_from_slice(b"a\"\n");
+
+        let results: Vec<_> = [
+            "97",
+            "\"aa\"",
+            "97 4..16",
+            "\"a\\\"\\n\"",
+            "0 10..12",
+            "256",
+            "\"\"",
+            "\"abc",
+            "1 9..3",
+            "1 0..17",
+        ]
+        .iter()
+        .map(|query| {
+            (
+                query,
+                Search::parse(query, memory.len()).map(|s| s.find(&memory)),
+            )
+        })
+        .collect();
+
+        insta::assert_debug_snapshot!(results, @r#"
+        [
+            (
+                "97",
+                Ok(
+                    [
+                        2,
+                        3,
+                        4,
+                        8,
+                    ],
+                ),
+            ),
+            (
+                "\"aa\"",
+                Ok(
+                    [
+                        2,
+                        3,
+                    ],
+                ),
+            ),
+            (
+                "97 4..16",
+                Ok(
+                    [
+                        4,
+                        8,
+                    ],
+                ),
+            ),
+            (
+                "\"a\\\"\\n\"",
+                Ok(
+                    [
+                        8,
+                    ],
+                ),
+            ),
+            (
+                "0 10..12",
+                Ok(
+                    [
+                        11,
+                    ],
+                ),
+            ),
+            (
+                "256",
+                Err(
+                    "Invalid byte '256' (expected 0-255)",
+                ),
+            ),
+            (
+                "\"\"",
+                Err(
+                    "Cannot search for empty text",
+                ),
+            ),
+            (
+                "\"abc",
+                Err(
+                    "Unterminated text (missing closing '\"')",
+                ),
+            ),
+            (
+                "1 9..3",
+                Err(
+                    "Invalid range 9..3",
+                ),
+            ),
+            (
+                "1 0..17",
+                Err(
+                    "Address 17 is out of bounds (0-16)",
+                ),
+            ),
+        ]
+        "#);
+    }
+}
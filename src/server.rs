@@ -0,0 +1,97 @@
+use std::io::{self, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::InterpreterBuilder;
+use crate::metrics::{self, Metrics};
+use crate::repl::Repl;
+
+/// How many instructions a run in a network session may execute.
+const SESSION_MAX_STEPS: u64 = 100_000_000;
+/// How long a run in a network session may take.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a session may wait for its next line before it is closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+/// How many sessions may be open at once; later connections are turned away.
+const MAX_SESSIONS: usize = 64;
+
+/// Serves one REPL session, with its own interpreter from `builder`, per TCP
+/// connection.
+///
+/// Sessions are restricted: they cannot touch the server's files or
+/// clipboard, and each run stops after [`SESSION_MAX_STEPS`] instructions or
+/// [`SESSION_TIMEOUT`], or sooner if `builder` says so. At most
+/// [`MAX_SESSIONS`] are open at once, and each closes after [`IDLE_TIMEOUT`]
+/// without input.
+///
+/// With `metrics_addr`, counters for all sessions are served over HTTP at
+/// `/metrics` on that address.
+pub fn serve(
+    addr: &str,
+    metrics_addr: Option<&str>,
+    builder: InterpreterBuilder,
+) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+
+    let metrics = Arc::new(Metrics::default());
+    if let Some(metrics_addr) = metrics_addr {
+        metrics::serve(metrics_addr, Arc::clone(&metrics))?;
+    }
+
+    let builder = builder.limit_runs(SESSION_MAX_STEPS, SESSION_TIMEOUT);
+
+    eprintln!("Serving the Brainfuck REPL on {}", local_addr);
+
+    let open_sessions = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                if open_sessions.fetch_add(1, Ordering::Relaxed) >= MAX_SESSIONS {
+                    open_sessions.fetch_sub(1, Ordering::Relaxed);
+                    let _ = writeln!(stream, "Too many sessions; try again later");
+                    continue;
+                }
+                let open_sessions = Arc::clone(&open_sessions);
+                let metrics = Arc::clone(&metrics);
+                let builder = builder.clone();
+                thread::spawn(move || {
+                    handle_connection(stream, builder, metrics);
+                    open_sessions.fetch_sub(1, Ordering::Relaxed);
+                });
+            }
+            Err(e) => eprintln!("Connection failed: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, builder: InterpreterBuilder, metrics: Arc<Metrics>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown peer".to_string(), |addr| addr.to_string());
+
+    eprintln!("{} connected", peer);
+
+    match open_session(stream, builder).and_then(|repl| repl.with_metrics(metrics).run()) {
+        Ok(()) => eprintln!("{} disconnected", peer),
+        Err(e) => eprintln!("{} disconnected: {}", peer, e),
+    }
+}
+
+fn open_session(stream: TcpStream, builder: InterpreterBuilder) -> io::Result<Repl> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let input = BufReader::new(stream.try_clone()?);
+    let output = stream.try_clone()?;
+
+    let mut repl =
+        Repl::new(Box::new(input), Box::new(output), Box::new(stream)).with_interpreter(builder);
+    repl.set_input_prompt(true);
+    repl.set_restricted(true);
+    Ok(repl)
+}
@@ -0,0 +1,67 @@
+use std::fs;
+
+use crate::gzip;
+
+/// Reads a program file, decompressing gzip data and decoding it to text.
+pub fn read_source(filename: &str) -> Result<String, String> {
+    let bytes = fs::read(filename).map_err(|e| format!("Cannot read {}: {}", filename, e))?;
+
+    let bytes = if gzip::is_gzip(&bytes) {
+        gzip::decompress(&bytes).map_err(|e| format!("{}: {}", filename, e))?
+    } else {
+        bytes
+    };
+
+    Ok(decode_source(&bytes))
+}
+
+/// Decodes source bytes, honoring UTF-8 and UTF-16 byte order marks.
+///
+/// Anything that is not valid UTF-8 is decoded lossily: only the eight
+/// command characters matter, so damaged comments are harmless.
+pub fn decode_source(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        String::from_utf8_lossy(rest).into_owned()
+    } else if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        decode_utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        decode_utf16(rest, u16::from_be_bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_source() {
+        insta::assert_debug_snapshot!(
+            [
+                &b"\xef\xbb\xbf+[-]"[..],
+                b"\xff\xfe+\x00.\x00",
+                b"\xfe\xff\x00+\x00.",
+                b"caf\xe9 +.",
+            ]
+            .map(decode_source),
+            @r#"
+        [
+            "+[-]",
+            "+.",
+            "+.",
+            "caf� +.",
+        ]
+        "#
+        );
+    }
+}
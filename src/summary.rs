@@ -0,0 +1,155 @@
+use crate::cell::Cell;
+use crate::{Interpreter, ascii};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    DataPointer,
+    Cell,
+    Char,
+    Steps,
+    InstructionPointer,
+    Input,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 6] = [
+        ("dp", Field::DataPointer),
+        ("cell", Field::Cell),
+        ("char", Field::Char),
+        ("steps", Field::Steps),
+        ("ip", Field::InstructionPointer),
+        ("input", Field::Input),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|&(_, field)| field)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+/// A user-defined template for the summary printed after a run.
+///
+/// Placeholders such as `{dp}` are replaced with interpreter state; `{{` and
+/// `}}` produce literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryFormat {
+    segments: Vec<Segment>,
+}
+
+impl SummaryFormat {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or("Unclosed '{' in summary format")?;
+                    let field = Field::from_name(name).ok_or_else(|| {
+                        let names: Vec<&str> = Field::NAMES.iter().map(|(name, _)| *name).collect();
+                        format!(
+                            "Unknown summary field '{{{}}}' (expected one of {})",
+                            name,
+                            names.join(", ")
+                        )
+                    })?;
+                    if !text.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(Segment::Field(field));
+                    chars = rest.chars();
+                }
+                '}' => return Err("Unmatched '}' in summary format".to_string()),
+                _ => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn render<C: Cell>(&self, interpreter: &Interpreter<C>) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Field(Field::DataPointer) => interpreter.data_pointer.to_string(),
+                Segment::Field(Field::Cell) => {
+                    interpreter.memory[interpreter.data_pointer].to_string()
+                }
+                Segment::Field(Field::Char) => interpreter.memory[interpreter.data_pointer]
+                    .try_to_byte()
+                    .and_then(ascii::describe)
+                    .unwrap_or_default(),
+                Segment::Field(Field::Steps) => interpreter.steps.to_string(),
+                Segment::Field(Field::InstructionPointer) => {
+                    interpreter.instruction_pointer.to_string()
+                }
+                Segment::Field(Field::Input) => interpreter.input_position.to_string(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_loops, tokenize};
+
+    #[test]
+    fn test_render_summary() {
+        let format =
+            SummaryFormat::parse("dp={dp} cell={cell} {char} steps={steps} {{ok}}").unwrap();
+        let tokens = tokenize("+++>++");
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b""[..], &mut Vec::new())
+            .unwrap();
+
+        insta::assert_snapshot!(format.render(&interpreter), @"dp=15001 cell=2 STX steps=6 {ok}");
+    }
+
+    #[test]
+    fn test_parse_summary_errors() {
+        insta::assert_debug_snapshot!(
+            ["{dp", "{pc}", "a}b"].map(SummaryFormat::parse),
+            @r#"
+        [
+            Err(
+                "Unclosed '{' in summary format",
+            ),
+            Err(
+                "Unknown summary field '{pc}' (expected one of dp, cell, char, steps, ip, input)",
+            ),
+            Err(
+                "Unmatched '}' in summary format",
+            ),
+        ]
+        "#
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs;
+
+use crate::cell::Cell;
+use crate::gif;
+
+/// Cells per row of a frame.
+const COLUMNS: usize = 64;
+/// Pixels per side of a cell.
+const SCALE: usize = 4;
+/// Frames kept before every other one is dropped and sampling slows down.
+const MAX_FRAMES: usize = 1000;
+/// Hundredths of a second each frame is shown for.
+const FRAME_DELAY: u16 = 5;
+/// The palette index marking the data pointer; the others are grays.
+const POINTER_COLOR: u8 = 255;
+
+/// The cells visited so far and the data pointer at one moment of a run.
+#[derive(Debug, Clone)]
+struct Frame {
+    start: usize,
+    cells: Vec<u8>,
+    data_pointer: usize,
+}
+
+/// Snapshots of the tape taken every `interval` steps of a run, rendered as
+/// an animated GIF or SVG.
+///
+/// Only the cells between the leftmost and rightmost data pointer positions
+/// seen so far are kept; the pointer can only have changed those.
+#[derive(Debug, Clone)]
+pub struct TapeRecording {
+    /// The interval asked for, before any slowing down.
+    base_interval: u64,
+    interval: u64,
+    frames: Vec<Frame>,
+    visited: Option<(usize, usize)>,
+}
+
+impl TapeRecording {
+    pub fn new(interval: u64) -> Self {
+        Self {
+            base_interval: interval.max(1),
+            interval: interval.max(1),
+            frames: Vec::new(),
+            visited: None,
+        }
+    }
+
+    /// Discards the frames, for a new run.
+    pub fn clear(&mut self) {
+        *self = Self::new(self.base_interval);
+    }
+
+    /// Records a frame if `steps` falls on the sampling interval.
+    pub fn observe<C: Cell>(&mut self, steps: u64, memory: &[C], data_pointer: usize) {
+        self.visit(data_pointer);
+        if steps.is_multiple_of(self.interval) {
+            self.capture(memory, data_pointer);
+        }
+    }
+
+    /// Records a frame regardless of the interval, as at the end of a run.
+    /// Cells wider than a byte are recorded as their low eight bits.
+    pub fn capture<C: Cell>(&mut self, memory: &[C], data_pointer: usize) {
+        self.visit(data_pointer);
+        let (start, end) = self.visited.expect("just visited");
+
+        if self.frames.len() == MAX_FRAMES {
+            let mut index = 0;
+            self.frames.retain(|_| {
+                index += 1;
+                index % 2 == 1
+            });
+            self.interval *= 2;
+        }
+
+        self.frames.push(Frame {
+            start,
+            cells: memory[start..=end].iter().map(Cell::to_byte).collect(),
+            data_pointer,
+        });
+    }
+
+    fn visit(&mut self, data_pointer: usize) {
+        self.visited = Some(match self.visited {
+            Some((start, end)) => (start.min(data_pointer), end.max(data_pointer)),
+            None => (data_pointer, data_pointer),
+        });
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the number of cells drawn, and the palette index of each in
+    /// every frame: the cell's value, or `POINTER_COLOR` under the data
+    /// pointer.
+    fn frame_colors(&self) -> (usize, Vec<Vec<u8>>) {
+        let (start, end) = self.visited.unwrap_or_default();
+        let cell_count = end - start + 1;
+        let colors = self
+            .frames
+            .iter()
+            .map(|frame| {
+                (start..=end)
+                    .map(|address| {
+                        if address == frame.data_pointer {
+                            POINTER_COLOR
+                        } else {
+                            address
+                                .checked_sub(frame.start)
+                                .and_then(|i| frame.cells.get(i))
+                                .map_or(0, |&value| value.min(POINTER_COLOR - 1))
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        (cell_count, colors)
+    }
+
+    /// Renders the frames as an animated GIF, `COLUMNS` cells to a row.
+    /// Brighter pixels are larger values and the data pointer is red.
+    pub fn render(&self) -> Vec<u8> {
+        let (cell_count, colors) = self.frame_colors();
+        let rows = cell_count.div_ceil(COLUMNS);
+        let columns = cell_count.min(COLUMNS);
+        let (width, height) = (columns * SCALE, rows * SCALE);
+
+        let mut palette = [[0u8; 3]; 256];
+        for (value, color) in palette.iter_mut().enumerate() {
+            *color = [value as u8; 3];
+        }
+        palette[POINTER_COLOR as usize] = [255, 0, 0];
+
+        let frames: Vec<Vec<u8>> = colors
+            .iter()
+            .map(|colors| {
+                let mut pixels = vec![0; width * height];
+                for (cell, &color) in colors.iter().enumerate() {
+                    let (x, y) = (cell % COLUMNS * SCALE, cell / COLUMNS * SCALE);
+                    for row in y..y + SCALE {
+                        pixels[row * width + x..row * width + x + SCALE].fill(color);
+                    }
+                }
+                pixels
+            })
+            .collect();
+
+        gif::encode(width as u16, height as u16, &palette, &frames, FRAME_DELAY)
+    }
+
+    /// Renders the frames as an SVG drawn like [`render`](Self::render)'s
+    /// GIF, which shows each frame in turn and loops. Being vector graphics,
+    /// it stays sharp at any size in slides and web pages.
+    pub fn render_svg(&self) -> String {
+        let (cell_count, colors) = self.frame_colors();
+        let rows = cell_count.div_ceil(COLUMNS);
+        let columns = cell_count.min(COLUMNS);
+        let delay = u64::from(FRAME_DELAY) * 10;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">\n",
+            columns * SCALE,
+            rows * SCALE,
+            columns,
+            rows
+        );
+        // Every frame is hidden except during its own slice of the loop.
+        svg.push_str(&format!(
+            "<style>g{{visibility:hidden;animation:frame {}ms step-end infinite}}\
+             @keyframes frame{{0%{{visibility:visible}}{:.3}%{{visibility:hidden}}}}</style>\n",
+            delay * colors.len() as u64,
+            100.0 / colors.len().max(1) as f64
+        ));
+        for (index, colors) in colors.iter().enumerate() {
+            svg.push_str(&format!(
+                "<g style=\"animation-delay:{}ms\">",
+                delay * index as u64
+            ));
+            for (cell, &color) in colors.iter().enumerate() {
+                let fill = match color {
+                    POINTER_COLOR => "#ff0000".to_string(),
+                    value => format!("#{:02x}{:02x}{:02x}", value, value, value),
+                };
+                svg.push_str(&format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"{}\"/>",
+                    cell % COLUMNS,
+                    cell / COLUMNS,
+                    fill
+                ));
+            }
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.render()).map_err(|e| format!("Cannot write {}: {}", path, e))
+    }
+
+    pub fn save_svg(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.render_svg()).map_err(|e| format!("Cannot write {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording() {
+        let mut recording = TapeRecording::new(2);
+        let mut memory = [0u8; 8];
+        for (steps, pointer) in [(1, 3), (2, 4), (3, 5), (4, 4)] {
+            memory[pointer] += 100;
+            recording.observe(steps, &memory, pointer);
+        }
+        recording.capture(&memory, 4);
+
+        let frames: Vec<_> = recording
+            .frames
+            .iter()
+            .map(|frame| (frame.start, frame.cells.clone(), frame.data_pointer))
+            .collect();
+        let gif = recording.render();
+
+        let header = (
+            String::from_utf8_lossy(&gif[..6]),
+            u16::from_le_bytes([gif[6], gif[7]]),
+            u16::from_le_bytes([gif[8], gif[9]]),
+        );
+
+        insta::assert_snapshot!(recording.render_svg(), @r##"
+        <svg xmlns="http://www.w3.org/2000/svg" width="12" height="4" viewBox="0 0 3 1" shape-rendering="crispEdges">
+        <style>g{visibility:hidden;animation:frame 150ms step-end infinite}@keyframes frame{0%{visibility:visible}33.333%{visibility:hidden}}</style>
+        <g style="animation-delay:0ms"><rect x="0" y="0" width="1" height="1" fill="#646464"/><rect x="1" y="0" width="1" height="1" fill="#ff0000"/><rect x="2" y="0" width="1" height="1" fill="#000000"/></g>
+        <g style="animation-delay:50ms"><rect x="0" y="0" width="1" height="1" fill="#646464"/><rect x="1" y="0" width="1" height="1" fill="#ff0000"/><rect x="2" y="0" width="1" height="1" fill="#646464"/></g>
+        <g style="animation-delay:100ms"><rect x="0" y="0" width="1" height="1" fill="#646464"/><rect x="1" y="0" width="1" height="1" fill="#ff0000"/><rect x="2" y="0" width="1" height="1" fill="#646464"/></g>
+        </svg>
+        "##);
+
+        insta::assert_debug_snapshot!((frames, header), @r#"
+        (
+            [
+                (
+                    3,
+                    [
+                        100,
+                        100,
+                    ],
+                    4,
+                ),
+                (
+                    3,
+                    [
+                        100,
+                        200,
+                        100,
+                    ],
+                    4,
+                ),
+                (
+                    3,
+                    [
+                        100,
+                        200,
+                        100,
+                    ],
+                    4,
+                ),
+            ],
+            (
+                "GIF89a",
+                12,
+                4,
+            ),
+        )
+        "#);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::Token;
+
+/// How many instructions a trace records unless told otherwise.
+pub const DEFAULT_LIMIT: u64 = 1_000_000;
+
+/// How a [`Trace`] writes each instruction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Tab-separated lines under a `step ip op dp cell` header.
+    #[default]
+    Text,
+    /// One JSON record per line, such as
+    /// `{"step":1,"ip":0,"op":"+","dp":15000,"cell":1}`, for other tools.
+    Jsonl,
+}
+
+impl TraceFormat {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(format!(
+                "Unknown trace format '{}' (expected 'text' or 'jsonl')",
+                name
+            )),
+        }
+    }
+}
+
+/// A log of every instruction a program executes, giving the step number
+/// (from 1), the index of the instruction, the instruction, and the data
+/// pointer and current cell after it ran.
+///
+/// Recording stops after `limit` instructions with a line saying so, but
+/// the program keeps running. Clones share the same trace.
+#[derive(Debug, Clone)]
+pub struct Trace(Arc<Mutex<TraceFile>>);
+
+#[derive(Debug)]
+struct TraceFile {
+    out: BufWriter<File>,
+    format: TraceFormat,
+    limit: u64,
+    recorded: u64,
+}
+
+impl Trace {
+    pub fn create(path: &str, format: TraceFormat, limit: u64) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        if format == TraceFormat::Text {
+            writeln!(out, "step\tip\top\tdp\tcell")
+                .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        }
+        Ok(Self(Arc::new(Mutex::new(TraceFile {
+            out,
+            format,
+            limit,
+            recorded: 0,
+        }))))
+    }
+
+    /// Records that step `step` executed `token`, the instruction at
+    /// `token_index`, leaving the data pointer at `data_pointer` on `cell`.
+    pub fn record(
+        &self,
+        step: u64,
+        token_index: usize,
+        token: Token,
+        data_pointer: usize,
+        cell: &impl Display,
+    ) -> Result<(), String> {
+        let mut trace = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if trace.recorded > trace.limit {
+            return Ok(());
+        }
+        trace.recorded += 1;
+
+        let limit = trace.limit;
+        match (trace.format, trace.recorded > limit) {
+            (TraceFormat::Text, true) => {
+                writeln!(trace.out, "Trace stopped after {} instructions", limit)
+                    .and_then(|_| trace.out.flush())
+            }
+            (TraceFormat::Jsonl, true) => {
+                writeln!(trace.out, "{{\"stopped\":true,\"limit\":{}}}", limit)
+                    .and_then(|_| trace.out.flush())
+            }
+            (TraceFormat::Text, false) => writeln!(
+                trace.out,
+                "{}\t{}\t{}\t{}\t{}",
+                step,
+                token_index,
+                token.symbol(),
+                data_pointer,
+                cell
+            ),
+            (TraceFormat::Jsonl, false) => writeln!(
+                trace.out,
+                "{{\"step\":{},\"ip\":{},\"op\":\"{}\",\"dp\":{},\"cell\":{}}}",
+                step,
+                token_index,
+                token.symbol(),
+                data_pointer,
+                cell
+            ),
+        }
+        .map_err(|e| format!("Cannot write trace: {}", e))
+    }
+
+    /// Writes out buffered lines, so the trace is complete up to now.
+    pub fn flush(&self) -> Result<(), String> {
+        let mut trace = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        trace
+            .out
+            .flush()
+            .map_err(|e| format!("Cannot write trace: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Interpreter, parse_loops, tokenize};
+
+    use super::*;
+
+    /// Runs `code` with a trace in `format` that stops after `limit`
+    /// instructions, and returns the trace.
+    fn trace(code: &str, format: TraceFormat, limit: u64) -> String {
+        let path =
+            std::env::temp_dir().join(format!("bf-repl-trace-{}-{:?}", std::process::id(), format));
+        let trace = Trace::create(path.to_str().unwrap(), format, limit).unwrap();
+        let tokens = tokenize(code);
+        let jump_table = parse_loops(&tokens).unwrap();
+        let mut interpreter = Interpreter::builder().trace(Some(trace)).build();
+        interpreter
+            .run_with_io(&tokens, &jump_table, &b""[..], &mut Vec::new())
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_trace() {
+        insta::assert_snapshot!(trace("++[>+<-]>.", TraceFormat::Text, 8), @r"
+        step	ip	op	dp	cell
+        1	0	+	15000	1
+        2	1	+	15000	2
+        3	2	[	15000	2
+        4	3	>	15001	0
+        5	4	+	15001	1
+        6	5	<	15000	2
+        7	6	-	15000	1
+        8	7	]	15000	1
+        Trace stopped after 8 instructions
+        ");
+    }
+
+    #[test]
+    fn test_jsonl_trace() {
+        insta::assert_snapshot!(trace("+[-]", TraceFormat::Jsonl, 3), @r#"
+        {"step":1,"ip":0,"op":"+","dp":15000,"cell":1}
+        {"step":2,"ip":1,"op":"[","dp":15000,"cell":1}
+        {"step":3,"ip":2,"op":"-","dp":15000,"cell":0}
+        {"stopped":true,"limit":3}
+        "#);
+    }
+}
@@ -0,0 +1,426 @@
+//! `--tui`: runs a program full-screen, showing its source with the next
+//! instruction highlighted, the cells around the data pointer, and its
+//! output, all updated as it runs.
+
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::diagnostic::line_column;
+use crate::line_editor::{self, RawMode};
+use crate::{Extensions, Interpreter, JumpTable, Token, parse_loops, token_for, tokenize_with};
+
+/// How many instructions a second run unless told otherwise.
+pub const DEFAULT_SPEED: u64 = 20;
+const MAX_SPEED: u64 = 1 << 24;
+/// How many times a second the screen is redrawn at most.
+const FRAME_RATE: u64 = 30;
+
+const CTRL_C: u8 = 3;
+const CTRL_D: u8 = 4;
+const HIGHLIGHT: &str = "\x1b[7m";
+const NORMAL: &str = "\x1b[0m";
+
+/// Runs `code` full-screen at `speed` instructions a second.
+///
+/// Space pauses and resumes, `s` runs one instruction and pauses, `+` and
+/// `-` double and halve the speed, and `q` or Ctrl-C quits. Keys typed
+/// while the program waits at a `,` are its input, with Ctrl-D for the end
+/// of input. The output is printed again after leaving the screen.
+pub fn run_tui(code: &str, interpreter: Interpreter, speed: u64) -> Result<(), String> {
+    let tokens = tokenize_with(code, interpreter.extensions());
+    let jump_table = parse_loops(&tokens)?;
+    let raw_mode = RawMode::enable().map_err(|_| "--tui needs an interactive terminal")?;
+
+    let mut tui = Tui {
+        code,
+        offsets: instruction_offsets(code, interpreter.extensions()),
+        tokens,
+        jump_table,
+        interpreter,
+        output: Vec::new(),
+        keys: spawn_key_reader(),
+        speed: speed.clamp(1, MAX_SPEED),
+        paused: false,
+    };
+
+    let mut out = io::stdout().lock();
+    let result = write!(out, "\x1b[?1049h\x1b[?25l")
+        .map_err(|e| e.to_string())
+        .and_then(|_| tui.run(&mut out));
+    let _ = write!(out, "\x1b[?25h\x1b[?1049l");
+    drop(raw_mode);
+
+    let _ = out.write_all(&tui.output);
+    let _ = out.flush();
+    result
+}
+
+/// Reads keys on a thread of its own, so the program keeps running between
+/// them.
+fn spawn_key_reader() -> Receiver<u8> {
+    let (sender, keys) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(Some(key)) = line_editor::read_byte() {
+            if sender.send(key).is_err() {
+                break;
+            }
+        }
+    });
+    keys
+}
+
+/// Returns the byte offset in `code` of each instruction.
+fn instruction_offsets(code: &str, extensions: Extensions) -> Vec<usize> {
+    code.char_indices()
+        .filter(|&(_, c)| token_for(c, extensions).is_some())
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+struct Tui<'a> {
+    code: &'a str,
+    offsets: Vec<usize>,
+    tokens: Vec<Token>,
+    jump_table: JumpTable,
+    interpreter: Interpreter,
+    /// The program's output, with the input typed for it echoed.
+    output: Vec<u8>,
+    keys: Receiver<u8>,
+    speed: u64,
+    paused: bool,
+}
+
+/// What a key asks for.
+enum Action {
+    Continue,
+    Step,
+    Quit,
+}
+
+impl Tui<'_> {
+    fn run(&mut self, out: &mut dyn Write) -> Result<(), String> {
+        self.interpreter.instruction_pointer = 0;
+        self.interpreter.steps = 0;
+
+        let mut step = false;
+        let end = loop {
+            if self.interpreter.instruction_pointer >= self.tokens.len() {
+                break Ok(format!("Finished after {} steps", self.interpreter.steps()));
+            }
+
+            // Slow speeds run one instruction a frame; fast ones run many
+            // and redraw FRAME_RATE times a second.
+            let (batch, frame) = match (step, self.paused) {
+                (true, _) => (1, Duration::ZERO),
+                (false, true) => (0, Duration::ZERO),
+                (false, false) if self.speed <= FRAME_RATE => {
+                    (1, Duration::from_secs(1) / self.speed as u32)
+                }
+                (false, false) => (
+                    self.speed / FRAME_RATE,
+                    Duration::from_secs(1) / FRAME_RATE as u32,
+                ),
+            };
+            let deadline = Instant::now() + frame;
+            step = false;
+
+            if let Err(e) = self.run_batch(batch, out) {
+                break Err(e);
+            }
+            self.draw(out, &self.state()).map_err(|e| e.to_string())?;
+
+            // Handle keys until the next frame is due, or until one arrives
+            // while paused.
+            loop {
+                let key = if self.paused {
+                    self.keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                } else {
+                    self.keys
+                        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                };
+                match key.map(|key| self.handle_key(key)) {
+                    Ok(Action::Quit) => return Ok(()),
+                    Ok(Action::Step) => {
+                        step = true;
+                        break;
+                    }
+                    Ok(Action::Continue) if self.paused => break,
+                    Ok(Action::Continue) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    // Without a keyboard there is no way to resume.
+                    Err(RecvTimeoutError::Disconnected) if self.paused => return Ok(()),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                        break;
+                    }
+                }
+            }
+        };
+
+        let status = match &end {
+            Ok(status) => status.clone(),
+            Err(e) => e.clone(),
+        };
+        self.draw(out, &format!("{}; press any key to exit", status))
+            .map_err(|e| e.to_string())?;
+        let _ = self.keys.recv();
+        end.map(|_| ())
+    }
+
+    /// Runs up to `count` instructions, stopping at the end of the program.
+    fn run_batch(&mut self, count: u64, out: &mut dyn Write) -> Result<(), String> {
+        for _ in 0..count {
+            let Some(&token) = self.tokens.get(self.interpreter.instruction_pointer) else {
+                break;
+            };
+            if token == Token::Input {
+                self.draw(out, "Waiting for input (Ctrl-D ends it)")
+                    .map_err(|e| e.to_string())?;
+            }
+
+            let mut input = KeyInput {
+                keys: &self.keys,
+                echo: Vec::new(),
+            };
+            let mut output = Vec::new();
+            let result =
+                self.interpreter
+                    .step(&self.tokens, &self.jump_table, &mut input, &mut output);
+            self.output.extend(input.echo);
+            self.output.extend(output);
+            result?;
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: u8) -> Action {
+        match key {
+            b' ' => self.paused = !self.paused,
+            b's' => {
+                self.paused = true;
+                return Action::Step;
+            }
+            b'+' | b'=' => self.speed = (self.speed * 2).min(MAX_SPEED),
+            b'-' => self.speed = (self.speed / 2).max(1),
+            b'q' | CTRL_C => return Action::Quit,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn state(&self) -> String {
+        format!(
+            "{} · step {} · {} steps/s · space pause · s step · +/- speed · q quit",
+            if self.paused { "Paused" } else { "Running" },
+            self.interpreter.steps(),
+            self.speed
+        )
+    }
+
+    fn draw(&self, out: &mut dyn Write, status: &str) -> io::Result<()> {
+        let (width, height) = line_editor::terminal_size();
+        let screen = Screen {
+            code: self.code,
+            offset: self
+                .offsets
+                .get(self.interpreter.instruction_pointer)
+                .copied(),
+            memory: &self.interpreter.memory,
+            data_pointer: self.interpreter.data_pointer,
+            output: &self.output,
+            status,
+        };
+        let lines = screen.render(width, height);
+        write!(out, "\x1b[H{}\x1b[K\x1b[J", lines.join("\x1b[K\r\n"))?;
+        out.flush()
+    }
+}
+
+/// Hands the program one key per `,`, echoing it like a terminal would.
+struct KeyInput<'a> {
+    keys: &'a Receiver<u8>,
+    echo: Vec<u8>,
+}
+
+impl Read for KeyInput<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        match self.keys.recv() {
+            Ok(CTRL_C) => Err(io::Error::new(io::ErrorKind::Interrupted, "Interrupted")),
+            Ok(CTRL_D) | Err(_) => Ok(0),
+            Ok(key) => {
+                // Enter sends a carriage return in raw mode.
+                buf[0] = if key == b'\r' { b'\n' } else { key };
+                self.echo.push(buf[0]);
+                Ok(1)
+            }
+        }
+    }
+}
+
+/// One frame of the view.
+struct Screen<'a> {
+    code: &'a str,
+    /// The byte offset in `code` of the next instruction, if any.
+    offset: Option<usize>,
+    memory: &'a [u8],
+    data_pointer: usize,
+    output: &'a [u8],
+    status: &'a str,
+}
+
+impl Screen<'_> {
+    /// Lays out the lines of a `width` by `height` screen: the source
+    /// around the next instruction, the cells around the data pointer, the
+    /// end of the output, and a status line.
+    fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let panes = height.saturating_sub(6).max(2);
+        let source_height = panes.div_ceil(2);
+        let output_height = panes - source_height;
+
+        let mut lines = vec![header("Source", width)];
+        lines.extend(self.source(width, source_height));
+        lines.push(header("Tape", width));
+        lines.extend(self.tape(width));
+        lines.push(header("Output", width));
+        lines.extend(self.output(width, output_height));
+        lines.push(format!(
+            "{}{:<width$}{}",
+            HIGHLIGHT,
+            self.status.chars().take(width).collect::<String>(),
+            NORMAL
+        ));
+        lines
+    }
+
+    fn source(&self, width: usize, height: usize) -> Vec<String> {
+        let code_lines: Vec<&str> = self.code.split('\n').collect();
+        let (line, column) = match self.offset {
+            Some(offset) => line_column(self.code, offset),
+            None => (code_lines.len(), 0),
+        };
+
+        // Scroll sideways far enough to show the next instruction.
+        let scroll = match column {
+            column if column > width => column - width / 2,
+            _ => 0,
+        };
+        let first = (line - 1)
+            .saturating_sub(height / 2)
+            .min(code_lines.len().saturating_sub(height));
+
+        let mut lines: Vec<String> = code_lines
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(height)
+            .map(|(index, text)| {
+                let marked = (self.offset.is_some() && index + 1 == line).then(|| column - 1);
+                let chars = text
+                    .chars()
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .enumerate()
+                    .skip(scroll)
+                    .take(width);
+                chars
+                    .map(|(i, c)| match marked {
+                        Some(marked) if marked == i => format!("{}{}{}", HIGHLIGHT, c, NORMAL),
+                        _ => c.to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+        lines.resize(height, String::new());
+        lines
+    }
+
+    /// Two rows: addresses and values, with the data pointer's highlighted.
+    fn tape(&self, width: usize) -> [String; 2] {
+        let cell_width = (self.memory.len() - 1).to_string().len().max(3) + 1;
+        let count = (width / cell_width).clamp(1, self.memory.len());
+        let start = self
+            .data_pointer
+            .saturating_sub(count / 2)
+            .min(self.memory.len() - count);
+
+        let row = |text: &dyn Fn(usize) -> String| -> String {
+            (start..start + count)
+                .map(|address| {
+                    let cell = format!("{:>1$} ", text(address), cell_width - 1);
+                    if address == self.data_pointer {
+                        format!("{}{}{}", HIGHLIGHT, cell, NORMAL)
+                    } else {
+                        cell
+                    }
+                })
+                .collect()
+        };
+        [
+            row(&|address| address.to_string()),
+            row(&|address| self.memory[address].to_string()),
+        ]
+    }
+
+    fn output(&self, width: usize, height: usize) -> Vec<String> {
+        let text = String::from_utf8_lossy(self.output);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        let output_lines: Vec<&str> = text.split('\n').collect();
+
+        let mut lines: Vec<String> = output_lines[output_lines.len().saturating_sub(height)..]
+            .iter()
+            .map(|line| {
+                line.chars()
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .take(width)
+                    .collect()
+            })
+            .collect();
+        lines.resize(height, String::new());
+        lines
+    }
+}
+
+fn header(title: &str, width: usize) -> String {
+    let rule = width.saturating_sub(title.chars().count() + 4);
+    format!("── {} {}", title, "─".repeat(rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_screen() {
+        let code = "Add two cells\n++[>+<-]\n>.\n";
+        let mut memory = vec![0u8; 100];
+        memory[50] = 1;
+        memory[51] = 1;
+        let screen = Screen {
+            code,
+            offset: Some(code.find('<').unwrap()),
+            memory: &memory,
+            data_pointer: 51,
+            output: b"first line\nsecond",
+            status: "Running · step 5",
+        };
+
+        let lines = screen.render(24, 11).join("\n");
+        insta::assert_snapshot!(lines.replace(HIGHLIGHT, "«").replace(NORMAL, "»"), @r"
+        ── Source ──────────────
+        Add two cells
+        ++[>+«<»-]
+        >.
+        ── Tape ────────────────
+         48  49  50 « 51 » 52  53 
+          0   0   1 «  1 »  0   0 
+        ── Output ──────────────
+        first line
+        second
+        «Running · step 5        »
+        ");
+    }
+}
@@ -0,0 +1,151 @@
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use crate::debugger::{Debugger, Stop};
+use crate::source::read_source;
+use crate::{Interpreter, InterpreterBuilder, Token};
+
+/// How many instructions a second run unless told otherwise.
+pub const DEFAULT_SPEED: u64 = 10;
+/// How many cells on each side of the data pointer are shown.
+const MEMORY_WINDOW: usize = 3;
+/// How much of the end of the output is shown.
+const OUTPUT_WIDTH: usize = 60;
+
+/// Runs `filename` at `speed` instructions a second, redrawing the next
+/// instruction and the cells around the data pointer after each one.
+pub fn run_visualize(
+    filename: &str,
+    builder: &InterpreterBuilder,
+    speed: u64,
+) -> Result<(), String> {
+    let code = read_source(filename)?;
+    let delay = Duration::from_secs(1) / speed.clamp(1, u32::MAX.into()) as u32;
+    visualize(
+        &code,
+        builder.build(),
+        delay,
+        &mut io::stdin(),
+        &mut io::stdout(),
+    )
+}
+
+fn visualize(
+    code: &str,
+    mut interpreter: Interpreter,
+    delay: Duration,
+    input: &mut dyn Read,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let mut debugger = Debugger::load(code, &interpreter)?;
+    let mut output = Vec::new();
+    let mut drawn_lines = 0;
+
+    loop {
+        let mut frame = Vec::new();
+        debugger
+            .write_location(&mut frame, &interpreter)
+            .map_err(|e| e.to_string())?;
+        write_output(&mut frame, &output);
+        redraw(out, &frame, drawn_lines).map_err(|e| e.to_string())?;
+        drawn_lines = frame.iter().filter(|&&b| b == b'\n').count();
+
+        // Input typed at the terminal moves the cursor, so the next frame
+        // starts below it instead of replacing this one.
+        if debugger.next_token() == Token::Input {
+            drawn_lines = 0;
+        } else {
+            thread::sleep(delay);
+        }
+
+        match debugger.step(&mut interpreter, input, &mut output) {
+            Stop::Finished => break,
+            Stop::Failed(e) => return Err(e),
+            Stop::Paused | Stop::Breakpoint | Stop::Watch { .. } | Stop::Interrupted => {}
+        }
+    }
+
+    let mut frame = Vec::new();
+    let _ = writeln!(frame, "Finished after {} steps", debugger.steps());
+    let _ = interpreter.write_memory_snapshot(&mut frame, MEMORY_WINDOW);
+    write_output(&mut frame, &output);
+    redraw(out, &frame, drawn_lines).map_err(|e| e.to_string())
+}
+
+/// Writes the end of `output`, escaped so it stays on one line.
+fn write_output(frame: &mut Vec<u8>, output: &[u8]) {
+    let output = String::from_utf8_lossy(output);
+    let escaped: Vec<char> = output.escape_debug().collect();
+    let tail: String = escaped[escaped.len().saturating_sub(OUTPUT_WIDTH)..]
+        .iter()
+        .collect();
+    let _ = writeln!(frame, "Output: {}", tail);
+}
+
+/// Replaces the `drawn_lines` lines above the cursor with `frame`.
+fn redraw(out: &mut dyn Write, frame: &[u8], drawn_lines: usize) -> io::Result<()> {
+    if drawn_lines > 0 {
+        write!(out, "\x1b[{}A\r\x1b[J", drawn_lines)?;
+    }
+    out.write_all(frame)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visualize() {
+        let mut out = Vec::new();
+        visualize(
+            "+>,.",
+            Interpreter::builder().tape_size(4).build(),
+            Duration::ZERO,
+            &mut &b"A"[..],
+            &mut out,
+        )
+        .unwrap();
+
+        let out = String::from_utf8(out)
+            .unwrap()
+            .replace('\x1b', "^[")
+            .replace('\r', "^M");
+        insta::assert_snapshot!(out, @r"
+        Step 0: paused at instruction 0 of 4 ('+')
+          +>,.
+          ^
+        Addr:      0      1      2      3
+        Data:      0      0      0      0
+        Ptrs:                ^^^^^       
+        Output: 
+        ^[[7A^M^[[JStep 1: paused at instruction 1 of 4 ('>')
+          +>,.
+           ^
+        Addr:      0      1      2      3
+        Data:      0      0      1      0
+        Ptrs:                ^^^^^       
+        Output: 
+        ^[[7A^M^[[JStep 2: paused at instruction 2 of 4 (',')
+          +>,.
+            ^
+        Addr:      0      1      2      3
+        Data:      0      0      1      0
+        Ptrs:                       ^^^^^
+        Output: 
+        Step 3: paused at instruction 3 of 4 ('.')
+          +>,.
+             ^
+        Addr:      0      1      2      3
+        Data:      0      0      1     65
+        Ptrs:                       ^^^^^
+        Output: 
+        ^[[7A^M^[[JFinished after 4 steps
+        Addr:      0      1      2      3
+        Data:      0      0      1     65
+        Ptrs:                       ^^^^^
+        Output: A
+        ");
+    }
+}
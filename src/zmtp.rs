@@ -0,0 +1,198 @@
+//! Just enough of ZMTP 3.0, the ZeroMQ wire protocol, for the Jupyter
+//! kernel: the NULL security mechanism and multipart messages, with one
+//! peer on each TCP connection.
+
+use std::io::{self, Read, Write};
+
+/// More frames of the same message follow.
+const MORE: u8 = 0x01;
+/// The size takes eight bytes rather than one.
+const LONG: u8 = 0x02;
+/// The frame is a command rather than part of a message.
+const COMMAND: u8 = 0x04;
+/// The largest frame accepted, so a peer cannot make reading allocate
+/// without bound.
+const MAX_FRAME_SIZE: u64 = 64 << 20;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Exchanges greetings and `READY` commands with a newly connected peer,
+/// announcing this end as a `socket_type` socket such as `ROUTER`.
+pub(crate) fn handshake(stream: &mut (impl Read + Write), socket_type: &str) -> io::Result<()> {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting)?;
+
+    let mut peer = [0u8; 64];
+    stream.read_exact(&mut peer)?;
+    if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 {
+        return Err(invalid("Peer does not speak ZMTP 3"));
+    }
+    if peer[12..32] != greeting[12..32] {
+        return Err(invalid("Peer wants a security mechanism other than NULL"));
+    }
+
+    let mut ready = vec![5];
+    ready.extend_from_slice(b"READY");
+    let name = "Socket-Type";
+    ready.push(name.len() as u8);
+    ready.extend_from_slice(name.as_bytes());
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type.as_bytes());
+    write_frame(stream, COMMAND, &ready)?;
+    stream.flush()?;
+
+    match read_frame(stream)? {
+        Some((flags, body)) if flags & COMMAND != 0 && body.starts_with(b"\x05READY") => Ok(()),
+        _ => Err(invalid("Peer did not send READY")),
+    }
+}
+
+/// Reads the frames of the next message, or `None` once the peer hangs up.
+/// Commands between messages are skipped.
+pub(crate) fn read_message(stream: &mut impl Read) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let mut frames = Vec::new();
+    loop {
+        let Some((flags, body)) = read_frame(stream)? else {
+            return match frames.is_empty() {
+                true => Ok(None),
+                false => Err(invalid("Peer hung up in the middle of a message")),
+            };
+        };
+        if flags & COMMAND != 0 {
+            continue;
+        }
+        frames.push(body);
+        if flags & MORE == 0 {
+            return Ok(Some(frames));
+        }
+    }
+}
+
+/// Writes `frames` as one message.
+pub(crate) fn write_message(
+    stream: &mut (impl Write + ?Sized),
+    frames: &[&[u8]],
+) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let more = if i + 1 < frames.len() { MORE } else { 0 };
+        write_frame(&mut bytes, more, frame)?;
+    }
+    stream.write_all(&bytes)?;
+    stream.flush()
+}
+
+fn write_frame(out: &mut impl Write, flags: u8, body: &[u8]) -> io::Result<()> {
+    match u8::try_from(body.len()) {
+        Ok(len) => out.write_all(&[flags, len])?,
+        Err(_) => {
+            out.write_all(&[flags | LONG])?;
+            out.write_all(&(body.len() as u64).to_be_bytes())?;
+        }
+    }
+    out.write_all(body)
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut flags = [0];
+    if stream.read(&mut flags)? == 0 {
+        return Ok(None);
+    }
+    let flags = flags[0];
+
+    let len = if flags & LONG != 0 {
+        let mut len = [0; 8];
+        stream.read_exact(&mut len)?;
+        u64::from_be_bytes(len)
+    } else {
+        let mut len = [0];
+        stream.read_exact(&mut len)?;
+        u64::from(len[0])
+    };
+    if len > MAX_FRAME_SIZE {
+        return Err(invalid("Frame too large"));
+    }
+
+    let mut body = vec![0; len as usize];
+    stream.read_exact(&mut body)?;
+    Ok(Some((flags, body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    #[test]
+    fn test_messages() {
+        let long = vec![b'x'; 300];
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &[b"<IDS|MSG>", b"", &long]).unwrap();
+        // A command between messages, such as a subscription, is skipped.
+        write_frame(&mut bytes, COMMAND, b"\x04PING").unwrap();
+        write_message(&mut bytes, &[b"ping"]).unwrap();
+
+        insta::assert_snapshot!(bytes[..15].escape_ascii().to_string(), @r"\x01\t<IDS|MSG>\x01\x00\x02\x00");
+
+        let mut stream = Cursor::new(bytes);
+        let messages = [(); 3].map(|_| {
+            read_message(&mut stream)
+                .unwrap()
+                .map(|frames| frames.iter().map(Vec::len).collect::<Vec<_>>())
+        });
+        insta::assert_debug_snapshot!(messages, @r"
+        [
+            Some(
+                [
+                    9,
+                    0,
+                    300,
+                ],
+            ),
+            Some(
+                [
+                    4,
+                ],
+            ),
+            None,
+        ]
+        ");
+    }
+
+    #[test]
+    fn test_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            handshake(&mut stream, "DEALER").unwrap();
+            write_message(&mut stream, &[b"hello", b"world"]).unwrap();
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        handshake(&mut stream, "ROUTER").unwrap();
+        let message = read_message(&mut stream).unwrap();
+        client.join().unwrap();
+
+        assert_eq!(message, Some(vec![b"hello".to_vec(), b"world".to_vec()]));
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        let mut stream = Cursor::new(b"\x02\xff\xff\xff\xff\xff\xff\xff\xff".to_vec());
+
+        insta::assert_debug_snapshot!(read_message(&mut stream).map_err(|e| e.to_string()), @r#"
+        Err(
+            "Frame too large",
+        )
+        "#);
+    }
+}
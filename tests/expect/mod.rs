@@ -0,0 +1,224 @@
+//! An expect-style harness that runs the `bf-repl` binary on a
+//! pseudo-terminal, so the line editor, prompts, and control keys behave as
+//! they do for someone typing.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long [`Session::expect`] waits for output.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running REPL attached to a pseudo-terminal.
+///
+/// Output is matched with ANSI escape sequences removed, and each match
+/// starts after the end of the previous one.
+pub struct Session {
+    child: Child,
+    terminal: File,
+    output: Vec<u8>,
+    matched: usize,
+    eof: bool,
+}
+
+impl Session {
+    /// Starts the REPL with `args`, ignoring any `BF_REPL_*` settings in the
+    /// test's own environment.
+    pub fn spawn(args: &[&str]) -> Self {
+        let (terminal, follower) = open_pty().expect("open a pseudo-terminal");
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_bf-repl"));
+        command.args(args);
+        for (name, _) in std::env::vars_os() {
+            if name.to_string_lossy().starts_with("BF_REPL_") {
+                command.env_remove(name);
+            }
+        }
+        // Keep tests from reading or adding to the real history file.
+        command.env("BF_REPL_HISTORY_FILE", "off");
+        command
+            .stdin(Stdio::from(follower.try_clone().unwrap()))
+            .stdout(Stdio::from(follower.try_clone().unwrap()))
+            .stderr(Stdio::from(follower));
+
+        // SAFETY: setsid and ioctl are async-signal-safe. The child becomes a
+        // session leader and takes its stdin as the controlling terminal, so
+        // Ctrl-C typed there reaches it.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = command.spawn().expect("start bf-repl");
+        // The parent's copies of the follower end were dropped with
+        // `command`'s stdio, so reads see EOF once the child exits.
+        drop(command);
+
+        Self {
+            child,
+            terminal: File::from(terminal),
+            output: Vec::new(),
+            matched: 0,
+            eof: false,
+        }
+    }
+
+    /// Types `bytes` as they are, so control characters such as `\x03`
+    /// (Ctrl-C) can be sent.
+    pub fn send(&mut self, bytes: &[u8]) {
+        self.terminal.write_all(bytes).expect("write to terminal");
+    }
+
+    /// Types `line` and presses Enter.
+    pub fn send_line(&mut self, line: &str) {
+        self.send(format!("{}\r", line).as_bytes());
+    }
+
+    /// Waits until `pattern` appears in the output, returning the text
+    /// between the previous match and this one. Panics with everything
+    /// printed so far if it does not appear before the timeout.
+    pub fn expect(&mut self, pattern: &str) -> String {
+        let deadline = Instant::now() + TIMEOUT;
+
+        loop {
+            let text = strip_ansi(&self.output);
+            if let Some(index) = text[self.matched..].find(pattern) {
+                let start = self.matched;
+                self.matched += index + pattern.len();
+                return text[start..start + index].to_string();
+            }
+
+            if self.eof {
+                panic!(
+                    "expected {:?}, but the REPL exited after:\n{}",
+                    pattern, text
+                );
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                panic!("timed out waiting for {:?} after:\n{}", pattern, text);
+            };
+            self.fill(remaining);
+        }
+    }
+
+    /// Waits for the REPL to exit, returning its status and whatever it
+    /// printed after the last match.
+    pub fn expect_exit(&mut self) -> (ExitStatus, String) {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while !self.eof {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                panic!(
+                    "timed out waiting for the REPL to exit after:\n{}",
+                    strip_ansi(&self.output)
+                );
+            };
+            self.fill(remaining);
+        }
+
+        let text = strip_ansi(&self.output);
+        let rest = text[self.matched..].to_string();
+        self.matched = text.len();
+        (self.child.wait().expect("wait for bf-repl"), rest)
+    }
+
+    /// Reads whatever output arrives within `timeout`.
+    fn fill(&mut self, timeout: Duration) {
+        let mut poll = libc::pollfd {
+            fd: self.terminal.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().clamp(1, i32::MAX as u128) as i32;
+
+        // SAFETY: `poll` points to one valid pollfd.
+        let ready = unsafe { libc::poll(&mut poll, 1, millis) };
+        if ready <= 0 {
+            return;
+        }
+
+        let mut buffer = [0; 4096];
+        match self.terminal.read(&mut buffer) {
+            Ok(0) => self.eof = true,
+            Ok(n) => self.output.extend_from_slice(&buffer[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            // Linux reports EIO once the last follower descriptor closes.
+            Err(_) => self.eof = true,
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Opens a pseudo-terminal, returning the controlling end and the end the
+/// child uses as its terminal.
+fn open_pty() -> io::Result<(OwnedFd, OwnedFd)> {
+    let (mut leader, mut follower) = (0, 0);
+    let size = libc::winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    // SAFETY: openpty writes two descriptors into the given locations, and
+    // they are owned by nothing else once it returns 0.
+    unsafe {
+        if libc::openpty(
+            &mut leader,
+            &mut follower,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((OwnedFd::from_raw_fd(leader), OwnedFd::from_raw_fd(follower)))
+    }
+}
+
+/// Removes CSI escape sequences (colors, cursor movement, line clearing)
+/// and carriage returns from terminal output. An unfinished sequence or character at the end is
+/// dropped too, so earlier text never shifts as more output arrives.
+fn strip_ansi(bytes: &[u8]) -> String {
+    let complete = match std::str::from_utf8(bytes) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => bytes.len(),
+    };
+    let text = String::from_utf8_lossy(&bytes[..complete]);
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => continue,
+            '\x1b' => {}
+            c => {
+                stripped.push(c);
+                continue;
+            }
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    stripped
+}
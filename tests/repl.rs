@@ -0,0 +1,198 @@
+//! Drives the interactive REPL through a pseudo-terminal.
+
+#![cfg(unix)]
+
+mod expect;
+
+use std::thread;
+use std::time::Duration;
+
+use expect::Session;
+
+#[test]
+fn test_banner_and_exit() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("Brainfuck REPL");
+    repl.expect("> ");
+    repl.send_line("exit");
+
+    let (status, _) = repl.expect_exit();
+    assert!(status.success());
+}
+
+#[test]
+fn test_run_and_meta_commands() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send_line("++++++++[>++++++++<-]>+.");
+    repl.expect("A");
+    repl.expect("> ");
+
+    repl.send_line(":show");
+    repl.expect("= 65 'A'");
+    repl.send_line(":jump 0");
+    repl.expect("Cell[0] = 0 NUL");
+    repl.send_line("<");
+    repl.expect("Data pointer out of bounds (left)");
+    repl.send_line(":history");
+    repl.expect("2  <");
+}
+
+#[test]
+fn test_input_prompt() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send_line(",.,.");
+    repl.expect("input> ");
+    // The terminal echoes the typed line, then the program prints it.
+    repl.send_line("hi");
+    repl.expect("hi\nhi\n");
+    repl.expect("> ");
+}
+
+#[test]
+fn test_ctrl_c_clears_line() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send(b"+++\x03");
+    repl.expect("^C");
+    repl.send_line(":show");
+    repl.expect("= 0 NUL");
+}
+
+#[test]
+fn test_ctrl_d_exits() {
+    let mut repl = Session::spawn(&["--quiet"]);
+    repl.expect("> ");
+    repl.send(b"\x04");
+
+    let (status, _) = repl.expect_exit();
+    assert!(status.success());
+}
+
+#[test]
+fn test_bracket_hint() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send(b"+[[-]");
+    repl.expect("+[[-] [1]");
+    repl.send_line("]");
+    repl.expect("> ");
+    repl.send_line(":show");
+    repl.expect("= 0 NUL");
+}
+
+#[test]
+fn test_history_keys() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send_line("+++");
+    repl.expect("> ");
+    repl.send_line(":show");
+    repl.expect("= 3 ETX");
+    repl.expect("> ");
+
+    // Up twice brings back "+++".
+    repl.send(b"\x1b[A\x1b[A\r");
+    repl.expect("> ");
+    repl.send_line(":show");
+    repl.expect("= 6 ACK");
+    repl.expect("> ");
+
+    repl.send(b"\x12++");
+    repl.expect("(reverse-i-search)'++': +++");
+    repl.send(b"\r");
+    repl.expect("> ");
+    repl.send_line(":show");
+    repl.expect("= 9");
+}
+
+#[test]
+fn test_tab_completion() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send(b":sh\t");
+    repl.expect(":show ");
+    repl.send(b"\r");
+    repl.expect("= 0 NUL");
+    repl.expect("> ");
+
+    // Tab completes as far as the matches agree, then lists them.
+    repl.send(b":hist\t");
+    repl.expect(":histo");
+    repl.send(b"\t");
+    repl.expect(":histogram   :history ");
+}
+
+#[test]
+fn test_history_file() {
+    let path = std::env::temp_dir().join(format!("bf-repl-history-pty-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut repl = Session::spawn(&["--history-file", path]);
+    repl.expect("> ");
+    repl.send_line("++++");
+    repl.expect("> ");
+    repl.send_line("exit");
+    repl.expect_exit();
+
+    let mut repl = Session::spawn(&["--history-file", path]);
+    repl.expect("> ");
+    // Up twice goes back past "exit" to the first session's code.
+    repl.send(b"\x1b[A\x1b[A\r");
+    repl.expect("> ");
+    repl.send_line(":show");
+    repl.expect("= 4 EOT");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_ctrl_c_interrupts_program() {
+    let mut repl = Session::spawn(&[]);
+    repl.expect("> ");
+
+    repl.send_line("+++>+[]");
+    repl.expect("never ends once entered");
+    // Give the program time to start, so the REPL rather than the prompt
+    // sees the key.
+    thread::sleep(Duration::from_millis(200));
+    repl.send(b"\x03");
+    repl.expect("Interrupted (");
+    repl.send_line(":show");
+    repl.expect("= 1 SOH");
+    repl.send_line("<.");
+    repl.send_line(":dry +[]");
+    thread::sleep(Duration::from_millis(200));
+    repl.send(b"\x03");
+    repl.expect("Error: Interrupted (");
+    repl.expect("Changes discarded");
+    repl.send_line("exit");
+
+    let (status, _) = repl.expect_exit();
+    assert!(status.success());
+}
+
+#[test]
+#[cfg(all(feature = "jit", target_arch = "x86_64"))]
+fn test_ctrl_c_interrupts_compiled_program() {
+    let mut repl = Session::spawn(&["--engine", "jit"]);
+    repl.expect("> ");
+
+    repl.send_line("+++>+[]");
+    repl.expect("never ends once entered");
+    thread::sleep(Duration::from_millis(200));
+    repl.send(b"\x03");
+    repl.expect("Interrupted (");
+    repl.send_line(":show");
+    repl.expect("= 1 SOH");
+    repl.send_line("exit");
+
+    let (status, _) = repl.expect_exit();
+    assert!(status.success());
+}